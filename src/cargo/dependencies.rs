@@ -0,0 +1,176 @@
+use serde_json::{json, Value};
+use std::{collections::BTreeSet, path::Path};
+
+/// Describes the dependencies a workspace member declares, from its entry in
+/// `cargo metadata` output. `manifest` is the member's `Cargo.toml`, used to
+/// tell which dependencies are inherited from `[workspace.dependencies]`,
+/// which cargo metadata reports already resolved.
+pub fn member_dependencies(package: &Value, workspace_root: &Path, manifest: &str) -> Value {
+    let inherited = inherited_dependencies(manifest);
+    let relative = |path: &str| {
+        Path::new(path)
+            .strip_prefix(workspace_root)
+            .map(|path| path.display().to_string())
+            .unwrap_or_else(|_| path.to_string())
+    };
+
+    let dependencies: Vec<Value> = package["dependencies"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|dependency| {
+            let package_name = dependency["name"].as_str().unwrap_or_default();
+            let name = dependency["rename"].as_str().unwrap_or(package_name);
+            let source = dependency["source"].as_str();
+            let source_type = match (dependency["path"].as_str(), source) {
+                (Some(_), _) => "path",
+                (None, Some(source)) if source.starts_with("git+") => "git",
+                _ => "registry",
+            };
+            let mut entry = json!({
+                "name": name,
+                "req": dependency["req"],
+                "kind": dependency["kind"].as_str().unwrap_or("normal"),
+                "source_type": source_type,
+                "workspace": inherited.contains(name),
+                "optional": dependency["optional"],
+                "default_features": dependency["uses_default_features"],
+                "features": dependency["features"]
+            });
+            if name != package_name {
+                entry["package"] = json!(package_name);
+            }
+            match source_type {
+                "path" => {
+                    entry["path"] = json!(relative(dependency["path"].as_str().unwrap_or_default()))
+                }
+                "git" => {
+                    entry["git"] = json!(source.and_then(|source| source.strip_prefix("git+")))
+                }
+                _ => {}
+            }
+            if let Some(target) = dependency["target"].as_str() {
+                entry["target"] = json!(target);
+            }
+            entry
+        })
+        .collect();
+
+    json!({
+        "package": package["name"],
+        "manifest_path": relative(package["manifest_path"].as_str().unwrap_or_default()),
+        "features": package["features"],
+        "dependencies": dependencies
+    })
+}
+
+/// Names of the dependencies a manifest inherits with `workspace = true`,
+/// written as `name.workspace = true`, `name = { workspace = true, ... }` or
+/// in a `[dependencies.name]` table.
+fn inherited_dependencies(manifest: &str) -> BTreeSet<String> {
+    let mut inherited = BTreeSet::new();
+    let mut section = String::new();
+    for line in manifest.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if let Some(header) = line.strip_prefix('[') {
+            section = header.trim_end_matches(']').trim().to_string();
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let (key, value) = (key.trim(), value.trim());
+        let is_workspace = |value: &str| value.replace(' ', "").contains("workspace=true");
+
+        // `[dependencies.name]` table with a `workspace = true` entry.
+        if let Some((table, name)) = section.rsplit_once('.') {
+            if table.ends_with("dependencies") && key == "workspace" && value == "true" {
+                inherited.insert(name.trim_matches('"').to_string());
+                continue;
+            }
+        }
+        if !section.ends_with("dependencies") || section.starts_with("workspace") {
+            continue;
+        }
+        if let Some(name) = key.strip_suffix(".workspace") {
+            if value == "true" {
+                inherited.insert(name.trim().trim_matches('"').to_string());
+            }
+        } else if value.starts_with('{') && is_workspace(value) {
+            inherited.insert(key.trim_matches('"').to_string());
+        }
+    }
+    inherited
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use std::path::Path;
+
+    use super::{inherited_dependencies, member_dependencies};
+
+    #[test]
+    fn test_inherited_dependencies() {
+        let manifest = r#"
+[workspace.dependencies]
+serde = "1.0"
+
+[dependencies]
+serde.workspace = true
+tokio = { workspace = true, features = ["full"] }
+log = "0.4"
+
+[target.'cfg(unix)'.dependencies.libc]
+workspace = true
+"#;
+
+        let inherited: Vec<String> = inherited_dependencies(manifest).into_iter().collect();
+
+        assert_eq!(inherited, ["libc", "serde", "tokio"]);
+    }
+
+    #[test]
+    fn test_member_dependencies_classifies_sources() {
+        let package = json!({
+            "name": "app",
+            "manifest_path": "/ws/app/Cargo.toml",
+            "features": {},
+            "dependencies": [
+                {
+                    "name": "serde", "req": "^1.0", "kind": null, "optional": false,
+                    "uses_default_features": true, "features": ["derive"],
+                    "source": "registry+https://github.com/rust-lang/crates.io-index"
+                },
+                {
+                    "name": "core-lib", "req": "*", "kind": null, "optional": false,
+                    "uses_default_features": true, "features": [], "source": null,
+                    "path": "/ws/core-lib"
+                },
+                {
+                    "name": "fork", "rename": "patched", "req": "*", "kind": "dev",
+                    "optional": true, "uses_default_features": false, "features": [],
+                    "source": "git+https://example.com/fork.git?branch=main"
+                }
+            ]
+        });
+        let manifest = "[dependencies]\nserde = { workspace = true }\n";
+
+        let summary = member_dependencies(&package, Path::new("/ws"), manifest);
+
+        assert_eq!(summary["manifest_path"], "app/Cargo.toml");
+        let dependencies = &summary["dependencies"];
+        assert_eq!(dependencies[0]["source_type"], "registry");
+        assert_eq!(dependencies[0]["workspace"], true);
+        assert_eq!(dependencies[0]["features"], json!(["derive"]));
+        assert_eq!(dependencies[1]["source_type"], "path");
+        assert_eq!(dependencies[1]["path"], "core-lib");
+        assert_eq!(dependencies[2]["name"], "patched");
+        assert_eq!(dependencies[2]["package"], "fork");
+        assert_eq!(dependencies[2]["kind"], "dev");
+        assert_eq!(
+            dependencies[2]["git"],
+            "https://example.com/fork.git?branch=main"
+        );
+    }
+}
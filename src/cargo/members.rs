@@ -0,0 +1,79 @@
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// The directory of each workspace member, taken from the manifest paths in
+/// `cargo metadata` output, used to tell which member a file belongs to.
+#[derive(Debug, Clone, Default)]
+pub struct MemberDirs {
+    members: Vec<(String, PathBuf)>,
+}
+
+impl MemberDirs {
+    pub fn from_metadata(metadata: &Value) -> Self {
+        let members = metadata["packages"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|package| {
+                let name = package["name"].as_str()?;
+                let dir = Path::new(package["manifest_path"].as_str()?).parent()?;
+                Some((name.to_string(), dir.to_path_buf()))
+            })
+            .collect();
+        Self { members }
+    }
+
+    /// Looks up a member by package name, treating `-` and `_` as equal.
+    pub fn find(&self, package: &str) -> Option<&str> {
+        let wanted = package.replace('-', "_");
+        self.members
+            .iter()
+            .find(|(name, _)| name.replace('-', "_") == wanted)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// The member owning `path`: the one with the deepest directory containing
+    /// it, so files of a member nested inside another belong to the inner one.
+    pub fn owner(&self, path: &Path) -> Option<&str> {
+        self.members
+            .iter()
+            .filter(|(_, dir)| path.starts_with(dir))
+            .max_by_key(|(_, dir)| dir.components().count())
+            .map(|(name, _)| name.as_str())
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.members.iter().map(|(name, _)| name.as_str()).collect();
+        names.sort_unstable();
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use std::path::Path;
+
+    use super::MemberDirs;
+
+    #[test]
+    fn test_member_dirs_owner_prefers_nested_members() {
+        let metadata = json!({
+            "packages": [
+                { "name": "app", "manifest_path": "/ws/Cargo.toml" },
+                { "name": "test-support", "manifest_path": "/ws/test-support/Cargo.toml" }
+            ]
+        });
+        let members = MemberDirs::from_metadata(&metadata);
+
+        assert_eq!(members.owner(Path::new("/ws/src/lib.rs")), Some("app"));
+        assert_eq!(
+            members.owner(Path::new("/ws/test-support/src/lib.rs")),
+            Some("test-support")
+        );
+        assert_eq!(members.owner(Path::new("/other/src/lib.rs")), None);
+        assert_eq!(members.find("test_support"), Some("test-support"));
+        assert_eq!(members.find("missing"), None);
+        assert_eq!(members.names(), ["app", "test-support"]);
+    }
+}
@@ -0,0 +1,185 @@
+use serde_json::{json, Value};
+use std::{collections::HashSet, path::Path};
+
+/// Compiler diagnostics collected from cargo's JSON message stream.
+#[derive(Debug, Default)]
+pub struct CompilerMessages {
+    pub diagnostics: Vec<Value>,
+    pub errors: u64,
+    pub warnings: u64,
+    /// Artifacts produced by the build, as `{ "target": ..., "kind": ..., "files": [...] }`.
+    pub artifacts: Vec<Value>,
+}
+
+impl CompilerMessages {
+    pub fn parse(output: &str, workspace_root: &Path) -> Self {
+        let mut messages = Self::default();
+        let mut seen = HashSet::new();
+
+        for line in output.lines() {
+            let Ok(message) = serde_json::from_str::<Value>(line) else {
+                continue;
+            };
+
+            match message.get("reason").and_then(|reason| reason.as_str()) {
+                Some("compiler-message") => {
+                    let Some(diagnostic) = message.get("message") else {
+                        continue;
+                    };
+
+                    // Checking several targets of the same crate repeats the same
+                    // diagnostic once per target.
+                    let rendered = diagnostic
+                        .get("rendered")
+                        .and_then(|rendered| rendered.as_str())
+                        .unwrap_or("")
+                        .to_string();
+                    if !seen.insert(rendered) {
+                        continue;
+                    }
+
+                    messages.push_diagnostic(&message, diagnostic, workspace_root);
+                }
+                Some("compiler-artifact") => {
+                    messages.artifacts.push(json!({
+                        "package_id": message.get("package_id").cloned().unwrap_or(json!(null)),
+                        "target": message.pointer("/target/name").cloned().unwrap_or(json!(null)),
+                        "kind": message.pointer("/target/kind").cloned().unwrap_or(json!([])),
+                        "files": message.get("filenames").cloned().unwrap_or(json!([])),
+                        "executable": message.get("executable").cloned().unwrap_or(json!(null))
+                    }));
+                }
+                _ => {}
+            }
+        }
+
+        messages
+    }
+
+    fn push_diagnostic(&mut self, message: &Value, diagnostic: &Value, workspace_root: &Path) {
+        let level = diagnostic
+            .get("level")
+            .and_then(|level| level.as_str())
+            .unwrap_or("unknown");
+
+        match level {
+            "error" | "error: internal compiler error" => self.errors += 1,
+            "warning" => self.warnings += 1,
+            // Summary lines such as "aborting due to previous error" carry no spans.
+            _ => {}
+        }
+
+        let primary_span = diagnostic
+            .get("spans")
+            .and_then(|spans| spans.as_array())
+            .and_then(|spans| {
+                spans
+                    .iter()
+                    .find(|span| span["is_primary"].as_bool() == Some(true))
+                    .or_else(|| spans.first())
+            });
+
+        let (file, range) = match primary_span {
+            Some(span) => (
+                span["file_name"]
+                    .as_str()
+                    .map(|name| workspace_root.join(name).display().to_string()),
+                span_to_range(span),
+            ),
+            None => (None, json!(null)),
+        };
+
+        let notes: Vec<Value> = diagnostic
+            .get("children")
+            .and_then(|children| children.as_array())
+            .map(|children| {
+                children
+                    .iter()
+                    .map(|child| {
+                        json!({
+                            "level": child["level"],
+                            "message": child["message"]
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        self.diagnostics.push(json!({
+            "level": level,
+            "code": diagnostic.pointer("/code/code").cloned().unwrap_or(json!(null)),
+            "message": diagnostic.get("message").cloned().unwrap_or(json!("")),
+            "file": file,
+            "range": range,
+            "target": message.pointer("/target/name").cloned().unwrap_or(json!(null)),
+            "notes": notes,
+            "rendered": diagnostic.get("rendered").cloned().unwrap_or(json!(null))
+        }));
+    }
+}
+
+/// Converts a rustc span (1-based lines and columns) to a 0-based LSP range.
+fn span_to_range(span: &Value) -> Value {
+    let field = |name: &str| span[name].as_u64().unwrap_or(1).saturating_sub(1);
+    json!({
+        "start": { "line": field("line_start"), "character": field("column_start") },
+        "end": { "line": field("line_end"), "character": field("column_end") }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use std::path::Path;
+
+    use super::CompilerMessages;
+
+    fn compiler_message(target: &str, level: &str, code: &str, rendered: &str) -> String {
+        json!({
+            "reason": "compiler-message",
+            "target": { "name": target, "kind": ["lib"] },
+            "message": {
+                "level": level,
+                "message": "mismatched types",
+                "code": { "code": code },
+                "rendered": rendered,
+                "spans": [{
+                    "file_name": "src/lib.rs",
+                    "is_primary": true,
+                    "line_start": 3,
+                    "line_end": 3,
+                    "column_start": 18,
+                    "column_end": 25
+                }],
+                "children": [{ "level": "note", "message": "expected `u32`", "spans": [] }]
+            }
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_parse_compiler_messages() {
+        let output = [
+            compiler_message("demo", "error", "E0308", "error[E0308]: mismatched types"),
+            // Same diagnostic reported again for the test target.
+            compiler_message("demo", "error", "E0308", "error[E0308]: mismatched types"),
+            compiler_message("demo", "warning", "unused_variables", "warning: unused"),
+            r#"{"reason":"build-finished","success":false}"#.to_string(),
+            "not json".to_string(),
+        ]
+        .join("\n");
+
+        let messages = CompilerMessages::parse(&output, Path::new("/ws"));
+
+        assert_eq!(messages.errors, 1);
+        assert_eq!(messages.warnings, 1);
+        assert_eq!(messages.diagnostics.len(), 2);
+
+        let first = &messages.diagnostics[0];
+        assert_eq!(first["code"], "E0308");
+        assert_eq!(first["file"], "/ws/src/lib.rs");
+        assert_eq!(first["range"]["start"]["line"], 2);
+        assert_eq!(first["range"]["start"]["character"], 17);
+        assert_eq!(first["notes"][0]["message"], "expected `u32`");
+    }
+}
@@ -0,0 +1,204 @@
+use anyhow::{anyhow, Result};
+use log::info;
+use serde_json::Value;
+use std::{
+    ffi::OsStr,
+    path::Path,
+    process::{Output, Stdio},
+    time::Duration,
+};
+use tokio::{io::AsyncWriteExt, process::Command};
+
+use crate::{
+    config::{CARGO_COMMAND_TIMEOUT_SECS, PASSTHROUGH_ENV_VARS},
+    deadline::wait_limit,
+    error::ToolError,
+};
+
+mod dependencies;
+mod members;
+mod messages;
+mod overview;
+mod toolchain;
+
+pub use dependencies::member_dependencies;
+pub use members::MemberDirs;
+pub use messages::CompilerMessages;
+pub use overview::{project_overview, workspace_members};
+pub use toolchain::{detect_rust_src, ensure_rust_src, toolchain_info, RustSrcStatus};
+
+/// Output of a finished cargo invocation that used `--message-format=json`.
+#[derive(Debug)]
+pub struct CargoRun {
+    pub success: bool,
+    pub messages: CompilerMessages,
+    pub stderr: String,
+}
+
+/// Runs `cargo <args> --message-format=json` in the workspace and parses the
+/// compiler messages it prints.
+pub async fn run_cargo_json(workspace_root: &Path, args: &[String]) -> Result<CargoRun> {
+    let mut cmd = workspace_command("cargo", workspace_root);
+    cmd.args(args).arg("--message-format=json");
+
+    info!("Running cargo {}", args.join(" "));
+    let child = cmd
+        .spawn()
+        .map_err(|e| anyhow!("Failed to start cargo: {}", e))?;
+
+    let output = tokio::time::timeout(
+        wait_limit(Duration::from_secs(CARGO_COMMAND_TIMEOUT_SECS)),
+        child.wait_with_output(),
+    )
+    .await
+    .map_err(|_| ToolError::Timeout(format!("cargo {} timed out", args.join(" "))))??;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(CargoRun {
+        success: output.status.success(),
+        messages: CompilerMessages::parse(&stdout, workspace_root),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+    })
+}
+
+/// Runs `cargo metadata` for the workspace. With `no_deps` only workspace
+/// members are resolved, which avoids touching the network.
+pub async fn cargo_metadata(workspace_root: &Path, no_deps: bool) -> Result<Value> {
+    let mut args = vec!["metadata", "--format-version", "1"];
+    if no_deps {
+        args.push("--no-deps");
+    }
+
+    let stdout = run_command(workspace_root, "cargo", &args).await?;
+    serde_json::from_str(&stdout).map_err(|e| anyhow!("Invalid cargo metadata output: {}", e))
+}
+
+/// Runs a short-lived command in the workspace and returns its stdout, failing
+/// with the command's stderr when it exits unsuccessfully.
+pub async fn run_command(workspace_root: &Path, program: &str, args: &[&str]) -> Result<String> {
+    let output = command_output(workspace_root, program, args).await?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{} {} failed: {}",
+            program,
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Runs a command in the workspace and collects its output whether or not it
+/// succeeds.
+pub async fn command_output<S: AsRef<OsStr>>(
+    workspace_root: &Path,
+    program: &str,
+    args: &[S],
+) -> Result<Output> {
+    let mut cmd = workspace_command(program, workspace_root);
+    cmd.args(args);
+
+    let child = cmd
+        .spawn()
+        .map_err(|e| anyhow!("Failed to start {}: {}", program, e))?;
+    let output = tokio::time::timeout(
+        wait_limit(Duration::from_secs(CARGO_COMMAND_TIMEOUT_SECS)),
+        child.wait_with_output(),
+    )
+    .await
+    .map_err(|_| {
+        let args: Vec<_> = args
+            .iter()
+            .map(|arg| arg.as_ref().to_string_lossy())
+            .collect();
+        ToolError::Timeout(format!("{} {} timed out", program, args.join(" ")))
+    })??;
+    Ok(output)
+}
+
+/// Formats a snippet of Rust code with rustfmt and the workspace's rustfmt
+/// configuration. Code that is not a list of items, such as an expression or
+/// statements, is formatted as a function body.
+pub async fn format_snippet(workspace_root: &Path, code: &str) -> Result<String> {
+    if let Ok(formatted) = rustfmt(workspace_root, code).await {
+        return Ok(formatted);
+    }
+
+    let wrapped = format!("fn __snippet() {{\n{code}\n}}\n");
+    let formatted = rustfmt(workspace_root, &wrapped).await?;
+    let lines: Vec<&str> = formatted.lines().collect();
+    let body = lines
+        .get(1..lines.len().saturating_sub(1))
+        .unwrap_or_default();
+    Ok(body
+        .iter()
+        .map(|line| format!("{}\n", line.strip_prefix("    ").unwrap_or(line)))
+        .collect())
+}
+
+async fn rustfmt(workspace_root: &Path, code: &str) -> Result<String> {
+    let mut cmd = workspace_command("rustfmt", workspace_root);
+    cmd.args(["--edition", "2021"]).stdin(Stdio::piped());
+
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| anyhow!("Failed to start rustfmt: {}", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(code.as_bytes()).await?;
+    }
+    let output = tokio::time::timeout(
+        wait_limit(Duration::from_secs(CARGO_COMMAND_TIMEOUT_SECS)),
+        child.wait_with_output(),
+    )
+    .await
+    .map_err(|_| ToolError::Timeout("rustfmt timed out".to_string()))??;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "rustfmt failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn workspace_command(program: &str, workspace_root: &Path) -> Command {
+    let mut cmd = Command::new(program);
+    cmd.current_dir(workspace_root)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    // Use the same isolation environment as the rust-analyzer process.
+    for name in PASSTHROUGH_ENV_VARS {
+        if let Ok(value) = std::env::var(name) {
+            cmd.env(name, value);
+        }
+    }
+
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_snippet;
+
+    #[tokio::test]
+    async fn test_format_snippet_handles_items_and_statements() -> anyhow::Result<()> {
+        let workspace = tempfile::tempdir()?;
+
+        let items = format_snippet(workspace.path(), "impl Foo{fn f(&self)->u32{1}}").await?;
+        assert_eq!(
+            items,
+            "impl Foo {\n    fn f(&self) -> u32 {\n        1\n    }\n}\n"
+        );
+
+        let statements = format_snippet(workspace.path(), "let x=1;x+1").await?;
+        assert_eq!(statements, "let x = 1;\nx + 1\n");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,197 @@
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+/// Summarizes `cargo metadata --no-deps` output: each workspace member with
+/// its edition, targets, features and dependency counts, the dependency edges
+/// between members, and the external crates they depend on directly.
+pub fn project_overview(metadata: &Value) -> Value {
+    let root = Path::new(metadata["workspace_root"].as_str().unwrap_or_default());
+    let relative = |path: &str| relative_path(root, path);
+
+    let packages: Vec<&Value> = metadata["packages"]
+        .as_array()
+        .map(|packages| packages.iter().collect())
+        .unwrap_or_default();
+    let member_names: BTreeSet<&str> = packages
+        .iter()
+        .filter_map(|package| package["name"].as_str())
+        .collect();
+
+    let mut editions = BTreeSet::new();
+    let mut crate_graph = Vec::new();
+    let mut external = BTreeSet::new();
+    let mut members = Vec::new();
+    for package in &packages {
+        let name = package["name"].as_str().unwrap_or_default();
+        if let Some(edition) = package["edition"].as_str() {
+            editions.insert(edition);
+        }
+
+        let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+        for dependency in package["dependencies"].as_array().into_iter().flatten() {
+            let dependency_name = dependency["name"].as_str().unwrap_or_default();
+            let kind = dependency["kind"].as_str().unwrap_or("normal");
+            *counts.entry(kind).or_default() += 1;
+            if member_names.contains(dependency_name) {
+                crate_graph.push(json!({ "from": name, "to": dependency_name, "kind": kind }));
+            } else {
+                external.insert(dependency_name);
+            }
+        }
+
+        let targets = member_targets(package, root);
+        members.push(json!({
+            "name": name,
+            "version": package["version"],
+            "edition": package["edition"],
+            "rust_version": package["rust_version"],
+            "manifest_path": relative(package["manifest_path"].as_str().unwrap_or_default()),
+            "targets": targets,
+            "features": package["features"],
+            "dependencies": counts
+        }));
+    }
+
+    json!({
+        "workspace_root": metadata["workspace_root"],
+        "editions": editions,
+        "members": members,
+        "crate_graph": crate_graph,
+        "external_dependencies": external
+    })
+}
+
+/// Lists the workspace members in `cargo metadata --no-deps` output with the
+/// directory each is rooted at and its targets, paths relative to the
+/// workspace root.
+pub fn workspace_members(metadata: &Value) -> Value {
+    let root = Path::new(metadata["workspace_root"].as_str().unwrap_or_default());
+    let members: Vec<Value> = metadata["packages"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|package| {
+            let manifest_path = package["manifest_path"].as_str().unwrap_or_default();
+            let member_root = Path::new(manifest_path)
+                .parent()
+                .map(|dir| relative_path(root, &dir.to_string_lossy()))
+                .filter(|dir| !dir.is_empty())
+                .unwrap_or_else(|| ".".to_string());
+            json!({
+                "name": package["name"],
+                "version": package["version"],
+                "root": member_root,
+                "manifest_path": relative_path(root, manifest_path),
+                "targets": member_targets(package, root)
+            })
+        })
+        .collect();
+
+    json!({
+        "workspace_root": metadata["workspace_root"],
+        "members": members
+    })
+}
+
+fn member_targets(package: &Value, root: &Path) -> Vec<Value> {
+    package["targets"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|target| {
+            json!({
+                "name": target["name"],
+                "kind": target["kind"][0],
+                "src_path": relative_path(root, target["src_path"].as_str().unwrap_or_default())
+            })
+        })
+        .collect()
+}
+
+fn relative_path(root: &Path, path: &str) -> String {
+    Path::new(path)
+        .strip_prefix(root)
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|_| path.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{project_overview, workspace_members};
+
+    fn metadata() -> serde_json::Value {
+        json!({
+            "workspace_root": "/ws",
+            "packages": [
+                {
+                    "name": "app",
+                    "version": "0.1.0",
+                    "edition": "2021",
+                    "manifest_path": "/ws/app/Cargo.toml",
+                    "features": { "default": ["fast"], "fast": [] },
+                    "targets": [{ "name": "app", "kind": ["bin"], "src_path": "/ws/app/src/main.rs" }],
+                    "dependencies": [
+                        { "name": "core-lib", "kind": null, "path": "/ws/core-lib" },
+                        { "name": "serde", "kind": null },
+                        { "name": "tempfile", "kind": "dev" }
+                    ]
+                },
+                {
+                    "name": "core-lib",
+                    "version": "0.1.0",
+                    "edition": "2024",
+                    "manifest_path": "/ws/core-lib/Cargo.toml",
+                    "features": {},
+                    "targets": [{ "name": "core_lib", "kind": ["lib"], "src_path": "/ws/core-lib/src/lib.rs" }],
+                    "dependencies": [{ "name": "serde", "kind": null }]
+                }
+            ]
+        })
+    }
+
+    #[test]
+    fn test_project_overview_links_members() {
+        let overview = project_overview(&metadata());
+
+        assert_eq!(overview["editions"], json!(["2021", "2024"]));
+        assert_eq!(
+            overview["crate_graph"],
+            json!([{ "from": "app", "to": "core-lib", "kind": "normal" }])
+        );
+        assert_eq!(
+            overview["external_dependencies"],
+            json!(["serde", "tempfile"])
+        );
+        let app = &overview["members"][0];
+        assert_eq!(app["manifest_path"], "app/Cargo.toml");
+        assert_eq!(app["targets"][0]["kind"], "bin");
+        assert_eq!(app["targets"][0]["src_path"], "app/src/main.rs");
+        assert_eq!(app["dependencies"], json!({ "normal": 2, "dev": 1 }));
+    }
+
+    #[test]
+    fn test_workspace_members_list_roots_and_targets() {
+        let members = workspace_members(&metadata());
+
+        assert_eq!(members["workspace_root"], "/ws");
+        let core = &members["members"][1];
+        assert_eq!(core["name"], "core-lib");
+        assert_eq!(core["root"], "core-lib");
+        assert_eq!(core["manifest_path"], "core-lib/Cargo.toml");
+        assert_eq!(
+            core["targets"],
+            json!([{ "name": "core_lib", "kind": "lib", "src_path": "core-lib/src/lib.rs" }])
+        );
+
+        let single = workspace_members(&json!({
+            "workspace_root": "/ws",
+            "packages": [{ "name": "app", "manifest_path": "/ws/Cargo.toml", "targets": [] }]
+        }));
+        assert_eq!(single["members"][0]["root"], ".");
+    }
+}
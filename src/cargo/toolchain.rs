@@ -0,0 +1,181 @@
+use log::{info, warn};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+use super::{cargo_metadata, run_command};
+
+/// Whether the `rust-src` component, which rust-analyzer needs to resolve
+/// standard library definitions, is available for the workspace toolchain.
+#[derive(Debug, Clone, Serialize)]
+pub struct RustSrcStatus {
+    pub installed: bool,
+    pub sysroot: Option<PathBuf>,
+    pub library_path: Option<PathBuf>,
+    /// Outcome of `rustup component add rust-src` when installation was attempted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub install_result: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+/// Checks for the standard library sources under the sysroot reported by `rustc`.
+pub async fn detect_rust_src(workspace_root: &Path) -> RustSrcStatus {
+    let sysroot = run_command(workspace_root, "rustc", &["--print", "sysroot"])
+        .await
+        .ok()
+        .map(|output| PathBuf::from(output.trim()));
+    let library_path = sysroot
+        .as_ref()
+        .map(|sysroot| sysroot.join("lib/rustlib/src/rust/library"));
+    let installed = library_path.as_ref().is_some_and(|path| path.is_dir());
+
+    RustSrcStatus {
+        installed,
+        hint: (!installed).then(|| {
+            "Standard library definitions are unavailable; run `rustup component add rust-src` \
+             or start the server with --install-rust-src"
+                .to_string()
+        }),
+        sysroot,
+        library_path,
+        install_result: None,
+    }
+}
+
+/// Detects `rust-src` and, when it is missing, installs it with rustup.
+pub async fn ensure_rust_src(workspace_root: &Path) -> RustSrcStatus {
+    let status = detect_rust_src(workspace_root).await;
+    if status.installed {
+        return status;
+    }
+
+    info!("rust-src component missing; running `rustup component add rust-src`");
+    let install_result =
+        match run_command(workspace_root, "rustup", &["component", "add", "rust-src"]).await {
+            Ok(_) => "installed".to_string(),
+            Err(e) => {
+                warn!("Failed to install rust-src: {}", e);
+                format!("failed: {}", e)
+            }
+        };
+
+    let mut status = detect_rust_src(workspace_root).await;
+    status.install_result = Some(install_result);
+    status
+}
+
+/// Collects the versions of the tools that build and analyze the workspace.
+///
+/// Every probe is best-effort: a missing `rustup` (e.g. distro-packaged Rust)
+/// leaves that field `null` instead of failing the whole report.
+pub async fn toolchain_info(workspace_root: &Path) -> Value {
+    let rustc = run_command(workspace_root, "rustc", &["-vV"]).await.ok();
+    let cargo = run_command(workspace_root, "cargo", &["--version"])
+        .await
+        .ok()
+        .map(|output| output.trim().to_string());
+    let active_toolchain = run_command(workspace_root, "rustup", &["show", "active-toolchain"])
+        .await
+        .ok()
+        .map(|output| output.trim().to_string());
+
+    let rustc_fields = rustc.as_deref().map(parse_rustc_verbose_version);
+    let release = rustc_fields
+        .as_ref()
+        .and_then(|fields| fields.get("release").cloned());
+
+    json!({
+        "rustc": {
+            "version": rustc.as_deref().and_then(|output| output.lines().next()).map(str::trim),
+            "release": release,
+            "commit_hash": rustc_fields.as_ref().and_then(|fields| fields.get("commit-hash").cloned()),
+            "host": rustc_fields.as_ref().and_then(|fields| fields.get("host").cloned()),
+            "llvm_version": rustc_fields.as_ref().and_then(|fields| fields.get("LLVM version").cloned())
+        },
+        "cargo": { "version": cargo },
+        "toolchain": {
+            "active": active_toolchain,
+            "channel": release.as_deref().map(release_channel)
+        },
+        "workspace": workspace_editions(workspace_root).await
+    })
+}
+
+fn parse_rustc_verbose_version(output: &str) -> std::collections::HashMap<String, String> {
+    output
+        .lines()
+        .filter_map(|line| line.split_once(": "))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+fn release_channel(release: &str) -> &'static str {
+    if release.contains("nightly") {
+        "nightly"
+    } else if release.contains("beta") {
+        "beta"
+    } else if release.contains("dev") {
+        "dev"
+    } else {
+        "stable"
+    }
+}
+
+async fn workspace_editions(workspace_root: &Path) -> Value {
+    let metadata = match cargo_metadata(workspace_root, true).await {
+        Ok(metadata) => metadata,
+        Err(e) => return json!({ "error": e.to_string() }),
+    };
+
+    let mut editions = BTreeSet::new();
+    let members: Vec<Value> = metadata["packages"]
+        .as_array()
+        .map(|packages| {
+            packages
+                .iter()
+                .map(|package| {
+                    if let Some(edition) = package["edition"].as_str() {
+                        editions.insert(edition.to_string());
+                    }
+                    json!({
+                        "name": package["name"],
+                        "edition": package["edition"],
+                        "rust_version": package["rust_version"]
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    json!({
+        "editions": editions,
+        "members": members
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_rustc_verbose_version, release_channel};
+
+    #[test]
+    fn test_parse_rustc_verbose_version() {
+        let output = "rustc 1.86.0-nightly (a1b2c3d 2025-01-01)\n\
+                      binary: rustc\n\
+                      commit-hash: a1b2c3d\n\
+                      host: x86_64-unknown-linux-gnu\n\
+                      release: 1.86.0-nightly\n\
+                      LLVM version: 19.1.6\n";
+
+        let fields = parse_rustc_verbose_version(output);
+
+        assert_eq!(fields["release"], "1.86.0-nightly");
+        assert_eq!(fields["host"], "x86_64-unknown-linux-gnu");
+        assert_eq!(fields["LLVM version"], "19.1.6");
+        assert_eq!(release_channel(&fields["release"]), "nightly");
+        assert_eq!(release_channel("1.85.0"), "stable");
+    }
+}
@@ -1,5 +1,966 @@
+use anyhow::{anyhow, Result};
+use std::{ffi::OsString, net::SocketAddr, path::PathBuf, time::Duration};
+
+use crate::{
+    logging::LogFormat,
+    lsp::{LspBackend, RetryPolicy},
+};
+
 /// Timeout for LSP requests in seconds.
 pub const LSP_REQUEST_TIMEOUT_SECS: u64 = 30;
 
+/// Upper bound for a tool's `timeout_ms` argument unless configured otherwise.
+pub const DEFAULT_MAX_TOOL_TIMEOUT_SECS: u64 = 600;
+
 /// Delay after opening a document to allow rust-analyzer to process it.
 pub const DOCUMENT_OPEN_DELAY_MILLIS: u64 = 200;
+
+/// How many files the workspace diagnostics fallback opens at a time.
+pub const DIAGNOSTICS_PARALLELISM: usize = 8;
+
+/// How long a `didSave` waits for further saves of the same document before
+/// it is sent, in milliseconds.
+pub const SAVE_DEBOUNCE_MILLIS: u64 = 300;
+
+/// How long rust-analyzer gets to acknowledge a shutdown request before it is
+/// killed, in milliseconds.
+pub const LSP_SHUTDOWN_TIMEOUT_MILLIS: u64 = 2000;
+
+/// How long requests being handled when a shutdown signal arrives may take to
+/// finish before they are cancelled, in seconds.
+pub const SHUTDOWN_DRAIN_TIMEOUT_SECS: u64 = 10;
+
+/// Timeout for cargo commands run on behalf of tools, in seconds.
+pub const CARGO_COMMAND_TIMEOUT_SECS: u64 = 600;
+
+/// How long warm-up mode waits for rust-analyzer to become ready before
+/// serving tools anyway, in seconds.
+pub const WARM_UP_TIMEOUT_SECS: u64 = 300;
+
+/// Interval at which warm-up mode checks whether rust-analyzer is ready.
+pub const WARM_UP_POLL_MILLIS: u64 = 250;
+
+/// Number of recent rust-analyzer stderr lines kept for the logs tool.
+pub const STDERR_LOG_LINES: usize = 1000;
+
+/// Size at which the log file is rotated, in bytes.
+pub const LOG_FILE_MAX_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Number of rotated log files kept next to the current one.
+pub const LOG_FILE_BACKUPS: usize = 3;
+
+/// Target directory given to rust-analyzer by `--isolated-target-dir`,
+/// relative to the workspace root.
+pub const ISOLATED_TARGET_DIR: &str = "target/ra-mcp";
+
+/// Environment variables forwarded to spawned rust-analyzer and cargo processes
+/// so isolated test and CI setups keep their caches and target directories.
+pub const PASSTHROUGH_ENV_VARS: [&str; 3] = ["XDG_CACHE_HOME", "CARGO_TARGET_DIR", "TMPDIR"];
+
+/// Runtime options for the server, read from `RUST_ANALYZER_MCP_*` environment
+/// variables and overridden by command line flags.
+#[derive(Debug, Clone, Default)]
+pub struct ServerConfig {
+    /// Run `rustup component add rust-src` when the component is missing.
+    pub install_rust_src: bool,
+    /// Append every LSP frame exchanged with rust-analyzer to this file.
+    pub trace_lsp: Option<PathBuf>,
+    /// Log level filter, e.g. `info,rust_analyzer_mcp::lsp=debug`; `info` when unset.
+    pub log_filter: Option<String>,
+    pub log_format: LogFormat,
+    /// Write logs to this file, rotated by size, instead of stderr.
+    pub log_file: Option<PathBuf>,
+    /// Largest `timeout_ms` a tool call may request, in milliseconds.
+    pub max_timeout_ms: Option<u64>,
+    /// Retries for LSP requests failing with transient errors.
+    pub lsp_retries: Option<u32>,
+    /// Delay before the first LSP retry in milliseconds, doubled for each further one.
+    pub lsp_retry_backoff_ms: Option<u64>,
+    /// Files the workspace diagnostics fallback opens at a time.
+    pub diagnostics_parallelism: Option<usize>,
+    /// Window in milliseconds within which saves of a document are coalesced
+    /// into one `didSave`; 0 sends every save right away.
+    pub save_debounce_ms: Option<u64>,
+    /// Start rust-analyzer at launch and hold tool calls until indexing and the
+    /// initial `cargo check` have finished.
+    pub warm_up: bool,
+    /// Leave `cargo check` off on save and run it only through
+    /// `rust_analyzer_run_check`.
+    pub check_on_demand: bool,
+    /// Point rust-analyzer's `CARGO_TARGET_DIR` at [`ISOLATED_TARGET_DIR`] so
+    /// its checks do not wait on the build lock of the user's own cargo runs.
+    pub isolated_target_dir: bool,
+    /// Keep rust-analyzer's cargo runs off the network and skip installing
+    /// rust-src, for sandboxed and air-gapped environments.
+    pub offline: bool,
+    /// Variables set on the rust-analyzer process on top of the inherited
+    /// environment, from `--env NAME=VALUE` or `--env NAME+=DIR`.
+    pub lsp_env: Vec<(String, OsString)>,
+    /// Rustup toolchain rust-analyzer analyzes and checks the workspace with,
+    /// e.g. a pinned nightly.
+    pub toolchain: Option<String>,
+    /// Sysroot rust-analyzer loads the standard library from: a path, or
+    /// `discover` to ask rustc.
+    pub sysroot: Option<String>,
+    /// Path to the `Cargo.toml` of rustc's sources, or `discover`, for
+    /// analyzing crates that use `rustc_private`.
+    pub rustc_source: Option<String>,
+    /// Turn off proc-macro expansion when broken proc-macros destabilize
+    /// rust-analyzer.
+    pub no_proc_macros: bool,
+    /// Proc-macro server binary rust-analyzer expands macros with instead of
+    /// the toolchain's.
+    pub proc_macro_server: Option<PathBuf>,
+    /// Proc-macros left unexpanded, as `(crate, macro)` pairs from
+    /// `--ignore-proc-macro crate::macro`.
+    pub ignored_proc_macros: Vec<(String, String)>,
+    /// Worker threads rust-analyzer answers requests with; its default when
+    /// unset.
+    pub num_threads: Option<usize>,
+    /// Syntax trees rust-analyzer keeps parsed, trading memory for latency.
+    pub lru_capacity: Option<usize>,
+    /// Skip indexing the workspace up front; queries then compute what they
+    /// need on first use.
+    pub no_cache_priming: bool,
+    /// Threads indexing the workspace up front.
+    pub cache_priming_threads: Option<usize>,
+    /// Directories, relative to the workspace root, that rust-analyzer does
+    /// not load and workspace scans skip, such as generated code.
+    pub exclude_dirs: Vec<PathBuf>,
+    /// Answer LSP requests from the built-in mock backend instead of rust-analyzer.
+    /// Only builds with the `mock` feature accept it.
+    pub mock: bool,
+    /// Answer LSP requests from a `--trace-lsp` recording instead of rust-analyzer.
+    pub replay: Option<PathBuf>,
+    /// JSON file declaring extra tools that run commands in the workspace.
+    pub tools_file: Option<PathBuf>,
+    /// Only these tools are listed and callable; all tools when unset.
+    pub allowed_tools: Option<Vec<String>>,
+    /// Tools that are neither listed nor callable.
+    pub denied_tools: Vec<String>,
+    /// Only offer tools that read the workspace: navigation, diagnostics and
+    /// suggested edits, but nothing that builds code or runs commands.
+    pub read_only: bool,
+    /// Wrap every tool result in a versioned envelope naming the tool and workspace.
+    pub response_envelope: bool,
+    /// Serve MCP clients connecting to this address, each in its own session,
+    /// instead of a single client on stdio.
+    pub listen: Option<SocketAddr>,
+    /// Requests a client may have waiting before further ones are refused as busy.
+    pub max_queued_requests: Option<usize>,
+    /// Tool calls a client may have waiting or running before further ones are
+    /// refused as busy.
+    pub max_pending_tool_calls: Option<usize>,
+}
+
+impl ServerConfig {
+    pub fn from_env() -> Result<Self> {
+        let log_format = match std::env::var("RUST_ANALYZER_MCP_LOG_FORMAT") {
+            Ok(format) => LogFormat::parse(&format)?,
+            Err(_) => LogFormat::default(),
+        };
+
+        Ok(Self {
+            install_rust_src: env_flag("RUST_ANALYZER_MCP_INSTALL_RUST_SRC"),
+            trace_lsp: std::env::var_os("RUST_ANALYZER_MCP_TRACE_LSP").map(PathBuf::from),
+            log_filter: std::env::var("RUST_ANALYZER_MCP_LOG")
+                .or_else(|_| std::env::var("RUST_LOG"))
+                .ok(),
+            log_format,
+            log_file: std::env::var_os("RUST_ANALYZER_MCP_LOG_FILE").map(PathBuf::from),
+            max_timeout_ms: match std::env::var("RUST_ANALYZER_MCP_MAX_TIMEOUT_MS") {
+                Ok(value) => Some(parse_millis(&value)?),
+                Err(_) => None,
+            },
+            lsp_retries: match std::env::var("RUST_ANALYZER_MCP_LSP_RETRIES") {
+                Ok(value) => Some(parse_count(&value)?),
+                Err(_) => None,
+            },
+            lsp_retry_backoff_ms: match std::env::var("RUST_ANALYZER_MCP_LSP_RETRY_BACKOFF_MS") {
+                Ok(value) => Some(parse_millis(&value)?),
+                Err(_) => None,
+            },
+            diagnostics_parallelism: match std::env::var(
+                "RUST_ANALYZER_MCP_DIAGNOSTICS_PARALLELISM",
+            ) {
+                Ok(value) => Some(parse_limit(&value)?),
+                Err(_) => None,
+            },
+            save_debounce_ms: match std::env::var("RUST_ANALYZER_MCP_SAVE_DEBOUNCE_MS") {
+                Ok(value) => Some(parse_delay(&value)?),
+                Err(_) => None,
+            },
+            warm_up: env_flag("RUST_ANALYZER_MCP_WARM_UP"),
+            check_on_demand: env_flag("RUST_ANALYZER_MCP_CHECK_ON_DEMAND"),
+            isolated_target_dir: env_flag("RUST_ANALYZER_MCP_ISOLATED_TARGET_DIR"),
+            offline: env_flag("RUST_ANALYZER_MCP_OFFLINE"),
+            lsp_env: match std::env::var("RUST_ANALYZER_MCP_ENV") {
+                Ok(value) => parse_env_settings(&value)?,
+                Err(_) => Vec::new(),
+            },
+            toolchain: std::env::var("RUST_ANALYZER_MCP_TOOLCHAIN").ok(),
+            sysroot: std::env::var("RUST_ANALYZER_MCP_SYSROOT").ok(),
+            rustc_source: std::env::var("RUST_ANALYZER_MCP_RUSTC_SOURCE").ok(),
+            no_proc_macros: env_flag("RUST_ANALYZER_MCP_NO_PROC_MACROS"),
+            num_threads: match std::env::var("RUST_ANALYZER_MCP_NUM_THREADS") {
+                Ok(value) => Some(parse_limit(&value)?),
+                Err(_) => None,
+            },
+            lru_capacity: match std::env::var("RUST_ANALYZER_MCP_LRU_CAPACITY") {
+                Ok(value) => Some(parse_limit(&value)?),
+                Err(_) => None,
+            },
+            no_cache_priming: env_flag("RUST_ANALYZER_MCP_NO_CACHE_PRIMING"),
+            exclude_dirs: std::env::var("RUST_ANALYZER_MCP_EXCLUDE_DIRS")
+                .map(|value| {
+                    parse_tool_list(&value)
+                        .into_iter()
+                        .map(PathBuf::from)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            cache_priming_threads: match std::env::var("RUST_ANALYZER_MCP_CACHE_PRIMING_THREADS") {
+                Ok(value) => Some(parse_limit(&value)?),
+                Err(_) => None,
+            },
+            proc_macro_server: std::env::var_os("RUST_ANALYZER_MCP_PROC_MACRO_SERVER")
+                .map(PathBuf::from),
+            ignored_proc_macros: match std::env::var("RUST_ANALYZER_MCP_IGNORE_PROC_MACROS") {
+                Ok(value) => parse_tool_list(&value)
+                    .iter()
+                    .map(|name| parse_proc_macro_name(name))
+                    .collect::<Result<_>>()?,
+                Err(_) => Vec::new(),
+            },
+            mock: env_flag("RUST_ANALYZER_MCP_MOCK"),
+            replay: std::env::var_os("RUST_ANALYZER_MCP_REPLAY").map(PathBuf::from),
+            tools_file: std::env::var_os("RUST_ANALYZER_MCP_TOOLS_FILE").map(PathBuf::from),
+            allowed_tools: std::env::var("RUST_ANALYZER_MCP_ALLOW_TOOLS")
+                .ok()
+                .map(|value| parse_tool_list(&value)),
+            denied_tools: std::env::var("RUST_ANALYZER_MCP_DENY_TOOLS")
+                .map(|value| parse_tool_list(&value))
+                .unwrap_or_default(),
+            read_only: env_flag("RUST_ANALYZER_MCP_READ_ONLY"),
+            response_envelope: env_flag("RUST_ANALYZER_MCP_RESPONSE_ENVELOPE"),
+            listen: match std::env::var("RUST_ANALYZER_MCP_LISTEN") {
+                Ok(value) => Some(parse_listen_address(&value)?),
+                Err(_) => None,
+            },
+            max_queued_requests: match std::env::var("RUST_ANALYZER_MCP_MAX_QUEUED_REQUESTS") {
+                Ok(value) => Some(parse_limit(&value)?),
+                Err(_) => None,
+            },
+            max_pending_tool_calls: match std::env::var("RUST_ANALYZER_MCP_MAX_PENDING_TOOL_CALLS")
+            {
+                Ok(value) => Some(parse_limit(&value)?),
+                Err(_) => None,
+            },
+        })
+    }
+
+    /// Whether LSP requests go to a real rust-analyzer process rather than the
+    /// mock or replay backend.
+    pub fn spawns_rust_analyzer(&self) -> bool {
+        !self.mock && self.replay.is_none()
+    }
+
+    /// The LSP server tool calls are answered by.
+    pub fn lsp_backend(&self) -> LspBackend {
+        #[cfg(feature = "mock")]
+        if self.mock {
+            return LspBackend::Mock;
+        }
+        match &self.replay {
+            Some(recording) => LspBackend::Replay {
+                recording: recording.clone(),
+            },
+            None => LspBackend::default(),
+        }
+    }
+
+    /// How LSP requests failing with content-modified or server-cancelled
+    /// errors are retried.
+    pub fn retry_policy(&self) -> RetryPolicy {
+        let mut policy = RetryPolicy::default();
+        if let Some(retries) = self.lsp_retries {
+            policy.max_retries = retries;
+        }
+        if let Some(backoff_ms) = self.lsp_retry_backoff_ms {
+            policy.initial_backoff = Duration::from_millis(backoff_ms);
+        }
+        policy
+    }
+
+    /// Whether the tool `name` may be listed and called. `read_only_tool` tells
+    /// whether it only reads the workspace.
+    pub fn tool_enabled(&self, name: &str, read_only_tool: bool) -> bool {
+        let allowed = self
+            .allowed_tools
+            .as_ref()
+            .is_none_or(|tools| tools.iter().any(|tool| tool == name));
+        allowed
+            && !self.denied_tools.iter().any(|tool| tool == name)
+            && (read_only_tool || !self.read_only)
+    }
+
+    /// The largest per-call timeout tools accept.
+    pub fn max_tool_timeout(&self) -> Duration {
+        self.max_timeout_ms
+            .map(Duration::from_millis)
+            .unwrap_or(Duration::from_secs(DEFAULT_MAX_TOOL_TIMEOUT_SECS))
+    }
+
+    /// Parses the binary's command line (without the program name), returning the
+    /// optional workspace path and the configuration.
+    pub fn from_args<I>(args: I) -> Result<(Option<PathBuf>, Self)>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut config = Self::from_env()?;
+        let mut workspace = None;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--install-rust-src" => config.install_rust_src = true,
+                "--trace-lsp" | "--record" => {
+                    let Some(path) = args.next() else {
+                        return Err(anyhow!("{} requires a file path", arg));
+                    };
+                    config.trace_lsp = Some(PathBuf::from(path));
+                }
+                "--replay" => {
+                    let Some(path) = args.next() else {
+                        return Err(anyhow!("--replay requires a recording file path"));
+                    };
+                    config.replay = Some(PathBuf::from(path));
+                }
+                "--log" => {
+                    let Some(filter) = args.next() else {
+                        return Err(anyhow!("--log requires a level filter"));
+                    };
+                    config.log_filter = Some(filter);
+                }
+                "--log-format" => {
+                    let Some(format) = args.next() else {
+                        return Err(anyhow!("--log-format requires human or json"));
+                    };
+                    config.log_format = LogFormat::parse(&format)?;
+                }
+                "--log-file" => {
+                    let Some(path) = args.next() else {
+                        return Err(anyhow!("--log-file requires a file path"));
+                    };
+                    config.log_file = Some(PathBuf::from(path));
+                }
+                "--max-timeout-ms" => {
+                    let Some(value) = args.next() else {
+                        return Err(anyhow!(
+                            "--max-timeout-ms requires a number of milliseconds"
+                        ));
+                    };
+                    config.max_timeout_ms = Some(parse_millis(&value)?);
+                }
+                "--lsp-retries" => {
+                    let Some(value) = args.next() else {
+                        return Err(anyhow!("--lsp-retries requires a number"));
+                    };
+                    config.lsp_retries = Some(parse_count(&value)?);
+                }
+                "--lsp-retry-backoff-ms" => {
+                    let Some(value) = args.next() else {
+                        return Err(anyhow!(
+                            "--lsp-retry-backoff-ms requires a number of milliseconds"
+                        ));
+                    };
+                    config.lsp_retry_backoff_ms = Some(parse_millis(&value)?);
+                }
+                "--diagnostics-parallelism" => {
+                    let Some(value) = args.next() else {
+                        return Err(anyhow!("--diagnostics-parallelism requires a number"));
+                    };
+                    config.diagnostics_parallelism = Some(parse_limit(&value)?);
+                }
+                "--save-debounce-ms" => {
+                    let Some(value) = args.next() else {
+                        return Err(anyhow!(
+                            "--save-debounce-ms requires a number of milliseconds"
+                        ));
+                    };
+                    config.save_debounce_ms = Some(parse_delay(&value)?);
+                }
+                "--tools-file" => {
+                    let Some(path) = args.next() else {
+                        return Err(anyhow!("--tools-file requires a file path"));
+                    };
+                    config.tools_file = Some(PathBuf::from(path));
+                }
+                "--allow-tools" | "--deny-tools" => {
+                    let Some(value) = args.next() else {
+                        return Err(anyhow!("{} requires a comma-separated list of tools", arg));
+                    };
+                    if arg == "--allow-tools" {
+                        config.allowed_tools = Some(parse_tool_list(&value));
+                    } else {
+                        config.denied_tools = parse_tool_list(&value);
+                    }
+                }
+                "--read-only" => config.read_only = true,
+                "--response-envelope" => config.response_envelope = true,
+                "--listen" => {
+                    let Some(value) = args.next() else {
+                        return Err(anyhow!(
+                            "--listen requires an address such as 127.0.0.1:7878"
+                        ));
+                    };
+                    config.listen = Some(parse_listen_address(&value)?);
+                }
+                "--max-queued-requests" | "--max-pending-tool-calls" => {
+                    let Some(value) = args.next() else {
+                        return Err(anyhow!("{} requires a number", arg));
+                    };
+                    let limit = parse_limit(&value)?;
+                    if arg == "--max-queued-requests" {
+                        config.max_queued_requests = Some(limit);
+                    } else {
+                        config.max_pending_tool_calls = Some(limit);
+                    }
+                }
+                "--warm-up" => config.warm_up = true,
+                "--check-on-demand" => config.check_on_demand = true,
+                "--isolated-target-dir" => config.isolated_target_dir = true,
+                "--offline" => config.offline = true,
+                "--no-proc-macros" => config.no_proc_macros = true,
+                "--num-threads" | "--lru-capacity" | "--cache-priming-threads" => {
+                    let Some(value) = args.next() else {
+                        return Err(anyhow!("{} requires a number", arg));
+                    };
+                    let limit = Some(parse_limit(&value)?);
+                    match arg.as_str() {
+                        "--num-threads" => config.num_threads = limit,
+                        "--lru-capacity" => config.lru_capacity = limit,
+                        _ => config.cache_priming_threads = limit,
+                    }
+                }
+                "--no-cache-priming" => config.no_cache_priming = true,
+                "--exclude-dir" => {
+                    let Some(dir) = args.next() else {
+                        return Err(anyhow!("--exclude-dir requires a directory"));
+                    };
+                    config.exclude_dirs.push(PathBuf::from(dir));
+                }
+                "--proc-macro-server" => {
+                    let Some(path) = args.next() else {
+                        return Err(anyhow!("--proc-macro-server requires a binary path"));
+                    };
+                    config.proc_macro_server = Some(PathBuf::from(path));
+                }
+                "--ignore-proc-macro" => {
+                    let Some(name) = args.next() else {
+                        return Err(anyhow!("--ignore-proc-macro requires crate::macro"));
+                    };
+                    config
+                        .ignored_proc_macros
+                        .push(parse_proc_macro_name(&name)?);
+                }
+                "--toolchain" => {
+                    let Some(toolchain) = args.next() else {
+                        return Err(anyhow!("--toolchain requires a toolchain name"));
+                    };
+                    config.toolchain = Some(toolchain);
+                }
+                "--sysroot" | "--rustc-source" => {
+                    let Some(path) = args.next() else {
+                        return Err(anyhow!("{} requires a path or `discover`", arg));
+                    };
+                    if arg == "--sysroot" {
+                        config.sysroot = Some(path);
+                    } else {
+                        config.rustc_source = Some(path);
+                    }
+                }
+                "--env" => {
+                    let Some(setting) = args.next() else {
+                        return Err(anyhow!("--env requires NAME=VALUE or NAME+=DIR"));
+                    };
+                    config.lsp_env.push(parse_env_setting(&setting)?);
+                }
+                "--mock" => config.mock = true,
+                flag if flag.starts_with("--") => return Err(anyhow!("Unknown option: {}", flag)),
+                path => {
+                    if workspace.is_some() {
+                        return Err(anyhow!("Unexpected argument: {}", path));
+                    }
+                    workspace = Some(PathBuf::from(path));
+                }
+            }
+        }
+
+        if config.mock && config.replay.is_some() {
+            return Err(anyhow!("--mock and --replay cannot be combined"));
+        }
+        if config.replay.is_some() && config.replay == config.trace_lsp {
+            return Err(anyhow!("--replay cannot read the file being recorded to"));
+        }
+        if config.mock && !cfg!(feature = "mock") {
+            return Err(anyhow!(
+                "--mock is unavailable: the server was built without the `mock` feature"
+            ));
+        }
+
+        Ok((workspace, config))
+    }
+}
+
+fn parse_millis(value: &str) -> Result<u64> {
+    match value.parse::<u64>() {
+        Ok(millis) if millis > 0 => Ok(millis),
+        _ => Err(anyhow!(
+            "Invalid timeout '{}': expected milliseconds",
+            value
+        )),
+    }
+}
+
+fn parse_delay(value: &str) -> Result<u64> {
+    value
+        .parse::<u64>()
+        .map_err(|_| anyhow!("Invalid delay '{}': expected milliseconds", value))
+}
+
+fn parse_count(value: &str) -> Result<u32> {
+    value
+        .parse::<u32>()
+        .map_err(|_| anyhow!("Invalid count '{}': expected a non-negative number", value))
+}
+
+fn parse_limit(value: &str) -> Result<usize> {
+    match value.parse::<usize>() {
+        Ok(limit) if limit > 0 => Ok(limit),
+        _ => Err(anyhow!(
+            "Invalid limit '{}': expected a positive number",
+            value
+        )),
+    }
+}
+
+fn parse_listen_address(value: &str) -> Result<SocketAddr> {
+    value.parse().map_err(|_| {
+        anyhow!(
+            "Invalid listen address '{}': expected an IP address and port",
+            value
+        )
+    })
+}
+
+/// Parses one `NAME=VALUE` or `NAME+=DIR` setting per line. Values such as
+/// `RUSTFLAGS` may contain commas and spaces, but not newlines.
+fn parse_env_settings(value: &str) -> Result<Vec<(String, OsString)>> {
+    value
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(parse_env_setting)
+        .collect()
+}
+
+/// Parses `NAME=VALUE`, or `NAME+=DIR`, which puts `DIR` in front of the
+/// server's own value of a path list such as `PATH`.
+fn parse_env_setting(setting: &str) -> Result<(String, OsString)> {
+    let invalid = || {
+        anyhow!(
+            "Invalid --env '{}': expected NAME=VALUE or NAME+=DIR",
+            setting
+        )
+    };
+    let (name, value) = setting.split_once('=').ok_or_else(invalid)?;
+    let (name, prepend) = match name.strip_suffix('+') {
+        Some(name) => (name.trim(), true),
+        None => (name.trim(), false),
+    };
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err(invalid());
+    }
+    if !prepend {
+        return Ok((name.to_string(), OsString::from(value)));
+    }
+
+    let mut dirs = vec![PathBuf::from(value)];
+    if let Some(inherited) = std::env::var_os(name) {
+        dirs.extend(std::env::split_paths(&inherited));
+    }
+    let joined =
+        std::env::join_paths(dirs).map_err(|e| anyhow!("Invalid --env '{}': {}", setting, e))?;
+    Ok((name.to_string(), joined))
+}
+
+/// Parses `crate::macro` into the crate and macro names.
+fn parse_proc_macro_name(value: &str) -> Result<(String, String)> {
+    match value.split_once("::") {
+        Some((krate, name)) if !krate.is_empty() && !name.is_empty() => {
+            Ok((krate.to_string(), name.to_string()))
+        }
+        _ => Err(anyhow!(
+            "Invalid proc-macro '{}': expected crate::macro, e.g. async_trait::async_trait",
+            value
+        )),
+    }
+}
+
+fn parse_tool_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn env_flag(name: &str) -> bool {
+    std::env::var(name)
+        .map(|value| {
+            matches!(
+                value.to_ascii_lowercase().as_str(),
+                "1" | "true" | "yes" | "on"
+            )
+        })
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, time::Duration};
+
+    use super::{parse_env_settings, LspBackend, ServerConfig};
+    use crate::logging::LogFormat;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn test_from_args_parses_workspace_and_flags() {
+        let (workspace, config) =
+            ServerConfig::from_args(args(&["/tmp/project", "--install-rust-src"])).unwrap();
+
+        assert_eq!(workspace, Some(PathBuf::from("/tmp/project")));
+        assert!(config.install_rust_src);
+        assert!(!config.warm_up);
+
+        let (_, config) = ServerConfig::from_args(args(&["--warm-up"])).unwrap();
+        assert!(config.warm_up);
+        let (_, config) = ServerConfig::from_args(args(&["--check-on-demand"])).unwrap();
+        assert!(config.check_on_demand);
+        let (_, config) = ServerConfig::from_args(args(&["--isolated-target-dir"])).unwrap();
+        assert!(config.isolated_target_dir);
+        let (_, config) = ServerConfig::from_args(args(&["--offline"])).unwrap();
+        assert!(config.offline);
+        assert_eq!(
+            ServerConfig::from_args(args(&["--mock"])).is_ok(),
+            cfg!(feature = "mock")
+        );
+    }
+
+    #[test]
+    fn test_from_args_parses_trace_lsp_file() {
+        let (workspace, config) =
+            ServerConfig::from_args(args(&["--trace-lsp", "/tmp/lsp.jsonl", "/tmp/project"]))
+                .unwrap();
+
+        assert_eq!(workspace, Some(PathBuf::from("/tmp/project")));
+        assert_eq!(config.trace_lsp, Some(PathBuf::from("/tmp/lsp.jsonl")));
+        assert!(ServerConfig::from_args(args(&["--trace-lsp"])).is_err());
+
+        let (_, config) =
+            ServerConfig::from_args(args(&["--tools-file", "/tmp/tools.json"])).unwrap();
+        assert_eq!(config.tools_file, Some(PathBuf::from("/tmp/tools.json")));
+        assert!(ServerConfig::from_args(args(&["--tools-file"])).is_err());
+    }
+
+    #[test]
+    fn test_from_args_parses_record_and_replay() {
+        let (_, config) = ServerConfig::from_args(args(&[
+            "--replay",
+            "/tmp/session.jsonl",
+            "--record",
+            "/tmp/again.jsonl",
+        ]))
+        .unwrap();
+
+        assert_eq!(config.replay, Some(PathBuf::from("/tmp/session.jsonl")));
+        assert_eq!(config.trace_lsp, Some(PathBuf::from("/tmp/again.jsonl")));
+        assert!(!config.spawns_rust_analyzer());
+        assert_eq!(
+            config.lsp_backend(),
+            LspBackend::Replay {
+                recording: PathBuf::from("/tmp/session.jsonl")
+            }
+        );
+        assert!(ServerConfig::from_args(args(&[
+            "--replay",
+            "/tmp/session.jsonl",
+            "--record",
+            "/tmp/session.jsonl"
+        ]))
+        .is_err());
+    }
+
+    #[test]
+    fn test_from_args_parses_logging_options() {
+        let (_, config) = ServerConfig::from_args(args(&[
+            "--log",
+            "warn,rust_analyzer_mcp::lsp=debug",
+            "--log-format",
+            "json",
+            "--log-file",
+            "/tmp/rust-analyzer-mcp.log",
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            config.log_filter.as_deref(),
+            Some("warn,rust_analyzer_mcp::lsp=debug")
+        );
+        assert_eq!(config.log_format, LogFormat::Json);
+        assert_eq!(
+            config.log_file,
+            Some(PathBuf::from("/tmp/rust-analyzer-mcp.log"))
+        );
+        assert!(ServerConfig::from_args(args(&["--log-format", "xml"])).is_err());
+    }
+
+    #[test]
+    fn test_from_args_parses_max_timeout() {
+        let (_, config) = ServerConfig::from_args(args(&["--max-timeout-ms", "120000"])).unwrap();
+
+        assert_eq!(config.max_tool_timeout(), Duration::from_secs(120));
+        assert_eq!(
+            ServerConfig::default().max_tool_timeout(),
+            Duration::from_secs(600)
+        );
+        assert!(ServerConfig::from_args(args(&["--max-timeout-ms", "0"])).is_err());
+    }
+
+    #[test]
+    fn test_from_args_parses_retry_policy() {
+        let (_, config) = ServerConfig::from_args(args(&[
+            "--lsp-retries",
+            "0",
+            "--lsp-retry-backoff-ms",
+            "250",
+        ]))
+        .unwrap();
+
+        let policy = config.retry_policy();
+        assert_eq!(policy.max_retries, 0);
+        assert_eq!(policy.initial_backoff, Duration::from_millis(250));
+        assert_eq!(ServerConfig::default().retry_policy().max_retries, 3);
+        assert!(ServerConfig::from_args(args(&["--lsp-retries", "-1"])).is_err());
+    }
+
+    #[test]
+    fn test_from_args_parses_diagnostics_parallelism() {
+        let (_, config) =
+            ServerConfig::from_args(args(&["--diagnostics-parallelism", "4"])).unwrap();
+
+        assert_eq!(config.diagnostics_parallelism, Some(4));
+        assert!(ServerConfig::from_args(args(&["--diagnostics-parallelism", "0"])).is_err());
+    }
+
+    #[test]
+    fn test_from_args_parses_save_debounce() {
+        let (_, config) = ServerConfig::from_args(args(&["--save-debounce-ms", "0"])).unwrap();
+
+        assert_eq!(config.save_debounce_ms, Some(0));
+        assert!(ServerConfig::from_args(args(&["--save-debounce-ms", "soon"])).is_err());
+    }
+
+    #[test]
+    fn test_from_args_parses_tool_filters() {
+        let (_, config) = ServerConfig::from_args(args(&[
+            "--allow-tools",
+            "rust_analyzer_hover, rust_analyzer_cargo_check,",
+            "--deny-tools",
+            "rust_analyzer_cargo_check",
+            "--read-only",
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            config.allowed_tools,
+            Some(vec![
+                "rust_analyzer_hover".to_string(),
+                "rust_analyzer_cargo_check".to_string()
+            ])
+        );
+        assert!(config.tool_enabled("rust_analyzer_hover", true));
+        assert!(!config.tool_enabled("rust_analyzer_definition", true));
+        assert!(!config.tool_enabled("rust_analyzer_cargo_check", true));
+
+        let (_, config) = ServerConfig::from_args(args(&["--read-only"])).unwrap();
+        assert!(config.tool_enabled("rust_analyzer_definition", true));
+        assert!(!config.tool_enabled("rust_analyzer_cargo_build", false));
+        assert!(ServerConfig::from_args(args(&["--deny-tools"])).is_err());
+    }
+
+    #[test]
+    fn test_from_args_parses_response_envelope() {
+        let (_, config) = ServerConfig::from_args(args(&["--response-envelope"])).unwrap();
+
+        assert!(config.response_envelope);
+        assert!(!ServerConfig::default().response_envelope);
+    }
+
+    #[test]
+    fn test_from_args_parses_listen_address() {
+        let (_, config) = ServerConfig::from_args(args(&["--listen", "127.0.0.1:7878"])).unwrap();
+
+        assert_eq!(config.listen, Some("127.0.0.1:7878".parse().unwrap()));
+        assert!(ServerConfig::from_args(args(&["--listen", "localhost"])).is_err());
+        assert!(ServerConfig::from_args(args(&["--listen"])).is_err());
+    }
+
+    #[test]
+    fn test_from_args_parses_queue_limits() {
+        let (_, config) = ServerConfig::from_args(args(&[
+            "--max-queued-requests",
+            "32",
+            "--max-pending-tool-calls",
+            "4",
+        ]))
+        .unwrap();
+
+        assert_eq!(config.max_queued_requests, Some(32));
+        assert_eq!(config.max_pending_tool_calls, Some(4));
+        assert_eq!(ServerConfig::default().max_queued_requests, None);
+        assert!(ServerConfig::from_args(args(&["--max-queued-requests", "0"])).is_err());
+        assert!(ServerConfig::from_args(args(&["--max-pending-tool-calls"])).is_err());
+    }
+
+    #[test]
+    fn test_from_args_parses_env_settings() {
+        let (_, config) = ServerConfig::from_args(args(&[
+            "--env",
+            "RUSTFLAGS=-C target-cpu=native",
+            "--env",
+            "PATH+=/opt/tools/bin",
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            config.lsp_env[0],
+            ("RUSTFLAGS".to_string(), "-C target-cpu=native".into())
+        );
+        let (name, path) = &config.lsp_env[1];
+        assert_eq!(name, "PATH");
+        let dirs: Vec<PathBuf> = std::env::split_paths(path).collect();
+        assert_eq!(dirs[0], PathBuf::from("/opt/tools/bin"));
+        assert!(ServerConfig::from_args(args(&["--env", "RUSTFLAGS"])).is_err());
+        assert!(ServerConfig::from_args(args(&["--env", "=1"])).is_err());
+        assert!(ServerConfig::from_args(args(&["--env"])).is_err());
+    }
+
+    #[test]
+    fn test_env_settings_are_split_by_line() {
+        let settings =
+            parse_env_settings("RUSTFLAGS=-C link-arg=-Wl,--as-needed\n\nCARGO_HOME=/opt/cargo\n")
+                .unwrap();
+
+        assert_eq!(
+            settings,
+            [
+                (
+                    "RUSTFLAGS".to_string(),
+                    "-C link-arg=-Wl,--as-needed".into()
+                ),
+                ("CARGO_HOME".to_string(), "/opt/cargo".into()),
+            ]
+        );
+        assert!(parse_env_settings("RUSTFLAGS=-C opt-level=2\nNOT_A_SETTING").is_err());
+    }
+
+    #[test]
+    fn test_from_args_parses_toolchain_overrides() {
+        let (_, config) = ServerConfig::from_args(args(&[
+            "--toolchain",
+            "nightly-2024-06-01",
+            "--sysroot",
+            "discover",
+            "--rustc-source",
+            "/src/rust/Cargo.toml",
+        ]))
+        .unwrap();
+
+        assert_eq!(config.toolchain.as_deref(), Some("nightly-2024-06-01"));
+        assert_eq!(config.sysroot.as_deref(), Some("discover"));
+        assert_eq!(config.rustc_source.as_deref(), Some("/src/rust/Cargo.toml"));
+        assert!(ServerConfig::from_args(args(&["--toolchain"])).is_err());
+    }
+
+    #[test]
+    fn test_from_args_parses_proc_macro_settings() {
+        let (_, config) = ServerConfig::from_args(args(&[
+            "--no-proc-macros",
+            "--proc-macro-server",
+            "/opt/ra/proc-macro-srv",
+            "--ignore-proc-macro",
+            "async_trait::async_trait",
+        ]))
+        .unwrap();
+
+        assert!(config.no_proc_macros);
+        assert_eq!(
+            config.proc_macro_server,
+            Some(PathBuf::from("/opt/ra/proc-macro-srv"))
+        );
+        assert_eq!(
+            config.ignored_proc_macros,
+            [("async_trait".to_string(), "async_trait".to_string())]
+        );
+        assert!(ServerConfig::from_args(args(&["--ignore-proc-macro", "async_trait"])).is_err());
+    }
+
+    #[test]
+    fn test_from_args_parses_performance_settings() {
+        let (_, config) = ServerConfig::from_args(args(&[
+            "--num-threads",
+            "8",
+            "--lru-capacity",
+            "2048",
+            "--cache-priming-threads",
+            "2",
+            "--no-cache-priming",
+        ]))
+        .unwrap();
+
+        assert_eq!(config.num_threads, Some(8));
+        assert_eq!(config.lru_capacity, Some(2048));
+        assert_eq!(config.cache_priming_threads, Some(2));
+        assert!(config.no_cache_priming);
+        assert!(ServerConfig::from_args(args(&["--num-threads", "0"])).is_err());
+        assert!(ServerConfig::from_args(args(&["--lru-capacity"])).is_err());
+    }
+
+    #[test]
+    fn test_from_args_collects_excluded_dirs() {
+        let (_, config) = ServerConfig::from_args(args(&[
+            "--exclude-dir",
+            "generated",
+            "--exclude-dir",
+            "vendor/bindings",
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            config.exclude_dirs,
+            [PathBuf::from("generated"), PathBuf::from("vendor/bindings")]
+        );
+        assert!(ServerConfig::from_args(args(&["--exclude-dir"])).is_err());
+    }
+
+    #[test]
+    fn test_from_args_rejects_unknown_options() {
+        assert!(ServerConfig::from_args(args(&["--bogus"])).is_err());
+        assert!(ServerConfig::from_args(args(&["/a", "/b"])).is_err());
+    }
+}
@@ -0,0 +1,48 @@
+//! The time budget of a tool call, given with its `timeout_ms` argument.
+//!
+//! The budget bounds how long the call waits for rust-analyzer's responses and
+//! for the commands it runs. It never interrupts work in progress, so messages
+//! to rust-analyzer are always written whole and edits are applied completely.
+
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+
+tokio::task_local! {
+    static DEADLINE: Instant;
+}
+
+/// Runs `future` with a budget that ends at `deadline`.
+pub async fn with_deadline<F: Future>(deadline: Instant, future: F) -> F::Output {
+    DEADLINE.scope(deadline, future).await
+}
+
+/// How long to wait for a response or command that is otherwise given
+/// `default`. Within a budget, what is left of it replaces the default.
+pub fn wait_limit(default: Duration) -> Duration {
+    DEADLINE
+        .try_with(|deadline| deadline.saturating_duration_since(Instant::now()))
+        .unwrap_or(default)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{wait_limit, with_deadline};
+
+    #[tokio::test]
+    async fn test_wait_limit_is_what_is_left_of_the_budget() {
+        let default = Duration::from_secs(30);
+        assert_eq!(wait_limit(default), default);
+
+        let deadline = Instant::now() + Duration::from_secs(120);
+        let limit = with_deadline(deadline, async { wait_limit(default) }).await;
+        assert!(limit > default && limit <= Duration::from_secs(120));
+
+        let elapsed = Instant::now() - Duration::from_secs(1);
+        let limit = with_deadline(elapsed, async { wait_limit(default) }).await;
+        assert_eq!(limit, Duration::ZERO);
+    }
+}
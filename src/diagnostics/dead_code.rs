@@ -0,0 +1,138 @@
+use serde_json::{json, Value};
+use std::{collections::BTreeMap, path::Path};
+
+use crate::symbols::{crate_root_for_file, module_path_for_file, CrateRoot};
+
+/// Whether a rustc lint code reports unused code, e.g. `dead_code`,
+/// `unused_imports` or `unused_variables`.
+fn is_dead_code_lint(code: &str) -> bool {
+    code == "dead_code" || code.starts_with("unused_")
+}
+
+/// Groups the unused-code warnings among cargo compiler diagnostics by crate
+/// and module, with a per-lint count.
+pub fn dead_code_report(diagnostics: &[Value], roots: &[CrateRoot]) -> Value {
+    let mut lints: BTreeMap<String, u64> = BTreeMap::new();
+    let mut crates: BTreeMap<String, BTreeMap<String, Vec<Value>>> = BTreeMap::new();
+    let mut total = 0;
+
+    for diagnostic in diagnostics {
+        let Some(code) = diagnostic["code"]
+            .as_str()
+            .filter(|code| is_dead_code_lint(code))
+        else {
+            continue;
+        };
+        let file = diagnostic["file"].as_str().unwrap_or_default();
+        let (crate_name, module) = module_of(roots, Path::new(file));
+
+        *lints.entry(code.to_string()).or_default() += 1;
+        total += 1;
+        crates
+            .entry(crate_name)
+            .or_default()
+            .entry(module)
+            .or_default()
+            .push(json!({
+                "lint": code,
+                "item": quoted_name(diagnostic["message"].as_str().unwrap_or_default()),
+                "message": diagnostic["message"],
+                "file": file,
+                "range": diagnostic["range"]
+            }));
+    }
+
+    let crates: Vec<Value> = crates
+        .into_iter()
+        .map(|(crate_name, modules)| {
+            let count: usize = modules.values().map(Vec::len).sum();
+            let modules: Vec<Value> = modules
+                .into_iter()
+                .map(|(module, items)| json!({ "module": module, "items": items }))
+                .collect();
+            json!({ "crate": crate_name, "count": count, "modules": modules })
+        })
+        .collect();
+
+    json!({
+        "total": total,
+        "lints": lints,
+        "crates": crates
+    })
+}
+
+/// Resolves the crate and `crate::`-prefixed module a file belongs to. Files
+/// outside every crate root are reported under their path.
+fn module_of(roots: &[CrateRoot], file: &Path) -> (String, String) {
+    let resolved = crate_root_for_file(roots, file).and_then(|root| {
+        let segments = module_path_for_file(&root.src_path, file)?;
+        let module = std::iter::once("crate".to_string())
+            .chain(segments)
+            .collect::<Vec<_>>()
+            .join("::");
+        Some((root.name.clone(), module))
+    });
+    resolved.unwrap_or_else(|| ("<unknown>".to_string(), file.display().to_string()))
+}
+
+/// Extracts the first backquoted name from a lint message such as
+/// "function `helper` is never used".
+fn quoted_name(message: &str) -> Option<&str> {
+    let (_, rest) = message.split_once('`')?;
+    let (name, _) = rest.split_once('`')?;
+    Some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::dead_code_report;
+    use crate::symbols::crate_roots;
+
+    #[test]
+    fn test_dead_code_report_groups_by_crate_and_module() {
+        let roots = crate_roots(&json!({
+            "packages": [{
+                "name": "demo",
+                "targets": [{ "name": "demo", "kind": ["lib"], "src_path": "/ws/src/lib.rs" }]
+            }]
+        }));
+        let diagnostic = |code: &str, message: &str, file: &str| {
+            json!({
+                "level": "warning",
+                "code": code,
+                "message": message,
+                "file": file,
+                "range": { "start": { "line": 3, "character": 3 }, "end": { "line": 3, "character": 9 } }
+            })
+        };
+        let diagnostics = [
+            diagnostic(
+                "dead_code",
+                "function `helper` is never used",
+                "/ws/src/net/http.rs",
+            ),
+            diagnostic(
+                "unused_imports",
+                "unused import: `std::fmt`",
+                "/ws/src/net/http.rs",
+            ),
+            diagnostic("unused_variables", "unused variable: `x`", "/ws/src/lib.rs"),
+            diagnostic("E0308", "mismatched types", "/ws/src/lib.rs"),
+        ];
+
+        let report = dead_code_report(&diagnostics, &roots);
+
+        assert_eq!(report["total"], 3);
+        assert_eq!(report["lints"]["dead_code"], 1);
+        assert_eq!(report["lints"]["unused_imports"], 1);
+        let demo = &report["crates"][0];
+        assert_eq!(demo["crate"], "demo");
+        assert_eq!(demo["count"], 3);
+        assert_eq!(demo["modules"][0]["module"], "crate");
+        assert_eq!(demo["modules"][1]["module"], "crate::net::http");
+        assert_eq!(demo["modules"][1]["items"][0]["item"], "helper");
+        assert_eq!(demo["modules"][1]["items"][1]["item"], "std::fmt");
+    }
+}
@@ -0,0 +1,108 @@
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+use super::severity_label;
+use crate::{lsp::path_from_uri, protocol::lsp::Diagnostic};
+
+/// Compares the diagnostics rust-analyzer published for checks run under
+/// several feature sets. `runs` pairs each feature set's label with its
+/// diagnostics by file URI. Errors and warnings reported under every set are
+/// listed once in `common`; the others go to `specific` with the labels of the
+/// sets reporting them.
+pub fn feature_matrix_report(runs: &[(String, BTreeMap<String, Vec<Diagnostic>>)]) -> Value {
+    // Keyed by what identifies a diagnostic across runs, in first-seen order.
+    let mut seen: BTreeMap<String, (usize, Value, Vec<&str>)> = BTreeMap::new();
+    for (label, result) in runs {
+        for (uri, diagnostics) in result {
+            for diagnostic in diagnostics {
+                let level = severity_label(diagnostic.severity);
+                if level != "error" && level != "warning" {
+                    continue;
+                }
+                let file = path_from_uri(uri)
+                    .map(|path| path.display().to_string())
+                    .unwrap_or_else(|| uri.clone());
+                let summary = json!({
+                    "level": level,
+                    "code": diagnostic.code.as_ref().map(ToString::to_string),
+                    "message": diagnostic.message,
+                    "file": file,
+                    "range": diagnostic.range
+                });
+                let order = seen.len();
+                let entry = seen
+                    .entry(summary.to_string())
+                    .or_insert_with(|| (order, summary, Vec::new()));
+                if !entry.2.contains(&label.as_str()) {
+                    entry.2.push(label);
+                }
+            }
+        }
+    }
+
+    let mut entries: Vec<_> = seen.into_values().collect();
+    entries.sort_by_key(|(order, _, _)| *order);
+    let mut common = Vec::new();
+    let mut specific = Vec::new();
+    for (_, mut summary, labels) in entries {
+        if labels.len() == runs.len() {
+            common.push(summary);
+        } else {
+            summary["feature_sets"] = json!(labels);
+            specific.push(summary);
+        }
+    }
+
+    json!({
+        "common": common,
+        "specific": specific
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    use super::feature_matrix_report;
+    use crate::protocol::lsp::Diagnostic;
+
+    fn diagnostic(severity: u64, message: &str, line: u64) -> Diagnostic {
+        serde_json::from_value(json!({
+            "severity": severity,
+            "message": message,
+            "range": { "start": { "line": line, "character": 0 }, "end": { "line": line, "character": 4 } }
+        }))
+        .unwrap()
+    }
+
+    fn published(diagnostics: Vec<Diagnostic>) -> BTreeMap<String, Vec<Diagnostic>> {
+        BTreeMap::from([("file:///ws/src/lib.rs".to_string(), diagnostics)])
+    }
+
+    #[test]
+    fn test_feature_matrix_report_splits_common_and_specific() {
+        let shared = diagnostic(2, "unused variable: `x`", 3);
+        let runs = vec![
+            ("default".to_string(), published(vec![shared.clone()])),
+            (
+                "tls".to_string(),
+                published(vec![
+                    shared.clone(),
+                    diagnostic(1, "cannot find type `Connector`", 10),
+                    diagnostic(4, "for more information", 10),
+                ]),
+            ),
+        ];
+
+        let report = feature_matrix_report(&runs);
+
+        assert_eq!(report["common"].as_array().unwrap().len(), 1);
+        assert_eq!(report["common"][0]["message"], "unused variable: `x`");
+        let specific = report["specific"].as_array().unwrap();
+        assert_eq!(specific.len(), 1);
+        assert_eq!(specific[0]["level"], "error");
+        assert_eq!(specific[0]["file"], "/ws/src/lib.rs");
+        assert_eq!(specific[0]["feature_sets"], json!(["tls"]));
+    }
+}
@@ -0,0 +1,104 @@
+use serde_json::{json, Value};
+
+use crate::protocol::lsp::{Diagnostic, NumberOrString, Position};
+
+/// rust-analyzer's diagnostic code for code disabled by `#[cfg]` attributes.
+const INACTIVE_CODE: &str = "inactive-code";
+
+/// The regions of a file that the current cfg options and features disable,
+/// from rust-analyzer's `inactive-code` diagnostics, in file order. Each gets
+/// the reason rust-analyzer gives, e.g. `feature = "tls" is disabled`.
+pub fn inactive_code_report(file_path: &str, diagnostics: &[Diagnostic]) -> Value {
+    let mut inactive: Vec<&Diagnostic> = diagnostics.iter().filter(is_inactive_code).collect();
+    inactive.sort_by_key(|diagnostic| diagnostic.range.start);
+
+    let regions: Vec<Value> = inactive
+        .iter()
+        .map(|diagnostic| {
+            json!({
+                "start_line": diagnostic.range.start.line,
+                "end_line": diagnostic.range.end.line,
+                "range": diagnostic.range,
+                "reason": inactive_reason(&diagnostic.message)
+            })
+        })
+        .collect();
+
+    json!({
+        "file": file_path,
+        "count": regions.len(),
+        "inactive_regions": regions
+    })
+}
+
+/// Why the code at `position` is disabled, or `None` when it is active.
+pub fn inactive_reason_at(diagnostics: &[Diagnostic], position: Position) -> Option<&str> {
+    diagnostics
+        .iter()
+        .filter(is_inactive_code)
+        .find(|diagnostic| diagnostic.range.start <= position && position <= diagnostic.range.end)
+        .map(|diagnostic| inactive_reason(&diagnostic.message))
+}
+
+fn is_inactive_code(diagnostic: &&Diagnostic) -> bool {
+    matches!(&diagnostic.code, Some(NumberOrString::String(code)) if code == INACTIVE_CODE)
+}
+
+/// The cfg part of "code is inactive due to #[cfg] directives: ...".
+fn inactive_reason(message: &str) -> &str {
+    message
+        .split_once("directives: ")
+        .map_or(message, |(_, reason)| reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{inactive_code_report, inactive_reason_at};
+    use crate::protocol::lsp::{Diagnostic, Position};
+
+    fn diagnostic(line: u32, code: &str, message: &str) -> Diagnostic {
+        serde_json::from_value(json!({
+            "range": {
+                "start": { "line": line, "character": 0 },
+                "end": { "line": line + 2, "character": 1 }
+            },
+            "severity": 4,
+            "code": code,
+            "message": message
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_inactive_code_report_lists_disabled_regions() {
+        let diagnostics = [
+            diagnostic(
+                9,
+                "inactive-code",
+                "code is inactive due to #[cfg] directives: target_os = \"windows\" is disabled",
+            ),
+            diagnostic(2, "unused_variables", "unused variable: `x`"),
+            diagnostic(
+                4,
+                "inactive-code",
+                "code is inactive due to #[cfg] directives: feature = \"tls\" is disabled",
+            ),
+        ];
+
+        let report = inactive_code_report("src/net.rs", &diagnostics);
+
+        assert_eq!(report["count"], 2);
+        let regions = report["inactive_regions"].as_array().unwrap();
+        assert_eq!(regions[0]["start_line"], 4);
+        assert_eq!(regions[0]["end_line"], 6);
+        assert_eq!(regions[0]["reason"], "feature = \"tls\" is disabled");
+        assert_eq!(regions[1]["reason"], "target_os = \"windows\" is disabled");
+
+        let at = |line, character| inactive_reason_at(&diagnostics, Position { line, character });
+        assert_eq!(at(5, 8), Some("feature = \"tls\" is disabled"));
+        assert_eq!(at(2, 4), None);
+        assert_eq!(at(7, 0), None);
+    }
+}
@@ -1,72 +1,142 @@
 use serde_json::{json, Value};
+use std::collections::BTreeMap;
 
-pub fn format_diagnostics(file_path: &str, result: &Value) -> Value {
-    let Some(diag_array) = result.as_array() else {
-        return json!({
-            "file": file_path,
-            "diagnostics": [],
-            "summary": {
-                "errors": 0,
-                "warnings": 0,
-                "information": 0,
-                "hints": 0
-            }
-        });
-    };
-
-    let mut output = json!({
-        "file": file_path,
-        "diagnostics": [],
-        "summary": {
-            "errors": 0,
-            "warnings": 0,
-            "information": 0,
-            "hints": 0
-        }
-    });
+use crate::protocol::lsp::Diagnostic;
+
+mod dead_code;
+mod feature_matrix;
+mod inactive_code;
+mod render;
+mod snapshot;
 
+pub use dead_code::dead_code_report;
+pub use feature_matrix::feature_matrix_report;
+pub use inactive_code::{inactive_code_report, inactive_reason_at};
+pub use render::{render_diagnostics_text, rustc_level};
+pub use snapshot::DiagnosticsSnapshot;
+
+pub fn format_diagnostics(file_path: &str, diagnostics: &[Diagnostic]) -> Value {
     let mut errors = 0;
     let mut warnings = 0;
     let mut information = 0;
     let mut hints = 0;
 
-    for diag in diag_array {
+    let mut formatted = Vec::new();
+    for diag in diagnostics {
         // Count by severity.
-        if let Some(severity) = diag.get("severity").and_then(|s| s.as_u64()) {
-            match severity {
-                1 => errors += 1,
-                2 => warnings += 1,
-                3 => information += 1,
-                4 => hints += 1,
-                _ => {}
-            }
+        match diag.severity {
+            Some(1) => errors += 1,
+            Some(2) => warnings += 1,
+            Some(3) => information += 1,
+            Some(4) => hints += 1,
+            _ => {}
         }
 
-        // Add formatted diagnostic.
-        let Some(diag_list) = output["diagnostics"].as_array_mut() else {
-            continue;
-        };
+        formatted.push(json!({
+            "severity": severity_label(diag.severity),
+            "range": diag.range,
+            "message": diag.message,
+            "code": diag.code,
+            "source": diag.source.as_deref().unwrap_or("rust-analyzer"),
+            "relatedInformation": diag.related_information
+        }));
+    }
+
+    json!({
+        "file": file_path,
+        "diagnostics": formatted,
+        "summary": {
+            "errors": errors,
+            "warnings": warnings,
+            "information": information,
+            "hints": hints
+        }
+    })
+}
 
-        diag_list.push(json!({
-            "severity": match diag.get("severity").and_then(|s| s.as_u64()) {
-                Some(1) => "error",
-                Some(2) => "warning",
-                Some(3) => "information",
-                Some(4) => "hint",
-                _ => "unknown"
+/// Keeps the `max` most severe diagnostics, errors first, each file's in their
+/// original order. Returns them with the number of diagnostics left out.
+pub fn most_severe_diagnostics(
+    result: &BTreeMap<String, Vec<Diagnostic>>,
+    max: usize,
+) -> (BTreeMap<String, Vec<Diagnostic>>, usize) {
+    let mut ranked: Vec<(u64, &str, usize)> = result
+        .iter()
+        .flat_map(|(uri, diagnostics)| {
+            diagnostics
+                .iter()
+                .enumerate()
+                .map(move |(index, diag)| (diag.severity.unwrap_or(u64::MAX), uri.as_str(), index))
+        })
+        .collect();
+    ranked.sort_unstable();
+    let omitted = ranked.len().saturating_sub(max);
+    ranked.truncate(max);
+    ranked.sort_unstable_by_key(|&(_, uri, index)| (uri, index));
+
+    let mut limited: BTreeMap<String, Vec<Diagnostic>> = BTreeMap::new();
+    for (_, uri, index) in ranked {
+        limited
+            .entry(uri.to_string())
+            .or_default()
+            .push(result[uri][index].clone());
+    }
+    (limited, omitted)
+}
+
+pub fn severity_label(severity: Option<u64>) -> &'static str {
+    match severity {
+        Some(1) => "error",
+        Some(2) => "warning",
+        Some(3) => "information",
+        Some(4) => "hint",
+        _ => "unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    use super::most_severe_diagnostics;
+    use crate::protocol::lsp::Diagnostic;
+
+    fn diagnostic(severity: u64, line: u32) -> Diagnostic {
+        serde_json::from_value(json!({
+            "range": {
+                "start": { "line": line, "character": 0 },
+                "end": { "line": line, "character": 1 }
             },
-            "range": diag.get("range").cloned().unwrap_or(json!(null)),
-            "message": diag.get("message").and_then(|m| m.as_str()).unwrap_or(""),
-            "code": diag.get("code").cloned().unwrap_or(json!(null)),
-            "source": diag.get("source").and_then(|s| s.as_str()).unwrap_or("rust-analyzer"),
-            "relatedInformation": diag.get("relatedInformation").cloned().unwrap_or(json!(null))
-        }));
+            "severity": severity,
+            "message": format!("diagnostic at {line}")
+        }))
+        .unwrap()
     }
 
-    output["summary"]["errors"] = json!(errors);
-    output["summary"]["warnings"] = json!(warnings);
-    output["summary"]["information"] = json!(information);
-    output["summary"]["hints"] = json!(hints);
+    #[test]
+    fn test_most_severe_diagnostics_keeps_errors_first() {
+        let result = BTreeMap::from([
+            (
+                "file:///ws/src/a.rs".to_string(),
+                vec![diagnostic(2, 1), diagnostic(1, 5)],
+            ),
+            (
+                "file:///ws/src/b.rs".to_string(),
+                vec![diagnostic(4, 0), diagnostic(1, 2)],
+            ),
+        ]);
 
-    output
+        let (limited, omitted) = most_severe_diagnostics(&result, 3);
+
+        assert_eq!(omitted, 1);
+        let lines = |uri: &str| -> Vec<u32> {
+            limited[uri]
+                .iter()
+                .map(|diag| diag.range.start.line)
+                .collect()
+        };
+        assert_eq!(lines("file:///ws/src/a.rs"), [1, 5]);
+        assert_eq!(lines("file:///ws/src/b.rs"), [2]);
+    }
 }
@@ -0,0 +1,155 @@
+use std::{collections::BTreeMap, path::Path};
+
+use crate::{
+    lsp::path_from_uri,
+    protocol::lsp::{Diagnostic, Location},
+};
+
+/// The rustc level of an LSP severity: informational diagnostics become
+/// notes and hints help, as rustc prints them. Diagnostics without a severity
+/// are shown as warnings.
+pub fn rustc_level(severity: Option<u64>) -> &'static str {
+    match severity {
+        Some(1) => "error",
+        Some(3) => "note",
+        Some(4) => "help",
+        _ => "warning",
+    }
+}
+
+/// Renders diagnostics, keyed by file URI, the way rustc prints them:
+///
+/// ```text
+/// error[E0308]: mismatched types
+///   --> src/lib.rs:3:5
+///    = note: src/lib.rs:1:8: expected due to this
+/// ```
+///
+/// followed by a count of what was shown. Lines and columns count from 1, as
+/// in rustc's output; columns are in UTF-16 units, as LSP positions are. Paths
+/// are relative to `root` where they can be.
+pub fn render_diagnostics_text(root: &Path, files: &BTreeMap<String, Vec<Diagnostic>>) -> String {
+    let mut text = String::new();
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for (uri, diagnostics) in files {
+        for diagnostic in diagnostics {
+            let level = rustc_level(diagnostic.severity);
+            *counts.entry(level).or_default() += 1;
+
+            let code = diagnostic
+                .code
+                .as_ref()
+                .map(|code| format!("[{}]", code))
+                .unwrap_or_default();
+            let mut message = diagnostic.message.lines();
+            let first = message.next().unwrap_or_default();
+            text.push_str(&format!("{}{}: {}\n", level, code, first));
+            for line in message {
+                text.push_str(&format!("   {}\n", line));
+            }
+            let location = Location {
+                uri: uri.clone(),
+                range: diagnostic.range,
+            };
+            text.push_str(&format!("  --> {}\n", location_text(root, &location)));
+            for related in diagnostic.related_information.iter().flatten() {
+                text.push_str(&format!(
+                    "   = note: {}: {}\n",
+                    location_text(root, &related.location),
+                    related.message
+                ));
+            }
+            text.push('\n');
+        }
+    }
+
+    let shown: Vec<String> = ["error", "warning", "note", "help"]
+        .into_iter()
+        .filter_map(|level| {
+            let count = counts.get(level)?;
+            let plural = if *count == 1 || level == "help" {
+                ""
+            } else {
+                "s"
+            };
+            Some(format!("{} {}{}", count, level, plural))
+        })
+        .collect();
+    if shown.is_empty() {
+        text.push_str("no diagnostics\n");
+    } else {
+        text.push_str(&format!("{} emitted\n", shown.join(", ")));
+    }
+    text
+}
+
+fn location_text(root: &Path, location: &Location) -> String {
+    let path = match path_from_uri(&location.uri) {
+        Some(path) => path
+            .strip_prefix(root)
+            .unwrap_or(&path)
+            .display()
+            .to_string(),
+        None => location.uri.clone(),
+    };
+    let start = location.range.start;
+    format!("{}:{}:{}", path, start.line + 1, start.character + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use std::{collections::BTreeMap, path::Path};
+
+    use super::render_diagnostics_text;
+    use crate::protocol::lsp::Diagnostic;
+
+    #[test]
+    fn test_render_diagnostics_text_looks_like_rustc() {
+        let mismatched: Diagnostic = serde_json::from_value(json!({
+            "range": {
+                "start": { "line": 2, "character": 4 },
+                "end": { "line": 2, "character": 9 }
+            },
+            "severity": 1,
+            "code": "E0308",
+            "message": "mismatched types\nexpected `u32`, found `&str`",
+            "relatedInformation": [{
+                "location": {
+                    "uri": "file:///ws/src/lib.rs",
+                    "range": {
+                        "start": { "line": 0, "character": 7 },
+                        "end": { "line": 0, "character": 10 }
+                    }
+                },
+                "message": "expected due to this"
+            }]
+        }))
+        .unwrap();
+        let unused: Diagnostic = serde_json::from_value(json!({
+            "range": {
+                "start": { "line": 0, "character": 0 },
+                "end": { "line": 0, "character": 1 }
+            },
+            "severity": 4,
+            "message": "remove the unused import"
+        }))
+        .unwrap();
+        let files = BTreeMap::from([
+            ("file:///ws/src/lib.rs".to_string(), vec![mismatched]),
+            ("file:///other/main.rs".to_string(), vec![unused]),
+        ]);
+
+        assert_eq!(
+            render_diagnostics_text(Path::new("/ws"), &files),
+            "help: remove the unused import\n  --> /other/main.rs:1:1\n\n\
+             error[E0308]: mismatched types\n   expected `u32`, found `&str`\n  \
+             --> src/lib.rs:3:5\n   = note: src/lib.rs:1:8: expected due to this\n\n\
+             1 error, 1 help emitted\n"
+        );
+        assert_eq!(
+            render_diagnostics_text(Path::new("/ws"), &BTreeMap::new()),
+            "no diagnostics\n"
+        );
+    }
+}
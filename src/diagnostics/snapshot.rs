@@ -0,0 +1,200 @@
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+use super::severity_label;
+use crate::protocol::lsp::Diagnostic;
+
+/// Identity of a diagnostic for diffing purposes.
+///
+/// Ranges are intentionally left out so that a diagnostic which merely moved
+/// because lines were inserted above it is not reported as both fixed and new.
+type DiagnosticKey = (String, u64, String, String);
+
+/// A point-in-time copy of the workspace diagnostics, keyed by file URI.
+#[derive(Debug, Clone, Default)]
+pub struct DiagnosticsSnapshot {
+    entries: BTreeMap<DiagnosticKey, Vec<Diagnostic>>,
+    total: usize,
+}
+
+impl DiagnosticsSnapshot {
+    /// Builds a snapshot from workspace diagnostics keyed by file URI.
+    pub fn from_workspace(diagnostics: &BTreeMap<String, Vec<Diagnostic>>) -> Self {
+        let mut snapshot = Self::default();
+        for (uri, file_diagnostics) in diagnostics {
+            for diagnostic in file_diagnostics {
+                snapshot
+                    .entries
+                    .entry(diagnostic_key(uri, diagnostic))
+                    .or_default()
+                    .push(diagnostic.clone());
+                snapshot.total += 1;
+            }
+        }
+        snapshot
+    }
+
+    pub fn len(&self) -> usize {
+        self.total
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total == 0
+    }
+
+    /// Number of distinct files that have at least one diagnostic.
+    pub fn file_count(&self) -> usize {
+        let mut files: Vec<&str> = self.entries.keys().map(|key| key.0.as_str()).collect();
+        files.dedup();
+        files.len()
+    }
+
+    /// Reports which diagnostics appeared in `current` and which disappeared
+    /// relative to this snapshot.
+    pub fn diff(&self, current: &DiagnosticsSnapshot) -> Value {
+        let mut appeared = Vec::new();
+        let mut disappeared = Vec::new();
+        let mut unchanged = 0;
+
+        for (key, before) in &self.entries {
+            let after = current.entries.get(key).map(Vec::as_slice).unwrap_or(&[]);
+            unchanged += before.len().min(after.len());
+            for diagnostic in before.iter().skip(after.len()) {
+                disappeared.push(render_entry(key, diagnostic));
+            }
+        }
+
+        for (key, after) in &current.entries {
+            let before = self.entries.get(key).map(Vec::len).unwrap_or(0);
+            for diagnostic in after.iter().skip(before) {
+                appeared.push(render_entry(key, diagnostic));
+            }
+        }
+
+        json!({
+            "appeared": appeared,
+            "disappeared": disappeared,
+            "summary": {
+                "appeared": appeared.len(),
+                "disappeared": disappeared.len(),
+                "unchanged": unchanged,
+                "errors_appeared": count_errors(&appeared),
+                "errors_disappeared": count_errors(&disappeared)
+            }
+        })
+    }
+}
+
+fn diagnostic_key(uri: &str, diagnostic: &Diagnostic) -> DiagnosticKey {
+    let code = diagnostic
+        .code
+        .as_ref()
+        .map(ToString::to_string)
+        .unwrap_or_default();
+
+    (
+        uri.to_string(),
+        diagnostic.severity.unwrap_or(0),
+        code,
+        diagnostic.message.clone(),
+    )
+}
+
+fn render_entry(key: &DiagnosticKey, diagnostic: &Diagnostic) -> Value {
+    let (uri, severity, code, message) = key;
+    json!({
+        "file": uri,
+        "severity": severity_label(Some(*severity)),
+        "code": if code.is_empty() { json!(null) } else { json!(code) },
+        "message": message,
+        "range": diagnostic.range
+    })
+}
+
+fn count_errors(entries: &[Value]) -> usize {
+    entries
+        .iter()
+        .filter(|entry| entry["severity"] == "error")
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    use super::DiagnosticsSnapshot;
+    use crate::protocol::lsp::Diagnostic;
+
+    fn diagnostic(line: u64, code: &str, message: &str) -> Diagnostic {
+        serde_json::from_value(json!({
+            "severity": 1,
+            "code": code,
+            "message": message,
+            "range": {
+                "start": { "line": line, "character": 0 },
+                "end": { "line": line, "character": 4 }
+            }
+        }))
+        .unwrap()
+    }
+
+    fn snapshot(files: &[(&str, Vec<Diagnostic>)]) -> DiagnosticsSnapshot {
+        let files: BTreeMap<String, Vec<Diagnostic>> = files
+            .iter()
+            .map(|(uri, diagnostics)| (uri.to_string(), diagnostics.clone()))
+            .collect();
+        DiagnosticsSnapshot::from_workspace(&files)
+    }
+
+    #[test]
+    fn test_diff_reports_fixed_and_new_diagnostics() {
+        let before = snapshot(&[
+            (
+                "file:///a.rs",
+                vec![diagnostic(1, "E0308", "mismatched types")],
+            ),
+            (
+                "file:///b.rs",
+                vec![diagnostic(3, "E0425", "cannot find value `x`")],
+            ),
+        ]);
+        let after = snapshot(&[(
+            "file:///b.rs",
+            vec![
+                diagnostic(5, "E0425", "cannot find value `x`"),
+                diagnostic(9, "E0599", "no method named `foo`"),
+            ],
+        )]);
+
+        let diff = before.diff(&after);
+
+        assert_eq!(diff["summary"]["disappeared"], 1);
+        assert_eq!(diff["summary"]["appeared"], 1);
+        assert_eq!(diff["summary"]["unchanged"], 1);
+        assert_eq!(diff["disappeared"][0]["code"], "E0308");
+        assert_eq!(diff["appeared"][0]["code"], "E0599");
+    }
+
+    #[test]
+    fn test_diff_counts_duplicate_diagnostics() {
+        let before = snapshot(&[(
+            "file:///a.rs",
+            vec![
+                diagnostic(1, "unused_variables", "unused variable"),
+                diagnostic(2, "unused_variables", "unused variable"),
+            ],
+        )]);
+        let after = snapshot(&[(
+            "file:///a.rs",
+            vec![diagnostic(2, "unused_variables", "unused variable")],
+        )]);
+
+        let diff = before.diff(&after);
+
+        assert_eq!(before.len(), 2);
+        assert_eq!(diff["summary"]["disappeared"], 1);
+        assert_eq!(diff["summary"]["appeared"], 0);
+        assert_eq!(diff["summary"]["errors_disappeared"], 1);
+    }
+}
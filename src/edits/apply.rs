@@ -0,0 +1,142 @@
+use anyhow::{anyhow, Result};
+
+use crate::protocol::lsp::{Position, TextEdit};
+
+/// Applies LSP text edits to `text`. Ranges refer to the original text and
+/// must not overlap; edits inserting at the same position keep their order.
+pub fn apply_text_edits(text: &str, edits: &[TextEdit]) -> Result<String> {
+    let mut resolved = edits
+        .iter()
+        .map(|edit| {
+            let start = offset_at(text, edit.range.start);
+            let end = offset_at(text, edit.range.end);
+            match (start, end) {
+                (Some(start), Some(end)) if start <= end => {
+                    Ok((start, end, edit.new_text.as_str()))
+                }
+                _ => Err(anyhow!(
+                    "Edit range {}:{}-{}:{} is outside the document",
+                    edit.range.start.line,
+                    edit.range.start.character,
+                    edit.range.end.line,
+                    edit.range.end.character
+                )),
+            }
+        })
+        .collect::<Result<Vec<_>>>()?;
+    resolved.sort_by_key(|(start, end, _)| (*start, *end));
+
+    let mut result = String::with_capacity(text.len());
+    let mut cursor = 0;
+    for (start, end, new_text) in resolved {
+        if start < cursor {
+            return Err(anyhow!("Text edits overlap"));
+        }
+        result.push_str(&text[cursor..start]);
+        result.push_str(new_text);
+        cursor = end;
+    }
+    result.push_str(&text[cursor..]);
+    Ok(result)
+}
+
+/// The byte offset of an LSP position, whose `character` counts UTF-16 code
+/// units. Characters past the end of a line stand for the line end.
+pub fn offset_at(text: &str, position: Position) -> Option<usize> {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        line_start += text[line_start..].find('\n')? + 1;
+    }
+    let line_end = text[line_start..]
+        .find('\n')
+        .map_or(text.len(), |index| line_start + index);
+    let line = &text[line_start..line_end];
+    let line = line.strip_suffix('\r').unwrap_or(line);
+
+    let mut units = 0;
+    for (index, ch) in line.char_indices() {
+        if units >= position.character as usize {
+            return Some(line_start + index);
+        }
+        units += ch.len_utf16();
+    }
+    Some(line_start + line.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_text_edits, offset_at};
+    use crate::protocol::lsp::{Position, Range, TextEdit};
+
+    fn edit(start: (u32, u32), end: (u32, u32), new_text: &str) -> TextEdit {
+        TextEdit {
+            range: Range {
+                start: Position {
+                    line: start.0,
+                    character: start.1,
+                },
+                end: Position {
+                    line: end.0,
+                    character: end.1,
+                },
+            },
+            new_text: new_text.to_string(),
+            annotation_id: None,
+        }
+    }
+
+    #[test]
+    fn test_edits_apply_against_the_original_text() {
+        let text = "fn old() {}\nfn main() { old(); }\n";
+        let edits = [
+            edit((1, 12), (1, 15), "new"),
+            edit((0, 3), (0, 6), "new"),
+            edit((2, 0), (2, 0), "// end\n"),
+        ];
+
+        assert_eq!(
+            apply_text_edits(text, &edits).unwrap(),
+            "fn new() {}\nfn main() { new(); }\n// end\n"
+        );
+        assert!(
+            apply_text_edits(text, &[edit((0, 0), (0, 5), ""), edit((0, 2), (0, 3), "")]).is_err()
+        );
+        assert!(apply_text_edits(text, &[edit((5, 0), (5, 0), "x")]).is_err());
+    }
+
+    #[test]
+    fn test_offsets_count_utf16_units() {
+        let text = "let s = \"😀x\";\r\nnext";
+
+        assert_eq!(
+            offset_at(
+                text,
+                Position {
+                    line: 0,
+                    character: 11
+                }
+            ),
+            Some(13)
+        );
+        assert_eq!(
+            offset_at(
+                text,
+                Position {
+                    line: 0,
+                    character: 99
+                }
+            ),
+            Some(16)
+        );
+        assert_eq!(
+            offset_at(
+                text,
+                Position {
+                    line: 1,
+                    character: 2
+                }
+            ),
+            Some(20)
+        );
+    }
+}
@@ -0,0 +1,261 @@
+use std::fmt::Write;
+
+/// A unified diff between two versions of a file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UnifiedDiff {
+    /// The diff text with `---`/`+++` headers, empty when nothing changed.
+    pub text: String,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineOp {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Diffs `old` against `new` line by line, showing `context` unchanged lines
+/// around each change. `old_name` and `new_name` go into the headers as given,
+/// e.g. `a/src/lib.rs` or `/dev/null`.
+pub fn unified_diff(
+    old_name: &str,
+    new_name: &str,
+    old: &str,
+    new: &str,
+    context: usize,
+) -> UnifiedDiff {
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+    let ops = line_ops(&old_lines, &new_lines);
+
+    let mut diff = UnifiedDiff {
+        insertions: ops.iter().filter(|op| **op == LineOp::Insert).count(),
+        deletions: ops.iter().filter(|op| **op == LineOp::Delete).count(),
+        ..UnifiedDiff::default()
+    };
+    if diff.insertions == 0 && diff.deletions == 0 {
+        return diff;
+    }
+
+    // Line positions in the old and new text before each operation.
+    let mut positions = Vec::with_capacity(ops.len() + 1);
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for op in &ops {
+        positions.push((old_pos, new_pos));
+        match op {
+            LineOp::Equal => {
+                old_pos += 1;
+                new_pos += 1;
+            }
+            LineOp::Delete => old_pos += 1,
+            LineOp::Insert => new_pos += 1,
+        }
+    }
+    positions.push((old_pos, new_pos));
+
+    let _ = writeln!(diff.text, "--- {old_name}");
+    let _ = writeln!(diff.text, "+++ {new_name}");
+    for hunk in hunks(&ops, context) {
+        let (old_start, new_start) = positions[hunk.start];
+        let (old_end, new_end) = positions[hunk.end];
+        let _ = writeln!(
+            diff.text,
+            "@@ -{} +{} @@",
+            hunk_range(old_start, old_end - old_start),
+            hunk_range(new_start, new_end - new_start)
+        );
+        for index in hunk {
+            let (old_pos, new_pos) = positions[index];
+            let (marker, line) = match ops[index] {
+                LineOp::Equal => (' ', old_lines[old_pos]),
+                LineOp::Delete => ('-', old_lines[old_pos]),
+                LineOp::Insert => ('+', new_lines[new_pos]),
+            };
+            diff.text.push(marker);
+            match line.strip_suffix('\n') {
+                Some(line) => {
+                    diff.text.push_str(line);
+                    diff.text.push('\n');
+                }
+                None => {
+                    diff.text.push_str(line);
+                    diff.text.push_str("\n\\ No newline at end of file\n");
+                }
+            }
+        }
+    }
+    diff
+}
+
+/// Formats a hunk's line range: one-based, with the line before it as the
+/// start when it is empty.
+fn hunk_range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{start},0"),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, count),
+    }
+}
+
+/// Groups operations into hunks, merging changes separated by no more than
+/// twice the context.
+fn hunks(ops: &[LineOp], context: usize) -> Vec<std::ops::Range<usize>> {
+    let mut hunks = Vec::new();
+    let mut index = 0;
+    while index < ops.len() {
+        if ops[index] == LineOp::Equal {
+            index += 1;
+            continue;
+        }
+        let start = index.saturating_sub(context);
+        let mut last_change = index;
+        let mut scan = index;
+        while scan < ops.len() {
+            if ops[scan] != LineOp::Equal {
+                last_change = scan;
+            } else if scan - last_change > 2 * context {
+                break;
+            }
+            scan += 1;
+        }
+        let end = (last_change + 1 + context).min(ops.len());
+        hunks.push(start..end);
+        index = end;
+    }
+    hunks
+}
+
+/// The shortest sequence of line operations turning `old` into `new`, found
+/// with Myers' algorithm after stripping the common prefix and suffix.
+fn line_ops(old: &[&str], new: &[&str]) -> Vec<LineOp> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut ops = vec![LineOp::Equal; prefix];
+    ops.extend(myers(old_middle, new_middle));
+    ops.extend(std::iter::repeat_n(LineOp::Equal, suffix));
+    ops
+}
+
+fn myers(old: &[&str], new: &[&str]) -> Vec<LineOp> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max = (n + m) as usize;
+    let offset = max as isize + 1;
+    let mut furthest = vec![0isize; 2 * max + 3];
+    // The furthest-reaching x on each diagonal before each round, kept for
+    // diagonals -d..=d, to walk the path back afterwards.
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    'search: for d in 0..=max as isize {
+        let low = (offset - d) as usize;
+        let high = (offset + d) as usize;
+        trace.push(furthest[low..=high].to_vec());
+        let mut k = -d;
+        while k <= d {
+            let index = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && furthest[index - 1] < furthest[index + 1]) {
+                furthest[index + 1]
+            } else {
+                furthest[index - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            furthest[index] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+            k += 2;
+        }
+    }
+
+    let mut ops = Vec::with_capacity(max);
+    let (mut x, mut y) = (n, m);
+    for (d, furthest) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let at = |k: isize| furthest[(k + d) as usize];
+        if d == 0 {
+            ops.extend(std::iter::repeat_n(LineOp::Equal, x as usize));
+            break;
+        }
+        let k = x - y;
+        let previous_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let previous_x = at(previous_k);
+        let previous_y = previous_x - previous_k;
+        while x > previous_x && y > previous_y {
+            ops.push(LineOp::Equal);
+            x -= 1;
+            y -= 1;
+        }
+        ops.push(if x == previous_x {
+            LineOp::Insert
+        } else {
+            LineOp::Delete
+        });
+        x = previous_x;
+        y = previous_y;
+    }
+    ops.reverse();
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::unified_diff;
+
+    #[test]
+    fn test_unified_diff_shows_changes_with_context() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\n";
+
+        let diff = unified_diff("a/x.rs", "b/x.rs", old, new, 1);
+
+        assert_eq!(
+            diff.text,
+            "--- a/x.rs\n+++ b/x.rs\n@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n@@ -10 +10,2 @@\n j\n+k\n"
+        );
+        assert_eq!((diff.insertions, diff.deletions), (2, 1));
+    }
+
+    #[test]
+    fn test_unified_diff_of_created_and_unchanged_files() {
+        let diff = unified_diff("/dev/null", "b/new.rs", "", "fn main() {}", 3);
+        assert_eq!(
+            diff.text,
+            "--- /dev/null\n+++ b/new.rs\n@@ -0,0 +1 @@\n+fn main() {}\n\\ No newline at end of file\n"
+        );
+
+        let unchanged = unified_diff("a/x.rs", "b/x.rs", "same\n", "same\n", 3);
+        assert!(unchanged.text.is_empty());
+        assert_eq!((unchanged.insertions, unchanged.deletions), (0, 0));
+    }
+
+    #[test]
+    fn test_unified_diff_finds_a_minimal_edit() {
+        let old = "x\na\nb\nc\ny\n";
+        let new = "a\nb\nz\nc\n";
+
+        let diff = unified_diff("a/f", "b/f", old, new, 0);
+
+        assert_eq!((diff.insertions, diff.deletions), (1, 2));
+        assert_eq!(
+            diff.text,
+            "--- a/f\n+++ b/f\n@@ -1 +0,0 @@\n-x\n@@ -3,0 +3 @@\n+z\n@@ -5 +4,0 @@\n-y\n"
+        );
+    }
+}
@@ -0,0 +1,9 @@
+mod apply;
+mod diff;
+mod plan;
+mod position;
+
+pub use apply::{apply_text_edits, offset_at};
+pub use diff::{unified_diff, UnifiedDiff};
+pub use plan::{EditPlan, FileOperation, PlannedFile};
+pub use position::{convert_position, PositionInput, TextPosition};
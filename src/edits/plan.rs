@@ -0,0 +1,456 @@
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    lsp::path_from_uri,
+    protocol::lsp::{DocumentChange, TextEdit, WorkspaceEdit},
+};
+
+use super::{
+    apply::apply_text_edits,
+    diff::{unified_diff, UnifiedDiff},
+};
+
+/// What a workspace edit does to one file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileOperation {
+    Create,
+    Modify,
+    Rename,
+    Delete,
+}
+
+impl FileOperation {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Modify => "modify",
+            Self::Rename => "rename",
+            Self::Delete => "delete",
+        }
+    }
+}
+
+/// A file as it was on disk and as a workspace edit leaves it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedFile {
+    /// Where the file was read from, or created.
+    pub original_path: PathBuf,
+    /// Content on disk before the edit; `None` when the file did not exist.
+    pub original: Option<String>,
+    /// Where the file ends up.
+    pub path: PathBuf,
+    /// Content after the edit; `None` when the edit deletes the file.
+    pub content: Option<String>,
+}
+
+impl PlannedFile {
+    pub fn operation(&self) -> FileOperation {
+        match (&self.original, &self.content) {
+            (None, _) => FileOperation::Create,
+            (Some(_), None) => FileOperation::Delete,
+            _ if self.path != self.original_path => FileOperation::Rename,
+            _ => FileOperation::Modify,
+        }
+    }
+
+    fn is_unchanged(&self) -> bool {
+        self.path == self.original_path && self.original == self.content
+    }
+
+    /// The change as a unified diff with paths relative to `root`.
+    pub fn diff(&self, root: &Path, context: usize) -> UnifiedDiff {
+        let display = |prefix: &str, path: &Path| {
+            let relative = path.strip_prefix(root).unwrap_or(path);
+            format!("{prefix}{}", relative.display())
+        };
+        let old_name = match self.original {
+            Some(_) => display("a/", &self.original_path),
+            None => "/dev/null".to_string(),
+        };
+        let new_name = match self.content {
+            Some(_) => display("b/", &self.path),
+            None => "/dev/null".to_string(),
+        };
+        unified_diff(
+            &old_name,
+            &new_name,
+            self.original.as_deref().unwrap_or(""),
+            self.content.as_deref().unwrap_or(""),
+            context,
+        )
+    }
+}
+
+/// The files a workspace edit touches and their content once it is applied,
+/// worked out without writing anything.
+#[derive(Debug, Clone, Default)]
+pub struct EditPlan {
+    files: Vec<PlannedFile>,
+    /// Index into `files` of the entry at each current path.
+    by_path: HashMap<PathBuf, usize>,
+}
+
+impl EditPlan {
+    /// Plans `edit`, reading the files it touches from disk. `documentChanges`
+    /// are followed in order when present, `changes` otherwise.
+    pub async fn new(edit: &WorkspaceEdit) -> Result<Self> {
+        let mut plan = Self::default();
+        if let Some(document_changes) = &edit.document_changes {
+            for change in document_changes {
+                match change {
+                    DocumentChange::Edit(edit) => {
+                        plan.edit(&edit.text_document.uri, &edit.edits).await?
+                    }
+                    DocumentChange::Operation(operation) => plan.operation(operation).await?,
+                }
+            }
+        } else if let Some(changes) = &edit.changes {
+            for (uri, edits) in changes {
+                plan.edit(uri, edits).await?;
+            }
+        }
+        Ok(plan)
+    }
+
+    /// The files the edit changes, in the order they were first touched.
+    pub fn files(&self) -> impl Iterator<Item = &PlannedFile> {
+        self.files.iter().filter(|file| !file.is_unchanged())
+    }
+
+    /// Adds moving the file at `old_path` to `new_path`, which must not exist,
+    /// after the changes planned so far.
+    pub async fn rename_file(&mut self, old_path: &Path, new_path: &Path) -> Result<()> {
+        self.move_file(old_path.to_path_buf(), new_path.to_path_buf(), false, false)
+            .await
+    }
+
+    /// Writes the planned changes to disk. Every new content is written to a
+    /// temporary file next to its target first, so a failed write leaves all
+    /// files as they were; the temporary files are then moved into place, and
+    /// only after that are files that moved away or were deleted removed.
+    pub async fn apply(&self) -> Result<()> {
+        let mut staged: Vec<(PathBuf, &Path)> = Vec::new();
+        for file in self.files() {
+            let Some(content) = &file.content else {
+                continue;
+            };
+            match stage(&file.path, content).await {
+                Ok(temporary) => staged.push((temporary, &file.path)),
+                Err(e) => {
+                    for (temporary, _) in &staged {
+                        let _ = tokio::fs::remove_file(temporary).await;
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        for (temporary, path) in &staged {
+            tokio::fs::rename(temporary, path)
+                .await
+                .map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+        }
+        for file in self.files() {
+            let moved_away = file.content.is_none() || file.path != file.original_path;
+            let replaced = staged.iter().any(|(_, path)| *path == file.original_path);
+            if file.original.is_some() && moved_away && !replaced {
+                tokio::fs::remove_file(&file.original_path)
+                    .await
+                    .map_err(|e| {
+                        anyhow!("Failed to remove {}: {}", file.original_path.display(), e)
+                    })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds `edits` to the document at `uri`, against its content as planned
+    /// so far.
+    pub async fn edit(&mut self, uri: &str, edits: &[TextEdit]) -> Result<()> {
+        let path = file_path(uri)?;
+        let index = self.load(&path).await?;
+        let file = &mut self.files[index];
+        let Some(content) = &file.content else {
+            return Err(anyhow!(
+                "Cannot edit {}: file does not exist",
+                path.display()
+            ));
+        };
+        file.content = Some(apply_text_edits(content, edits)?);
+        Ok(())
+    }
+
+    async fn operation(&mut self, operation: &Value) -> Result<()> {
+        let options = &operation["options"];
+        let flag = |name: &str| options[name].as_bool().unwrap_or(false);
+        match operation["kind"].as_str() {
+            Some("create") => {
+                let path = file_path(operation["uri"].as_str().unwrap_or_default())?;
+                let index = self.load(&path).await?;
+                let file = &mut self.files[index];
+                if file.content.is_some() && !flag("overwrite") {
+                    if flag("ignoreIfExists") {
+                        return Ok(());
+                    }
+                    return Err(anyhow!("Cannot create {}: file exists", path.display()));
+                }
+                file.content = Some(String::new());
+            }
+            Some("delete") => {
+                let path = file_path(operation["uri"].as_str().unwrap_or_default())?;
+                let index = self.load(&path).await?;
+                let file = &mut self.files[index];
+                if file.content.is_none() {
+                    if flag("ignoreIfNotExists") {
+                        return Ok(());
+                    }
+                    return Err(anyhow!(
+                        "Cannot delete {}: file does not exist",
+                        path.display()
+                    ));
+                }
+                file.content = None;
+            }
+            Some("rename") => {
+                let old_path = file_path(operation["oldUri"].as_str().unwrap_or_default())?;
+                let new_path = file_path(operation["newUri"].as_str().unwrap_or_default())?;
+                self.move_file(
+                    old_path,
+                    new_path,
+                    flag("overwrite"),
+                    flag("ignoreIfExists"),
+                )
+                .await?;
+            }
+            _ => return Err(anyhow!("Unsupported file operation: {}", operation)),
+        }
+        Ok(())
+    }
+
+    async fn move_file(
+        &mut self,
+        old_path: PathBuf,
+        new_path: PathBuf,
+        overwrite: bool,
+        ignore_if_exists: bool,
+    ) -> Result<()> {
+        let source = self.load(&old_path).await?;
+        if self.files[source].content.is_none() {
+            return Err(anyhow!(
+                "Cannot rename {}: file does not exist",
+                old_path.display()
+            ));
+        }
+        let target = self.load(&new_path).await?;
+        if self.files[target].content.is_some() {
+            if !overwrite {
+                if ignore_if_exists {
+                    return Ok(());
+                }
+                return Err(anyhow!(
+                    "Cannot rename to {}: file exists",
+                    new_path.display()
+                ));
+            }
+            self.files[target].content = None;
+        }
+
+        // The file moves; whatever was at the target is replaced, and the old
+        // path is left empty.
+        self.files[source].path = new_path.clone();
+        self.by_path.insert(new_path, source);
+        self.by_path.insert(old_path.clone(), self.files.len());
+        self.files.push(PlannedFile {
+            original_path: old_path.clone(),
+            original: None,
+            path: old_path,
+            content: None,
+        });
+        Ok(())
+    }
+
+    /// The entry for the file currently at `path`, read from disk the first
+    /// time it is touched.
+    async fn load(&mut self, path: &Path) -> Result<usize> {
+        if let Some(index) = self.by_path.get(path) {
+            return Ok(*index);
+        }
+        let original = match tokio::fs::read_to_string(path).await {
+            Ok(content) => Some(content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(anyhow!("Failed to read {}: {}", path.display(), e)),
+        };
+        self.files.push(PlannedFile {
+            original_path: path.to_path_buf(),
+            content: original.clone(),
+            original,
+            path: path.to_path_buf(),
+        });
+        self.by_path
+            .insert(path.to_path_buf(), self.files.len() - 1);
+        Ok(self.files.len() - 1)
+    }
+}
+
+/// Writes `content` to a temporary file next to `path`, creating its directory,
+/// and returns the temporary file's path.
+async fn stage(path: &Path, content: &str) -> Result<PathBuf> {
+    let Some(parent) = path.parent() else {
+        return Err(anyhow!(
+            "Cannot write {}: no parent directory",
+            path.display()
+        ));
+    };
+    tokio::fs::create_dir_all(parent)
+        .await
+        .map_err(|e| anyhow!("Failed to create {}: {}", parent.display(), e))?;
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temporary = parent.join(format!(".{name}.rust-analyzer-mcp.tmp"));
+    tokio::fs::write(&temporary, content)
+        .await
+        .map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+    Ok(temporary)
+}
+
+fn file_path(uri: &str) -> Result<PathBuf> {
+    path_from_uri(uri).ok_or_else(|| anyhow!("Not a file URI: {}", uri))
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use serde_json::json;
+
+    use super::{EditPlan, FileOperation};
+    use crate::{lsp::uri_from_path, protocol::lsp::WorkspaceEdit};
+
+    #[tokio::test]
+    async fn test_plan_follows_document_changes_in_order() -> Result<()> {
+        let root = tempfile::tempdir()?;
+        let root = root.path().canonicalize()?;
+        std::fs::write(root.join("lib.rs"), "mod old;\n")?;
+        std::fs::write(root.join("old.rs"), "pub fn f() {}\n")?;
+        let uri = |name: &str| format!("{}/{name}", uri_from_path(&root));
+
+        let edit: WorkspaceEdit = serde_json::from_value(json!({
+            "documentChanges": [
+                {
+                    "textDocument": { "uri": uri("lib.rs"), "version": null },
+                    "edits": [{
+                        "range": { "start": { "line": 0, "character": 4 }, "end": { "line": 0, "character": 7 } },
+                        "newText": "new"
+                    }]
+                },
+                { "kind": "rename", "oldUri": uri("old.rs"), "newUri": uri("new.rs") },
+                { "kind": "create", "uri": uri("extra.rs") },
+                { "kind": "delete", "uri": uri("missing.rs"), "options": { "ignoreIfNotExists": true } }
+            ]
+        }))?;
+        let plan = EditPlan::new(&edit).await?;
+
+        let files: Vec<_> = plan
+            .files()
+            .map(|file| (file.path.file_name().unwrap().to_owned(), file.operation()))
+            .collect();
+        assert_eq!(
+            files,
+            [
+                ("lib.rs".into(), FileOperation::Modify),
+                ("new.rs".into(), FileOperation::Rename),
+                ("extra.rs".into(), FileOperation::Create),
+            ]
+        );
+        let lib = plan.files().next().unwrap();
+        assert_eq!(lib.content.as_deref(), Some("mod new;\n"));
+        assert_eq!(
+            lib.diff(&root, 3).text,
+            "--- a/lib.rs\n+++ b/lib.rs\n@@ -1 +1 @@\n-mod old;\n+mod new;\n"
+        );
+        // Nothing is written.
+        assert!(root.join("old.rs").exists());
+
+        let missing: WorkspaceEdit = serde_json::from_value(json!({
+            "documentChanges": [{ "kind": "delete", "uri": uri("missing.rs") }]
+        }))?;
+        assert!(EditPlan::new(&missing).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_apply_writes_edits_and_moves_files() -> Result<()> {
+        let root = tempfile::tempdir()?;
+        let root = root.path().canonicalize()?;
+        std::fs::write(root.join("lib.rs"), "mod old;\n")?;
+        std::fs::write(root.join("old.rs"), "pub fn f() {}\n")?;
+        let uri = |name: &str| format!("{}/{name}", uri_from_path(&root));
+
+        let edit: WorkspaceEdit = serde_json::from_value(json!({
+            "changes": {
+                uri("lib.rs"): [{
+                    "range": { "start": { "line": 0, "character": 4 }, "end": { "line": 0, "character": 7 } },
+                    "newText": "new"
+                }]
+            }
+        }))?;
+        let mut plan = EditPlan::new(&edit).await?;
+        plan.rename_file(&root.join("old.rs"), &root.join("nested/new.rs"))
+            .await?;
+        assert!(plan
+            .clone()
+            .rename_file(&root.join("lib.rs"), &root.join("nested/new.rs"))
+            .await
+            .is_err());
+        plan.apply().await?;
+
+        assert_eq!(std::fs::read_to_string(root.join("lib.rs"))?, "mod new;\n");
+        assert_eq!(
+            std::fs::read_to_string(root.join("nested/new.rs"))?,
+            "pub fn f() {}\n"
+        );
+        assert!(!root.join("old.rs").exists());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_failed_apply_leaves_files_untouched() -> Result<()> {
+        let root = tempfile::tempdir()?;
+        let root = root.path().canonicalize()?;
+        std::fs::write(root.join("lib.rs"), "mod old;\n")?;
+        std::fs::write(root.join("old.rs"), "pub fn f() {}\n")?;
+        let uri = |name: &str| format!("{}/{name}", uri_from_path(&root));
+
+        let edit: WorkspaceEdit = serde_json::from_value(json!({
+            "changes": {
+                uri("lib.rs"): [{
+                    "range": { "start": { "line": 0, "character": 4 }, "end": { "line": 0, "character": 7 } },
+                    "newText": "new"
+                }]
+            }
+        }))?;
+        let mut plan = EditPlan::new(&edit).await?;
+        plan.rename_file(&root.join("old.rs"), &root.join("nested/new.rs"))
+            .await?;
+        // A file where the target directory should be, so it cannot be created.
+        std::fs::write(root.join("nested"), "")?;
+        assert!(plan.apply().await.is_err());
+
+        assert_eq!(std::fs::read_to_string(root.join("lib.rs"))?, "mod old;\n");
+        assert_eq!(
+            std::fs::read_to_string(root.join("old.rs"))?,
+            "pub fn f() {}\n"
+        );
+        let mut names: Vec<_> = std::fs::read_dir(&root)?
+            .map(|entry| entry.map(|entry| entry.file_name()))
+            .collect::<Result<_, _>>()?;
+        names.sort();
+        assert_eq!(names, ["lib.rs", "nested", "old.rs"]);
+
+        Ok(())
+    }
+}
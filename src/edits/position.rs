@@ -0,0 +1,220 @@
+use anyhow::{anyhow, Result};
+
+/// A position in a file, in one of the coordinate systems clients use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PositionInput {
+    /// A byte offset from the start of the file.
+    Offset(usize),
+    /// A line with a column counted in UTF-8 bytes.
+    Utf8Column { line: u32, column: u32 },
+    /// A line with a column counted in UTF-16 code units, as LSP positions are.
+    Utf16Column { line: u32, character: u32 },
+}
+
+/// A position expressed in every coordinate system, with the text of its line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextPosition {
+    pub offset: usize,
+    pub line: u32,
+    pub utf8_column: u32,
+    pub utf16_column: u32,
+    /// The line's text, without its line ending.
+    pub line_text: String,
+}
+
+/// Converts `input` into all coordinate systems. Unlike `offset_at`, positions
+/// past the end of their line or inside a multi-byte character are errors
+/// rather than clamped, since the caller asked about that exact position.
+pub fn convert_position(text: &str, input: PositionInput) -> Result<TextPosition> {
+    let (line, line_start, utf8_column) = match input {
+        PositionInput::Offset(offset) => {
+            if offset > text.len() {
+                return Err(anyhow!(
+                    "Offset {} is past the end of the file ({} bytes)",
+                    offset,
+                    text.len()
+                ));
+            }
+            if !text.is_char_boundary(offset) {
+                return Err(anyhow!("Offset {} falls inside a character", offset));
+            }
+            let line = text[..offset].matches('\n').count() as u32;
+            let line_start = text[..offset].rfind('\n').map_or(0, |index| index + 1);
+            if offset - line_start > line_text(text, line_start).len() {
+                return Err(anyhow!("Offset {} falls inside a line ending", offset));
+            }
+            (line, line_start, offset - line_start)
+        }
+        PositionInput::Utf8Column { line, column } => {
+            let line_start = line_start(text, line)?;
+            let line_text = line_text(text, line_start);
+            let column = column as usize;
+            if column > line_text.len() {
+                return Err(anyhow!(
+                    "Column {} is past the end of line {} ({} bytes)",
+                    column,
+                    line,
+                    line_text.len()
+                ));
+            }
+            if !line_text.is_char_boundary(column) {
+                return Err(anyhow!(
+                    "Column {} falls inside a character on line {}",
+                    column,
+                    line
+                ));
+            }
+            (line, line_start, column)
+        }
+        PositionInput::Utf16Column { line, character } => {
+            let line_start = line_start(text, line)?;
+            let line_text = line_text(text, line_start);
+            (line, line_start, utf16_to_utf8(line_text, line, character)?)
+        }
+    };
+
+    let line_text = line_text(text, line_start);
+    Ok(TextPosition {
+        offset: line_start + utf8_column,
+        line,
+        utf8_column: utf8_column as u32,
+        utf16_column: line_text[..utf8_column].encode_utf16().count() as u32,
+        line_text: line_text.to_string(),
+    })
+}
+
+/// The byte offset where `line` starts. The empty line after a final newline
+/// counts as a line, as it does for LSP positions.
+fn line_start(text: &str, line: u32) -> Result<usize> {
+    let mut start = 0;
+    for _ in 0..line {
+        match text[start..].find('\n') {
+            Some(index) => start += index + 1,
+            None => {
+                return Err(anyhow!(
+                    "Line {} is past the end of the file ({} lines)",
+                    line,
+                    text.matches('\n').count() + 1
+                ))
+            }
+        }
+    }
+    Ok(start)
+}
+
+fn line_text(text: &str, line_start: usize) -> &str {
+    let rest = &text[line_start..];
+    let line = rest.find('\n').map_or(rest, |index| &rest[..index]);
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+fn utf16_to_utf8(line_text: &str, line: u32, character: u32) -> Result<usize> {
+    let mut units = 0;
+    for (index, ch) in line_text.char_indices() {
+        if units == character as usize {
+            return Ok(index);
+        }
+        if units > character as usize {
+            break;
+        }
+        units += ch.len_utf16();
+    }
+    if units == character as usize {
+        return Ok(line_text.len());
+    }
+    if units < character as usize {
+        return Err(anyhow!(
+            "Character {} is past the end of line {} ({} UTF-16 units)",
+            character,
+            line,
+            units
+        ));
+    }
+    Err(anyhow!(
+        "Character {} falls inside a surrogate pair on line {}",
+        character,
+        line
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{convert_position, PositionInput};
+
+    #[test]
+    fn test_convert_position_between_coordinate_systems() {
+        let text = "fn main() {\r\n    let s = \"é🦀x\";\n}\n";
+        let expected = convert_position(text, PositionInput::Offset(32)).unwrap();
+
+        assert_eq!(expected.line, 1);
+        assert_eq!(expected.utf8_column, 19);
+        assert_eq!(expected.utf16_column, 16);
+        assert_eq!(expected.line_text, "    let s = \"é🦀x\";");
+        assert_eq!(
+            convert_position(
+                text,
+                PositionInput::Utf8Column {
+                    line: 1,
+                    column: 19
+                }
+            )
+            .unwrap(),
+            expected
+        );
+        assert_eq!(
+            convert_position(
+                text,
+                PositionInput::Utf16Column {
+                    line: 1,
+                    character: 16
+                }
+            )
+            .unwrap(),
+            expected
+        );
+        assert_eq!(
+            convert_position(text, PositionInput::Offset(text.len()))
+                .unwrap()
+                .line,
+            3
+        );
+    }
+
+    #[test]
+    fn test_convert_position_rejects_positions_outside_the_text() {
+        let text = "let s = \"🦀\";\n";
+        let error = |input| convert_position(text, input).unwrap_err().to_string();
+
+        assert_eq!(
+            error(PositionInput::Offset(10)),
+            "Offset 10 falls inside a character"
+        );
+        assert_eq!(
+            convert_position("a\r\nb", PositionInput::Offset(2))
+                .unwrap_err()
+                .to_string(),
+            "Offset 2 falls inside a line ending"
+        );
+        assert_eq!(
+            error(PositionInput::Utf16Column {
+                line: 0,
+                character: 10
+            }),
+            "Character 10 falls inside a surrogate pair on line 0"
+        );
+        assert_eq!(
+            error(PositionInput::Utf8Column {
+                line: 0,
+                column: 40
+            }),
+            "Column 40 is past the end of line 0 (15 bytes)"
+        );
+        assert_eq!(
+            error(PositionInput::Utf16Column {
+                line: 2,
+                character: 0
+            }),
+            "Line 2 is past the end of the file (2 lines)"
+        );
+    }
+}
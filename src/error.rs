@@ -0,0 +1,87 @@
+use std::fmt;
+
+/// A failure that callers handle differently from others, such as a timeout
+/// worth retrying with a larger budget or an argument to correct. The MCP
+/// server tells these apart by type to build a tool error's code and `data`;
+/// other failures are plain `anyhow` errors. Errors rust-analyzer answered
+/// with are [`LspError`](crate::lsp::LspError)s.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ToolError {
+    /// An LSP request or a command outlasted its time limit.
+    Timeout(String),
+    /// The work was cancelled, e.g. because the server is shutting down.
+    Cancelled(String),
+    /// A required argument was not given.
+    MissingArgument { argument: String, message: String },
+    /// An argument has the wrong type or value.
+    InvalidArgument {
+        argument: Option<String>,
+        message: String,
+    },
+    /// A file named by the call could not be read.
+    FileNotFound { path: String, reason: String },
+    /// A crate or package name that is not a workspace member.
+    UnknownPackage(String),
+    /// rust-analyzer is not running or could not be started.
+    Unavailable(String),
+    /// The tool is hidden by `--allow-tools`, `--deny-tools` or `--read-only`.
+    Disabled { tool: String },
+    /// rust-analyzer does not advertise the capability the tool needs.
+    Unsupported { tool: String, capability: String },
+}
+
+impl ToolError {
+    pub fn missing(argument: &str) -> Self {
+        Self::MissingArgument {
+            argument: argument.to_string(),
+            message: format!("Missing {}", argument),
+        }
+    }
+
+    /// An invalid value for `argument`.
+    pub fn invalid(argument: &str, message: impl Into<String>) -> Self {
+        Self::InvalidArgument {
+            argument: Some(argument.to_string()),
+            message: message.into(),
+        }
+    }
+
+    /// Invalid arguments that are not down to a single one.
+    pub fn invalid_arguments(message: impl Into<String>) -> Self {
+        Self::InvalidArgument {
+            argument: None,
+            message: message.into(),
+        }
+    }
+
+    /// The error for tools called while no rust-analyzer client is running.
+    pub fn client_not_initialized() -> Self {
+        Self::Unavailable("Client not initialized".to_string())
+    }
+}
+
+impl fmt::Display for ToolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Timeout(message)
+            | Self::Cancelled(message)
+            | Self::MissingArgument { message, .. }
+            | Self::InvalidArgument { message, .. }
+            | Self::UnknownPackage(message)
+            | Self::Unavailable(message) => f.write_str(message),
+            Self::FileNotFound { path, reason } => {
+                write!(f, "Failed to read file {}: {}", path, reason)
+            }
+            Self::Disabled { tool } => {
+                write!(f, "Tool '{}' is disabled by the server configuration", tool)
+            }
+            Self::Unsupported { tool, capability } => write!(
+                f,
+                "Tool '{}' is not supported by this rust-analyzer: it does not advertise {}",
+                tool, capability
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ToolError {}
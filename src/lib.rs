@@ -1,7 +1,21 @@
+//! An MCP server exposing rust-analyzer to AI assistants.
+//!
+//! The managed rust-analyzer client in [`lsp`] can also be embedded directly,
+//! without going through MCP: see [`ClientBuilder`].
+
+pub mod cargo;
 pub mod config;
+pub mod deadline;
 pub mod diagnostics;
+pub mod edits;
+pub mod error;
+pub mod logging;
 pub mod lsp;
 pub mod mcp;
+pub mod metrics;
 pub mod protocol;
+pub mod symbols;
 
-pub use mcp::RustAnalyzerMCPServer;
+pub use error::ToolError;
+pub use lsp::{ClientBuilder, LspBackend, RustAnalyzerClient, StartOptions};
+pub use mcp::{CustomTool, MessageObserver, RustAnalyzerMCPServer};
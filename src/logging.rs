@@ -0,0 +1,184 @@
+use anyhow::{anyhow, Result};
+use env_logger::{Target, WriteStyle};
+use log::Record;
+use serde_json::json;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::config::{ServerConfig, LOG_FILE_BACKUPS, LOG_FILE_MAX_BYTES};
+
+/// How log records are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// env_logger's `[timestamp LEVEL target] message` lines.
+    #[default]
+    Human,
+    /// One JSON object per line with `timestamp_ms`, `level`, `target` and `message`.
+    Json,
+}
+
+impl LogFormat {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "human" => Ok(Self::Human),
+            "json" => Ok(Self::Json),
+            _ => Err(anyhow!(
+                "Unknown log format '{}'; expected human or json",
+                name
+            )),
+        }
+    }
+}
+
+/// Installs the global logger. Logs go to stderr unless a log file is
+/// configured, since stdout carries MCP traffic.
+pub fn init(config: &ServerConfig) -> Result<()> {
+    let mut builder = env_logger::Builder::new();
+    builder.parse_filters(config.log_filter.as_deref().unwrap_or("info"));
+
+    if config.log_format == LogFormat::Json {
+        builder.format(|buf, record| writeln!(buf, "{}", format_json(record)));
+    }
+
+    if let Some(path) = &config.log_file {
+        let file = RotatingFile::open(path, LOG_FILE_MAX_BYTES, LOG_FILE_BACKUPS)
+            .map_err(|e| anyhow!("Failed to open log file {}: {}", path.display(), e))?;
+        builder
+            .target(Target::Pipe(Box::new(file)))
+            .write_style(WriteStyle::Never);
+    }
+
+    builder
+        .try_init()
+        .map_err(|e| anyhow!("Failed to initialize logging: {}", e))
+}
+
+fn format_json(record: &Record) -> String {
+    let timestamp_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0);
+    json!({
+        "timestamp_ms": timestamp_ms,
+        "level": record.level().as_str(),
+        "target": record.target(),
+        "message": record.args().to_string()
+    })
+    .to_string()
+}
+
+/// A log file that is renamed to `<file>.1` once it reaches `max_bytes`,
+/// shifting older files up to `<file>.<backups>`.
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    max_bytes: u64,
+    backups: usize,
+}
+
+impl RotatingFile {
+    fn open(path: &Path, max_bytes: u64, backups: usize) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            written,
+            max_bytes,
+            backups,
+        })
+    }
+
+    fn backup_path(&self, index: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", index));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.backups == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            for index in (1..self.backups).rev() {
+                let from = self.backup_path(index);
+                if from.exists() {
+                    fs::rename(&from, self.backup_path(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.backup_path(1))?;
+        }
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        self.file.write_all(buf)?;
+        self.written += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::{Level, Record};
+    use serde_json::Value;
+    use std::io::Write;
+
+    use super::{format_json, LogFormat, RotatingFile};
+
+    #[test]
+    fn test_format_json_record() {
+        let line = format_json(
+            &Record::builder()
+                .level(Level::Warn)
+                .target("rust_analyzer_mcp::lsp::client")
+                .args(format_args!("Request timeout for {}", "textDocument/hover"))
+                .build(),
+        );
+
+        let record: Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(record["level"], "WARN");
+        assert_eq!(record["target"], "rust_analyzer_mcp::lsp::client");
+        assert_eq!(record["message"], "Request timeout for textDocument/hover");
+        assert!(LogFormat::parse("yaml").is_err());
+    }
+
+    #[test]
+    fn test_rotating_file_keeps_bounded_backups() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("server.log");
+
+        let mut file = RotatingFile::open(&path, 16, 2).unwrap();
+        for index in 0..4 {
+            file.write_all(format!("record {:02}\n", index).as_bytes())
+                .unwrap();
+        }
+        file.flush().unwrap();
+
+        let read = |name: &str| std::fs::read_to_string(dir.path().join(name)).unwrap();
+        assert_eq!(read("server.log"), "record 03\n");
+        assert_eq!(read("server.log.1"), "record 02\n");
+        assert_eq!(read("server.log.2"), "record 01\n");
+        assert!(!dir.path().join("server.log.3").exists());
+    }
+}
@@ -0,0 +1,359 @@
+use anyhow::Result;
+use std::{collections::BTreeMap, ffi::OsString, path::PathBuf, sync::Arc, time::Duration};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{
+    config::{DIAGNOSTICS_PARALLELISM, LSP_REQUEST_TIMEOUT_SECS, SAVE_DEBOUNCE_MILLIS},
+    metrics::Metrics,
+};
+
+use super::{
+    client::RustAnalyzerClient, connection::WindowMessage, retry::RetryPolicy, trace::LspTracer,
+};
+
+/// The LSP server a [`RustAnalyzerClient`] talks to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LspBackend {
+    /// A rust-analyzer process. Without a `path`, the binary is looked up on
+    /// `PATH` and in `~/.cargo/bin`.
+    RustAnalyzer { path: Option<PathBuf> },
+    /// Responses from a session recorded with `--trace-lsp`/`--record`.
+    Replay { recording: PathBuf },
+    /// Canned responses from the built-in mock backend.
+    #[cfg(feature = "mock")]
+    Mock,
+}
+
+impl Default for LspBackend {
+    fn default() -> Self {
+        Self::RustAnalyzer { path: None }
+    }
+}
+
+/// How a [`RustAnalyzerClient`] starts and talks to its backend.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StartOptions {
+    pub backend: LspBackend,
+    /// How long each LSP request waits for its response.
+    pub request_timeout: Duration,
+    /// How requests failing with content-modified or server-cancelled errors
+    /// are retried.
+    pub retry_policy: RetryPolicy,
+    /// How many files the workspace diagnostics fallback opens at a time when
+    /// the server does not support `workspace/diagnostic`.
+    pub diagnostics_parallelism: usize,
+    /// How long a `didSave` waits for further saves of the same document, so
+    /// that a burst of edits triggers one `cargo check`; zero sends each save
+    /// right away.
+    pub save_debounce: Duration,
+    /// Whether rust-analyzer runs `cargo check` when a document is saved.
+    /// Without it, checks only run through
+    /// [`RustAnalyzerClient::run_check`].
+    pub check_on_save: bool,
+    /// `CARGO_TARGET_DIR` for the rust-analyzer process, overriding the
+    /// inherited one, so its cargo runs build apart from the user's.
+    pub target_dir: Option<PathBuf>,
+    /// Variables set on the rust-analyzer process on top of the inherited
+    /// environment; they reach the cargo and proc-macro builds it runs.
+    pub env: Vec<(String, OsString)>,
+    /// Run rust-analyzer's cargo commands with `CARGO_NET_OFFLINE`, so
+    /// loading and checking the workspace never reaches for the network.
+    pub offline: bool,
+    /// Rustup toolchain the rust-analyzer process and the cargo and rustc it
+    /// runs use, through `RUSTUP_TOOLCHAIN`.
+    pub toolchain: Option<String>,
+    /// rust-analyzer's `cargo.sysroot` setting: a sysroot path or `discover`.
+    pub sysroot: Option<String>,
+    /// rust-analyzer's `rustc.source` setting: rustc's `Cargo.toml` or
+    /// `discover`.
+    pub rustc_source: Option<String>,
+    /// Whether rust-analyzer expands proc-macros.
+    pub proc_macros: bool,
+    /// rust-analyzer's `procMacro.server` setting: the proc-macro server
+    /// binary to expand with.
+    pub proc_macro_server: Option<PathBuf>,
+    /// rust-analyzer's `procMacro.ignored` setting: macros left unexpanded,
+    /// by crate.
+    pub ignored_proc_macros: BTreeMap<String, Vec<String>>,
+    /// rust-analyzer's `numThreads` setting; its own default when unset.
+    pub num_threads: Option<usize>,
+    /// rust-analyzer's `lru.capacity` setting: how many syntax trees stay
+    /// parsed.
+    pub lru_capacity: Option<usize>,
+    /// Whether rust-analyzer indexes the whole workspace after loading it.
+    pub cache_priming: bool,
+    /// rust-analyzer's `cachePriming.numThreads` setting.
+    pub cache_priming_threads: Option<usize>,
+    /// rust-analyzer's `files.excludeDirs` setting: directories it does not
+    /// load, relative to the workspace root. The diagnostics fallback skips
+    /// them too.
+    pub exclude_dirs: Vec<PathBuf>,
+}
+
+impl Default for StartOptions {
+    fn default() -> Self {
+        Self {
+            backend: LspBackend::default(),
+            request_timeout: Duration::from_secs(LSP_REQUEST_TIMEOUT_SECS),
+            retry_policy: RetryPolicy::default(),
+            diagnostics_parallelism: DIAGNOSTICS_PARALLELISM,
+            save_debounce: Duration::from_millis(SAVE_DEBOUNCE_MILLIS),
+            check_on_save: true,
+            target_dir: None,
+            env: Vec::new(),
+            offline: false,
+            toolchain: None,
+            sysroot: None,
+            rustc_source: None,
+            proc_macros: true,
+            proc_macro_server: None,
+            ignored_proc_macros: BTreeMap::new(),
+            num_threads: None,
+            lru_capacity: None,
+            cache_priming: true,
+            cache_priming_threads: None,
+            exclude_dirs: Vec::new(),
+        }
+    }
+}
+
+/// Configures and starts a [`RustAnalyzerClient`] for embedding rust-analyzer
+/// in other tools without going through MCP.
+///
+/// ```no_run
+/// use rust_analyzer_mcp::{lsp::uri_from_path, ClientBuilder};
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// let mut client = ClientBuilder::new("path/to/crate").start().await?;
+///
+/// let path = std::path::Path::new("path/to/crate/src/lib.rs").canonicalize()?;
+/// let uri = uri_from_path(&path);
+/// client.open_document(&uri, &std::fs::read_to_string(&path)?).await?;
+/// let hover = client.hover(&uri, 0, 7).await?;
+/// println!("{hover:?}");
+///
+/// client.shutdown().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ClientBuilder {
+    workspace_root: PathBuf,
+    options: StartOptions,
+    metrics: Option<Arc<Metrics>>,
+    window_messages: Option<UnboundedSender<WindowMessage>>,
+    tracer: Option<Arc<LspTracer>>,
+}
+
+impl ClientBuilder {
+    /// A client for the Cargo workspace or package at `workspace_root`.
+    pub fn new(workspace_root: impl Into<PathBuf>) -> Self {
+        Self {
+            workspace_root: workspace_root.into(),
+            options: StartOptions::default(),
+            metrics: None,
+            window_messages: None,
+            tracer: None,
+        }
+    }
+
+    /// Replaces all start options at once.
+    pub fn options(mut self, options: StartOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn backend(mut self, backend: LspBackend) -> Self {
+        self.options.backend = backend;
+        self
+    }
+
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.options.request_timeout = timeout;
+        self
+    }
+
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.options.retry_policy = policy;
+        self
+    }
+
+    pub fn diagnostics_parallelism(mut self, files: usize) -> Self {
+        self.options.diagnostics_parallelism = files.max(1);
+        self
+    }
+
+    pub fn save_debounce(mut self, window: Duration) -> Self {
+        self.options.save_debounce = window;
+        self
+    }
+
+    pub fn check_on_save(mut self, enabled: bool) -> Self {
+        self.options.check_on_save = enabled;
+        self
+    }
+
+    pub fn target_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.options.target_dir = Some(dir.into());
+        self
+    }
+
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.options.offline = offline;
+        self
+    }
+
+    pub fn toolchain(mut self, toolchain: impl Into<String>) -> Self {
+        self.options.toolchain = Some(toolchain.into());
+        self
+    }
+
+    pub fn sysroot(mut self, sysroot: impl Into<String>) -> Self {
+        self.options.sysroot = Some(sysroot.into());
+        self
+    }
+
+    pub fn rustc_source(mut self, source: impl Into<String>) -> Self {
+        self.options.rustc_source = Some(source.into());
+        self
+    }
+
+    pub fn proc_macros(mut self, enabled: bool) -> Self {
+        self.options.proc_macros = enabled;
+        self
+    }
+
+    pub fn proc_macro_server(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.proc_macro_server = Some(path.into());
+        self
+    }
+
+    /// Leaves the proc-macro `name` from `krate` unexpanded.
+    pub fn ignore_proc_macro(mut self, krate: impl Into<String>, name: impl Into<String>) -> Self {
+        self.options
+            .ignored_proc_macros
+            .entry(krate.into())
+            .or_default()
+            .push(name.into());
+        self
+    }
+
+    pub fn num_threads(mut self, threads: usize) -> Self {
+        self.options.num_threads = Some(threads);
+        self
+    }
+
+    pub fn lru_capacity(mut self, capacity: usize) -> Self {
+        self.options.lru_capacity = Some(capacity);
+        self
+    }
+
+    pub fn cache_priming(mut self, enabled: bool) -> Self {
+        self.options.cache_priming = enabled;
+        self
+    }
+
+    pub fn cache_priming_threads(mut self, threads: usize) -> Self {
+        self.options.cache_priming_threads = Some(threads);
+        self
+    }
+
+    pub fn exclude_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.options.exclude_dirs.push(dir.into());
+        self
+    }
+
+    /// Sets an environment variable on the rust-analyzer process.
+    pub fn env(mut self, name: impl Into<String>, value: impl Into<OsString>) -> Self {
+        self.options.env.push((name.into(), value.into()));
+        self
+    }
+
+    /// Records LSP request timings into `metrics` instead of a private collector.
+    pub fn metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Forwards rust-analyzer's `window/showMessage` and `window/logMessage`
+    /// notifications to `sender`.
+    pub fn window_messages(mut self, sender: UnboundedSender<WindowMessage>) -> Self {
+        self.window_messages = Some(sender);
+        self
+    }
+
+    /// Writes every LSP frame sent to and received from the backend to `tracer`.
+    pub fn lsp_trace(mut self, tracer: Arc<LspTracer>) -> Self {
+        self.tracer = Some(tracer);
+        self
+    }
+
+    /// Creates the client without starting its backend; see
+    /// [`RustAnalyzerClient::start`].
+    pub fn build(self) -> RustAnalyzerClient {
+        let mut client = RustAnalyzerClient::unstarted(self.workspace_root, self.options);
+        if let Some(metrics) = self.metrics {
+            client.metrics = metrics;
+        }
+        client.window_messages = self.window_messages;
+        client.tracer = self.tracer;
+        client
+    }
+
+    /// Creates the client and starts its backend, returning once the LSP
+    /// session is initialized. Indexing continues in the background; see
+    /// [`RustAnalyzerClient::readiness`].
+    pub async fn start(self) -> Result<RustAnalyzerClient> {
+        let mut client = self.build();
+        client.start().await?;
+        Ok(client)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, time::Duration};
+
+    use super::{ClientBuilder, LspBackend, StartOptions};
+    use crate::lsp::RetryPolicy;
+
+    #[test]
+    fn test_builder_applies_start_options() {
+        let policy = RetryPolicy {
+            max_retries: 1,
+            ..RetryPolicy::default()
+        };
+        let mut client = ClientBuilder::new("/tmp/project")
+            .backend(LspBackend::Replay {
+                recording: PathBuf::from("/tmp/session.jsonl"),
+            })
+            .request_timeout(Duration::from_secs(5))
+            .retry_policy(policy)
+            .diagnostics_parallelism(0)
+            .target_dir("/tmp/project/target/ra-mcp")
+            .env("RUSTFLAGS", "--cfg ci")
+            .offline(true)
+            .build();
+
+        assert_eq!(client.workspace_root, PathBuf::from("/tmp/project"));
+        assert_eq!(client.options().retry_policy, policy);
+        assert_eq!(client.options().diagnostics_parallelism, 1);
+        assert_eq!(
+            client.options().target_dir,
+            Some(PathBuf::from("/tmp/project/target/ra-mcp"))
+        );
+        assert_eq!(
+            client.options().env,
+            [("RUSTFLAGS".to_string(), "--cfg ci".into())]
+        );
+        assert!(client.options().offline);
+        assert_eq!(client.request_timeout, Duration::from_secs(5));
+
+        client.set_request_timeout(Duration::from_secs(60));
+        client.reset_request_timeout();
+        assert_eq!(client.request_timeout, Duration::from_secs(5));
+
+        let defaults = ClientBuilder::new("/tmp/project").build();
+        assert_eq!(defaults.options(), &StartOptions::default());
+    }
+}
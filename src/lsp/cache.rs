@@ -0,0 +1,99 @@
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// How long a cached result is trusted. Documents changed through the client
+/// invalidate the cache right away, but rust-analyzer also picks up edits
+/// made on disk by others, which the client does not see.
+const QUERY_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// The most results kept; the cache is emptied when it fills up.
+const MAX_CACHED_QUERIES: usize = 512;
+
+/// Results of pure queries such as hover, keyed by document URI and request
+/// and valid for the document version they were computed for. Results can
+/// depend on other documents too, so any document change empties the whole
+/// cache.
+#[derive(Debug, Default)]
+pub(super) struct QueryCache {
+    entries: HashMap<(String, String), CachedQuery>,
+}
+
+#[derive(Debug)]
+struct CachedQuery {
+    version: i32,
+    stored: Instant,
+    result: Value,
+}
+
+impl QueryCache {
+    /// The result cached for `key` on `uri` at `version`, unless it expired.
+    pub fn get(&self, uri: &str, key: &str, version: i32) -> Option<Value> {
+        self.entries
+            .get(&(uri.to_string(), key.to_string()))
+            .filter(|entry| entry.version == version && entry.stored.elapsed() < QUERY_CACHE_TTL)
+            .map(|entry| entry.result.clone())
+    }
+
+    pub fn insert(&mut self, uri: &str, key: &str, version: i32, result: Value) {
+        if self.entries.len() >= MAX_CACHED_QUERIES {
+            self.entries.clear();
+        }
+        let entry = CachedQuery {
+            version,
+            stored: Instant::now(),
+            result,
+        };
+        self.entries
+            .insert((uri.to_string(), key.to_string()), entry);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// Notifications after which cached query results may be stale.
+pub(super) fn invalidates_queries(method: &str) -> bool {
+    matches!(
+        method,
+        "textDocument/didOpen"
+            | "textDocument/didChange"
+            | "textDocument/didClose"
+            | "workspace/didRenameFiles"
+            | "workspace/didChangeWatchedFiles"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{invalidates_queries, QueryCache};
+
+    #[test]
+    fn test_query_cache_is_keyed_by_document_version() {
+        let mut cache = QueryCache::default();
+        let key = "textDocument/hover 3:7";
+        cache.insert(
+            "file:///ws/src/lib.rs",
+            key,
+            2,
+            json!({ "contents": "u32" }),
+        );
+
+        assert_eq!(
+            cache.get("file:///ws/src/lib.rs", key, 2),
+            Some(json!({ "contents": "u32" }))
+        );
+        assert_eq!(cache.get("file:///ws/src/lib.rs", key, 3), None);
+        assert_eq!(cache.get("file:///ws/src/main.rs", key, 2), None);
+
+        cache.clear();
+        assert_eq!(cache.get("file:///ws/src/lib.rs", key, 2), None);
+        assert!(invalidates_queries("textDocument/didChange"));
+        assert!(!invalidates_queries("textDocument/didSave"));
+    }
+}
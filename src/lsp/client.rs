@@ -1,44 +1,177 @@
 use anyhow::{anyhow, Result};
-use log::info;
+use log::{error, info, warn};
 use serde_json::{json, Value};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet, VecDeque},
     path::PathBuf,
     process::Stdio,
     sync::Arc,
     time::Duration,
 };
 use tokio::{
-    io::{AsyncWriteExt, BufWriter},
-    process::{Child, Command},
-    sync::{oneshot, Mutex},
+    io::BufWriter,
+    process::{Child, ChildStderr, Command},
+    sync::{mpsc::UnboundedSender, oneshot, Mutex},
+    time::Instant,
 };
 
 use crate::{
-    config::{DOCUMENT_OPEN_DELAY_MILLIS, LSP_REQUEST_TIMEOUT_SECS},
-    protocol::lsp::LSPRequest,
+    config::{DOCUMENT_OPEN_DELAY_MILLIS, LSP_SHUTDOWN_TIMEOUT_MILLIS, PASSTHROUGH_ENV_VARS},
+    deadline::wait_limit,
+    error::ToolError,
+    metrics::{CallOutcome, Metrics},
+    protocol::lsp::{Diagnostic, LSPRequest},
+};
+
+use super::{
+    builder::{ClientBuilder, LspBackend, StartOptions},
+    cache::{invalidates_queries, QueryCache},
+    connection::{
+        write_frame, LspReader, LspResult, LspSink, LspWriter, MessageSinks, WindowMessage,
+    },
+    progress::{IndexingProgress, Readiness},
+    retry::{is_transient_error, LspError},
+    trace::LspTracer,
+    uri::path_from_uri,
 };
 
 #[derive(Debug, Clone)]
 pub(super) struct OpenDocumentState {
     version: i32,
     content: String,
+    read_only: bool,
+    /// `documentSymbol` result and the document version it was computed for.
+    symbols: Option<(i32, Value)>,
+    /// When a caller last opened or synced the document.
+    last_access: Instant,
 }
 
+impl OpenDocumentState {
+    fn new(content: &str, read_only: bool) -> Self {
+        Self {
+            version: 1,
+            content: content.to_string(),
+            read_only,
+            symbols: None,
+            last_access: Instant::now(),
+        }
+    }
+
+    pub(super) fn version(&self) -> i32 {
+        self.version
+    }
+
+    /// Returns the cached document symbols if they belong to the current version.
+    pub(super) fn cached_symbols(&self) -> Option<&Value> {
+        self.symbols
+            .as_ref()
+            .filter(|(version, _)| *version == self.version)
+            .map(|(_, symbols)| symbols)
+    }
+
+    /// Caches document symbols computed for `version`, unless the document has
+    /// changed while they were being requested.
+    pub(super) fn cache_symbols(&mut self, version: i32, symbols: Value) {
+        if version == self.version {
+            self.symbols = Some((version, symbols));
+        }
+    }
+
+    fn update(&mut self, content: &str) {
+        self.version += 1;
+        self.content = content.to_string();
+        self.symbols = None;
+    }
+}
+
+/// A document open in rust-analyzer, as listed by
+/// [`RustAnalyzerClient::open_documents`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenDocument {
+    pub uri: String,
+    pub version: i32,
+    /// Size of the content rust-analyzer holds, in bytes.
+    pub size: usize,
+    pub read_only: bool,
+    /// Time since a caller last opened or synced the document.
+    pub idle: Duration,
+}
+
+/// `didSave` notifications waiting out the debounce window, by URI, each with
+/// the ticket of the latest save asked for.
+#[derive(Debug, Default)]
+pub(super) struct PendingSaves {
+    next_ticket: u64,
+    tickets: HashMap<String, u64>,
+}
+
+impl PendingSaves {
+    /// Records a save of `uri`, superseding any pending one, and returns its ticket.
+    fn schedule(&mut self, uri: &str) -> u64 {
+        self.next_ticket += 1;
+        self.tickets.insert(uri.to_string(), self.next_ticket);
+        self.next_ticket
+    }
+
+    /// Whether `ticket` is still the latest save of `uri`, forgetting it if so.
+    fn take(&mut self, uri: &str, ticket: u64) -> bool {
+        if self.tickets.get(uri) != Some(&ticket) {
+            return false;
+        }
+        self.tickets.remove(uri);
+        true
+    }
+}
+
+/// A managed rust-analyzer session for one workspace: the process, the LSP
+/// connection to it and the documents opened through it.
+///
+/// Create one with [`RustAnalyzerClient::builder`]. The client owns the
+/// rust-analyzer process, which is killed when the client is dropped; call
+/// [`RustAnalyzerClient::shutdown`] to end the session gracefully instead.
 pub struct RustAnalyzerClient {
     pub(super) process: Option<Child>,
     pub(super) request_id: Arc<Mutex<u64>>,
     pub(super) workspace_root: PathBuf,
-    pub(super) stdin: Option<BufWriter<tokio::process::ChildStdin>>,
-    pub(super) pending_requests: Arc<Mutex<HashMap<u64, oneshot::Sender<Value>>>>,
+    pub(super) stdin: Option<LspWriter>,
+    pub(super) pending_requests: Arc<Mutex<HashMap<u64, oneshot::Sender<LspResult>>>>,
     pub(super) initialized: bool,
     pub(super) workspace_diagnostics_supported: bool,
+    /// The `capabilities` rust-analyzer answered `initialize` with.
+    pub(super) capabilities: Value,
+    pub(super) server_info: Option<Value>,
     pub(super) open_documents: Arc<Mutex<HashMap<String, OpenDocumentState>>>,
-    pub(super) diagnostics: Arc<Mutex<HashMap<String, Vec<Value>>>>,
+    /// Documents callers asked to open since `take_synced_documents`.
+    pub(super) synced_documents: HashSet<String>,
+    pub(super) diagnostics: Arc<Mutex<HashMap<String, Vec<Diagnostic>>>>,
+    /// Results of pure queries, such as hover, per document version.
+    pub(super) query_cache: Arc<Mutex<QueryCache>>,
+    pub(super) pending_saves: Arc<Mutex<PendingSaves>>,
+    pub(super) stderr_log: Arc<Mutex<VecDeque<String>>>,
+    pub(super) progress: Arc<Mutex<IndexingProgress>>,
+    pub(super) metrics: Arc<Metrics>,
+    pub(super) tracer: Option<Arc<LspTracer>>,
+    pub(super) window_messages: Option<UnboundedSender<WindowMessage>>,
+    /// Timeout for the next requests; `options.request_timeout` unless overridden.
+    pub(super) request_timeout: Duration,
+    /// The rust-analyzer settings in effect: those the options give, with the
+    /// overrides of [`RustAnalyzerClient::reconfigure`] applied.
+    pub(super) settings: Arc<Mutex<Value>>,
+    pub(super) options: StartOptions,
 }
 
 impl RustAnalyzerClient {
+    /// Configures a client for the workspace at `workspace_root`.
+    pub fn builder(workspace_root: impl Into<PathBuf>) -> ClientBuilder {
+        ClientBuilder::new(workspace_root)
+    }
+
+    /// A client for `workspace_root` with default [`StartOptions`], not yet started.
     pub fn new(workspace_root: PathBuf) -> Self {
+        ClientBuilder::new(workspace_root).build()
+    }
+
+    pub(super) fn unstarted(workspace_root: PathBuf, options: StartOptions) -> Self {
         // Ensure the workspace root is absolute.
         let workspace_root = workspace_root.canonicalize().unwrap_or_else(|_| {
             if workspace_root.is_absolute() {
@@ -58,44 +191,137 @@ impl RustAnalyzerClient {
             pending_requests: Arc::new(Mutex::new(HashMap::new())),
             initialized: false,
             workspace_diagnostics_supported: false,
+            capabilities: Value::Null,
+            server_info: None,
             open_documents: Arc::new(Mutex::new(HashMap::new())),
+            synced_documents: HashSet::new(),
             diagnostics: Arc::new(Mutex::new(HashMap::new())),
+            query_cache: Arc::new(Mutex::new(QueryCache::default())),
+            pending_saves: Arc::new(Mutex::new(PendingSaves::default())),
+            stderr_log: Arc::new(Mutex::new(VecDeque::new())),
+            progress: Arc::new(Mutex::new(IndexingProgress::default())),
+            metrics: Arc::new(Metrics::new()),
+            tracer: None,
+            window_messages: None,
+            request_timeout: options.request_timeout,
+            settings: Arc::new(Mutex::new(initialization_options(&options))),
+            options,
         }
     }
 
+    /// Sets how long requests wait for rust-analyzer's response.
+    pub fn set_request_timeout(&mut self, timeout: Duration) {
+        self.request_timeout = timeout;
+    }
+
+    /// Restores the request timeout the client was started with.
+    pub fn reset_request_timeout(&mut self) {
+        self.request_timeout = self.options.request_timeout;
+    }
+
+    /// The options the client was built with.
+    pub fn options(&self) -> &StartOptions {
+        &self.options
+    }
+
+    /// The rust-analyzer settings in effect.
+    pub async fn settings(&self) -> Value {
+        self.settings.lock().await.clone()
+    }
+
+    /// Starts the backend and initializes the LSP session. Indexing continues
+    /// in the background; see [`RustAnalyzerClient::readiness`].
     pub async fn start(&mut self) -> Result<()> {
         info!(
             "Starting rust-analyzer process in workspace: {}",
             self.workspace_root.display()
         );
 
-        // Clear any existing diagnostics from previous sessions.
+        // Clear any existing diagnostics, cached results and progress from
+        // previous sessions.
         self.diagnostics.lock().await.clear();
+        self.query_cache.lock().await.clear();
+        *self.progress.lock().await = IndexingProgress::default();
+
+        let (stdout, stdin, stderr) = self.spawn_backend()?;
+        let stdin = Arc::new(Mutex::new(BufWriter::new(stdin)));
+        self.stdin = Some(Arc::clone(&stdin));
+
+        // Start connection handlers.
+        super::connection::start_handlers(
+            stdout,
+            stderr,
+            stdin,
+            Arc::clone(&self.stderr_log),
+            MessageSinks {
+                pending_requests: Arc::clone(&self.pending_requests),
+                diagnostics: Arc::clone(&self.diagnostics),
+                progress: Arc::clone(&self.progress),
+                window_messages: self.window_messages.clone(),
+                settings: Arc::clone(&self.settings),
+            },
+            self.tracer.clone(),
+        );
+
+        // Initialize LSP.
+        *self.settings.lock().await = initialization_options(&self.options);
+        self.initialize().await?;
+        self.initialized = true;
+
+        // Send workspace/didChangeConfiguration to ensure settings are applied.
+        let _ = self.send_settings().await;
+
+        info!("rust-analyzer client started and initialized");
+        Ok(())
+    }
 
-        // Find rust-analyzer executable.
-        let rust_analyzer_path = find_rust_analyzer()?;
+    /// Spawns the configured backend, returning the streams the LSP session
+    /// runs over.
+    fn spawn_backend(&mut self) -> Result<(LspReader, LspSink, Option<ChildStderr>)> {
+        let rust_analyzer_path = match &self.options.backend {
+            LspBackend::RustAnalyzer { path: Some(path) } => path.clone(),
+            LspBackend::RustAnalyzer { path: None } => find_rust_analyzer()?,
+            LspBackend::Replay { recording } => {
+                info!("Replaying LSP recording {}", recording.display());
+                let (stdout, stdin) = super::replay::spawn(recording)?;
+                return Ok((stdout, stdin, None));
+            }
+            #[cfg(feature = "mock")]
+            LspBackend::Mock => {
+                info!("Using the mock LSP backend instead of rust-analyzer");
+                let (stdout, stdin) = super::mock::spawn();
+                return Ok((stdout, stdin, None));
+            }
+        };
         info!("Using rust-analyzer at: {}", rust_analyzer_path.display());
 
         let mut cmd = Command::new(rust_analyzer_path);
         cmd.current_dir(&self.workspace_root)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
 
         // Pass through isolation environment variables if they're set.
-        if let Ok(cache_home) = std::env::var("XDG_CACHE_HOME") {
-            cmd.env("XDG_CACHE_HOME", cache_home);
+        for name in PASSTHROUGH_ENV_VARS {
+            if let Ok(value) = std::env::var(name) {
+                cmd.env(name, value);
+            }
         }
-        if let Ok(target_dir) = std::env::var("CARGO_TARGET_DIR") {
+        if let Some(target_dir) = &self.options.target_dir {
             cmd.env("CARGO_TARGET_DIR", target_dir);
         }
-        if let Ok(tmpdir) = std::env::var("TMPDIR") {
-            cmd.env("TMPDIR", tmpdir);
+        if let Some(toolchain) = &self.options.toolchain {
+            cmd.env("RUSTUP_TOOLCHAIN", toolchain);
+        }
+        if self.options.offline {
+            cmd.env("CARGO_NET_OFFLINE", "true");
         }
+        cmd.envs(self.options.env.iter().map(|(name, value)| (name, value)));
 
         let mut child = cmd
             .spawn()
-            .map_err(|e| anyhow!("Failed to start rust-analyzer: {}", e))?;
+            .map_err(|e| ToolError::Unavailable(format!("Failed to start rust-analyzer: {}", e)))?;
 
         let stdin = child
             .stdin
@@ -110,40 +336,90 @@ impl RustAnalyzerClient {
             .take()
             .ok_or_else(|| anyhow!("Failed to get stderr"))?;
 
-        self.stdin = Some(BufWriter::new(stdin));
+        self.process = Some(child);
+        Ok((Box::new(stdout), Box::new(stdin), Some(stderr)))
+    }
 
-        // Start connection handlers.
-        super::connection::start_handlers(
-            stdout,
-            stderr,
-            Arc::clone(&self.pending_requests),
-            Arc::clone(&self.diagnostics),
-        );
+    /// Returns up to `limit` of the most recent rust-analyzer stderr lines, oldest
+    /// first, optionally only those containing `filter`.
+    pub async fn recent_stderr(&self, limit: usize, filter: Option<&str>) -> Vec<String> {
+        let log = self.stderr_log.lock().await;
+        let mut lines: Vec<String> = log
+            .iter()
+            .rev()
+            .filter(|line| filter.is_none_or(|filter| line.contains(filter)))
+            .take(limit)
+            .cloned()
+            .collect();
+        lines.reverse();
+        lines
+    }
 
-        self.process = Some(child);
+    /// Whether rust-analyzer has finished loading and indexing the workspace
+    /// and has no `cargo check` or other background task running.
+    pub async fn is_ready(&self) -> bool {
+        self.initialized && self.progress.lock().await.is_ready()
+    }
 
-        // Initialize LSP.
-        self.initialize().await?;
-        self.initialized = true;
+    /// Indexing progress and whether rust-analyzer is ready.
+    pub async fn readiness(&self) -> Readiness {
+        if !self.initialized {
+            return Readiness::not_started();
+        }
+        self.progress.lock().await.readiness()
+    }
 
-        // Send workspace/didChangeConfiguration to ensure settings are applied.
-        let config_params = json!({
-            "settings": {
-                "rust-analyzer": {
-                    "checkOnSave": {
-                        "enable": true,
-                        "command": "check",
-                        "allTargets": true
-                    }
-                }
-            }
-        });
-        let _ = self
-            .send_notification("workspace/didChangeConfiguration", Some(config_params))
-            .await;
+    /// The version of an open document whose query results may be cached: only
+    /// once rust-analyzer is ready, as answers given while indexing can be
+    /// incomplete.
+    pub(super) async fn cacheable_version(&self, uri: &str) -> Option<i32> {
+        if !self.is_ready().await {
+            return None;
+        }
+        self.open_documents
+            .lock()
+            .await
+            .get(uri)
+            .map(OpenDocumentState::version)
+    }
 
-        info!("rust-analyzer client started and initialized");
-        Ok(())
+    /// Forgets cached query results, so that the next queries reach
+    /// rust-analyzer.
+    pub async fn clear_cached_queries(&self) {
+        self.query_cache.lock().await.clear();
+    }
+
+    /// The `serverInfo` (name and version) reported by rust-analyzer during initialization.
+    pub fn server_info(&self) -> Option<&Value> {
+        self.server_info.as_ref()
+    }
+
+    /// Whether rust-analyzer advertises the server `capability`, such as
+    /// `documentLinkProvider`; assumed until it has been initialized and told
+    /// otherwise.
+    pub fn supports(&self, capability: &str) -> bool {
+        !self.initialized
+            || self.capabilities[capability] != Value::Null
+                && self.capabilities[capability] != json!(false)
+    }
+
+    /// Whether rust-analyzer wants `didSave` notifications. Sync options
+    /// without `save` turn them off; the plain sync kind, or a server not
+    /// initialized yet, gets them.
+    fn wants_saves(&self) -> bool {
+        let sync = &self.capabilities["textDocumentSync"];
+        !sync.is_object() || !matches!(sync["save"], Value::Null | Value::Bool(false))
+    }
+
+    /// Whether rust-analyzer asked for the document text in `didSave`.
+    fn save_includes_text(&self) -> bool {
+        self.capabilities["textDocumentSync"]["save"]["includeText"] == json!(true)
+    }
+
+    /// Whether rust-analyzer wants a say, through `willSaveWaitUntil`, in what
+    /// documents look like when they are saved.
+    pub fn wants_will_save_wait_until(&self) -> bool {
+        self.capabilities["textDocumentSync"]["willSaveWaitUntil"] == json!(true)
     }
 
     pub(super) async fn send_notification(
@@ -151,6 +427,10 @@ impl RustAnalyzerClient {
         method: &str,
         params: Option<Value>,
     ) -> Result<()> {
+        if invalidates_queries(method) {
+            self.query_cache.lock().await.clear();
+        }
+
         let notification = json!({
             "jsonrpc": "2.0",
             "method": method,
@@ -158,24 +438,61 @@ impl RustAnalyzerClient {
         });
 
         let content = serde_json::to_string(&notification)?;
-        let message = format!("Content-Length: {}\r\n\r\n{}", content.len(), content);
 
         info!("Sending LSP notification: {}", method);
 
-        let Some(stdin) = &mut self.stdin else {
+        let Some(stdin) = &self.stdin else {
             return Err(anyhow!("No stdin available"));
         };
 
-        stdin.write_all(message.as_bytes()).await?;
-        stdin.flush().await?;
+        write_frame(stdin, &self.tracer, &content).await?;
         Ok(())
     }
 
-    pub(super) async fn send_request(
-        &mut self,
-        method: &str,
-        params: Option<Value>,
-    ) -> Result<Value> {
+    /// Sends a request and waits for its result, retrying transient failures
+    /// such as content-modified errors during indexing. Transient errors that
+    /// outlast the retries fail with an [`LspError`]; other LSP errors yield
+    /// `null`.
+    pub(super) async fn send_request(&self, method: &str, params: Option<Value>) -> Result<Value> {
+        match self.request(method, params).await {
+            Err(error) => match error.downcast::<LspError>() {
+                Ok(lsp_error) if !lsp_error.is_transient() => {
+                    error!("{}", lsp_error);
+                    Ok(json!(null))
+                }
+                Ok(lsp_error) => Err(lsp_error.into()),
+                Err(error) => Err(error),
+            },
+            result => result,
+        }
+    }
+
+    /// Like [`send_request`](Self::send_request), but every LSP error fails
+    /// with an [`LspError`] carrying rust-analyzer's code and message, for
+    /// requests whose errors explain what was wrong with them.
+    pub(super) async fn request(&self, method: &str, params: Option<Value>) -> Result<Value> {
+        let mut attempt = 0;
+        loop {
+            match self.send_request_once(method, params.clone()).await? {
+                Ok(result) => return Ok(result),
+                Err(error)
+                    if is_transient_error(&error)
+                        && attempt < self.options.retry_policy.max_retries =>
+                {
+                    let delay = self.options.retry_policy.backoff(attempt);
+                    warn!(
+                        "LSP request {} failed with {}; retrying in {:?}",
+                        method, error, delay
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(error) => return Err(LspError::new(method, &error).into()),
+            }
+        }
+    }
+
+    async fn send_request_once(&self, method: &str, params: Option<Value>) -> Result<LspResult> {
         let mut request_id_lock = self.request_id.lock().await;
         let id = *request_id_lock;
         *request_id_lock += 1;
@@ -189,67 +506,91 @@ impl RustAnalyzerClient {
         };
 
         let content = serde_json::to_string(&request)?;
-        let message = format!("Content-Length: {}\r\n\r\n{}", content.len(), content);
 
         info!("Sending LSP request: {} with params: {:?}", method, params);
 
-        let Some(stdin) = &mut self.stdin else {
+        let Some(stdin) = &self.stdin else {
             return Err(anyhow!("No stdin available"));
         };
 
-        stdin.write_all(message.as_bytes()).await?;
-        stdin.flush().await?;
+        write_frame(stdin, &self.tracer, &content).await?;
 
         // Set up response channel.
         let (tx, rx) = oneshot::channel();
         self.pending_requests.lock().await.insert(id, tx);
 
-        // Wait for response with timeout.
-        tokio::time::timeout(Duration::from_secs(LSP_REQUEST_TIMEOUT_SECS), rx)
-            .await
-            .map_err(|_| anyhow!("Request timeout"))?
-            .map_err(|_| anyhow!("Request cancelled"))
+        // Wait for response with timeout, or for what is left of the call's
+        // budget.
+        let started = Instant::now();
+        let response = tokio::time::timeout(wait_limit(self.request_timeout), rx).await;
+        let outcome = match &response {
+            Ok(Ok(Ok(_))) => CallOutcome::Ok,
+            Ok(Ok(Err(_)) | Err(_)) => CallOutcome::Error,
+            Err(_) => CallOutcome::Timeout,
+        };
+        self.metrics
+            .record_lsp_request(method, started.elapsed(), outcome);
+
+        let Ok(response) = response else {
+            self.abandon_request(id).await;
+            return Err(ToolError::Timeout("Request timeout".to_string()).into());
+        };
+        response.map_err(|_| ToolError::Cancelled("Request cancelled".to_string()).into())
+    }
+
+    /// Forgets a request that timed out and asks rust-analyzer to stop
+    /// working on it.
+    async fn abandon_request(&self, id: u64) {
+        self.pending_requests.lock().await.remove(&id);
+        let cancel = json!({
+            "jsonrpc": "2.0",
+            "method": "$/cancelRequest",
+            "params": { "id": id }
+        });
+        if let Some(stdin) = &self.stdin {
+            if let Err(e) = write_frame(stdin, &self.tracer, &cancel.to_string()).await {
+                warn!("Failed to cancel LSP request {}: {}", id, e);
+            }
+        }
     }
 
     async fn initialize(&mut self) -> Result<()> {
         let init_params = json!({
             "processId": std::process::id(),
             "rootUri": format!("file://{}", self.workspace_root.display()),
-            "initializationOptions": {
-                "cargo": {
-                    "buildScripts": {
-                        "enable": true
-                    }
-                },
-                "checkOnSave": {
-                    "enable": true,
-                    "command": "check",
-                    "allTargets": true
-                },
-                "diagnostics": {
-                    "enable": true,
-                    "experimental": {
-                        "enable": true
-                    }
-                },
-                "procMacro": {
-                    "enable": true
-                }
-            },
+            "initializationOptions": self.settings.lock().await.clone(),
             "capabilities": {
                 "textDocument": {
+                    "synchronization": {
+                        "didSave": true,
+                        "willSaveWaitUntil": true
+                    },
                     "hover": {
                         "contentFormat": ["markdown", "plaintext"]
                     },
                     "completion": {
                         "completionItem": {
-                            "snippetSupport": true
+                            "snippetSupport": true,
+                            "resolveSupport": {
+                                "properties": ["additionalTextEdits"]
+                            }
                         }
                     },
                     "definition": {
                         "linkSupport": true
                     },
                     "references": {},
+                    "documentLink": {},
+                    "documentHighlight": {},
+                    "callHierarchy": {},
+                    "inlayHint": {
+                        "resolveSupport": {
+                            "properties": ["tooltip", "label.tooltip", "label.location"]
+                        }
+                    },
+                    "rename": {
+                        "prepareSupport": true
+                    },
                     "documentSymbol": {},
                     "codeAction": {
                         "codeActionLiteralSupport": {
@@ -280,6 +621,23 @@ impl RustAnalyzerClient {
                 "workspace": {
                     "didChangeConfiguration": {
                         "dynamicRegistration": false
+                    },
+                    "configuration": true,
+                    "fileOperations": {
+                        "willRename": true,
+                        "didRename": true
+                    }
+                },
+                "window": {
+                    "workDoneProgress": true
+                },
+                "experimental": {
+                    "serverStatusNotification": true,
+                    "localDocs": true,
+                    "hoverActions": true,
+                    "hoverRange": true,
+                    "commands": {
+                        "commands": ["rust-analyzer.showReferences", "rust-analyzer.gotoLocation"]
                     }
                 }
             }
@@ -296,6 +654,11 @@ impl RustAnalyzerClient {
             "workspace/diagnostic support: {}",
             self.workspace_diagnostics_supported
         );
+        self.capabilities = init_response
+            .get("capabilities")
+            .cloned()
+            .unwrap_or_default();
+        self.server_info = init_response.get("serverInfo").cloned();
         self.send_notification("initialized", Some(json!({})))
             .await?;
 
@@ -307,7 +670,44 @@ impl RustAnalyzerClient {
         Ok(())
     }
 
+    /// Opens a document with `content`, or syncs it if it is already open and
+    /// the content changed. Requests about a document need it open first.
     pub async fn open_document(&mut self, uri: &str, content: &str) -> Result<()> {
+        self.synced_documents.insert(uri.to_string());
+        self.sync_document(uri, content, false).await
+    }
+
+    /// Opens a file that lives outside the workspace, such as dependency sources in
+    /// `~/.cargo/registry` or the standard library. These documents are never
+    /// saved, so they do not trigger flycheck runs.
+    pub async fn open_read_only_document(&mut self, uri: &str, content: &str) -> Result<()> {
+        self.synced_documents.insert(uri.to_string());
+        self.sync_document(uri, content, true).await
+    }
+
+    async fn sync_document(&mut self, uri: &str, content: &str, read_only: bool) -> Result<()> {
+        if self.send_document(uri, content, read_only).await? {
+            // Give rust-analyzer time to process the document and run cargo check.
+            tokio::time::sleep(Duration::from_millis(DOCUMENT_OPEN_DELAY_MILLIS)).await;
+        }
+        Ok(())
+    }
+
+    /// Opens a workspace document like [`open_document`](Self::open_document)
+    /// without waiting for rust-analyzer to process it, so several documents
+    /// can be sent before one shared delay. Returns whether anything was sent.
+    pub(super) async fn open_document_without_delay(
+        &mut self,
+        uri: &str,
+        content: &str,
+    ) -> Result<bool> {
+        self.synced_documents.insert(uri.to_string());
+        self.send_document(uri, content, false).await
+    }
+
+    /// Sends `didOpen` or `didChange` (and `didSave` for writable documents)
+    /// for a document whose content differs from what the server has.
+    async fn send_document(&mut self, uri: &str, content: &str, read_only: bool) -> Result<bool> {
         enum DocumentSyncAction {
             NoChange,
             Open { version: i32 },
@@ -316,33 +716,29 @@ impl RustAnalyzerClient {
 
         let action = {
             let mut open_docs = self.open_documents.lock().await;
+            if let Some(state) = open_docs.get_mut(uri) {
+                state.last_access = Instant::now();
+            }
             match open_docs.get_mut(uri) {
                 Some(state) if state.content == content => {
                     info!("Document already open and up to date: {}", uri);
                     DocumentSyncAction::NoChange
                 }
                 Some(state) => {
-                    state.version += 1;
-                    state.content = content.to_string();
+                    state.update(content);
                     DocumentSyncAction::Change {
                         version: state.version,
                     }
                 }
                 None => {
-                    open_docs.insert(
-                        uri.to_string(),
-                        OpenDocumentState {
-                            version: 1,
-                            content: content.to_string(),
-                        },
-                    );
+                    open_docs.insert(uri.to_string(), OpenDocumentState::new(content, read_only));
                     DocumentSyncAction::Open { version: 1 }
                 }
             }
         };
 
         if matches!(action, DocumentSyncAction::NoChange) {
-            return Ok(());
+            return Ok(false);
         }
 
         // Clear existing diagnostics for this URI so callers don't see stale entries
@@ -383,23 +779,146 @@ impl RustAnalyzerClient {
         }
 
         // Send didSave to trigger checkOnSave diagnostics refresh.
-        let save_params = json!({
+        if !read_only && self.options.check_on_save && self.wants_saves() {
+            self.save_document(uri, content).await?;
+        }
+
+        Ok(true)
+    }
+
+    /// Sends `didSave` for `uri`, which makes rust-analyzer run `cargo check`.
+    /// Within the debounce window the save waits for further saves of the
+    /// same document and only the last one is sent, so a burst of edits runs
+    /// one check instead of queueing one per edit. `content` goes along when
+    /// rust-analyzer asked for the text, so documents that only exist as
+    /// overlays are checked as they are.
+    async fn save_document(&mut self, uri: &str, content: &str) -> Result<()> {
+        let mut params = json!({
             "textDocument": {
                 "uri": uri
             }
         });
-        self.send_notification("textDocument/didSave", Some(save_params))
-            .await?;
+        if self.save_includes_text() {
+            params["text"] = json!(content);
+        }
+        let window = self.options.save_debounce;
+        if window.is_zero() {
+            return self
+                .send_notification("textDocument/didSave", Some(params))
+                .await;
+        }
+
+        let Some(stdin) = self.stdin.clone() else {
+            return Err(anyhow!("No stdin available"));
+        };
+        let ticket = self.pending_saves.lock().await.schedule(uri);
+        let pending_saves = Arc::clone(&self.pending_saves);
+        let tracer = self.tracer.clone();
+        let uri = uri.to_string();
+        let content = json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didSave",
+            "params": params
+        })
+        .to_string();
+        tokio::spawn(async move {
+            tokio::time::sleep(window).await;
+            if !pending_saves.lock().await.take(&uri, ticket) {
+                return;
+            }
+            info!("Sending LSP notification: textDocument/didSave");
+            if let Err(e) = write_frame(&stdin, &tracer, &content).await {
+                error!("Failed to send didSave for {}: {}", uri, e);
+            }
+        });
+        Ok(())
+    }
+
+    /// Closes a document opened with [`open_document`](Self::open_document);
+    /// does nothing when it is not open.
+    pub async fn close_document(&mut self, uri: &str) -> Result<()> {
+        if self.open_documents.lock().await.remove(uri).is_none() {
+            return Ok(());
+        }
+        self.pending_saves.lock().await.tickets.remove(uri);
+
+        info!("Closing document: {}", uri);
+        let params = json!({
+            "textDocument": {
+                "uri": uri
+            }
+        });
+        self.send_notification("textDocument/didClose", Some(params))
+            .await
+    }
+
+    /// Whether `uri` is currently open in rust-analyzer.
+    pub async fn is_document_open(&self, uri: &str) -> bool {
+        self.open_documents.lock().await.contains_key(uri)
+    }
+
+    /// The documents open in rust-analyzer, by URI.
+    pub async fn open_documents(&self) -> Vec<OpenDocument> {
+        let mut documents: Vec<OpenDocument> = self
+            .open_documents
+            .lock()
+            .await
+            .iter()
+            .map(|(uri, state)| OpenDocument {
+                uri: uri.clone(),
+                version: state.version,
+                size: state.content.len(),
+                read_only: state.read_only,
+                idle: state.last_access.elapsed(),
+            })
+            .collect();
+        documents.sort_by(|a, b| a.uri.cmp(&b.uri));
+        documents
+    }
+
+    /// Takes the URIs passed to [`open_document`](Self::open_document) or
+    /// [`open_read_only_document`](Self::open_read_only_document) since the
+    /// last call.
+    pub fn take_synced_documents(&mut self) -> HashSet<String> {
+        std::mem::take(&mut self.synced_documents)
+    }
 
-        // Give rust-analyzer time to process the document and run cargo check.
-        tokio::time::sleep(Duration::from_millis(DOCUMENT_OPEN_DELAY_MILLIS)).await;
+    /// Re-reads every open document from disk and syncs the ones that changed, so
+    /// diagnostics reflect edits made outside of this server.
+    pub async fn refresh_open_documents(&mut self) -> Result<()> {
+        let documents: Vec<(String, bool)> = self
+            .open_documents
+            .lock()
+            .await
+            .iter()
+            .map(|(uri, state)| (uri.clone(), state.read_only))
+            .collect();
+
+        for (uri, read_only) in documents {
+            let Some(path) = path_from_uri(&uri) else {
+                continue;
+            };
+
+            match tokio::fs::read_to_string(&path).await {
+                Ok(content) => self.sync_document(&uri, &content, read_only).await?,
+                Err(e) => info!("Skipping refresh of {}: {}", uri, e),
+            }
+        }
 
         Ok(())
     }
 
+    /// Ends the LSP session and stops the backend. The client can be started
+    /// again afterwards.
     pub async fn shutdown(&mut self) -> Result<()> {
         if self.initialized {
-            let _ = self.send_request("shutdown", None).await;
+            // The process is killed below anyway, so a busy server only gets a
+            // moment to acknowledge the shutdown.
+            let _ = tokio::time::timeout(
+                Duration::from_millis(LSP_SHUTDOWN_TIMEOUT_MILLIS),
+                self.send_request("shutdown", None),
+            )
+            .await;
             let _ = self.send_notification("exit", None).await;
         }
 
@@ -409,13 +928,99 @@ impl RustAnalyzerClient {
             let _ = process.wait().await;
         }
 
-        // Clear open documents and diagnostics.
+        // Clear open documents, diagnostics, cached results and pending saves.
         self.open_documents.lock().await.clear();
         self.diagnostics.lock().await.clear();
+        self.query_cache.lock().await.clear();
+        self.pending_saves.lock().await.tickets.clear();
         self.initialized = false;
         self.workspace_diagnostics_supported = false;
+        self.capabilities = Value::Null;
+        self.server_info = None;
         Ok(())
     }
+
+    /// Sends the settings in effect with `workspace/didChangeConfiguration`.
+    /// rust-analyzer fetches them again with `workspace/configuration`.
+    pub(super) async fn send_settings(&mut self) -> Result<()> {
+        let settings = self.settings.lock().await.clone();
+        let params = json!({ "settings": { "rust-analyzer": settings } });
+        self.send_notification("workspace/didChangeConfiguration", Some(params))
+            .await
+    }
+}
+
+/// Copies `overrides` into `settings`, merging objects key by key; any other
+/// value replaces the one it overrides.
+pub(super) fn merge_settings(settings: &mut Value, overrides: &Value) {
+    match (settings, overrides) {
+        (Value::Object(settings), Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                merge_settings(settings.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+        (settings, overrides) => *settings = overrides.clone(),
+    }
+}
+
+/// The rust-analyzer settings the client starts with.
+pub(super) fn initialization_options(options: &StartOptions) -> Value {
+    let mut settings = json!({
+        "cargo": {
+            "buildScripts": {
+                "enable": true
+            }
+        },
+        "checkOnSave": {
+            "enable": options.check_on_save,
+            "command": "check",
+            "allTargets": true
+        },
+        "diagnostics": {
+            "enable": true,
+            "experimental": {
+                "enable": true
+            }
+        },
+        "hover": {
+            "actions": {
+                "references": {
+                    "enable": true
+                }
+            }
+        },
+        "procMacro": {
+            "enable": options.proc_macros
+        },
+        "cachePriming": {
+            "enable": options.cache_priming
+        }
+    });
+    if !options.exclude_dirs.is_empty() {
+        settings["files"] = json!({ "excludeDirs": options.exclude_dirs });
+    }
+    if let Some(threads) = options.num_threads {
+        settings["numThreads"] = json!(threads);
+    }
+    if let Some(capacity) = options.lru_capacity {
+        settings["lru"] = json!({ "capacity": capacity });
+    }
+    if let Some(threads) = options.cache_priming_threads {
+        settings["cachePriming"]["numThreads"] = json!(threads);
+    }
+    if let Some(sysroot) = &options.sysroot {
+        settings["cargo"]["sysroot"] = json!(sysroot);
+    }
+    if let Some(source) = &options.rustc_source {
+        settings["rustc"] = json!({ "source": source });
+    }
+    if let Some(server) = &options.proc_macro_server {
+        settings["procMacro"]["server"] = json!(server);
+    }
+    if !options.ignored_proc_macros.is_empty() {
+        settings["procMacro"]["ignored"] = json!(options.ignored_proc_macros);
+    }
+    settings
 }
 
 fn find_rust_analyzer() -> Result<PathBuf> {
@@ -436,3 +1041,161 @@ fn find_rust_analyzer() -> Result<PathBuf> {
         )
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{
+        initialization_options, merge_settings, OpenDocumentState, PendingSaves, RustAnalyzerClient,
+    };
+    use crate::lsp::StartOptions;
+
+    #[test]
+    fn test_initialization_options_carry_proc_macro_settings() {
+        let defaults = initialization_options(&StartOptions::default());
+        assert_eq!(defaults["procMacro"], json!({ "enable": true }));
+
+        let mut options = StartOptions {
+            proc_macros: false,
+            proc_macro_server: Some("/opt/ra/proc-macro-srv".into()),
+            ..StartOptions::default()
+        };
+        options
+            .ignored_proc_macros
+            .insert("async_trait".to_string(), vec!["async_trait".to_string()]);
+        assert_eq!(
+            initialization_options(&options)["procMacro"],
+            json!({
+                "enable": false,
+                "server": "/opt/ra/proc-macro-srv",
+                "ignored": { "async_trait": ["async_trait"] }
+            })
+        );
+    }
+
+    #[test]
+    fn test_supports_follows_advertised_capabilities() {
+        let options = StartOptions::default();
+        let mut client = RustAnalyzerClient::unstarted("/tmp/project".into(), options);
+        assert!(client.supports("typeHierarchyProvider"));
+
+        client.initialized = true;
+        client.capabilities = json!({
+            "hoverProvider": true,
+            "documentLinkProvider": {},
+            "callHierarchyProvider": false
+        });
+        assert!(client.supports("hoverProvider"));
+        assert!(client.supports("documentLinkProvider"));
+        assert!(!client.supports("callHierarchyProvider"));
+        assert!(!client.supports("typeHierarchyProvider"));
+    }
+
+    #[test]
+    fn test_save_notifications_follow_text_document_sync() {
+        let options = StartOptions::default();
+        let mut client = RustAnalyzerClient::unstarted("/tmp/project".into(), options);
+        assert!(client.wants_saves());
+        assert!(!client.save_includes_text());
+
+        client.capabilities = json!({ "textDocumentSync": 1 });
+        assert!(client.wants_saves());
+
+        client.capabilities = json!({ "textDocumentSync": { "openClose": true, "change": 2 } });
+        assert!(!client.wants_saves());
+
+        assert!(!client.wants_will_save_wait_until());
+
+        client.capabilities = json!({
+            "textDocumentSync": {
+                "change": 2,
+                "save": { "includeText": true },
+                "willSaveWaitUntil": true
+            }
+        });
+        assert!(client.wants_saves());
+        assert!(client.save_includes_text());
+        assert!(client.wants_will_save_wait_until());
+    }
+
+    #[test]
+    fn test_initialization_options_carry_performance_settings() {
+        let defaults = initialization_options(&StartOptions::default());
+        assert!(defaults.get("numThreads").is_none());
+        assert!(defaults.get("lru").is_none());
+        assert_eq!(defaults["cachePriming"], json!({ "enable": true }));
+
+        let options = StartOptions {
+            num_threads: Some(8),
+            lru_capacity: Some(2048),
+            cache_priming_threads: Some(2),
+            ..StartOptions::default()
+        };
+        let settings = initialization_options(&options);
+        assert_eq!(settings["numThreads"], 8);
+        assert_eq!(settings["lru"]["capacity"], 2048);
+        assert_eq!(settings["cachePriming"]["numThreads"], 2);
+        assert!(defaults.get("files").is_none());
+    }
+
+    #[test]
+    fn test_initialization_options_carry_sysroot_overrides() {
+        let defaults = initialization_options(&StartOptions::default());
+        assert!(defaults["cargo"].get("sysroot").is_none());
+        assert!(defaults.get("rustc").is_none());
+
+        let options = StartOptions {
+            sysroot: Some("discover".to_string()),
+            rustc_source: Some("/src/rust/Cargo.toml".to_string()),
+            check_on_save: false,
+            ..StartOptions::default()
+        };
+        let settings = initialization_options(&options);
+        assert_eq!(settings["cargo"]["sysroot"], "discover");
+        assert_eq!(settings["rustc"]["source"], "/src/rust/Cargo.toml");
+        assert_eq!(settings["checkOnSave"]["enable"], false);
+        assert_eq!(settings["cargo"]["buildScripts"]["enable"], true);
+    }
+
+    #[test]
+    fn test_merged_settings_keep_what_overrides_leave_out() {
+        let mut settings = initialization_options(&StartOptions::default());
+        merge_settings(
+            &mut settings,
+            &json!({ "cargo": { "features": ["tls"], "target": "wasm32-unknown-unknown" } }),
+        );
+
+        assert_eq!(settings["cargo"]["features"], json!(["tls"]));
+        assert_eq!(settings["cargo"]["target"], "wasm32-unknown-unknown");
+        assert_eq!(settings["cargo"]["buildScripts"]["enable"], true);
+        assert_eq!(settings["checkOnSave"]["allTargets"], true);
+    }
+
+    #[test]
+    fn test_symbol_cache_follows_document_version() {
+        let mut state = OpenDocumentState::new("fn a() {}", false);
+        state.cache_symbols(state.version(), json!(["a"]));
+        assert_eq!(state.cached_symbols(), Some(&json!(["a"])));
+
+        // A response computed before an edit must not be cached for the new version.
+        let stale_version = state.version();
+        state.update("fn b() {}");
+        assert_eq!(state.cached_symbols(), None);
+        state.cache_symbols(stale_version, json!(["a"]));
+        assert_eq!(state.cached_symbols(), None);
+    }
+
+    #[test]
+    fn test_pending_saves_keep_only_the_latest_save() {
+        let mut saves = PendingSaves::default();
+        let first = saves.schedule("file:///ws/src/lib.rs");
+        let second = saves.schedule("file:///ws/src/lib.rs");
+        let other = saves.schedule("file:///ws/src/main.rs");
+
+        assert!(!saves.take("file:///ws/src/lib.rs", first));
+        assert!(saves.take("file:///ws/src/lib.rs", second));
+        assert!(!saves.take("file:///ws/src/lib.rs", second));
+        assert!(saves.take("file:///ws/src/main.rs", other));
+    }
+}
@@ -0,0 +1,358 @@
+use serde_json::{json, Value};
+
+/// LSP `CompletionItemKind` names, indexed by kind number minus one.
+const COMPLETION_KINDS: [&str; 25] = [
+    "text",
+    "method",
+    "function",
+    "constructor",
+    "field",
+    "variable",
+    "class",
+    "interface",
+    "module",
+    "property",
+    "unit",
+    "value",
+    "enum",
+    "keyword",
+    "snippet",
+    "color",
+    "file",
+    "reference",
+    "folder",
+    "enum_member",
+    "constant",
+    "struct",
+    "event",
+    "operator",
+    "type_parameter",
+];
+
+/// Server-side narrowing of a completion list.
+#[derive(Debug, Clone, Default)]
+pub struct CompletionFilter {
+    /// Only keep items whose filter text starts with this, ignoring case.
+    pub prefix: Option<String>,
+    /// Only keep items of these kinds, e.g. `method` or `field`.
+    pub kinds: Vec<String>,
+    /// Maximum number of items to return.
+    pub limit: Option<usize>,
+    /// Include each item's insert text with snippet placeholders resolved, and
+    /// the imports it requires.
+    pub plain_text: bool,
+}
+
+/// Returns the name of an LSP `CompletionItemKind` (trait completions are
+/// reported as `interface`).
+pub fn completion_kind_name(kind: u64) -> &'static str {
+    (kind as usize)
+        .checked_sub(1)
+        .and_then(|index| COMPLETION_KINDS.get(index))
+        .copied()
+        .unwrap_or("unknown")
+}
+
+pub fn is_completion_kind(name: &str) -> bool {
+    COMPLETION_KINDS.contains(&name)
+}
+
+/// The completion items a [`CompletionFilter`] keeps, in the order they are
+/// returned.
+#[derive(Debug, Clone, Default)]
+pub struct SelectedCompletions {
+    /// The LSP completion items, cut to the filter's limit.
+    pub items: Vec<Value>,
+    /// How many items matched before the limit was applied.
+    pub total: usize,
+    pub is_incomplete: bool,
+}
+
+/// Filters a `textDocument/completion` response, orders it by rust-analyzer's
+/// relevance (`sortText`) and trims each item to its label, kind and detail.
+pub fn filter_completions(response: &Value, filter: &CompletionFilter) -> Value {
+    select_completions(response, filter).render(filter)
+}
+
+/// Filters a `textDocument/completion` response and orders it by
+/// rust-analyzer's relevance (`sortText`), keeping the items as sent.
+pub fn select_completions(response: &Value, filter: &CompletionFilter) -> SelectedCompletions {
+    let (items, is_incomplete) = match response {
+        Value::Array(items) => (items.as_slice(), false),
+        Value::Object(list) => (
+            list.get("items")
+                .and_then(|items| items.as_array())
+                .map(Vec::as_slice)
+                .unwrap_or_default(),
+            list.get("isIncomplete")
+                .and_then(|incomplete| incomplete.as_bool())
+                .unwrap_or(false),
+        ),
+        _ => (&[][..], false),
+    };
+
+    let prefix = filter.prefix.as_deref().map(str::to_lowercase);
+    let mut matched: Vec<&Value> = items
+        .iter()
+        .filter(|item| {
+            let kind = completion_kind_name(item["kind"].as_u64().unwrap_or(0));
+            filter.kinds.is_empty() || filter.kinds.iter().any(|wanted| wanted == kind)
+        })
+        .filter(|item| {
+            let Some(prefix) = &prefix else {
+                return true;
+            };
+            let text = item["filterText"]
+                .as_str()
+                .or_else(|| item["label"].as_str())
+                .unwrap_or_default();
+            text.to_lowercase().starts_with(prefix.as_str())
+        })
+        .collect();
+
+    // Preselected items first, then rust-analyzer's relevance order.
+    matched.sort_by_key(|item| {
+        (
+            !item["preselect"].as_bool().unwrap_or(false),
+            item["sortText"]
+                .as_str()
+                .or_else(|| item["label"].as_str())
+                .unwrap_or_default()
+                .to_string(),
+        )
+    });
+
+    let total = matched.len();
+    let limit = filter.limit.unwrap_or(total);
+    SelectedCompletions {
+        items: matched.into_iter().take(limit).cloned().collect(),
+        total,
+        is_incomplete,
+    }
+}
+
+impl SelectedCompletions {
+    /// Trims each item to its label, kind and detail, plus the plain insert
+    /// text and imports when the filter asks for them.
+    pub fn render(&self, filter: &CompletionFilter) -> Value {
+        let items: Vec<Value> = self
+            .items
+            .iter()
+            .map(|item| {
+                let mut entry = json!({
+                    "label": item["label"],
+                    "kind": completion_kind_name(item["kind"].as_u64().unwrap_or(0)),
+                    "detail": item.get("detail").or_else(|| item.pointer("/labelDetails/detail"))
+                });
+                if item["deprecated"].as_bool() == Some(true) {
+                    entry["deprecated"] = json!(true);
+                }
+                if filter.plain_text {
+                    entry["insert_text"] = json!(plain_insert_text(item));
+                    let edits = item["additionalTextEdits"].as_array();
+                    let imports: Vec<&str> = edits
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|edit| edit["newText"].as_str())
+                        .map(str::trim)
+                        .filter(|text| !text.is_empty())
+                        .collect();
+                    if !imports.is_empty() {
+                        entry["imports"] = json!(imports);
+                        entry["import_edits"] = json!(edits);
+                    }
+                }
+                entry
+            })
+            .collect();
+
+        json!({
+            "is_incomplete": self.is_incomplete,
+            "total": self.total,
+            "truncated": self.total > items.len(),
+            "omitted": self.total - items.len(),
+            "items": items
+        })
+    }
+}
+
+/// Whether rust-analyzer left the imports a completion needs, such as
+/// `use std::collections::HashMap;`, to `completionItem/resolve`.
+pub fn needs_import_resolve(item: &Value) -> bool {
+    item.get("additionalTextEdits").is_none()
+        && item["data"]["imports"]
+            .as_array()
+            .is_some_and(|imports| !imports.is_empty())
+}
+
+/// The text a completion inserts, with snippet syntax resolved when the item
+/// uses the snippet format.
+fn plain_insert_text(item: &Value) -> String {
+    let text = item
+        .pointer("/textEdit/newText")
+        .or_else(|| item.get("insertText"))
+        .or_else(|| item.get("label"))
+        .and_then(|text| text.as_str())
+        .unwrap_or_default();
+    if item["insertTextFormat"].as_u64() == Some(2) {
+        strip_snippet(text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Resolves LSP snippet syntax to plain text: tabstops such as `$0` and `${1}`
+/// are dropped, placeholders such as `${1:name}` keep their default text and
+/// choices `${1|a,b|}` keep the first option.
+fn strip_snippet(snippet: &str) -> String {
+    let mut chars = snippet.chars().peekable();
+    strip_snippet_until(&mut chars, None)
+}
+
+fn strip_snippet_until(
+    chars: &mut std::iter::Peekable<std::str::Chars<'_>>,
+    terminator: Option<char>,
+) -> String {
+    let mut text = String::new();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    text.push(escaped);
+                }
+            }
+            _ if Some(ch) == terminator => break,
+            '$' if chars.peek().is_some_and(char::is_ascii_digit) => {
+                while chars.next_if(char::is_ascii_digit).is_some() {}
+            }
+            '$' if chars.peek() == Some(&'{') => {
+                chars.next();
+                while chars
+                    .next_if(|ch| ch.is_ascii_alphanumeric() || *ch == '_')
+                    .is_some()
+                {}
+                match chars.next() {
+                    Some(':') => text.push_str(&strip_snippet_until(chars, Some('}'))),
+                    Some('|') => {
+                        let choices = strip_snippet_until(chars, Some('|'));
+                        text.push_str(choices.split(',').next().unwrap_or_default());
+                        chars.next_if_eq(&'}');
+                    }
+                    _ => {}
+                }
+            }
+            _ => text.push(ch),
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{filter_completions, needs_import_resolve, strip_snippet, CompletionFilter};
+
+    #[test]
+    fn test_filter_completions_by_kind_prefix_and_limit() {
+        let response = json!({
+            "isIncomplete": true,
+            "items": [
+                { "label": "multiply", "kind": 2, "detail": "fn(&self, i32, i32) -> i32", "sortText": "7ffffff0" },
+                { "label": "add", "kind": 2, "detail": "fn(&self, i32, i32) -> i32", "sortText": "7fffffef" },
+                { "label": "async", "kind": 14, "sortText": "80000000" },
+                { "label": "mul_add", "kind": 3, "sortText": "7fffffee" },
+                { "label": "max", "kind": 2, "sortText": "7ffffff1", "deprecated": true }
+            ]
+        });
+
+        let methods = filter_completions(
+            &response,
+            &CompletionFilter {
+                kinds: vec!["method".to_string()],
+                ..CompletionFilter::default()
+            },
+        );
+        let labels: Vec<&str> = methods["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["label"].as_str().unwrap())
+            .collect();
+        assert_eq!(labels, ["add", "multiply", "max"]);
+        assert_eq!(methods["is_incomplete"], true);
+        assert_eq!(methods["items"][2]["deprecated"], true);
+
+        let limited = filter_completions(
+            &response,
+            &CompletionFilter {
+                prefix: Some("MU".to_string()),
+                limit: Some(1),
+                ..CompletionFilter::default()
+            },
+        );
+        assert_eq!(limited["total"], 2);
+        assert_eq!(limited["truncated"], true);
+        assert_eq!(limited["items"][0]["label"], "mul_add");
+        assert_eq!(limited["items"][0]["kind"], "function");
+    }
+
+    #[test]
+    fn test_strip_snippet() {
+        assert_eq!(strip_snippet("new()$0"), "new()");
+        assert_eq!(strip_snippet("add(${1:a}, ${2:b})$0"), "add(a, b)");
+        assert_eq!(
+            strip_snippet("match ${1:expr} {\n    $0\n}"),
+            "match expr {\n    \n}"
+        );
+        assert_eq!(strip_snippet("${1|Ok,Err|}(${2:()})"), "Ok(())");
+        assert_eq!(strip_snippet("format!(\"\\$\")"), "format!(\"$\")");
+    }
+
+    #[test]
+    fn test_plain_text_completions_include_imports() {
+        let response = json!([{
+            "label": "HashMap",
+            "kind": 22,
+            "insertTextFormat": 2,
+            "textEdit": { "newText": "HashMap::new()$0", "range": {} },
+            "additionalTextEdits": [{ "newText": "use std::collections::HashMap;\n", "range": {} }]
+        }]);
+
+        let result = filter_completions(
+            &response,
+            &CompletionFilter {
+                plain_text: true,
+                ..CompletionFilter::default()
+            },
+        );
+
+        assert_eq!(result["items"][0]["insert_text"], "HashMap::new()");
+        assert_eq!(
+            result["items"][0]["imports"],
+            json!(["use std::collections::HashMap;"])
+        );
+        assert_eq!(
+            result["items"][0]["import_edits"][0]["newText"],
+            "use std::collections::HashMap;\n"
+        );
+    }
+
+    #[test]
+    fn test_only_items_with_pending_imports_need_resolving() {
+        let pending = json!({
+            "label": "HashMap",
+            "data": { "imports": [{ "full_import_path": "std::collections::HashMap" }] }
+        });
+        let resolved = json!({
+            "label": "HashMap",
+            "additionalTextEdits": [],
+            "data": { "imports": [{ "full_import_path": "std::collections::HashMap" }] }
+        });
+        let local = json!({ "label": "map", "data": { "imports": [] } });
+
+        assert!(needs_import_resolve(&pending));
+        assert!(!needs_import_resolve(&resolved));
+        assert!(!needs_import_resolve(&local));
+    }
+}
@@ -1,27 +1,101 @@
 use log::{debug, error, info};
-use serde_json::Value;
-use std::{collections::HashMap, sync::Arc};
+use serde_json::{json, Value};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+};
 use tokio::{
-    io::{AsyncBufReadExt, AsyncReadExt, BufReader},
-    sync::{oneshot, Mutex},
+    io::{
+        AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, BufWriter,
+    },
+    sync::{mpsc::UnboundedSender, oneshot, Mutex},
 };
 
-use crate::protocol::lsp::LSPResponse;
+use super::{
+    handlers::parse_items,
+    progress::IndexingProgress,
+    trace::{LspTracer, TraceDirection},
+};
+use crate::{
+    config::STDERR_LOG_LINES,
+    protocol::lsp::{Diagnostic, LSPResponse},
+};
 
-pub fn start_handlers(
-    stdout: tokio::process::ChildStdout,
-    stderr: tokio::process::ChildStderr,
-    pending_requests: Arc<Mutex<HashMap<u64, oneshot::Sender<Value>>>>,
-    diagnostics: Arc<Mutex<HashMap<String, Vec<Value>>>>,
+/// An LSP request's `result`, or the JSON-RPC `error` object rust-analyzer
+/// answered it with.
+pub(super) type LspResult = Result<Value, Value>;
+
+/// rust-analyzer's stdout, or the mock backend's end of an in-memory pipe.
+pub(super) type LspReader = Box<dyn AsyncRead + Send + Unpin>;
+
+/// rust-analyzer's stdin, or the client's end of the mock backend's pipe.
+pub(super) type LspSink = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// The LSP input stream, shared by the client and the handler answering
+/// requests rust-analyzer sends to us.
+pub(super) type LspWriter = Arc<Mutex<BufWriter<LspSink>>>;
+
+/// A `window/showMessage` or `window/logMessage` notification from rust-analyzer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowMessage {
+    /// LSP `MessageType`: 1 = error, 2 = warning, 3 = info, 4 = log, 5 = debug.
+    pub message_type: u64,
+    pub method: String,
+    pub text: String,
+}
+
+/// Where the messages read from rust-analyzer's stdout are delivered.
+pub(super) struct MessageSinks {
+    pub pending_requests: Arc<Mutex<HashMap<u64, oneshot::Sender<LspResult>>>>,
+    pub diagnostics: Arc<Mutex<HashMap<String, Vec<Diagnostic>>>>,
+    pub progress: Arc<Mutex<IndexingProgress>>,
+    pub window_messages: Option<UnboundedSender<WindowMessage>>,
+    /// The `rust-analyzer` settings section `workspace/configuration`
+    /// requests are answered with.
+    pub settings: Arc<Mutex<Value>>,
+}
+
+pub(super) fn start_handlers(
+    stdout: LspReader,
+    stderr: Option<tokio::process::ChildStderr>,
+    stdin: LspWriter,
+    stderr_log: Arc<Mutex<VecDeque<String>>>,
+    sinks: MessageSinks,
+    tracer: Option<Arc<LspTracer>>,
 ) {
-    // Log stderr in background.
-    tokio::spawn(handle_stderr(stderr));
+    // Log stderr in background, keeping the most recent lines.
+    if let Some(stderr) = stderr {
+        tokio::spawn(handle_stderr(
+            stderr,
+            stderr_log,
+            Arc::clone(&sinks.progress),
+        ));
+    }
 
     // Start response handler task.
-    tokio::spawn(handle_stdout(stdout, pending_requests, diagnostics));
+    tokio::spawn(handle_stdout(stdout, stdin, sinks, tracer));
 }
 
-async fn handle_stderr(stderr: tokio::process::ChildStderr) {
+/// Writes one LSP frame to rust-analyzer's stdin.
+pub(super) async fn write_frame(
+    stdin: &LspWriter,
+    tracer: &Option<Arc<LspTracer>>,
+    content: &str,
+) -> std::io::Result<()> {
+    if let Some(tracer) = tracer {
+        tracer.record(TraceDirection::Sent, content.as_bytes());
+    }
+    let message = format!("Content-Length: {}\r\n\r\n{}", content.len(), content);
+    let mut stdin = stdin.lock().await;
+    stdin.write_all(message.as_bytes()).await?;
+    stdin.flush().await
+}
+
+async fn handle_stderr(
+    stderr: tokio::process::ChildStderr,
+    log: Arc<Mutex<VecDeque<String>>>,
+    progress: Arc<Mutex<IndexingProgress>>,
+) {
     let mut reader = BufReader::new(stderr);
     let mut buffer = String::new();
 
@@ -39,17 +113,20 @@ async fn handle_stderr(stderr: tokio::process::ChildStderr) {
             break; // EOF
         }
 
-        let trimmed = buffer.trim();
-        if !trimmed.is_empty() {
+        let trimmed = buffer.trim_end();
+        if !trimmed.trim_start().is_empty() {
             debug!("rust-analyzer stderr: {}", trimmed);
+            progress.lock().await.apply_stderr(trimmed);
+            push_bounded(&mut *log.lock().await, trimmed.to_string());
         }
     }
 }
 
 async fn handle_stdout(
-    stdout: tokio::process::ChildStdout,
-    pending: Arc<Mutex<HashMap<u64, oneshot::Sender<Value>>>>,
-    diagnostics: Arc<Mutex<HashMap<String, Vec<Value>>>>,
+    stdout: LspReader,
+    stdin: LspWriter,
+    sinks: MessageSinks,
+    tracer: Option<Arc<LspTracer>>,
 ) {
     let mut reader = BufReader::new(stdout);
     let mut buffer = String::new();
@@ -89,70 +166,151 @@ async fn handle_stdout(
 
         let response_str = String::from_utf8_lossy(&json_buffer);
         debug!("Received LSP message: {}", response_str);
+        if let Some(tracer) = &tracer {
+            tracer.record(TraceDirection::Received, &json_buffer);
+        }
 
-        handle_lsp_message(&json_buffer, &pending, &diagnostics).await;
+        let Some(reply) = handle_lsp_message(&json_buffer, &sinks).await else {
+            continue;
+        };
+        if let Err(e) = write_frame(&stdin, &tracer, &reply.to_string()).await {
+            error!("Error answering rust-analyzer request: {}", e);
+        }
     }
 }
 
+/// Appends a line, dropping the oldest once `STDERR_LOG_LINES` are kept.
+fn push_bounded(log: &mut VecDeque<String>, line: String) {
+    if log.len() == STDERR_LOG_LINES {
+        log.pop_front();
+    }
+    log.push_back(line);
+}
+
 fn parse_content_length(header: &str) -> Option<usize> {
     header
         .strip_prefix("Content-Length: ")
         .and_then(|s| s.trim().parse().ok())
 }
 
-async fn handle_lsp_message(
-    json_buffer: &[u8],
-    pending: &Arc<Mutex<HashMap<u64, oneshot::Sender<Value>>>>,
-    diagnostics: &Arc<Mutex<HashMap<String, Vec<Value>>>>,
-) {
+/// Routes one message from rust-analyzer, returning the reply to send back if
+/// it was a request.
+async fn handle_lsp_message(json_buffer: &[u8], sinks: &MessageSinks) -> Option<Value> {
     let Ok(json_value) = serde_json::from_slice::<Value>(json_buffer) else {
         error!(
             "Failed to parse LSP message: {}",
             String::from_utf8_lossy(json_buffer)
         );
-        return;
+        return None;
     };
 
     // Check if it's a notification (has method but no id).
     if json_value.get("method").is_some() && json_value.get("id").is_none() {
-        handle_notification(json_value, diagnostics).await;
-        return;
+        handle_notification(json_value, sinks).await;
+        return None;
+    }
+
+    // Requests from rust-analyzer carry ids of their own that may collide with
+    // ours, so they must not be mistaken for responses.
+    if let Some(method) = json_value.get("method").and_then(|m| m.as_str()) {
+        let settings = sinks.settings.lock().await;
+        return Some(server_request_reply(method, &json_value, &settings));
     }
 
     // Try to handle as response.
     let Ok(response) = serde_json::from_value::<LSPResponse>(json_value) else {
-        return;
+        return None;
     };
 
-    let Some(id) = response.id else {
-        return;
-    };
+    let id = response.id?;
 
-    let mut pending_lock = pending.lock().await;
-    let Some(sender) = pending_lock.remove(&id) else {
-        return;
-    };
+    let mut pending_lock = sinks.pending_requests.lock().await;
+    let sender = pending_lock.remove(&id)?;
 
     if let Some(error) = response.error {
-        error!("LSP error for request {}: {}", id, error);
-        let _ = sender.send(serde_json::json!(null));
+        debug!("LSP error for request {}: {}", id, error);
+        let _ = sender.send(Err(error));
     } else {
         let result = response.result.unwrap_or(serde_json::json!(null));
         info!("Sending result for request {}: {:?}", id, result);
-        let _ = sender.send(result);
+        let _ = sender.send(Ok(result));
     }
+    None
 }
 
-async fn handle_notification(
-    json_value: Value,
-    diagnostics: &Arc<Mutex<HashMap<String, Vec<Value>>>>,
-) {
+/// The reply to a request rust-analyzer sent us. Progress tokens and refresh
+/// requests are acknowledged, `workspace/configuration` is answered with the
+/// client's current `settings` and anything else is declined.
+fn server_request_reply(method: &str, request: &Value, settings: &Value) -> Value {
+    debug!("Received server request: {}", method);
+    let id = &request["id"];
+    match method {
+        "window/workDoneProgress/create" | "workspace/diagnostic/refresh" => {
+            json!({ "jsonrpc": "2.0", "id": id, "result": null })
+        }
+        // rust-analyzer asks for its section after `didChangeConfiguration`,
+        // ignoring the settings the notification carried.
+        "workspace/configuration" => {
+            let items = request["params"]["items"].as_array();
+            let result: Vec<Value> = items
+                .into_iter()
+                .flatten()
+                .map(|item| match item["section"].as_str() {
+                    Some("rust-analyzer") => settings.clone(),
+                    _ => Value::Null,
+                })
+                .collect();
+            json!({ "jsonrpc": "2.0", "id": id, "result": result })
+        }
+        _ => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": -32601, "message": format!("Unsupported request: {}", method) }
+        }),
+    }
+}
+
+async fn handle_notification(json_value: Value, sinks: &MessageSinks) {
     let Some(method) = json_value.get("method").and_then(|m| m.as_str()) else {
         return;
     };
 
     debug!("Received notification: {}", method);
 
+    if matches!(method, "window/showMessage" | "window/logMessage") {
+        let params = &json_value["params"];
+        let message = WindowMessage {
+            message_type: params["type"].as_u64().unwrap_or(3),
+            method: method.to_string(),
+            text: params["message"].as_str().unwrap_or_default().to_string(),
+        };
+        info!("rust-analyzer {}: {}", method, message.text);
+        if let Some(sender) = &sinks.window_messages {
+            let _ = sender.send(message);
+        }
+        return;
+    }
+
+    match method {
+        "$/progress" => {
+            sinks
+                .progress
+                .lock()
+                .await
+                .apply_progress(&json_value["params"]);
+            return;
+        }
+        "experimental/serverStatus" => {
+            sinks
+                .progress
+                .lock()
+                .await
+                .apply_server_status(&json_value["params"]);
+            return;
+        }
+        _ => {}
+    }
+
     if method != "textDocument/publishDiagnostics" {
         return;
     }
@@ -165,11 +323,106 @@ async fn handle_notification(
         return;
     };
 
-    let Some(diags) = params.get("diagnostics").and_then(|d| d.as_array()) else {
+    let Some(diags) = params.get("diagnostics").filter(|d| d.is_array()) else {
         return;
     };
 
-    let mut diag_lock = diagnostics.lock().await;
-    diag_lock.insert(uri.to_string(), diags.clone());
+    let diags: Vec<Diagnostic> = parse_items(diags);
+    let mut diag_lock = sinks.diagnostics.lock().await;
     info!("Stored {} diagnostics for {}", diags.len(), uri);
+    diag_lock.insert(uri.to_string(), diags);
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use std::{
+        collections::{HashMap, VecDeque},
+        sync::Arc,
+    };
+    use tokio::sync::{oneshot, Mutex};
+
+    use super::{handle_lsp_message, push_bounded, MessageSinks};
+    use crate::config::STDERR_LOG_LINES;
+
+    #[test]
+    fn test_push_bounded_drops_oldest_lines() {
+        let mut log = VecDeque::new();
+        for index in 0..STDERR_LOG_LINES + 2 {
+            push_bounded(&mut log, format!("line {index}"));
+        }
+
+        assert_eq!(log.len(), STDERR_LOG_LINES);
+        assert_eq!(log.front().map(String::as_str), Some("line 2"));
+    }
+
+    fn sinks() -> MessageSinks {
+        MessageSinks {
+            pending_requests: Arc::new(Mutex::new(HashMap::new())),
+            diagnostics: Arc::new(Mutex::new(HashMap::new())),
+            progress: Arc::new(Mutex::new(Default::default())),
+            window_messages: None,
+            settings: Arc::new(Mutex::new(json!({ "cargo": { "features": ["tls"] } }))),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_error_responses_reach_the_waiting_request() {
+        let sinks = sinks();
+        let (tx, rx) = oneshot::channel();
+        sinks.pending_requests.lock().await.insert(7, tx);
+
+        let response = json!({
+            "jsonrpc": "2.0",
+            "id": 7,
+            "error": { "code": -32801, "message": "content modified" }
+        });
+        let reply = handle_lsp_message(response.to_string().as_bytes(), &sinks).await;
+
+        assert!(reply.is_none());
+        let error = rx.await.unwrap().unwrap_err();
+        assert_eq!(error["code"], -32801);
+    }
+
+    #[tokio::test]
+    async fn test_server_requests_are_answered_not_taken_as_responses() {
+        let sinks = sinks();
+        let (tx, mut rx) = oneshot::channel();
+        sinks.pending_requests.lock().await.insert(1, tx);
+
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "window/workDoneProgress/create",
+            "params": { "token": "rustAnalyzer/Fetching" }
+        });
+        let reply = handle_lsp_message(request.to_string().as_bytes(), &sinks).await;
+
+        assert_eq!(
+            reply,
+            Some(json!({ "jsonrpc": "2.0", "id": 1, "result": null }))
+        );
+        assert!(rx.try_recv().is_err(), "our request 1 is still pending");
+
+        let configuration = json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "workspace/configuration",
+            "params": { "items": [{ "section": "rust-analyzer" }, { "section": "files" }] }
+        });
+        let reply = handle_lsp_message(configuration.to_string().as_bytes(), &sinks).await;
+        assert_eq!(
+            reply.unwrap()["result"],
+            json!([{ "cargo": { "features": ["tls"] } }, null])
+        );
+
+        let unknown = json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "method": "window/showDocument",
+            "params": { "uri": "file:///ws/src/lib.rs" }
+        });
+        let reply = handle_lsp_message(unknown.to_string().as_bytes(), &sinks).await;
+        assert_eq!(reply.unwrap()["error"]["code"], -32601);
+    }
 }
@@ -1,44 +1,367 @@
-use anyhow::Result;
-use log::info;
+use anyhow::{anyhow, Result};
+use futures::future::join_all;
+use log::{debug, info};
+use serde::de::DeserializeOwned;
 use serde_json::{json, Value};
-use std::path::{Path, PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 
-use super::client::RustAnalyzerClient;
+use super::{
+    cancel::CancellationToken,
+    client::{initialization_options, merge_settings, RustAnalyzerClient},
+    inlay_hints::needs_tooltip_resolve,
+    uri::uri_from_path,
+};
+use crate::{
+    config::{CARGO_COMMAND_TIMEOUT_SECS, DOCUMENT_OPEN_DELAY_MILLIS},
+    error::ToolError,
+    protocol::lsp::{
+        CallHierarchyIncomingCall, CallHierarchyItem, CallHierarchyOutgoingCall, CodeAction,
+        Command, Diagnostic, DocumentHighlight, DocumentLink, ExpandedMacro, ExternalDocs, Hover,
+        InlayHint, Location, LocationLink, PrepareRename, SymbolInformation, TextEdit,
+        WorkspaceEdit,
+    },
+};
 
 const MAX_WORKSPACE_DIAGNOSTIC_FILES: usize = 128;
+
+/// How many hints of one `textDocument/inlayHint` response are resolved for
+/// their tooltips; the rest are returned without.
+const MAX_INLAY_HINT_RESOLVES: usize = 64;
+
+/// How long `run_check` and `reconfigure` wait for the work they asked for to
+/// show up as a running task, and how often they look.
+const CHECK_START_GRACE_MILLIS: u64 = 1000;
+const CHECK_POLL_MILLIS: u64 = 100;
 const SKIPPED_WORKSPACE_DIRS: [&str; 5] = [".git", "target", "node_modules", ".idea", ".vscode"];
 
 impl RustAnalyzerClient {
-    pub async fn hover(&mut self, uri: &str, line: u32, character: u32) -> Result<Value> {
+    /// `textDocument/hover` at a zero-based position in an open document,
+    /// cached per document version.
+    pub async fn hover(&self, uri: &str, line: u32, character: u32) -> Result<Option<Hover>> {
+        let key = format!("textDocument/hover {}:{}", line, character);
+        let version = self.cacheable_version(uri).await;
+        let cached = match version {
+            Some(version) => self.query_cache.lock().await.get(uri, &key, version),
+            None => None,
+        };
+
+        let response = match cached {
+            Some(response) => response,
+            None => {
+                let params = json!({
+                    "textDocument": { "uri": uri },
+                    "position": { "line": line, "character": character }
+                });
+                let response = self
+                    .send_request("textDocument/hover", Some(params))
+                    .await?;
+                if let Some(version) = version.filter(|_| !response.is_null()) {
+                    let mut cache = self.query_cache.lock().await;
+                    cache.insert(uri, &key, version, response.clone());
+                }
+                response
+            }
+        };
+        serde_json::from_value(response)
+            .map_err(|e| anyhow!("Unexpected textDocument/hover response: {}", e))
+    }
+
+    /// `textDocument/hover` over a selection, through rust-analyzer's
+    /// `hoverRange` extension: the type of the selected expression, e.g.
+    /// `i32` for `a + b`.
+    pub async fn hover_range(
+        &self,
+        uri: &str,
+        start: (u32, u32),
+        end: (u32, u32),
+    ) -> Result<Option<Hover>> {
+        let params = json!({
+            "textDocument": { "uri": uri },
+            "position": {
+                "start": { "line": start.0, "character": start.1 },
+                "end": { "line": end.0, "character": end.1 }
+            }
+        });
+
+        let response = self
+            .send_request("textDocument/hover", Some(params))
+            .await?;
+        serde_json::from_value(response)
+            .map_err(|e| anyhow!("Unexpected textDocument/hover response: {}", e))
+    }
+
+    /// `textDocument/definition`: where the symbol at the position is defined.
+    pub async fn definition(&self, uri: &str, line: u32, character: u32) -> Result<Vec<Location>> {
         let params = json!({
             "textDocument": { "uri": uri },
             "position": { "line": line, "character": character }
         });
 
-        self.send_request("textDocument/hover", Some(params)).await
+        let response = self
+            .send_request("textDocument/definition", Some(params))
+            .await?;
+        Ok(locations_from_response(response))
     }
 
-    pub async fn definition(&mut self, uri: &str, line: u32, character: u32) -> Result<Value> {
+    /// `textDocument/implementation`: implementations of the trait or method at
+    /// the position.
+    pub async fn implementation(
+        &mut self,
+        uri: &str,
+        line: u32,
+        character: u32,
+    ) -> Result<Vec<Location>> {
         let params = json!({
             "textDocument": { "uri": uri },
             "position": { "line": line, "character": character }
         });
 
-        self.send_request("textDocument/definition", Some(params))
-            .await
+        let response = self
+            .send_request("textDocument/implementation", Some(params))
+            .await?;
+        Ok(locations_from_response(response))
     }
 
-    pub async fn references(&mut self, uri: &str, line: u32, character: u32) -> Result<Value> {
+    /// `textDocument/references`, including the declaration itself.
+    pub async fn references(
+        &mut self,
+        uri: &str,
+        line: u32,
+        character: u32,
+    ) -> Result<Vec<Location>> {
         let params = json!({
             "textDocument": { "uri": uri },
             "position": { "line": line, "character": character },
             "context": { "includeDeclaration": true }
         });
 
-        self.send_request("textDocument/references", Some(params))
-            .await
+        let response = self
+            .send_request("textDocument/references", Some(params))
+            .await?;
+        Ok(locations_from_response(response))
+    }
+
+    /// `experimental/parentModule`: the `mod` declarations of the module a
+    /// document belongs to, pointing at the module's name.
+    pub async fn parent_module(&mut self, uri: &str) -> Result<Vec<Location>> {
+        let params = json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": 0, "character": 0 }
+        });
+
+        let response = self
+            .send_request("experimental/parentModule", Some(params))
+            .await?;
+        Ok(locations_from_response(response))
+    }
+
+    /// `textDocument/rename` of the symbol at the position to `new_name`.
+    /// Fails with rust-analyzer's [`LspError`](super::LspError) when it cannot
+    /// rename the symbol, e.g. because the new name is not a valid identifier.
+    pub async fn rename(
+        &mut self,
+        uri: &str,
+        line: u32,
+        character: u32,
+        new_name: &str,
+    ) -> Result<Option<WorkspaceEdit>> {
+        let params = json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": line, "character": character },
+            "newName": new_name
+        });
+
+        let response = self.request("textDocument/rename", Some(params)).await?;
+        serde_json::from_value(response)
+            .map_err(|e| anyhow!("Unexpected textDocument/rename response: {}", e))
+    }
+
+    /// `textDocument/willSaveWaitUntil` for an explicit save of `uri`: the
+    /// edits rust-analyzer wants made to the document, as it has it, before it
+    /// is written. Empty unless rust-analyzer advertises the request.
+    pub async fn will_save_wait_until(&mut self, uri: &str) -> Result<Vec<TextEdit>> {
+        if !self.wants_will_save_wait_until() {
+            return Ok(Vec::new());
+        }
+        let params = json!({
+            "textDocument": { "uri": uri },
+            "reason": 1
+        });
+
+        let response = self
+            .request("textDocument/willSaveWaitUntil", Some(params))
+            .await?;
+        if response.is_null() {
+            return Ok(Vec::new());
+        }
+        serde_json::from_value(response)
+            .map_err(|e| anyhow!("Unexpected textDocument/willSaveWaitUntil response: {}", e))
+    }
+
+    /// `textDocument/prepareRename`: what a rename at the position would
+    /// change. Fails with rust-analyzer's [`LspError`](super::LspError) when
+    /// the symbol cannot be renamed, e.g. a keyword or a name a macro made up.
+    pub async fn prepare_rename(
+        &mut self,
+        uri: &str,
+        line: u32,
+        character: u32,
+    ) -> Result<Option<PrepareRename>> {
+        let params = json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": line, "character": character }
+        });
+
+        let response = self
+            .request("textDocument/prepareRename", Some(params))
+            .await?;
+        serde_json::from_value(response)
+            .map_err(|e| anyhow!("Unexpected textDocument/prepareRename response: {}", e))
+    }
+
+    /// `textDocument/prepareCallHierarchy`: the function or method at the
+    /// position, as the starting point for `incoming_calls` and
+    /// `outgoing_calls`. Empty when the position is not on one.
+    pub async fn prepare_call_hierarchy(
+        &self,
+        uri: &str,
+        line: u32,
+        character: u32,
+    ) -> Result<Vec<CallHierarchyItem>> {
+        let params = json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": line, "character": character }
+        });
+
+        let response = self
+            .send_request("textDocument/prepareCallHierarchy", Some(params))
+            .await?;
+        Ok(parse_items(&response))
+    }
+
+    /// `callHierarchy/incomingCalls`: the functions calling `item`.
+    pub async fn incoming_calls(
+        &self,
+        item: &CallHierarchyItem,
+    ) -> Result<Vec<CallHierarchyIncomingCall>> {
+        let params = json!({ "item": item });
+        let response = self
+            .send_request("callHierarchy/incomingCalls", Some(params))
+            .await?;
+        Ok(parse_items(&response))
     }
 
+    /// `callHierarchy/outgoingCalls`: the functions `item` calls.
+    pub async fn outgoing_calls(
+        &self,
+        item: &CallHierarchyItem,
+    ) -> Result<Vec<CallHierarchyOutgoingCall>> {
+        let params = json!({ "item": item });
+        let response = self
+            .send_request("callHierarchy/outgoingCalls", Some(params))
+            .await?;
+        Ok(parse_items(&response))
+    }
+
+    /// `textDocument/documentHighlight`: ranges related to the position. On
+    /// `return`, `?` or `fn` rust-analyzer returns the function's exit points,
+    /// on `.await` or `async` its yield points, and on `break` or `loop` the
+    /// loop's break points.
+    pub async fn document_highlights(
+        &self,
+        uri: &str,
+        line: u32,
+        character: u32,
+    ) -> Result<Vec<DocumentHighlight>> {
+        let params = json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": line, "character": character }
+        });
+
+        let response = self
+            .send_request("textDocument/documentHighlight", Some(params))
+            .await?;
+        Ok(parse_items(&response))
+    }
+
+    /// `rust-analyzer/expandMacro`: the macro call or derive at the position,
+    /// expanded recursively; `None` when there is no macro there.
+    pub async fn expand_macro(
+        &self,
+        uri: &str,
+        line: u32,
+        character: u32,
+    ) -> Result<Option<ExpandedMacro>> {
+        let params = json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": line, "character": character }
+        });
+
+        let response = self
+            .send_request("rust-analyzer/expandMacro", Some(params))
+            .await?;
+        serde_json::from_value(response)
+            .map_err(|e| anyhow!("Unexpected rust-analyzer/expandMacro response: {}", e))
+    }
+
+    /// `experimental/ssr`: structural search and replace for a query like
+    /// `foo($a) ==>> bar($a)`, with paths in it resolved from the position.
+    /// A query rust-analyzer cannot parse fails with its
+    /// [`LspError`](super::LspError), which explains the problem.
+    pub async fn ssr(
+        &self,
+        query: &str,
+        uri: &str,
+        line: u32,
+        character: u32,
+    ) -> Result<Option<WorkspaceEdit>> {
+        let params = json!({
+            "query": query,
+            "parseOnly": false,
+            "textDocument": { "uri": uri },
+            "position": { "line": line, "character": character },
+            "selections": []
+        });
+
+        let response = self.request("experimental/ssr", Some(params)).await?;
+        serde_json::from_value(response)
+            .map_err(|e| anyhow!("Unexpected experimental/ssr response: {}", e))
+    }
+
+    /// `experimental/externalDocs`: documentation links for the item at the
+    /// position. Servers that predate `localDocs` answer with a bare web URL.
+    pub async fn external_docs(
+        &self,
+        uri: &str,
+        line: u32,
+        character: u32,
+    ) -> Result<Option<ExternalDocs>> {
+        let params = json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": line, "character": character }
+        });
+
+        let response = self
+            .send_request("experimental/externalDocs", Some(params))
+            .await?;
+        match response {
+            Value::Null => Ok(None),
+            Value::String(web) => Ok(Some(ExternalDocs {
+                web: Some(web),
+                local: None,
+            })),
+            links => serde_json::from_value(links)
+                .map(Some)
+                .map_err(|e| anyhow!("Unexpected experimental/externalDocs response: {}", e)),
+        }
+    }
+
+    /// `textDocument/completion` at the position, unfiltered; see
+    /// [`filter_completions`](super::filter_completions).
     pub async fn completion(&mut self, uri: &str, line: u32, character: u32) -> Result<Value> {
         let params = json!({
             "textDocument": { "uri": uri },
@@ -49,16 +372,162 @@ impl RustAnalyzerClient {
             .await
     }
 
+    /// `completionItem/resolve`: fills in what rust-analyzer computes lazily,
+    /// such as the `additionalTextEdits` adding an item's imports. Returns the
+    /// item unchanged when it cannot be resolved.
+    pub async fn resolve_completion(&self, item: &Value) -> Result<Value> {
+        let response = self
+            .send_request("completionItem/resolve", Some(item.clone()))
+            .await?;
+        Ok(if response.is_object() {
+            response
+        } else {
+            item.clone()
+        })
+    }
+
+    /// `textDocument/documentSymbol`, cached per document version.
     pub async fn document_symbols(&mut self, uri: &str) -> Result<Value> {
+        // Symbols only depend on the document text, so they stay valid until the
+        // next didChange bumps the version.
+        let version = match self.open_documents.lock().await.get(uri) {
+            Some(state) => {
+                if let Some(symbols) = state.cached_symbols() {
+                    return Ok(symbols.clone());
+                }
+                Some(state.version())
+            }
+            None => None,
+        };
+
         let params = json!({
             "textDocument": { "uri": uri }
         });
 
-        self.send_request("textDocument/documentSymbol", Some(params))
-            .await
+        let symbols = self
+            .send_request("textDocument/documentSymbol", Some(params))
+            .await?;
+
+        // Empty answers may come from a file rust-analyzer has not loaded yet.
+        let cacheable = symbols
+            .as_array()
+            .is_some_and(|symbols| !symbols.is_empty());
+        if let Some(version) = version.filter(|_| cacheable) {
+            if let Some(state) = self.open_documents.lock().await.get_mut(uri) {
+                state.cache_symbols(version, symbols.clone());
+            }
+        }
+
+        Ok(symbols)
+    }
+
+    /// `workspace/symbol` for a fuzzy `query`.
+    pub async fn workspace_symbols(&mut self, query: &str) -> Result<Vec<SymbolInformation>> {
+        let params = json!({
+            "query": query
+        });
+
+        let response = self.send_request("workspace/symbol", Some(params)).await?;
+        Ok(parse_items(&response))
+    }
+
+    /// `workspace/symbol` for every kind of item, including the functions and
+    /// methods rust-analyzer leaves out by default, optionally searching the
+    /// workspace's dependencies too.
+    pub async fn search_symbols(
+        &self,
+        query: &str,
+        include_dependencies: bool,
+    ) -> Result<Vec<SymbolInformation>> {
+        let scope = if include_dependencies {
+            "workspaceAndDependencies"
+        } else {
+            "workspace"
+        };
+        let params = json!({
+            "query": query,
+            "searchScope": scope,
+            "searchKind": "allSymbols"
+        });
+
+        let response = self.send_request("workspace/symbol", Some(params)).await?;
+        Ok(parse_items(&response))
     }
 
-    pub async fn formatting(&mut self, uri: &str) -> Result<Value> {
+    /// `textDocument/documentLink`: clickable ranges such as links in doc
+    /// comments, with links lacking a target resolved one by one.
+    pub async fn document_links(&self, uri: &str) -> Result<Vec<DocumentLink>> {
+        if !self.supports("documentLinkProvider") {
+            return Err(anyhow!(
+                "The language server does not provide document links"
+            ));
+        }
+        let params = json!({
+            "textDocument": { "uri": uri }
+        });
+
+        let response = self
+            .send_request("textDocument/documentLink", Some(params))
+            .await?;
+        let mut links: Vec<DocumentLink> = parse_items(&response);
+        for link in &mut links {
+            if link.target.is_some() {
+                continue;
+            }
+            let resolved = self
+                .send_request("documentLink/resolve", Some(serde_json::to_value(&*link)?))
+                .await?;
+            if let Ok(resolved) = serde_json::from_value(resolved) {
+                *link = resolved;
+            }
+        }
+        Ok(links)
+    }
+
+    /// `textDocument/inlayHint` for lines `start_line` to `end_line`, both
+    /// included. Hints sent without a tooltip are resolved when they carry the
+    /// `data` to resolve them with.
+    pub async fn inlay_hints(
+        &self,
+        uri: &str,
+        start_line: u32,
+        end_line: u32,
+    ) -> Result<Vec<InlayHint>> {
+        if !self.supports("inlayHintProvider") {
+            return Err(anyhow!("The language server does not provide inlay hints"));
+        }
+        let params = json!({
+            "textDocument": { "uri": uri },
+            "range": {
+                "start": { "line": start_line, "character": 0 },
+                "end": { "line": end_line + 1, "character": 0 }
+            }
+        });
+
+        let response = self
+            .send_request("textDocument/inlayHint", Some(params))
+            .await?;
+        let mut hints: Vec<InlayHint> = parse_items(&response);
+        let pending: Vec<usize> = (0..hints.len())
+            .filter(|&index| needs_tooltip_resolve(&hints[index]))
+            .take(MAX_INLAY_HINT_RESOLVES)
+            .collect();
+        let resolved = join_all(pending.iter().map(|&index| {
+            let hint = serde_json::to_value(&hints[index]);
+            async move { self.send_request("inlayHint/resolve", Some(hint?)).await }
+        }))
+        .await;
+        for (index, resolved) in pending.into_iter().zip(resolved) {
+            if let Ok(resolved) = serde_json::from_value(resolved?) {
+                hints[index] = resolved;
+            }
+        }
+        Ok(hints)
+    }
+
+    /// `textDocument/formatting` with four-space indentation; `None` when the
+    /// document is already formatted.
+    pub async fn formatting(&mut self, uri: &str) -> Result<Option<Vec<TextEdit>>> {
         let params = json!({
             "textDocument": { "uri": uri },
             "options": {
@@ -67,11 +536,15 @@ impl RustAnalyzerClient {
             }
         });
 
-        self.send_request("textDocument/formatting", Some(params))
-            .await
+        let response = self
+            .send_request("textDocument/formatting", Some(params))
+            .await?;
+        Ok((!response.is_null()).then(|| parse_items(&response)))
     }
 
-    pub async fn diagnostics(&mut self, uri: &str) -> Result<Value> {
+    /// Diagnostics for a document: those last published by rust-analyzer, else
+    /// a `textDocument/diagnostic` pull.
+    pub async fn diagnostics(&mut self, uri: &str) -> Result<Vec<Diagnostic>> {
         // First check if we have stored diagnostics from publishDiagnostics.
         let diag_lock = self.diagnostics.lock().await;
         info!("Looking for diagnostics for URI: {}", uri);
@@ -81,7 +554,7 @@ impl RustAnalyzerClient {
         );
         if let Some(diags) = diag_lock.get(uri) {
             info!("Found {} stored diagnostics for {}", diags.len(), uri);
-            return Ok(json!(diags));
+            return Ok(diags.clone());
         }
         drop(diag_lock);
 
@@ -95,15 +568,16 @@ impl RustAnalyzerClient {
             .send_request("textDocument/diagnostic", Some(params))
             .await?;
 
-        // Extract diagnostics from the response.
-        if let Some(items) = response.get("items") {
-            Ok(items.clone())
-        } else {
-            Ok(json!([]))
-        }
+        Ok(parse_items(&response["items"]))
     }
 
-    pub async fn workspace_diagnostics(&mut self) -> Result<Value> {
+    /// Diagnostics for the whole workspace, keyed by document URI. Without
+    /// `workspace/diagnostic` support, workspace files are opened to collect
+    /// their diagnostics; `cancel` stops that between batches of files.
+    pub async fn workspace_diagnostics(
+        &mut self,
+        cancel: &CancellationToken,
+    ) -> Result<BTreeMap<String, Vec<Diagnostic>>> {
         if self.workspace_diagnostics_supported {
             let params = json!({
                 "identifier": "rust-analyzer",
@@ -129,29 +603,55 @@ impl RustAnalyzerClient {
             info!("workspace/diagnostic not supported by server; using fallback");
         }
 
-        self.workspace_diagnostics_fallback().await
+        self.workspace_diagnostics_fallback(cancel).await
     }
 
-    async fn workspace_diagnostics_fallback(&mut self) -> Result<Value> {
-        let stored = self.diagnostics.lock().await.clone();
-        let mut all_diagnostics = diagnostics_map_to_value(&stored);
+    async fn workspace_diagnostics_fallback(
+        &mut self,
+        cancel: &CancellationToken,
+    ) -> Result<BTreeMap<String, Vec<Diagnostic>>> {
+        let mut all_diagnostics = sorted_diagnostics(&*self.diagnostics.lock().await);
 
         // If nothing is known yet, open workspace files to trigger publishDiagnostics.
+        // Files are read and sent a batch at a time, sharing one processing delay.
         if all_diagnostics.is_empty() {
-            for file_path in collect_workspace_rust_files(&self.workspace_root) {
-                let uri = uri_from_path(&file_path);
-                if let Ok(content) = tokio::fs::read_to_string(&file_path).await {
-                    let _ = self.open_document(&uri, &content).await;
+            let files = workspace_rust_files(
+                &self.workspace_root,
+                &self.options.exclude_dirs,
+                MAX_WORKSPACE_DIAGNOSTIC_FILES,
+            );
+            let parallelism = self.options.diagnostics_parallelism.max(1);
+            for batch in files.chunks(parallelism) {
+                let contents = join_all(batch.iter().map(tokio::fs::read_to_string)).await;
+                let mut sent = false;
+                for (file_path, content) in batch.iter().zip(contents) {
+                    if cancel.is_cancelled() {
+                        return Err(ToolError::Cancelled(
+                            "Workspace diagnostics cancelled".to_string(),
+                        )
+                        .into());
+                    }
+                    let Ok(content) = content else {
+                        continue;
+                    };
+                    let uri = uri_from_path(file_path);
+                    sent |= self
+                        .open_document_without_delay(&uri, &content)
+                        .await
+                        .unwrap_or(false);
+                }
+                if sent {
+                    tokio::time::sleep(Duration::from_millis(DOCUMENT_OPEN_DELAY_MILLIS)).await;
                 }
             }
 
-            let stored = self.diagnostics.lock().await.clone();
-            all_diagnostics = diagnostics_map_to_value(&stored);
+            all_diagnostics = sorted_diagnostics(&*self.diagnostics.lock().await);
         }
 
-        Ok(Value::Object(all_diagnostics))
+        Ok(all_diagnostics)
     }
 
+    /// `textDocument/codeAction` for a range.
     pub async fn code_actions(
         &mut self,
         uri: &str,
@@ -159,12 +659,12 @@ impl RustAnalyzerClient {
         start_char: u32,
         end_line: u32,
         end_char: u32,
-    ) -> Result<Value> {
+    ) -> Result<Vec<CodeAction>> {
         // First, try to get diagnostics for this range.
-        let diagnostics = self.diagnostics(uri).await.unwrap_or(json!([]));
+        let diagnostics = self.diagnostics(uri).await.unwrap_or_default();
 
         // Filter diagnostics to only those in the requested range.
-        let filtered_diagnostics = filter_diagnostics_in_range(&diagnostics, start_line, end_line);
+        let filtered_diagnostics = filter_diagnostics_in_range(diagnostics, start_line, end_line);
 
         let params = json!({
             "textDocument": { "uri": uri },
@@ -178,86 +678,257 @@ impl RustAnalyzerClient {
             }
         });
 
-        self.send_request("textDocument/codeAction", Some(params))
+        let response = self
+            .send_request("textDocument/codeAction", Some(params))
+            .await?;
+        Ok(code_actions_from_response(&response))
+    }
+
+    /// `workspace/willRenameFiles` for one file: the edits rust-analyzer wants
+    /// applied before `old_uri` moves to `new_uri`, such as the parent `mod`
+    /// declaration and paths naming the module. `None` when nothing refers to it.
+    pub async fn will_rename_file(
+        &mut self,
+        old_uri: &str,
+        new_uri: &str,
+    ) -> Result<Option<WorkspaceEdit>> {
+        let params = json!({
+            "files": [{ "oldUri": old_uri, "newUri": new_uri }]
+        });
+
+        let response = self
+            .send_request("workspace/willRenameFiles", Some(params))
+            .await?;
+        serde_json::from_value(response)
+            .map_err(|e| anyhow!("Unexpected workspace/willRenameFiles response: {}", e))
+    }
+
+    /// Runs rust-analyzer's `cargo check` of the whole workspace with
+    /// `rust-analyzer/runFlycheck`, waits until it has finished and returns
+    /// the diagnostics rust-analyzer has published, by URI. This is how checks
+    /// run when [`StartOptions::check_on_save`](super::StartOptions) is off.
+    pub async fn run_check(
+        &mut self,
+        cancel: &CancellationToken,
+    ) -> Result<BTreeMap<String, Vec<Diagnostic>>> {
+        self.send_notification(
+            "rust-analyzer/runFlycheck",
+            Some(json!({ "textDocument": null })),
+        )
+        .await?;
+        self.wait_until_idle("cargo check", cancel).await?;
+
+        Ok(self
+            .diagnostics
+            .lock()
             .await
+            .iter()
+            .filter(|(_, diagnostics)| !diagnostics.is_empty())
+            .map(|(uri, diagnostics)| (uri.clone(), diagnostics.clone()))
+            .collect())
     }
-}
 
-fn filter_diagnostics_in_range(diagnostics: &Value, start_line: u32, end_line: u32) -> Value {
-    let Some(diag_array) = diagnostics.as_array() else {
-        return json!([]);
-    };
+    /// Applies `overrides`, such as `{ "cargo": { "features": ["tls"] } }`, on
+    /// top of the settings the client started with, replacing earlier
+    /// overrides, and waits until rust-analyzer has reloaded the workspace
+    /// with them. `{}` restores the settings the client started with.
+    pub async fn reconfigure(
+        &mut self,
+        overrides: &Value,
+        cancel: &CancellationToken,
+    ) -> Result<()> {
+        let mut settings = initialization_options(&self.options);
+        merge_settings(&mut settings, overrides);
+        *self.settings.lock().await = settings;
 
-    let filtered: Vec<Value> = diag_array
-        .iter()
-        .filter(|d| {
-            let Some(range) = d.get("range") else {
-                return false;
-            };
-            let Some(start) = range.get("start") else {
-                return false;
-            };
-            let Some(end) = range.get("end") else {
-                return false;
-            };
+        self.send_settings().await?;
+        self.wait_until_idle("Workspace reload", cancel).await
+    }
+
+    /// Polls until rust-analyzer is idle after being asked for `work`, which
+    /// starts asynchronously, so it gets a moment to show up as a running task
+    /// before an idle server counts as done.
+    async fn wait_until_idle(&self, work: &str, cancel: &CancellationToken) -> Result<()> {
+        let started = Instant::now();
+        let mut seen_running = false;
+        loop {
+            if cancel.is_cancelled() {
+                return Err(ToolError::Cancelled(format!("{} cancelled", work)).into());
+            }
+            if started.elapsed() > Duration::from_secs(CARGO_COMMAND_TIMEOUT_SECS) {
+                return Err(ToolError::Timeout(format!(
+                    "{} timed out after {} seconds",
+                    work, CARGO_COMMAND_TIMEOUT_SECS
+                ))
+                .into());
+            }
+            let readiness = self.readiness().await;
+            if let Some(blocked) = readiness.blocked {
+                return Err(anyhow!("{}; retry once it has finished", blocked));
+            }
+            let ready = readiness.ready;
+            seen_running |= !ready;
+            let grace = Duration::from_millis(CHECK_START_GRACE_MILLIS);
+            if ready && (seen_running || started.elapsed() >= grace) {
+                return Ok(());
+            }
+            tokio::time::sleep(Duration::from_millis(CHECK_POLL_MILLIS)).await;
+        }
+    }
 
-            let diag_start_line = start.get("line").and_then(|l| l.as_u64()).unwrap_or(0) as u32;
-            let diag_end_line = end.get("line").and_then(|l| l.as_u64()).unwrap_or(0) as u32;
+    /// Tells rust-analyzer that `old_uri` moved to `new_uri` on disk, closing
+    /// the document at the old URI first.
+    pub async fn did_rename_file(&mut self, old_uri: &str, new_uri: &str) -> Result<()> {
+        self.close_document(old_uri).await?;
+        let params = json!({
+            "files": [{ "oldUri": old_uri, "newUri": new_uri }]
+        });
+        self.send_notification("workspace/didRenameFiles", Some(params))
+            .await
+    }
+}
 
-            // Check if diagnostic overlaps with requested range.
-            diag_start_line <= end_line && diag_end_line >= start_line
+/// Deserializes the elements of an array response, skipping malformed ones.
+/// Anything other than an array yields no elements.
+pub(super) fn parse_items<T: DeserializeOwned>(response: &Value) -> Vec<T> {
+    response
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|item| match serde_json::from_value(item.clone()) {
+            Ok(item) => Some(item),
+            Err(e) => {
+                debug!("Skipping malformed LSP result item {}: {}", item, e);
+                None
+            }
         })
-        .cloned()
-        .collect();
+        .collect()
+}
+
+/// Normalizes a `Location`, `Location[]` or `LocationLink[]` response to
+/// locations; links point at their target's selection range.
+fn locations_from_response(response: Value) -> Vec<Location> {
+    let items = match response {
+        Value::Array(items) => items,
+        Value::Object(_) => vec![response],
+        _ => Vec::new(),
+    };
 
-    json!(filtered)
+    items
+        .into_iter()
+        .filter_map(|item| {
+            if item.get("targetUri").is_some() {
+                serde_json::from_value::<LocationLink>(item)
+                    .ok()
+                    .map(Location::from)
+            } else {
+                serde_json::from_value(item).ok()
+            }
+        })
+        .collect()
 }
 
-fn normalize_workspace_diagnostic_report(response: &Value) -> Option<Value> {
-    if response.is_null() {
-        return None;
+/// Reads a `(Command | CodeAction)[]` response, wrapping bare commands in a
+/// code action of the same title.
+fn code_actions_from_response(response: &Value) -> Vec<CodeAction> {
+    let mut actions = Vec::new();
+    for item in response.as_array().into_iter().flatten() {
+        if item["command"].is_string() {
+            if let Ok(command) = serde_json::from_value::<Command>(item.clone()) {
+                actions.push(CodeAction {
+                    title: command.title.clone(),
+                    kind: None,
+                    diagnostics: None,
+                    is_preferred: None,
+                    disabled: None,
+                    edit: None,
+                    command: Some(command),
+                    data: None,
+                });
+            }
+        } else {
+            actions.extend(parse_items(&json!([item])));
+        }
     }
+    actions
+}
+
+fn filter_diagnostics_in_range(
+    diagnostics: Vec<Diagnostic>,
+    start_line: u32,
+    end_line: u32,
+) -> Vec<Diagnostic> {
+    diagnostics
+        .into_iter()
+        // Keep diagnostics that overlap the requested lines.
+        .filter(|d| d.range.start.line <= end_line && d.range.end.line >= start_line)
+        .collect()
+}
 
-    if let Some(obj) = response.as_object() {
-        // LSP pull-diagnostics shape: { "items": [ { "uri": "...", "items": [...] }, ... ] }
-        if let Some(items) = obj.get("items").and_then(|value| value.as_array()) {
-            let mut normalized = serde_json::Map::new();
-            for item in items {
-                let Some(uri) = item.get("uri").and_then(|value| value.as_str()) else {
-                    continue;
-                };
+fn normalize_workspace_diagnostic_report(
+    response: &Value,
+) -> Option<BTreeMap<String, Vec<Diagnostic>>> {
+    let obj = response.as_object()?;
 
-                let diagnostics = item
-                    .get("items")
-                    .or_else(|| item.get("diagnostics"))
-                    .cloned()
-                    .unwrap_or_else(|| json!([]));
+    // LSP pull-diagnostics shape: { "items": [ { "uri": "...", "items": [...] }, ... ] }
+    if let Some(items) = obj.get("items").and_then(|value| value.as_array()) {
+        let mut normalized = BTreeMap::new();
+        for item in items {
+            let Some(uri) = item.get("uri").and_then(|value| value.as_str()) else {
+                continue;
+            };
 
-                if diagnostics.is_array() {
-                    normalized.insert(uri.to_string(), diagnostics);
+            let diagnostics = item.get("items").or_else(|| item.get("diagnostics"));
+            match diagnostics {
+                Some(diagnostics) if diagnostics.is_array() => {
+                    normalized.insert(uri.to_string(), parse_items(diagnostics));
+                }
+                None => {
+                    normalized.insert(uri.to_string(), Vec::new());
                 }
+                Some(_) => {}
             }
-            return Some(Value::Object(normalized));
         }
+        return Some(normalized);
+    }
 
-        // Already normalized map: { "file://...": [ ... ] }
-        if obj.is_empty() || obj.values().all(Value::is_array) {
-            return Some(response.clone());
-        }
+    // Already normalized map: { "file://...": [ ... ] }
+    if obj.values().all(Value::is_array) {
+        return Some(
+            obj.iter()
+                .map(|(uri, diagnostics)| (uri.clone(), parse_items(diagnostics)))
+                .collect(),
+        );
     }
 
     None
 }
 
-fn collect_workspace_rust_files(workspace_root: &Path) -> Vec<PathBuf> {
+/// Up to `limit` Rust files under `workspace_root`, sorted, skipping `target`,
+/// editor or VCS directories and `exclude_dirs`, which are relative to the
+/// workspace root.
+pub fn workspace_rust_files(
+    workspace_root: &Path,
+    exclude_dirs: &[PathBuf],
+    limit: usize,
+) -> Vec<PathBuf> {
+    let excluded: Vec<PathBuf> = exclude_dirs
+        .iter()
+        .map(|dir| workspace_root.join(dir))
+        .collect();
     let mut files = Vec::new();
-    collect_workspace_rust_files_recursive(workspace_root, &mut files);
+    collect_workspace_rust_files_recursive(workspace_root, &excluded, limit, &mut files);
     files.sort();
     files
 }
 
-fn collect_workspace_rust_files_recursive(dir: &Path, files: &mut Vec<PathBuf>) {
-    if files.len() >= MAX_WORKSPACE_DIAGNOSTIC_FILES {
+fn collect_workspace_rust_files_recursive(
+    dir: &Path,
+    excluded: &[PathBuf],
+    limit: usize,
+    files: &mut Vec<PathBuf>,
+) {
+    if files.len() >= limit {
         return;
     }
 
@@ -269,11 +940,11 @@ fn collect_workspace_rust_files_recursive(dir: &Path, files: &mut Vec<PathBuf>)
         let path = entry.path();
 
         if path.is_dir() {
-            if should_skip_workspace_dir(&path) {
+            if should_skip_workspace_dir(&path) || excluded.contains(&path) {
                 continue;
             }
-            collect_workspace_rust_files_recursive(&path, files);
-            if files.len() >= MAX_WORKSPACE_DIAGNOSTIC_FILES {
+            collect_workspace_rust_files_recursive(&path, excluded, limit, files);
+            if files.len() >= limit {
                 return;
             }
             continue;
@@ -282,7 +953,7 @@ fn collect_workspace_rust_files_recursive(dir: &Path, files: &mut Vec<PathBuf>)
         let is_rust_file = path.extension().and_then(|ext| ext.to_str()) == Some("rs");
         if is_rust_file {
             files.push(path);
-            if files.len() >= MAX_WORKSPACE_DIAGNOSTIC_FILES {
+            if files.len() >= limit {
                 return;
             }
         }
@@ -297,16 +968,122 @@ fn should_skip_workspace_dir(path: &Path) -> bool {
     SKIPPED_WORKSPACE_DIRS.contains(&name)
 }
 
-fn uri_from_path(path: &Path) -> String {
-    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
-    format!("file://{}", canonical.display())
-}
-
-fn diagnostics_map_to_value(
-    diagnostics: &std::collections::HashMap<String, Vec<Value>>,
-) -> serde_json::Map<String, Value> {
+fn sorted_diagnostics(
+    diagnostics: &HashMap<String, Vec<Diagnostic>>,
+) -> BTreeMap<String, Vec<Diagnostic>> {
     diagnostics
         .iter()
-        .map(|(uri, items)| (uri.clone(), json!(items)))
+        .map(|(uri, items)| (uri.clone(), items.clone()))
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use std::path::PathBuf;
+
+    use super::{
+        code_actions_from_response, locations_from_response, normalize_workspace_diagnostic_report,
+        workspace_rust_files,
+    };
+
+    fn range(line: u32) -> serde_json::Value {
+        json!({
+            "start": { "line": line, "character": 4 },
+            "end": { "line": line, "character": 9 }
+        })
+    }
+
+    #[test]
+    fn test_locations_from_links_point_at_the_target_name() {
+        let links = json!([{
+            "originSelectionRange": range(1),
+            "targetUri": "file:///work/src/lib.rs",
+            "targetRange": range(10),
+            "targetSelectionRange": range(11)
+        }]);
+        let locations = locations_from_response(links);
+        assert_eq!(locations.len(), 1);
+        assert_eq!(locations[0].uri, "file:///work/src/lib.rs");
+        assert_eq!(locations[0].range.start.line, 11);
+
+        let single = json!({ "uri": "file:///work/src/main.rs", "range": range(3) });
+        assert_eq!(locations_from_response(single)[0].range.start.line, 3);
+        assert!(locations_from_response(json!(null)).is_empty());
+    }
+
+    #[test]
+    fn test_workspace_report_skips_malformed_diagnostics() {
+        let report = json!({
+            "items": [
+                {
+                    "uri": "file:///work/src/lib.rs",
+                    "kind": "full",
+                    "items": [
+                        { "range": range(2), "severity": 1, "code": "E0308", "message": "mismatched types" },
+                        { "message": "no range" }
+                    ]
+                },
+                { "uri": "file:///work/src/main.rs", "kind": "unchanged" }
+            ]
+        });
+
+        let normalized = normalize_workspace_diagnostic_report(&report).unwrap();
+        let lib = &normalized["file:///work/src/lib.rs"];
+        assert_eq!(lib.len(), 1);
+        assert_eq!(lib[0].code.as_ref().unwrap().to_string(), "E0308");
+        assert!(normalized["file:///work/src/main.rs"].is_empty());
+        assert!(normalize_workspace_diagnostic_report(&json!(null)).is_none());
+    }
+
+    #[test]
+    fn test_code_actions_wrap_bare_commands() {
+        let actions = code_actions_from_response(&json!([
+            { "title": "Run test", "command": "rust-analyzer.runSingle", "arguments": [] },
+            {
+                "title": "Add missing import",
+                "kind": "quickfix",
+                "edit": {
+                    "changes": {
+                        "file:///work/src/lib.rs": [{ "range": range(0), "newText": "use std::fmt;\n" }]
+                    }
+                }
+            }
+        ]));
+
+        assert_eq!(actions.len(), 2);
+        assert_eq!(
+            actions[0].command.as_ref().unwrap().command,
+            "rust-analyzer.runSingle"
+        );
+        let edits = actions[1].edit.as_ref().unwrap().changes.as_ref().unwrap();
+        assert_eq!(
+            edits["file:///work/src/lib.rs"][0].new_text,
+            "use std::fmt;\n"
+        );
+    }
+
+    #[test]
+    fn test_workspace_rust_files_skip_excluded_dirs() {
+        let workspace = tempfile::tempdir().unwrap();
+        let root = workspace.path();
+        let files = ["src/lib.rs", "src/gen/bindings.rs", "target/debug/build.rs"];
+        for file in files {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+
+        let relative = |exclude_dirs: &[PathBuf]| -> Vec<PathBuf> {
+            workspace_rust_files(root, exclude_dirs, 10)
+                .iter()
+                .map(|file| file.strip_prefix(root).unwrap().to_path_buf())
+                .collect()
+        };
+        let lib = PathBuf::from("src/lib.rs");
+        let bindings = PathBuf::from("src/gen/bindings.rs");
+        assert_eq!(relative(&[]), [bindings, lib.clone()]);
+        assert_eq!(relative(&[PathBuf::from("src/gen/")]), [lib]);
+    }
+}
@@ -0,0 +1,409 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+
+use crate::{
+    error::ToolError,
+    protocol::lsp::{Hover, HoverContents, Location, MarkedString, Position},
+};
+
+/// How hover contents are returned to the caller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HoverFormat {
+    /// The markdown text rust-analyzer renders.
+    Markdown,
+    /// Markdown with code fences, rules and link targets removed.
+    PlainText,
+    /// Only the item's declaration, e.g. `pub fn new() -> Self`.
+    SignatureOnly,
+    /// Only the item's doc comment, as plain text.
+    DocsOnly,
+    /// The LSP `Hover` response as rust-analyzer sends it.
+    Json,
+}
+
+impl HoverFormat {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "markdown" => Ok(Self::Markdown),
+            "plaintext" => Ok(Self::PlainText),
+            "signature_only" => Ok(Self::SignatureOnly),
+            "docs_only" => Ok(Self::DocsOnly),
+            "json" => Ok(Self::Json),
+            _ => Err(ToolError::invalid(
+                "format",
+                format!(
+                    "Unknown hover format '{}'; expected markdown, plaintext, signature_only, docs_only or json",
+                    name
+                ),
+            )
+            .into()),
+        }
+    }
+}
+
+/// Renders a `textDocument/hover` response as text in one of the markup
+/// formats. Returns `None` when the hover is empty or the format is `Json`.
+pub fn render_hover(hover: &Hover, format: HoverFormat) -> Option<String> {
+    let markdown = hover_markdown(&hover.contents)?;
+    match format {
+        HoverFormat::Markdown => Some(markdown),
+        HoverFormat::PlainText => Some(markdown_to_plain_text(&markdown)),
+        HoverFormat::SignatureOnly => hover_signature(&markdown),
+        HoverFormat::DocsOnly => hover_docs(&markdown),
+        HoverFormat::Json => None,
+    }
+}
+
+/// Joins `MarkupContent`, `MarkedString` or `MarkedString[]` hover contents
+/// into one markdown string.
+fn hover_markdown(contents: &HoverContents) -> Option<String> {
+    match contents {
+        HoverContents::Markup(markup) => Some(markup.value.clone()),
+        HoverContents::Scalar(marked) => Some(marked_string_markdown(marked)),
+        HoverContents::Array(parts) => {
+            let parts: Vec<String> = parts.iter().map(marked_string_markdown).collect();
+            (!parts.is_empty()).then(|| parts.join("\n\n"))
+        }
+    }
+}
+
+fn marked_string_markdown(marked: &MarkedString) -> String {
+    match marked {
+        MarkedString::String(text) => text.clone(),
+        MarkedString::LanguageString { language, value } => {
+            format!("```{}\n{}\n```", language, value)
+        }
+    }
+}
+
+/// What to look at next, from rust-analyzer's hover actions: for each type the
+/// hovered item mentions and for its references, the tool call that shows
+/// them; for its implementations, their locations.
+pub fn hover_follow_ups(hover: &Hover) -> Vec<Value> {
+    let mut follow_ups = Vec::new();
+    for group in hover.actions.iter().flatten() {
+        let prefix = group.title.as_deref().unwrap_or_default();
+        for command in &group.commands {
+            let title = format!("{}{}", prefix, command.title);
+            let arguments = command.arguments.as_deref().unwrap_or_default();
+            let argument = |index: usize| arguments.get(index).cloned().unwrap_or_default();
+            let first = argument(0);
+            match command.command.as_str() {
+                "rust-analyzer.gotoLocation" => {
+                    let Ok(location) = serde_json::from_value::<Location>(first) else {
+                        continue;
+                    };
+                    follow_ups.push(json!({
+                        "title": title,
+                        "kind": "type",
+                        "tool": "rust_analyzer_hover",
+                        "arguments": position_arguments(&location.uri, location.range.start)
+                    }));
+                }
+                "rust-analyzer.showReferences" => {
+                    let (Some(uri), Ok(position)) = (
+                        first.as_str(),
+                        serde_json::from_value::<Position>(argument(1)),
+                    ) else {
+                        continue;
+                    };
+                    let locations: Vec<Location> =
+                        serde_json::from_value(argument(2)).unwrap_or_default();
+                    follow_ups.push(if command.title.contains("implementation") {
+                        json!({
+                            "title": title,
+                            "kind": "implementations",
+                            "count": locations.len(),
+                            "locations": locations
+                        })
+                    } else {
+                        json!({
+                            "title": title,
+                            "kind": "references",
+                            "count": locations.len(),
+                            "tool": "rust_analyzer_references",
+                            "arguments": position_arguments(uri, position)
+                        })
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+    follow_ups
+}
+
+/// Follow-ups as a list to append to a rendered hover.
+pub fn follow_ups_text(follow_ups: &[Value]) -> String {
+    let mut text = String::from("Follow-ups:");
+    for follow_up in follow_ups {
+        let next = match follow_up["tool"].as_str() {
+            Some(tool) => format!("{} {}", tool, follow_up["arguments"]),
+            None => follow_up["locations"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|location| {
+                    let start = &location["range"]["start"];
+                    format!(
+                        "{}:{}:{}",
+                        location["uri"].as_str().unwrap_or_default(),
+                        start["line"],
+                        start["character"]
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", "),
+        };
+        text.push_str(&format!(
+            "\n- {}: {}",
+            follow_up["title"].as_str().unwrap_or_default(),
+            next
+        ));
+    }
+    text
+}
+
+fn position_arguments(uri: &str, position: Position) -> Value {
+    json!({
+        "file_path": uri,
+        "line": position.line,
+        "character": position.character
+    })
+}
+
+/// The path of the module or type containing the hovered item, e.g.
+/// `demo::Calculator` for its `add` method. Locals and other items without a
+/// container yield `None`.
+pub fn hover_container(hover: &Hover) -> Option<String> {
+    let mut blocks = header_blocks(&hover_markdown(&hover.contents)?);
+    blocks.pop()?;
+    blocks
+        .into_iter()
+        .next()
+        .map(|block| block.trim().to_string())
+        .filter(|path| !path.is_empty())
+}
+
+/// The declaration shown in a hover: rust-analyzer puts the containing path
+/// and then the declaration in code blocks before the first `---` rule.
+fn hover_signature(markdown: &str) -> Option<String> {
+    header_blocks(markdown)
+        .pop()
+        .filter(|block| !block.trim().is_empty())
+}
+
+fn header_blocks(markdown: &str) -> Vec<String> {
+    let header = markdown.split("\n---").next().unwrap_or(markdown);
+    let mut blocks = Vec::new();
+    let mut current: Option<Vec<&str>> = None;
+    for line in header.lines() {
+        if line.trim_start().starts_with("```") {
+            match current.take() {
+                Some(block) => blocks.push(block.join("\n")),
+                None => current = Some(Vec::new()),
+            }
+        } else if let Some(block) = current.as_mut() {
+            block.push(line);
+        }
+    }
+    blocks
+}
+
+/// The doc comment shown in a hover: the sections after the declaration,
+/// without the memory layout rust-analyzer adds for types and fields.
+fn hover_docs(markdown: &str) -> Option<String> {
+    let docs: Vec<&str> = markdown
+        .split("\n---")
+        .skip(1)
+        .map(str::trim)
+        .filter(|section| !section.is_empty() && !is_layout_section(section))
+        .collect();
+    let text = markdown_to_plain_text(&docs.join("\n\n"));
+    (!text.is_empty()).then_some(text)
+}
+
+/// Whether a hover section is rust-analyzer's layout summary, e.g.
+/// `size = 16 (0x10), align = 0x8, niches = 1`.
+fn is_layout_section(section: &str) -> bool {
+    ["size = ", "align = ", "offset = "]
+        .iter()
+        .any(|prefix| section.starts_with(prefix))
+}
+
+fn markdown_to_plain_text(markdown: &str) -> String {
+    let mut lines = Vec::new();
+    for line in markdown.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") {
+            continue;
+        }
+        if trimmed == "---" {
+            lines.push(String::new());
+            continue;
+        }
+        lines.push(strip_links(
+            line.trim_start_matches('#').trim_start_matches(' '),
+        ));
+    }
+
+    // Collapse the blank lines left behind by removed fences and rules.
+    let mut text = String::new();
+    let mut blank = true;
+    for line in lines {
+        if line.trim().is_empty() {
+            if !blank {
+                text.push('\n');
+            }
+            blank = true;
+        } else {
+            text.push_str(&line);
+            text.push('\n');
+            blank = false;
+        }
+    }
+    text.trim_end().to_string()
+}
+
+/// Replaces markdown links `[text](target)` with their text.
+fn strip_links(line: &str) -> String {
+    let mut result = String::new();
+    let mut rest = line;
+    while let Some(open) = rest.find('[') {
+        let Some(close) = rest[open..].find("](").map(|close| open + close) else {
+            break;
+        };
+        let Some(end) = rest[close..].find(')').map(|end| close + end) else {
+            break;
+        };
+        result.push_str(&rest[..open]);
+        result.push_str(&rest[open + 1..close]);
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{follow_ups_text, hover_container, hover_follow_ups, render_hover, HoverFormat};
+    use crate::protocol::lsp::Hover;
+
+    fn hover() -> Hover {
+        serde_json::from_value(json!({
+            "contents": {
+                "kind": "markdown",
+                "value": "\n```rust\ndemo::Calculator\n```\n\n```rust\npub fn add(&self, a: i32, b: i32) -> i32\n```\n\n---\n\nAdds two numbers, see [`Calculator`](https://docs.rs/demo).\n\n# Examples"
+            },
+            "range": { "start": { "line": 5, "character": 17 }, "end": { "line": 5, "character": 20 } }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_render_hover_formats() {
+        let hover = hover();
+
+        assert_eq!(
+            render_hover(&hover, HoverFormat::SignatureOnly).as_deref(),
+            Some("pub fn add(&self, a: i32, b: i32) -> i32")
+        );
+        assert_eq!(
+            render_hover(&hover, HoverFormat::PlainText).as_deref(),
+            Some(
+                "demo::Calculator\n\npub fn add(&self, a: i32, b: i32) -> i32\n\n\
+                 Adds two numbers, see `Calculator`.\n\nExamples"
+            )
+        );
+        assert_eq!(
+            render_hover(&hover, HoverFormat::DocsOnly).as_deref(),
+            Some("Adds two numbers, see `Calculator`.\n\nExamples")
+        );
+        assert!(render_hover(&hover, HoverFormat::Markdown)
+            .unwrap()
+            .contains("```rust"));
+        let marked: Hover = serde_json::from_value(json!({
+            "contents": [{ "language": "rust", "value": "struct Calculator" }, "A calculator."]
+        }))
+        .unwrap();
+        assert_eq!(
+            render_hover(&marked, HoverFormat::Markdown).as_deref(),
+            Some("```rust\nstruct Calculator\n```\n\nA calculator.")
+        );
+        assert_eq!(hover_container(&hover).as_deref(), Some("demo::Calculator"));
+        assert_eq!(hover_container(&marked), None);
+        assert_eq!(render_hover(&marked, HoverFormat::Json), None);
+        assert_eq!(render_hover(&marked, HoverFormat::DocsOnly), None);
+
+        let laid_out: Hover = serde_json::from_value(json!({
+            "contents": {
+                "kind": "markdown",
+                "value": "```rust\npub struct Config\n```\n\n---\n\nsize = 24 (0x18), align = 0x8\n\n---\n\nSettings for a run."
+            }
+        }))
+        .unwrap();
+        assert_eq!(
+            render_hover(&laid_out, HoverFormat::DocsOnly).as_deref(),
+            Some("Settings for a run.")
+        );
+    }
+
+    #[test]
+    fn test_hover_follow_ups_come_from_hover_actions() {
+        let location = |line: u32| {
+            json!({
+                "uri": "file:///ws/src/lib.rs",
+                "range": {
+                    "start": { "line": line, "character": 4 },
+                    "end": { "line": line, "character": 9 }
+                }
+            })
+        };
+        let mut hover = hover();
+        hover.actions = serde_json::from_value(json!([
+            {
+                "title": "Go to ",
+                "commands": [{
+                    "title": "demo::Calculator",
+                    "command": "rust-analyzer.gotoLocation",
+                    "arguments": [location(2)]
+                }]
+            },
+            {
+                "commands": [{
+                    "title": "2 implementations",
+                    "command": "rust-analyzer.showReferences",
+                    "arguments": ["file:///ws/src/lib.rs", { "line": 2, "character": 11 }, [location(8), location(12)]]
+                }]
+            },
+            {
+                "commands": [{
+                    "title": "1 reference",
+                    "command": "rust-analyzer.showReferences",
+                    "arguments": ["file:///ws/src/lib.rs", { "line": 5, "character": 17 }, [location(20)]]
+                }]
+            }
+        ]))
+        .unwrap();
+
+        let follow_ups = hover_follow_ups(&hover);
+
+        assert_eq!(follow_ups.len(), 3);
+        assert_eq!(follow_ups[0]["title"], "Go to demo::Calculator");
+        assert_eq!(follow_ups[0]["tool"], "rust_analyzer_hover");
+        assert_eq!(follow_ups[0]["arguments"]["line"], 2);
+        assert_eq!(follow_ups[1]["kind"], "implementations");
+        assert_eq!(follow_ups[1]["count"], 2);
+        assert_eq!(follow_ups[2]["kind"], "references");
+        assert_eq!(follow_ups[2]["arguments"]["character"], 17);
+        assert_eq!(
+            follow_ups_text(&follow_ups[1..]),
+            "Follow-ups:\n\
+             - 2 implementations: file:///ws/src/lib.rs:8:4, file:///ws/src/lib.rs:12:4\n\
+             - 1 reference: rust_analyzer_references \
+             {\"character\":17,\"file_path\":\"file:///ws/src/lib.rs\",\"line\":5}"
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use log::{debug, error};
+use serde_json::Value;
+use tokio::io::{
+    split, AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+    DuplexStream,
+};
+
+use super::connection::{LspReader, LspSink};
+
+/// Buffer size of the in-memory pipe between the client and the backend.
+const PIPE_CAPACITY: usize = 64 * 1024;
+
+/// An LSP server that runs as a task in this process instead of a child
+/// process, such as the mock and replay backends.
+pub(super) trait InProcessBackend: Send + 'static {
+    /// The messages to send back for `message`: a response for requests, and
+    /// any notifications that follow it.
+    fn respond(&mut self, message: &Value) -> Vec<Value>;
+}
+
+/// Starts `backend` on a task of its own and returns the client's ends of the
+/// pipe: the backend's output and where to write requests.
+pub(super) fn spawn<B: InProcessBackend>(backend: B) -> (LspReader, LspSink) {
+    let (client_io, backend_io) = tokio::io::duplex(PIPE_CAPACITY);
+    tokio::spawn(serve(backend_io, backend));
+    let (reader, writer) = split(client_io);
+    (Box::new(reader), Box::new(writer))
+}
+
+/// Answers LSP messages until `exit` is received or the client hangs up.
+async fn serve<B: InProcessBackend>(stream: DuplexStream, mut backend: B) {
+    let (reader, mut writer) = split(stream);
+    let mut reader = BufReader::new(reader);
+
+    loop {
+        let message = match read_frame(&mut reader).await {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            Err(e) => {
+                error!("In-process LSP backend failed to read a message: {}", e);
+                break;
+            }
+        };
+        if message["method"] == "exit" {
+            break;
+        }
+
+        for reply in backend.respond(&message) {
+            if let Err(e) = write_frame(&mut writer, &reply).await {
+                error!("In-process LSP backend failed to write a message: {}", e);
+                return;
+            }
+        }
+    }
+    debug!("In-process LSP backend stopped");
+}
+
+async fn read_frame<R>(reader: &mut R) -> std::io::Result<Option<Value>>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if let Some(value) = line.strip_prefix("Content-Length: ") {
+            length = value.trim().parse::<usize>().ok();
+        } else if line.is_empty() && length.is_some() {
+            break;
+        }
+    }
+
+    let mut body = vec![0u8; length.unwrap_or_default()];
+    reader.read_exact(&mut body).await?;
+    Ok(Some(serde_json::from_slice(&body).unwrap_or(Value::Null)))
+}
+
+async fn write_frame<W>(writer: &mut W, message: &Value) -> std::io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let content = message.to_string();
+    let frame = format!("Content-Length: {}\r\n\r\n{}", content.len(), content);
+    writer.write_all(frame.as_bytes()).await?;
+    writer.flush().await
+}
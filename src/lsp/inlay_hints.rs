@@ -0,0 +1,114 @@
+use crate::protocol::lsp::{InlayHint, InlayHintLabel, InlayHintTooltip};
+
+/// The text rust-analyzer shows for a hint, e.g. `Vec<u32>` or `count:`.
+pub fn inlay_hint_label(hint: &InlayHint) -> String {
+    match &hint.label {
+        InlayHintLabel::String(label) => label.clone(),
+        InlayHintLabel::Parts(parts) => parts.iter().map(|part| part.value.as_str()).collect(),
+    }
+}
+
+/// The hint's tooltip, or the tooltips of its label parts joined by lines.
+pub fn inlay_hint_tooltip(hint: &InlayHint) -> Option<String> {
+    let text = |tooltip: &InlayHintTooltip| match tooltip {
+        InlayHintTooltip::String(text) => text.clone(),
+        InlayHintTooltip::Markup(markup) => markup.value.clone(),
+    };
+    if let Some(tooltip) = &hint.tooltip {
+        return Some(text(tooltip));
+    }
+    let InlayHintLabel::Parts(parts) = &hint.label else {
+        return None;
+    };
+    let tooltips: Vec<String> = parts
+        .iter()
+        .filter_map(|part| part.tooltip.as_ref().map(text))
+        .collect();
+    (!tooltips.is_empty()).then(|| tooltips.join("\n"))
+}
+
+/// Whether the hint has no tooltip yet but can be resolved for one. Type
+/// hints usually carry theirs in their label parts already.
+pub fn needs_tooltip_resolve(hint: &InlayHint) -> bool {
+    hint.data.is_some() && inlay_hint_tooltip(hint).is_none()
+}
+
+/// `type`, `parameter`, `chaining` or `other`. LSP has no kind for chaining
+/// hints, so they are told apart from other type hints by where rust-analyzer
+/// puts them: at the end of a line whose method chain goes on with a `.` on
+/// the next non-blank line.
+pub fn inlay_hint_kind(hint: &InlayHint, lines: &[&str]) -> &'static str {
+    match hint.kind {
+        Some(1) if ends_chain_link(hint, lines) => "chaining",
+        Some(1) => "type",
+        Some(2) => "parameter",
+        _ => "other",
+    }
+}
+
+fn ends_chain_link(hint: &InlayHint, lines: &[&str]) -> bool {
+    let line = hint.position.line as usize;
+    let Some(text) = lines.get(line) else {
+        return false;
+    };
+    let line_end = text.trim_end().encode_utf16().count() as u32;
+    hint.position.character >= line_end
+        && lines[line + 1..]
+            .iter()
+            .map(|next| next.trim_start())
+            .find(|next| !next.is_empty())
+            .is_some_and(|next| next.starts_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{inlay_hint_kind, inlay_hint_label, inlay_hint_tooltip, needs_tooltip_resolve};
+    use crate::protocol::lsp::InlayHint;
+
+    fn hint(line: u32, character: u32, kind: u8, label: serde_json::Value) -> InlayHint {
+        serde_json::from_value(json!({
+            "position": { "line": line, "character": character },
+            "label": label,
+            "kind": kind
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_inlay_hints_are_classified_by_kind_and_placement() {
+        let text = "let total = items\n    .iter()\n\n    .sum();\nlet n = count(3);\n";
+        let lines: Vec<&str> = text.lines().collect();
+
+        let chained = hint(
+            1,
+            11,
+            1,
+            json!([
+                { "value": "Iter<'_, ", "tooltip": "struct Iter" },
+                { "value": "u32>" }
+            ]),
+        );
+        assert_eq!(inlay_hint_kind(&chained, &lines), "chaining");
+        assert_eq!(inlay_hint_label(&chained), "Iter<'_, u32>");
+        assert_eq!(inlay_hint_tooltip(&chained).as_deref(), Some("struct Iter"));
+        assert!(!needs_tooltip_resolve(&chained));
+
+        let binding = hint(0, 9, 1, json!(": u32"));
+        assert_eq!(inlay_hint_kind(&binding, &lines), "type");
+        assert_eq!(inlay_hint_tooltip(&binding), None);
+        assert!(!needs_tooltip_resolve(&binding));
+        let mut unresolved = binding.clone();
+        unresolved.data = Some(json!({ "file_id": 0 }));
+        assert!(needs_tooltip_resolve(&unresolved));
+        assert_eq!(
+            inlay_hint_kind(&hint(3, 11, 1, json!("u32")), &lines),
+            "type"
+        );
+        assert_eq!(
+            inlay_hint_kind(&hint(4, 14, 2, json!("n:")), &lines),
+            "parameter"
+        );
+    }
+}
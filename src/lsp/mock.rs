@@ -0,0 +1,333 @@
+use serde_json::{json, Value};
+
+use super::{
+    connection::{LspReader, LspSink},
+    in_process::{self, InProcessBackend},
+};
+
+/// Starts the mock backend and returns the client's ends of its pipe.
+pub(super) fn spawn() -> (LspReader, LspSink) {
+    in_process::spawn(MockBackend::default())
+}
+
+/// Canned answers for every LSP method the client uses. Positions are echoed
+/// back where a result points into a document, so definitions and references
+/// land in the file that was asked about.
+#[derive(Debug, Default)]
+struct MockBackend {
+    root_uri: String,
+}
+
+impl InProcessBackend for MockBackend {
+    /// Answers requests, and sends the notifications rust-analyzer would send
+    /// in reaction, such as diagnostics for an opened document.
+    fn respond(&mut self, message: &Value) -> Vec<Value> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let Some(id) = message.get("id") else {
+            return self.notify(method, params);
+        };
+
+        let result = match method {
+            "initialize" => {
+                self.root_uri = params["rootUri"]
+                    .as_str()
+                    .unwrap_or("file:///mock")
+                    .to_string();
+                initialize_result()
+            }
+            "shutdown" | "rust-analyzer/reloadWorkspace" => Value::Null,
+            "textDocument/hover" => json!({
+                "contents": {
+                    "kind": "markdown",
+                    "value": concat!(
+                        "```rust\nfn mock_function() -> u32\n```\n\n",
+                        "Canned hover from the mock backend."
+                    )
+                },
+                "range": range_at(&params["position"]),
+                "actions": [{
+                    "commands": [{
+                        "title": "1 reference",
+                        "command": "rust-analyzer.showReferences",
+                        "arguments": [
+                            params["textDocument"]["uri"],
+                            params["position"],
+                            [{
+                                "uri": params["textDocument"]["uri"],
+                                "range": range_at(&params["position"])
+                            }]
+                        ]
+                    }]
+                }]
+            }),
+            "textDocument/definition"
+            | "textDocument/implementation"
+            | "textDocument/references" => json!([{
+                "uri": params["textDocument"]["uri"],
+                "range": range_at(&params["position"])
+            }]),
+            "textDocument/prepareCallHierarchy" => json!([{
+                "name": "mock_function",
+                "kind": 12,
+                "detail": "fn mock_function() -> u32",
+                "uri": params["textDocument"]["uri"],
+                "range": range(0, 0, 2, 1),
+                "selectionRange": range(0, 7, 0, 20)
+            }]),
+            // Every function is called from `main`, which also calls itself.
+            "callHierarchy/incomingCalls" => json!([{
+                "from": {
+                    "name": "main",
+                    "kind": 12,
+                    "detail": "fn main()",
+                    "uri": format!("{}/src/main.rs", self.root_uri),
+                    "range": range(0, 0, 3, 1),
+                    "selectionRange": range(0, 3, 0, 7)
+                },
+                "fromRanges": [range(1, 4, 1, 17)]
+            }]),
+            "callHierarchy/outgoingCalls" => json!([]),
+            "textDocument/completion" => json!({
+                "isIncomplete": false,
+                "items": [
+                    {
+                        "label": "mock_function",
+                        "kind": 3,
+                        "detail": "fn mock_function() -> u32",
+                        "sortText": "0"
+                    },
+                    {
+                        "label": "MockStruct",
+                        "kind": 22,
+                        "detail": "struct MockStruct",
+                        "sortText": "1"
+                    }
+                ]
+            }),
+            "textDocument/documentSymbol" => json!([{
+                "name": "mock_function",
+                "detail": "fn mock_function() -> u32",
+                "kind": 12,
+                "range": range(0, 0, 2, 1),
+                "selectionRange": range(0, 7, 0, 20),
+                "children": []
+            }]),
+            "workspace/symbol" => json!([{
+                "name": "mock_function",
+                "kind": 12,
+                "location": {
+                    "uri": format!("{}/src/lib.rs", self.root_uri),
+                    "range": range(0, 7, 0, 20)
+                }
+            }]),
+            "textDocument/documentHighlight" => json!([{
+                "range": range_at(&params["position"]),
+                "kind": 1
+            }]),
+            "textDocument/documentLink" => json!([{
+                "range": range(0, 4, 0, 12),
+                "target": "https://docs.rs/mock/latest/mock/"
+            }]),
+            "textDocument/inlayHint" => json!([{
+                "position": { "line": 0, "character": 19 },
+                "label": [{ "value": "u32" }],
+                "kind": 1,
+                "data": { "file_id": 0 }
+            }]),
+            "inlayHint/resolve" => {
+                let mut hint = params.clone();
+                hint["tooltip"] = json!("The answer");
+                hint
+            }
+            "experimental/ssr" => json!({
+                "changes": {
+                    params["textDocument"]["uri"].as_str().unwrap_or_default(): [{
+                        "range": range(0, 7, 0, 13),
+                        "newText": "answer"
+                    }]
+                }
+            }),
+            "textDocument/prepareRename" => json!({
+                "range": range(0, 7, 0, 13),
+                "placeholder": "answer"
+            }),
+            "textDocument/rename" => json!({
+                "changes": {
+                    params["textDocument"]["uri"].as_str().unwrap_or_default(): [{
+                        "range": range(0, 7, 0, 13),
+                        "newText": params["newName"]
+                    }]
+                }
+            }),
+            "experimental/externalDocs" => json!({
+                "web": "https://docs.rs/mock/latest/mock/fn.mock_function.html",
+                "local": format!("{}/target/doc/mock/fn.mock_function.html", self.root_uri)
+            }),
+            "textDocument/formatting" | "textDocument/codeAction" => json!([]),
+            "textDocument/diagnostic" => json!({ "kind": "full", "items": [] }),
+            "workspace/diagnostic" => json!({ "items": [] }),
+            _ => {
+                return vec![json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": { "code": -32601, "message": format!("Method not found: {}", method) }
+                })];
+            }
+        };
+        vec![json!({ "jsonrpc": "2.0", "id": id, "result": result })]
+    }
+}
+
+impl MockBackend {
+    fn notify(&self, method: &str, params: &Value) -> Vec<Value> {
+        match method {
+            // Nothing to index, so the mock is ready as soon as the session starts.
+            "initialized" => vec![json!({
+                "jsonrpc": "2.0",
+                "method": "experimental/serverStatus",
+                "params": { "health": "ok", "quiescent": true }
+            })],
+            // A check of the empty workspace begins and ends right away.
+            "rust-analyzer/runFlycheck" => ["begin", "end"]
+                .into_iter()
+                .map(|kind| {
+                    json!({
+                        "jsonrpc": "2.0",
+                        "method": "$/progress",
+                        "params": {
+                            "token": "rustAnalyzer/flycheck/0",
+                            "value": { "kind": kind, "title": "cargo check" }
+                        }
+                    })
+                })
+                .collect(),
+            // New settings reload the empty workspace, which finishes right away.
+            "workspace/didChangeConfiguration" => [false, true]
+                .into_iter()
+                .map(|quiescent| {
+                    json!({
+                        "jsonrpc": "2.0",
+                        "method": "experimental/serverStatus",
+                        "params": { "health": "ok", "quiescent": quiescent }
+                    })
+                })
+                .collect(),
+            "textDocument/didOpen" | "textDocument/didChange" | "textDocument/didSave" => {
+                vec![json!({
+                    "jsonrpc": "2.0",
+                    "method": "textDocument/publishDiagnostics",
+                    "params": { "uri": params["textDocument"]["uri"], "diagnostics": [] }
+                })]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+fn initialize_result() -> Value {
+    json!({
+        "capabilities": {
+            "textDocumentSync": {
+                "openClose": true,
+                "change": 1,
+                "save": { "includeText": true }
+            },
+            "hoverProvider": true,
+            "definitionProvider": true,
+            "implementationProvider": true,
+            "referencesProvider": true,
+            "documentHighlightProvider": true,
+            "callHierarchyProvider": true,
+            "completionProvider": {},
+            "documentSymbolProvider": true,
+            "workspaceSymbolProvider": true,
+            "documentFormattingProvider": true,
+            "codeActionProvider": true,
+            "renameProvider": { "prepareProvider": true },
+            "documentLinkProvider": {},
+            "inlayHintProvider": { "resolveProvider": true },
+            "diagnosticProvider": {
+                "interFileDependencies": true,
+                "workspaceDiagnostics": true
+            }
+        },
+        "serverInfo": {
+            "name": "rust-analyzer-mcp mock backend",
+            "version": env!("CARGO_PKG_VERSION")
+        }
+    })
+}
+
+fn range(start_line: u64, start_character: u64, end_line: u64, end_character: u64) -> Value {
+    json!({
+        "start": { "line": start_line, "character": start_character },
+        "end": { "line": end_line, "character": end_character }
+    })
+}
+
+/// An empty range at an LSP `Position`.
+fn range_at(position: &Value) -> Value {
+    json!({ "start": position, "end": position })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{InProcessBackend, MockBackend};
+
+    fn request(id: u64, method: &str, params: serde_json::Value) -> serde_json::Value {
+        json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+    }
+
+    #[test]
+    fn test_mock_backend_answers_supported_methods() {
+        let mut backend = MockBackend::default();
+        let initialize = backend.respond(&request(
+            1,
+            "initialize",
+            json!({ "rootUri": "file:///work" }),
+        ));
+        assert_eq!(
+            initialize[0]["result"]["capabilities"]["diagnosticProvider"]["workspaceDiagnostics"],
+            true
+        );
+
+        let position = json!({
+            "textDocument": { "uri": "file:///work/src/lib.rs" },
+            "position": { "line": 4, "character": 8 }
+        });
+        let definition = backend.respond(&request(2, "textDocument/definition", position));
+        assert_eq!(definition[0]["id"], 2);
+        assert_eq!(definition[0]["result"][0]["uri"], "file:///work/src/lib.rs");
+        assert_eq!(definition[0]["result"][0]["range"]["start"]["line"], 4);
+
+        let symbols = backend.respond(&request(3, "workspace/symbol", json!({ "query": "" })));
+        assert_eq!(
+            symbols[0]["result"][0]["location"]["uri"],
+            "file:///work/src/lib.rs"
+        );
+
+        let unknown = backend.respond(&request(4, "rust-analyzer/expandMacro", json!({})));
+        assert_eq!(unknown[0]["error"]["code"], -32601);
+    }
+
+    #[test]
+    fn test_mock_backend_publishes_diagnostics_for_opened_documents() {
+        let mut backend = MockBackend::default();
+        let opened = backend.respond(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": { "textDocument": { "uri": "file:///work/src/lib.rs", "text": "" } }
+        }));
+
+        assert_eq!(opened[0]["method"], "textDocument/publishDiagnostics");
+        assert_eq!(opened[0]["params"]["diagnostics"], json!([]));
+        assert_eq!(
+            backend.respond(&json!({ "jsonrpc": "2.0", "method": "initialized" }))[0]["params"]
+                ["quiescent"],
+            true
+        );
+    }
+}
@@ -1,5 +1,35 @@
+mod builder;
+mod cache;
+mod cancel;
 mod client;
+mod completion;
 mod connection;
 mod handlers;
+mod hover;
+mod in_process;
+mod inlay_hints;
+#[cfg(feature = "mock")]
+mod mock;
+mod progress;
+mod replay;
+mod retry;
+mod ssr;
+mod trace;
+mod uri;
 
-pub use client::RustAnalyzerClient;
+pub use builder::{ClientBuilder, LspBackend, StartOptions};
+pub use cancel::CancellationToken;
+pub use client::{OpenDocument, RustAnalyzerClient};
+pub use completion::{
+    filter_completions, is_completion_kind, needs_import_resolve, select_completions,
+    CompletionFilter, SelectedCompletions,
+};
+pub use connection::WindowMessage;
+pub use handlers::workspace_rust_files;
+pub use hover::{follow_ups_text, hover_container, hover_follow_ups, render_hover, HoverFormat};
+pub use inlay_hints::{inlay_hint_kind, inlay_hint_label, inlay_hint_tooltip};
+pub use progress::{ProgressTask, Readiness};
+pub use retry::{LspError, RetryPolicy};
+pub use ssr::ssr_search_query;
+pub use trace::{LspTracer, TraceDirection};
+pub use uri::{path_from_uri, uri_from_path};
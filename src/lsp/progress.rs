@@ -0,0 +1,259 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// A `$/progress` task rust-analyzer has begun but not yet ended, such as
+/// indexing or `cargo check`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProgressTask {
+    pub title: String,
+    pub message: Option<String>,
+    pub percentage: Option<u64>,
+}
+
+/// Whether rust-analyzer can answer reliably yet. Until it is ready, empty
+/// results may mean "not indexed yet" rather than "no matches".
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    /// `100` once ready, otherwise the least advanced running task that
+    /// reports a percentage, e.g. indexing; `None` while nothing reports one.
+    pub percentage: Option<u64>,
+    /// `ok`, `warning` or `error` from `experimental/serverStatus`.
+    pub health: Option<String>,
+    /// Explanation accompanying a `warning` or `error` health, e.g. a failed
+    /// workspace load.
+    pub message: Option<String>,
+    /// Running progress tasks such as indexing or `cargo check`.
+    pub tasks: Vec<ProgressTask>,
+    /// Set while a running task waits for a cargo lock held by another cargo
+    /// process, e.g. "check blocked by another cargo process (waiting for the
+    /// lock on build directory)".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocked: Option<String>,
+}
+
+impl Readiness {
+    /// The readiness of a rust-analyzer process that is not running.
+    pub fn not_started() -> Self {
+        Self {
+            ready: false,
+            percentage: None,
+            health: None,
+            message: None,
+            tasks: Vec::new(),
+            blocked: None,
+        }
+    }
+}
+
+/// rust-analyzer's loading state, assembled from `$/progress` and
+/// `experimental/serverStatus` notifications.
+#[derive(Debug, Default)]
+pub struct IndexingProgress {
+    /// Tasks in flight, by progress token.
+    tasks: BTreeMap<String, ProgressTask>,
+    /// The last `quiescent` flag from `experimental/serverStatus`, if any was sent.
+    quiescent: Option<bool>,
+    health: Option<String>,
+    status_message: Option<String>,
+    /// Whether any progress task has ended, for servers without `serverStatus`.
+    finished_any: bool,
+    /// The cargo lock the last progress message or stderr line said cargo is
+    /// waiting for, until progress moves on.
+    lock_wait: Option<String>,
+}
+
+impl IndexingProgress {
+    /// Applies the params of a `$/progress` notification.
+    pub fn apply_progress(&mut self, params: &Value) {
+        let token = match &params["token"] {
+            Value::String(token) => token.clone(),
+            token => token.to_string(),
+        };
+        let value = &params["value"];
+        let message = value["message"].as_str().map(str::to_string);
+        let percentage = value["percentage"].as_u64();
+        self.lock_wait = message.as_deref().and_then(cargo_lock_wait);
+
+        match value["kind"].as_str() {
+            Some("begin") => {
+                let task = ProgressTask {
+                    title: value["title"].as_str().unwrap_or(&token).to_string(),
+                    message,
+                    percentage,
+                };
+                self.tasks.insert(token, task);
+            }
+            Some("report") => {
+                if let Some(task) = self.tasks.get_mut(&token) {
+                    task.message = message.or(task.message.take());
+                    task.percentage = percentage.or(task.percentage);
+                }
+            }
+            Some("end") => {
+                self.tasks.remove(&token);
+                self.finished_any = true;
+            }
+            _ => {}
+        }
+    }
+
+    /// Applies the params of an `experimental/serverStatus` notification.
+    pub fn apply_server_status(&mut self, params: &Value) {
+        if let Some(quiescent) = params["quiescent"].as_bool() {
+            self.quiescent = Some(quiescent);
+        }
+        self.health = params["health"].as_str().map(str::to_string);
+        self.status_message = params["message"].as_str().map(str::to_string);
+    }
+
+    /// Applies a line rust-analyzer wrote to stderr, where cargo's own output
+    /// can show up.
+    pub fn apply_stderr(&mut self, line: &str) {
+        if let Some(lock) = cargo_lock_wait(line) {
+            self.lock_wait = Some(lock);
+        }
+    }
+
+    /// Whether rust-analyzer has loaded the workspace, finished indexing and
+    /// has no check or other progress task running.
+    pub fn is_ready(&self) -> bool {
+        self.quiescent.unwrap_or(self.finished_any) && self.tasks.is_empty()
+    }
+
+    pub fn readiness(&self) -> Readiness {
+        let ready = self.is_ready();
+        let percentage = if ready {
+            Some(100)
+        } else {
+            self.tasks.values().filter_map(|task| task.percentage).min()
+        };
+        Readiness {
+            ready,
+            percentage,
+            health: self.health.clone(),
+            message: self.status_message.clone(),
+            tasks: self.tasks.values().cloned().collect(),
+            blocked: self
+                .lock_wait
+                .as_ref()
+                .filter(|_| !self.tasks.is_empty())
+                .map(|lock| {
+                    format!(
+                        "check blocked by another cargo process (waiting for the lock on {})",
+                        lock
+                    )
+                }),
+        }
+    }
+}
+
+/// The lock named by cargo's "Blocking waiting for file lock on ..." message,
+/// such as `package cache` or `build directory`.
+fn cargo_lock_wait(text: &str) -> Option<String> {
+    let (_, lock) = text.split_once("waiting for file lock on ")?;
+    Some(lock.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::IndexingProgress;
+
+    fn progress(token: &str, value: serde_json::Value) -> serde_json::Value {
+        json!({ "token": token, "value": value })
+    }
+
+    #[test]
+    fn test_ready_once_quiescent_and_all_tasks_ended() {
+        let mut state = IndexingProgress::default();
+        assert!(!state.is_ready());
+
+        state.apply_server_status(&json!({ "health": "ok", "quiescent": false }));
+        state.apply_progress(&progress(
+            "rustAnalyzer/cachePriming",
+            json!({ "kind": "begin", "title": "Indexing", "percentage": 0 }),
+        ));
+        state.apply_progress(&progress(
+            "rust-analyzer/flycheck/0",
+            json!({ "kind": "begin", "title": "cargo check" }),
+        ));
+        state.apply_progress(&progress(
+            "rustAnalyzer/cachePriming",
+            json!({ "kind": "report", "message": "2/5 (core)", "percentage": 40 }),
+        ));
+
+        let readiness = state.readiness();
+        assert!(!readiness.ready);
+        assert_eq!(readiness.percentage, Some(40));
+        assert_eq!(readiness.tasks.len(), 2);
+        let indexing = &state.tasks["rustAnalyzer/cachePriming"];
+        assert_eq!(indexing.title, "Indexing");
+        assert_eq!(indexing.message.as_deref(), Some("2/5 (core)"));
+
+        state.apply_progress(&progress(
+            "rustAnalyzer/cachePriming",
+            json!({ "kind": "end" }),
+        ));
+        state.apply_server_status(&json!({ "health": "ok", "quiescent": true }));
+        assert!(!state.is_ready(), "cargo check is still running");
+
+        state.apply_progress(&progress(
+            "rust-analyzer/flycheck/0",
+            json!({ "kind": "end" }),
+        ));
+        assert!(state.is_ready());
+        assert_eq!(state.readiness().percentage, Some(100));
+    }
+
+    #[test]
+    fn test_readiness_reports_cargo_lock_waits() {
+        let mut state = IndexingProgress::default();
+        state.apply_stderr("    Blocking waiting for file lock on build directory");
+        assert_eq!(state.readiness().blocked, None, "nothing is running");
+
+        state.apply_progress(&progress(
+            "rust-analyzer/flycheck/0",
+            json!({ "kind": "begin", "title": "cargo check" }),
+        ));
+        state.apply_progress(&progress(
+            "rust-analyzer/flycheck/0",
+            json!({
+                "kind": "report",
+                "message": "Blocking waiting for file lock on package cache"
+            }),
+        ));
+        assert_eq!(
+            state.readiness().blocked.as_deref(),
+            Some("check blocked by another cargo process (waiting for the lock on package cache)")
+        );
+
+        state.apply_progress(&progress(
+            "rust-analyzer/flycheck/0",
+            json!({ "kind": "report", "message": "serde" }),
+        ));
+        assert_eq!(state.readiness().blocked, None);
+        state.apply_stderr("Blocking waiting for file lock on build directory");
+        assert!(state.readiness().blocked.is_some());
+    }
+
+    #[test]
+    fn test_readiness_reports_server_health() {
+        let mut state = IndexingProgress::default();
+        state.apply_server_status(&json!({
+            "health": "warning",
+            "quiescent": true,
+            "message": "Failed to load workspaces"
+        }));
+
+        let readiness = state.readiness();
+        assert!(readiness.ready);
+        assert_eq!(readiness.health.as_deref(), Some("warning"));
+        assert_eq!(
+            readiness.message.as_deref(),
+            Some("Failed to load workspaces")
+        );
+    }
+}
@@ -0,0 +1,351 @@
+use anyhow::{anyhow, Result};
+use log::{debug, warn};
+use serde_json::{json, Value};
+use std::{
+    collections::{HashMap, VecDeque},
+    path::Path,
+};
+
+use super::{
+    connection::{LspReader, LspSink},
+    in_process::{self, InProcessBackend},
+};
+
+/// Loads an LSP recording and starts a backend answering from it.
+pub(super) fn spawn(recording: &Path) -> Result<(LspReader, LspSink)> {
+    let content = std::fs::read_to_string(recording).map_err(|e| {
+        anyhow!(
+            "Failed to read LSP recording {}: {}",
+            recording.display(),
+            e
+        )
+    })?;
+    let sessions = parse_recording(&content);
+    if sessions.is_empty() {
+        return Err(anyhow!(
+            "LSP recording {} contains no initialize request",
+            recording.display()
+        ));
+    }
+    Ok(in_process::spawn(ReplayBackend::new(sessions)))
+}
+
+/// A request recorded together with rust-analyzer's answer to it.
+#[derive(Debug, Clone)]
+struct Exchange {
+    method: String,
+    params: Value,
+    /// The `result` or `error` member of the recorded response.
+    answer: Option<(&'static str, Value)>,
+    replayed: bool,
+}
+
+/// One rust-analyzer session, from `initialize` until the next one.
+#[derive(Debug, Clone, Default)]
+struct Session {
+    root_uri: Option<String>,
+    exchanges: Vec<Exchange>,
+    /// Notifications rust-analyzer sent, each with the number of client
+    /// messages that had been sent before it.
+    notifications: VecDeque<(usize, Value)>,
+}
+
+impl Session {
+    /// Rewrites the recorded workspace root to `root_uri`, so a recording
+    /// taken in one checkout replays in another.
+    fn relocate(&mut self, root_uri: &str) {
+        let Some(recorded) = self.root_uri.take() else {
+            return;
+        };
+        let relocate = |value: &mut Value| {
+            if let Ok(moved) = serde_json::from_str(&value.to_string().replace(&recorded, root_uri))
+            {
+                *value = moved;
+            }
+        };
+        for exchange in &mut self.exchanges {
+            relocate(&mut exchange.params);
+            if let Some((_, answer)) = &mut exchange.answer {
+                relocate(answer);
+            }
+        }
+        for (_, notification) in &mut self.notifications {
+            relocate(notification);
+        }
+        self.root_uri = Some(root_uri.to_string());
+    }
+}
+
+/// Splits a `--trace-lsp` recording into sessions, pairing requests with their
+/// responses by id.
+fn parse_recording(content: &str) -> Vec<Session> {
+    let mut sessions: Vec<Session> = Vec::new();
+    let mut pending: HashMap<String, usize> = HashMap::new();
+    let mut sent = 0;
+
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        let Ok(entry) = serde_json::from_str::<Value>(line) else {
+            warn!("Skipping unreadable LSP recording line: {}", line);
+            continue;
+        };
+        let message = &entry["message"];
+        let method = message["method"].as_str();
+        let id = message.get("id").map(Value::to_string);
+
+        if entry["direction"] == "sent" {
+            // Replies to rust-analyzer's own requests are not replayed.
+            let Some(method) = method else {
+                continue;
+            };
+            if method == "initialize" {
+                sessions.push(Session {
+                    root_uri: message["params"]["rootUri"].as_str().map(str::to_string),
+                    ..Session::default()
+                });
+                pending.clear();
+                sent = 0;
+            }
+            let Some(session) = sessions.last_mut() else {
+                continue;
+            };
+            sent += 1;
+            if let Some(id) = id {
+                pending.insert(id, session.exchanges.len());
+                session.exchanges.push(Exchange {
+                    method: method.to_string(),
+                    params: message["params"].clone(),
+                    answer: None,
+                    replayed: false,
+                });
+            }
+            continue;
+        }
+
+        let Some(session) = sessions.last_mut() else {
+            continue;
+        };
+        match (method, id) {
+            (Some(_), None) => session.notifications.push_back((sent, message.clone())),
+            (None, Some(id)) => {
+                let Some(index) = pending.remove(&id) else {
+                    continue;
+                };
+                session.exchanges[index].answer = match message.get("error") {
+                    Some(error) => Some(("error", error.clone())),
+                    None => Some(("result", message["result"].clone())),
+                };
+            }
+            // Requests from rust-analyzer are answered by the connection itself.
+            _ => {}
+        }
+    }
+
+    sessions
+}
+
+/// Answers requests with the responses rust-analyzer gave in a recorded
+/// session and replays its notifications at the same points of the exchange.
+struct ReplayBackend {
+    sessions: Vec<Session>,
+    session: Session,
+    /// Client messages received in the current session.
+    received: usize,
+}
+
+impl ReplayBackend {
+    fn new(sessions: Vec<Session>) -> Self {
+        Self {
+            sessions,
+            session: Session::default(),
+            received: 0,
+        }
+    }
+
+    /// Picks the recorded session for the workspace being initialized, or the
+    /// first one if no session was recorded for it.
+    fn start_session(&mut self, root_uri: Option<&str>) {
+        let index = self
+            .sessions
+            .iter()
+            .position(|session| session.root_uri.as_deref() == root_uri)
+            .unwrap_or(0);
+        self.session = self.sessions[index].clone();
+        if let Some(root_uri) = root_uri {
+            self.session.relocate(root_uri);
+        }
+        self.received = 0;
+    }
+
+    /// The recorded answer to a request: an identical one not replayed yet,
+    /// then any identical one, then the next unreplayed one with the same method.
+    fn answer(&mut self, method: &str, params: &Value) -> Option<(&'static str, Value)> {
+        let exchanges = &mut self.session.exchanges;
+        let index = exchanges
+            .iter()
+            .position(|e| !e.replayed && e.method == method && e.params == *params)
+            .or_else(|| {
+                exchanges
+                    .iter()
+                    .position(|e| e.method == method && e.params == *params)
+            })
+            .or_else(|| {
+                exchanges
+                    .iter()
+                    .position(|e| !e.replayed && e.method == method)
+            })?;
+        let exchange = &mut exchanges[index];
+        exchange.replayed = true;
+        exchange.answer.clone()
+    }
+}
+
+impl InProcessBackend for ReplayBackend {
+    fn respond(&mut self, message: &Value) -> Vec<Value> {
+        let Some(method) = message["method"].as_str() else {
+            return Vec::new();
+        };
+        if method == "initialize" {
+            self.start_session(message["params"]["rootUri"].as_str());
+        }
+        self.received += 1;
+
+        let mut replies = Vec::new();
+        if let Some(id) = message.get("id") {
+            let reply = match self.answer(method, &message["params"]) {
+                Some((member, answer)) => {
+                    let mut reply = json!({ "jsonrpc": "2.0", "id": id });
+                    reply[member] = answer;
+                    reply
+                }
+                None => {
+                    debug!("No recorded response for {}", method);
+                    json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": {
+                            "code": -32601,
+                            "message": format!("No recorded response for {}", method)
+                        }
+                    })
+                }
+            };
+            replies.push(reply);
+        }
+
+        while let Some((sent, _)) = self.session.notifications.front() {
+            if *sent > self.received {
+                break;
+            }
+            if let Some((_, notification)) = self.session.notifications.pop_front() {
+                replies.push(notification);
+            }
+        }
+        replies
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::{parse_recording, InProcessBackend, ReplayBackend};
+
+    fn line(direction: &str, message: Value) -> String {
+        json!({ "timestamp_ms": 1, "direction": direction, "message": message }).to_string()
+    }
+
+    fn recording() -> String {
+        [
+            line(
+                "sent",
+                json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize",
+                        "params": { "rootUri": "file:///recorded" } }),
+            ),
+            line(
+                "received",
+                json!({ "jsonrpc": "2.0", "id": 0, "method": "window/workDoneProgress/create",
+                        "params": { "token": "rustAnalyzer/Fetching" } }),
+            ),
+            line("sent", json!({ "jsonrpc": "2.0", "id": 0, "result": null })),
+            line(
+                "received",
+                json!({ "jsonrpc": "2.0", "id": 1, "result": { "capabilities": {} } }),
+            ),
+            line(
+                "sent",
+                json!({ "jsonrpc": "2.0", "method": "textDocument/didOpen",
+                        "params": { "textDocument": { "uri": "file:///recorded/src/lib.rs" } } }),
+            ),
+            line(
+                "received",
+                json!({ "jsonrpc": "2.0", "method": "textDocument/publishDiagnostics",
+                        "params": { "uri": "file:///recorded/src/lib.rs", "diagnostics": [] } }),
+            ),
+            line(
+                "sent",
+                json!({ "jsonrpc": "2.0", "id": 2, "method": "textDocument/hover",
+                        "params": { "textDocument": { "uri": "file:///recorded/src/lib.rs" },
+                                    "position": { "line": 0, "character": 7 } } }),
+            ),
+            line(
+                "received",
+                json!({ "jsonrpc": "2.0", "id": 2,
+                        "result": { "contents": { "kind": "markdown", "value": "fn answer()" } } }),
+            ),
+        ]
+        .join("\n")
+    }
+
+    #[test]
+    fn test_parse_recording_pairs_requests_with_responses() {
+        let sessions = parse_recording(&recording());
+
+        assert_eq!(sessions.len(), 1);
+        let session = &sessions[0];
+        assert_eq!(session.root_uri.as_deref(), Some("file:///recorded"));
+        let methods: Vec<&str> = session
+            .exchanges
+            .iter()
+            .map(|exchange| exchange.method.as_str())
+            .collect();
+        assert_eq!(methods, ["initialize", "textDocument/hover"]);
+        assert_eq!(session.notifications.len(), 1);
+        assert_eq!(session.notifications[0].0, 2);
+    }
+
+    #[test]
+    fn test_replay_answers_in_a_relocated_workspace() {
+        let mut backend = ReplayBackend::new(parse_recording(&recording()));
+
+        let initialize = backend.respond(&json!({
+            "jsonrpc": "2.0", "id": 7, "method": "initialize",
+            "params": { "rootUri": "file:///elsewhere" }
+        }));
+        assert_eq!(
+            initialize,
+            [json!({ "jsonrpc": "2.0", "id": 7, "result": { "capabilities": {} } })]
+        );
+
+        let opened = backend.respond(&json!({
+            "jsonrpc": "2.0", "method": "textDocument/didOpen",
+            "params": { "textDocument": { "uri": "file:///elsewhere/src/lib.rs" } }
+        }));
+        assert_eq!(opened[0]["params"]["uri"], "file:///elsewhere/src/lib.rs");
+
+        let hover = json!({
+            "jsonrpc": "2.0", "id": 8, "method": "textDocument/hover",
+            "params": { "textDocument": { "uri": "file:///elsewhere/src/lib.rs" },
+                        "position": { "line": 0, "character": 7 } }
+        });
+        for _ in 0..2 {
+            let replies = backend.respond(&hover);
+            assert_eq!(replies[0]["id"], 8);
+            assert_eq!(replies[0]["result"]["contents"]["value"], "fn answer()");
+        }
+
+        let unknown = backend.respond(&json!({
+            "jsonrpc": "2.0", "id": 9, "method": "textDocument/formatting", "params": {}
+        }));
+        assert_eq!(unknown[0]["error"]["code"], -32601);
+    }
+}
@@ -0,0 +1,117 @@
+use serde_json::Value;
+use std::{fmt, time::Duration};
+
+/// JSON-RPC error code rust-analyzer returns when a document changed while a
+/// request was being computed, e.g. during indexing.
+const CONTENT_MODIFIED: i64 = -32801;
+
+/// JSON-RPC error code for requests the server cancelled because it was busy.
+const SERVER_CANCELLED: i64 = -32802;
+
+/// How often and how long to wait before resending an LSP request that failed
+/// with a transient error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt; `0` disables retrying.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for each further one.
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay between retries.
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(2),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retry number `attempt`, counting from zero.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff)
+    }
+}
+
+/// Whether an LSP error response is worth retrying: the result was discarded
+/// because the document changed or the server was busy, not because the
+/// request was invalid.
+pub fn is_transient_error(error: &Value) -> bool {
+    matches!(
+        error["code"].as_i64(),
+        Some(CONTENT_MODIFIED | SERVER_CANCELLED)
+    )
+}
+
+/// An LSP error response that persisted through every retry, such as
+/// "content modified" while rust-analyzer keeps re-indexing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LspError {
+    pub method: String,
+    pub code: i64,
+    pub message: String,
+}
+
+impl LspError {
+    pub(super) fn new(method: &str, error: &Value) -> Self {
+        Self {
+            method: method.to_string(),
+            code: error["code"].as_i64().unwrap_or_default(),
+            message: error["message"].as_str().unwrap_or_default().to_string(),
+        }
+    }
+
+    /// Whether the error came from rust-analyzer being busy rather than from
+    /// the request itself.
+    pub fn is_transient(&self) -> bool {
+        matches!(self.code, CONTENT_MODIFIED | SERVER_CANCELLED)
+    }
+}
+
+impl fmt::Display for LspError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "LSP error for {}: {} ({})",
+            self.method, self.message, self.code
+        )
+    }
+}
+
+impl std::error::Error for LspError {}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use std::time::Duration;
+
+    use super::{is_transient_error, RetryPolicy};
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_cap() {
+        let policy = RetryPolicy::default();
+
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(400));
+        assert_eq!(policy.backoff(10), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_only_content_modified_and_cancelled_are_transient() {
+        assert!(is_transient_error(
+            &json!({ "code": -32801, "message": "content modified" })
+        ));
+        assert!(is_transient_error(
+            &json!({ "code": -32802, "message": "server cancelled" })
+        ));
+        assert!(!is_transient_error(
+            &json!({ "code": -32602, "message": "invalid params" })
+        ));
+    }
+}
@@ -0,0 +1,54 @@
+use anyhow::{anyhow, Result};
+
+/// Builds an `experimental/ssr` query that finds `pattern` without changing
+/// anything: rust-analyzer only offers search and replace, so each match is
+/// replaced by itself. Placeholder constraints such as `${a:kind(literal)}`
+/// are only allowed in the search half and are dropped from the replacement.
+pub fn ssr_search_query(pattern: &str) -> Result<String> {
+    let pattern = pattern.trim();
+    if pattern.is_empty() {
+        return Err(anyhow!("Empty search pattern"));
+    }
+    if pattern.contains("==>>") {
+        return Err(anyhow!(
+            "Pass only the search pattern, without '==>>' and a replacement"
+        ));
+    }
+
+    let mut replacement = String::new();
+    let mut rest = pattern;
+    while let Some(start) = rest.find("${") {
+        replacement.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}').map(|end| start + end) else {
+            return Err(anyhow!("Unclosed placeholder in '{}'", pattern));
+        };
+        let placeholder = &rest[start + 2..end];
+        let name = placeholder.split(':').next().unwrap_or(placeholder).trim();
+        replacement.push('$');
+        replacement.push_str(name);
+        rest = &rest[end + 1..];
+    }
+    replacement.push_str(rest);
+
+    Ok(format!("{} ==>> {}", pattern, replacement))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ssr_search_query;
+
+    #[test]
+    fn test_ssr_search_query() {
+        assert_eq!(
+            ssr_search_query("$a.unwrap()").unwrap(),
+            "$a.unwrap() ==>> $a.unwrap()"
+        );
+        assert_eq!(
+            ssr_search_query("foo(${a:kind(literal)}, $b)").unwrap(),
+            "foo(${a:kind(literal)}, $b) ==>> foo($a, $b)"
+        );
+        assert!(ssr_search_query("foo($a) ==>> bar($a)").is_err());
+        assert!(ssr_search_query("foo(${a").is_err());
+        assert!(ssr_search_query("  ").is_err());
+    }
+}
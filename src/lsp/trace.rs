@@ -0,0 +1,92 @@
+use anyhow::{anyhow, Result};
+use log::error;
+use serde_json::{json, Value};
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Direction of a traced LSP frame, seen from this server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceDirection {
+    Sent,
+    Received,
+}
+
+/// Appends every LSP frame exchanged with rust-analyzer to a file, one JSON
+/// object per line with a timestamp, the direction and the message.
+#[derive(Debug)]
+pub struct LspTracer {
+    file: Mutex<File>,
+}
+
+impl LspTracer {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| anyhow!("Failed to open LSP trace file {}: {}", path.display(), e))?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn record(&self, direction: TraceDirection, body: &[u8]) {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or(0);
+        let message = serde_json::from_slice::<Value>(body)
+            .unwrap_or_else(|_| json!(String::from_utf8_lossy(body)));
+        let line = json!({
+            "timestamp_ms": timestamp_ms,
+            "direction": match direction {
+                TraceDirection::Sent => "sent",
+                TraceDirection::Received => "received",
+            },
+            "message": message
+        });
+
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = writeln!(file, "{}", line) {
+            error!("Failed to write LSP trace: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::{LspTracer, TraceDirection};
+
+    #[test]
+    fn test_tracer_appends_json_lines() {
+        let path = std::env::temp_dir().join(format!("lsp-trace-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let tracer = LspTracer::open(&path).unwrap();
+        tracer.record(
+            TraceDirection::Sent,
+            br#"{"jsonrpc":"2.0","id":1,"method":"initialize"}"#,
+        );
+        tracer.record(TraceDirection::Received, b"not json");
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let lines: Vec<Value> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["direction"], "sent");
+        assert_eq!(lines[0]["message"]["method"], "initialize");
+        assert_eq!(lines[1]["direction"], "received");
+        assert_eq!(lines[1]["message"], "not json");
+        assert!(lines[0]["timestamp_ms"].as_u64().unwrap() > 0);
+    }
+}
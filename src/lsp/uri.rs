@@ -0,0 +1,63 @@
+use std::path::{Path, PathBuf};
+
+/// Builds a `file://` URI for a path, canonicalizing it when possible.
+pub fn uri_from_path(path: &Path) -> String {
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    format!("file://{}", canonical.display())
+}
+
+/// Converts a `file://` URI back into a filesystem path, decoding percent-escapes
+/// such as the `%20` rust-analyzer uses for spaces.
+pub fn path_from_uri(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?;
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(byte) = hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    // Windows URIs look like `file:///C:/...`; drop the slash before the drive letter.
+    let mut path = String::from_utf8(decoded).ok()?;
+    if cfg!(windows) && path.len() > 2 && path.as_bytes()[2] == b':' {
+        path.remove(0);
+    }
+
+    Some(PathBuf::from(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::path_from_uri;
+
+    #[test]
+    fn test_path_from_uri_decodes_escapes() {
+        assert_eq!(
+            path_from_uri("file:///home/me/my%20crate/src/lib.rs"),
+            Some(PathBuf::from("/home/me/my crate/src/lib.rs"))
+        );
+        assert_eq!(
+            path_from_uri("file:///rustlib/src/rust/library/alloc/src/string.rs"),
+            Some(PathBuf::from(
+                "/rustlib/src/rust/library/alloc/src/string.rs"
+            ))
+        );
+        assert_eq!(
+            path_from_uri("file:///trailing%2"),
+            Some(PathBuf::from("/trailing%2"))
+        );
+        assert_eq!(path_from_uri("untitled:Untitled-1"), None);
+    }
+}
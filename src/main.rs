@@ -1,22 +1,41 @@
 use anyhow::Result;
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc};
 
-use rust_analyzer_mcp::RustAnalyzerMCPServer;
+use rust_analyzer_mcp::{config::ServerConfig, logging, mcp::CommandTool, RustAnalyzerMCPServer};
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    // Initialize logging.
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+fn main() -> Result<()> {
+    let runtime = tokio::runtime::Runtime::new()?;
+    let result = runtime.block_on(serve());
+    // Reading stdin blocks a runtime thread until the client closes it, so don't
+    // wait for that read when the server stopped because of a signal.
+    runtime.shutdown_background();
+    result
+}
+
+async fn serve() -> Result<()> {
+    // Get workspace path and options from command line, defaulting to the current directory.
+    let (workspace_path, config) = ServerConfig::from_args(std::env::args().skip(1))?;
 
-    // Get workspace path from command line or use current directory.
-    let workspace_path = std::env::args()
-        .nth(1)
-        .map(PathBuf::from)
+    let workspace_path: PathBuf = workspace_path
         .unwrap_or_else(|| std::env::current_dir().expect("Failed to get current directory"));
 
-    // Create and run the server.
-    let mut server = RustAnalyzerMCPServer::with_workspace(workspace_path);
-    server.run().await?;
+    // Initialize logging; stdout is reserved for MCP traffic.
+    logging::init(&config)?;
+
+    // Create the server with any tools declared in the tools file, then run it.
+    let tools = match &config.tools_file {
+        Some(path) => CommandTool::load(path)?,
+        None => Vec::new(),
+    };
+    let listen = config.listen;
+    let mut server = RustAnalyzerMCPServer::with_config(workspace_path, config);
+    for tool in tools {
+        server.register_tool(Arc::new(tool))?;
+    }
+    match listen {
+        Some(addr) => server.listen(addr).await?,
+        None => server.run().await?,
+    }
 
     Ok(())
 }
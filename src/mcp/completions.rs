@@ -0,0 +1,109 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+
+use crate::{error::ToolError, lsp::workspace_rust_files};
+
+use super::server::RustAnalyzerMCPServer;
+
+/// The most values one `completion/complete` answer lists.
+const MAX_COMPLETION_VALUES: usize = 100;
+
+/// The most workspace files searched for `file_path` completions.
+const MAX_COMPLETION_FILES: usize = 10_000;
+
+/// Tool arguments naming a workspace file.
+const FILE_ARGUMENTS: [&str; 2] = ["file_path", "old_path"];
+
+/// Tool arguments naming a symbol, completed from `workspace/symbol`.
+const SYMBOL_ARGUMENTS: [&str; 5] = ["symbol", "symbols", "query", "type_name", "trait_name"];
+
+/// Answers `completion/complete` for a tool argument: workspace-relative Rust
+/// files for file paths and workspace symbol names for symbol arguments.
+/// Completion goes by argument name, so any reference works; other arguments
+/// get no values.
+pub(super) async fn complete_argument(
+    server: &mut RustAnalyzerMCPServer,
+    params: &Value,
+) -> Result<Value> {
+    let Some(name) = params["argument"]["name"].as_str() else {
+        return Err(ToolError::missing("argument.name").into());
+    };
+    let value = params["argument"]["value"].as_str().unwrap_or_default();
+
+    let candidates = if FILE_ARGUMENTS.contains(&name) {
+        file_candidates(server, value)
+    } else if SYMBOL_ARGUMENTS.contains(&name) && !value.is_empty() {
+        symbol_candidates(server, value).await?
+    } else {
+        Vec::new()
+    };
+
+    let total = candidates.len();
+    let values: Vec<String> = candidates.into_iter().take(MAX_COMPLETION_VALUES).collect();
+    Ok(json!({
+        "completion": {
+            "values": values,
+            "total": total,
+            "hasMore": total > MAX_COMPLETION_VALUES
+        }
+    }))
+}
+
+fn file_candidates(server: &RustAnalyzerMCPServer, value: &str) -> Vec<String> {
+    let root = &server.workspace_root;
+    let paths = workspace_rust_files(root, &server.config.exclude_dirs, MAX_COMPLETION_FILES)
+        .into_iter()
+        .filter_map(|path| {
+            let relative = path.strip_prefix(&server.workspace_root).ok()?;
+            Some(relative.to_string_lossy().replace('\\', "/"))
+        });
+    rank(paths, value)
+}
+
+async fn symbol_candidates(server: &mut RustAnalyzerMCPServer, value: &str) -> Result<Vec<String>> {
+    server.ensure_client_started().await?;
+    let Some(client) = &mut server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+    let symbols = client.workspace_symbols(value).await?;
+    let mut names: Vec<String> = Vec::new();
+    for symbol in symbols {
+        if !names.contains(&symbol.name) {
+            names.push(symbol.name);
+        }
+    }
+    Ok(rank(names.into_iter(), value))
+}
+
+/// The candidates containing `value`, ignoring case: those starting with it
+/// first, each group in its original order.
+fn rank(candidates: impl Iterator<Item = String>, value: &str) -> Vec<String> {
+    let value = value.to_lowercase();
+    let (mut prefixed, contained): (Vec<String>, Vec<String>) = candidates
+        .filter(|candidate| candidate.to_lowercase().contains(&value))
+        .partition(|candidate| candidate.to_lowercase().starts_with(&value));
+    prefixed.extend(contained);
+    prefixed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::rank;
+
+    #[test]
+    fn test_rank_puts_prefix_matches_first() {
+        let candidates = ["src/main.rs", "src/lib.rs", "tests/lib_test.rs", "build.rs"]
+            .map(str::to_string)
+            .into_iter();
+
+        assert_eq!(
+            rank(candidates.clone(), "SRC/"),
+            ["src/main.rs", "src/lib.rs"]
+        );
+        assert_eq!(
+            rank(candidates.clone(), "lib"),
+            ["src/lib.rs", "tests/lib_test.rs"]
+        );
+        assert_eq!(rank(candidates, "").len(), 4);
+    }
+}
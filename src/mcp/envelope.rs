@@ -0,0 +1,86 @@
+use serde_json::{json, Value};
+use std::path::Path;
+
+use crate::protocol::mcp::{ContentItem, ToolResult};
+
+/// Version of the tool output formats, raised whenever the shape of a tool's
+/// `data` changes incompatibly.
+pub(super) const RESPONSE_SCHEMA_VERSION: u32 = 1;
+
+/// Wraps a tool's result in the versioned envelope enabled with
+/// `--response-envelope`. JSON text becomes `data` as is; other text, such as
+/// markdown outlines, is kept as a string. Resource links follow the envelope.
+pub(super) fn envelope(tool: &str, workspace: &Path, result: ToolResult) -> ToolResult {
+    let (texts, links): (Vec<ContentItem>, Vec<ContentItem>) =
+        result.content.into_iter().partition(ContentItem::is_text);
+    let mut data: Vec<Value> = texts
+        .into_iter()
+        .filter_map(ContentItem::into_text)
+        .map(|text| serde_json::from_str(&text).unwrap_or(Value::String(text)))
+        .collect();
+    let data = if data.len() == 1 {
+        data.remove(0)
+    } else {
+        Value::Array(data)
+    };
+
+    let envelope = json!({
+        "schema_version": RESPONSE_SCHEMA_VERSION,
+        "tool": tool,
+        "workspace": workspace.display().to_string(),
+        "data": data
+    });
+    let mut content = vec![ContentItem::text(
+        serde_json::to_string_pretty(&envelope).unwrap_or_default(),
+    )];
+    content.extend(links);
+    ToolResult { content }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use std::path::Path;
+
+    use super::{envelope, RESPONSE_SCHEMA_VERSION};
+    use crate::protocol::mcp::{ContentItem, ToolResult};
+
+    fn text_result(text: &str) -> ToolResult {
+        ToolResult {
+            content: vec![ContentItem::text(text.to_string())],
+        }
+    }
+
+    #[test]
+    fn test_envelope_wraps_json_and_text() {
+        let mut result = text_result(r#"[{"uri": "file:///ws/src/lib.rs"}]"#);
+        result.content.push(ContentItem::resource_link(
+            "file:///ws/src/lib.rs",
+            "src/lib.rs",
+        ));
+        let wrapped = envelope("rust_analyzer_references", Path::new("/ws"), result);
+        let body: Value = serde_json::from_str(wrapped.content[0].as_text().unwrap()).unwrap();
+
+        assert_eq!(body["schema_version"], RESPONSE_SCHEMA_VERSION);
+        assert_eq!(body["tool"], "rust_analyzer_references");
+        assert_eq!(body["workspace"], "/ws");
+        assert_eq!(body["data"], json!([{ "uri": "file:///ws/src/lib.rs" }]));
+        assert_eq!(
+            serde_json::to_value(&wrapped.content[1]).unwrap(),
+            json!({
+                "type": "resource_link",
+                "uri": "file:///ws/src/lib.rs",
+                "name": "src/lib.rs",
+                "mimeType": "text/x-rust"
+            })
+        );
+
+        let wrapped = envelope(
+            "rust_analyzer_outline_markdown",
+            Path::new("/ws"),
+            text_result("# src/lib.rs"),
+        );
+        let body: Value = serde_json::from_str(wrapped.content[0].as_text().unwrap()).unwrap();
+        assert_eq!(body["data"], "# src/lib.rs");
+    }
+}
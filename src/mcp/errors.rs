@@ -0,0 +1,228 @@
+use serde_json::{json, Value};
+use std::path::Path;
+
+use crate::{
+    error::ToolError,
+    lsp::LspError,
+    protocol::mcp::{
+        MCPError, INTERNAL_ERROR, INVALID_PARAMS, REQUEST_CANCELLED, REQUEST_TIMEOUT,
+        RUST_ANALYZER_UNAVAILABLE, TOOL_UNSUPPORTED,
+    },
+};
+
+/// The JSON-RPC error for a failed tool call. Its code tells the kind of
+/// failure apart; errors rust-analyzer answered with keep its own code.
+pub(super) fn tool_error(tool: &str, error: &anyhow::Error, workspace: &Path) -> MCPError {
+    let data = tool_error_data(tool, error, workspace);
+    let code = match data["kind"].as_str() {
+        Some("lsp_error") => error
+            .downcast_ref::<LspError>()
+            .map_or(INTERNAL_ERROR, |error| error.code as i32),
+        Some("timeout") => REQUEST_TIMEOUT,
+        Some("rust_analyzer_unavailable") => RUST_ANALYZER_UNAVAILABLE,
+        Some("cancelled") => REQUEST_CANCELLED,
+        Some("unsupported") => TOOL_UNSUPPORTED,
+        Some(
+            "missing_argument" | "invalid_argument" | "file_not_found" | "unknown_package"
+            | "tool_disabled",
+        ) => INVALID_PARAMS,
+        _ => INTERNAL_ERROR,
+    };
+    MCPError {
+        code,
+        message: error.to_string(),
+        data: Some(data),
+    }
+}
+
+/// Structured `data` for a failed tool call: what kind of failure it was, the
+/// argument at fault, the LSP error code if rust-analyzer refused the request,
+/// and what to change before calling again, so agents can correct themselves.
+pub(super) fn tool_error_data(tool: &str, error: &anyhow::Error, workspace: &Path) -> Value {
+    let lsp_error = error.downcast_ref::<LspError>();
+
+    let (kind, argument, remediation) = if let Some(lsp_error) = lsp_error {
+        let remediation = lsp_error.is_transient().then(|| {
+            "rust-analyzer is busy indexing or the document changed meanwhile; retry shortly"
+                .to_string()
+        });
+        ("lsp_error", None, remediation)
+    } else {
+        match error.downcast_ref::<ToolError>() {
+            Some(ToolError::Timeout(_)) => (
+                "timeout",
+                None,
+                Some(
+                    "Retry with a larger timeout_ms, up to the server's --max-timeout-ms"
+                        .to_string(),
+                ),
+            ),
+            Some(ToolError::Disabled { .. }) => (
+                "tool_disabled",
+                None,
+                Some(
+                    "The server was started with --allow-tools, --deny-tools or --read-only \
+                      settings that hide this tool"
+                        .to_string(),
+                ),
+            ),
+            Some(ToolError::Unsupported { .. }) => (
+                "unsupported",
+                None,
+                Some(
+                    "The running rust-analyzer lacks the LSP feature this tool needs; update \
+                      it, e.g. with `rustup update`, and call rust_analyzer_status for its version"
+                        .to_string(),
+                ),
+            ),
+            Some(ToolError::MissingArgument { argument, .. }) => (
+                "missing_argument",
+                Some(argument.clone()),
+                Some(format!(
+                    "Pass `{}` as described in the tool's input schema",
+                    argument
+                )),
+            ),
+            Some(ToolError::FileNotFound { .. }) => (
+                "file_not_found",
+                Some("file_path".to_string()),
+                Some(format!(
+                    "File not found; relative paths are resolved against {}",
+                    workspace.display()
+                )),
+            ),
+            Some(ToolError::UnknownPackage(_)) => (
+                "unknown_package",
+                None,
+                Some(
+                    "Use one of the available workspace members listed in the message".to_string(),
+                ),
+            ),
+            Some(ToolError::InvalidArgument { argument, .. }) => (
+                "invalid_argument",
+                argument.clone(),
+                Some("Check the argument against the tool's input schema".to_string()),
+            ),
+            Some(ToolError::Unavailable(_)) => (
+                "rust_analyzer_unavailable",
+                None,
+                Some(
+                    "Check that rust-analyzer is installed, e.g. with `rustup component add \
+                      rust-analyzer`, and see rust_analyzer_logs"
+                        .to_string(),
+                ),
+            ),
+            Some(ToolError::Cancelled(_)) => ("cancelled", None, None),
+            None => ("tool_failed", None, None),
+        }
+    };
+
+    json!({
+        "tool": tool,
+        "kind": kind,
+        "argument": argument,
+        "lsp_code": lsp_error.map(|error| error.code),
+        "remediation": remediation
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+    use std::path::Path;
+
+    use super::{tool_error, tool_error_data};
+    use crate::{
+        error::ToolError,
+        lsp::LspError,
+        protocol::mcp::{INTERNAL_ERROR, INVALID_PARAMS, REQUEST_TIMEOUT, TOOL_UNSUPPORTED},
+    };
+
+    #[test]
+    fn test_tool_error_data_classifies_failures() {
+        let workspace = Path::new("/ws");
+        let data = |error: anyhow::Error| tool_error_data("rust_analyzer_hover", &error, workspace);
+
+        let missing = data(ToolError::missing("file_path").into());
+        assert_eq!(missing["kind"], "missing_argument");
+        assert_eq!(missing["argument"], "file_path");
+
+        let not_found = data(
+            ToolError::FileNotFound {
+                path: "src/nope.rs".to_string(),
+                reason: "No such file".to_string(),
+            }
+            .into(),
+        );
+        assert_eq!(not_found["kind"], "file_not_found");
+        assert_eq!(not_found["argument"], "file_path");
+        assert!(not_found["remediation"].as_str().unwrap().contains("/ws"));
+
+        let invalid =
+            data(ToolError::invalid("max_results", "max_results must be a positive number").into());
+        assert_eq!(invalid["kind"], "invalid_argument");
+        assert_eq!(invalid["argument"], "max_results");
+
+        let busy = data(
+            LspError {
+                method: "textDocument/hover".to_string(),
+                code: -32801,
+                message: "content modified".to_string(),
+            }
+            .into(),
+        );
+        assert_eq!(busy["kind"], "lsp_error");
+        assert_eq!(busy["lsp_code"], -32801);
+        assert!(busy["remediation"].is_string());
+
+        let other = data(anyhow!("Something else"));
+        assert_eq!(other["kind"], "tool_failed");
+        assert!(other["argument"].is_null());
+
+        // Only the error's type counts, not words in its message.
+        let worded = data(anyhow!(
+            "Missing symbol: the build timed out and was cancelled"
+        ));
+        assert_eq!(worded["kind"], "tool_failed");
+        assert!(worded["argument"].is_null());
+    }
+
+    #[test]
+    fn test_tool_error_codes() {
+        let workspace = Path::new("/ws");
+        let code =
+            |error: anyhow::Error| tool_error("rust_analyzer_rename", &error, workspace).code;
+
+        assert_eq!(code(ToolError::missing("new_name").into()), INVALID_PARAMS);
+        assert_eq!(
+            code(ToolError::Timeout("Request timeout".to_string()).into()),
+            REQUEST_TIMEOUT
+        );
+        assert_eq!(code(anyhow!("Request timeout")), INTERNAL_ERROR);
+        assert_eq!(code(anyhow!("Something else")), INTERNAL_ERROR);
+        assert_eq!(
+            code(
+                ToolError::Unsupported {
+                    tool: "rust_analyzer_rename".to_string(),
+                    capability: "renameProvider".to_string(),
+                }
+                .into()
+            ),
+            TOOL_UNSUPPORTED
+        );
+
+        let rejected = tool_error(
+            "rust_analyzer_rename",
+            &LspError {
+                method: "textDocument/rename".to_string(),
+                code: -32602,
+                message: "Invalid name `1x`".to_string(),
+            }
+            .into(),
+            workspace,
+        );
+        assert_eq!(rejected.code, -32602);
+        assert!(rejected.message.contains("Invalid name `1x`"));
+        assert_eq!(rejected.data.unwrap()["lsp_code"], -32602);
+    }
+}
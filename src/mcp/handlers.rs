@@ -1,323 +1,3411 @@
 use anyhow::{anyhow, Result};
+use futures::future::join_all;
 use log::debug;
 use serde_json::{json, Value};
-use std::path::{Path, PathBuf};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use crate::{
-    diagnostics::format_diagnostics,
-    protocol::mcp::{ContentItem, ToolResult},
+    cargo::{
+        cargo_metadata, format_snippet, member_dependencies, project_overview, run_cargo_json,
+        toolchain_info, workspace_members, CargoRun, MemberDirs,
+    },
+    deadline::with_deadline,
+    diagnostics::{
+        dead_code_report, feature_matrix_report, format_diagnostics, inactive_code_report,
+        inactive_reason_at, most_severe_diagnostics, render_diagnostics_text, DiagnosticsSnapshot,
+    },
+    edits::{convert_position, offset_at, EditPlan, FileOperation, PositionInput},
+    error::ToolError,
+    lsp::{
+        follow_ups_text, hover_container, hover_follow_ups, inlay_hint_kind, inlay_hint_label,
+        inlay_hint_tooltip, is_completion_kind, needs_import_resolve, path_from_uri, render_hover,
+        select_completions, ssr_search_query, uri_from_path, CancellationToken, CompletionFilter,
+        HoverFormat, LspError, RustAnalyzerClient,
+    },
+    metrics::latency_summary,
+    protocol::{
+        lsp::{
+            CallHierarchyItem, Diagnostic, DocumentChange, Location, Position, PrepareRename,
+            Range, WorkspaceEdit,
+        },
+        mcp::{ContentItem, ToolResult},
+    },
+    symbols::{
+        build_symbol_tree, cfg_attributes, collect_public_items, crate_root_for_file, crate_roots,
+        enclosing_symbols, identifier_column, impl_block_at, item_header, item_kind_name,
+        join_container_path, matched_path_suffix, module_path_for_file, outline, outline_markdown,
+        qualified_path_at, qualified_symbols, symbol_path_components, trait_surface, word_at,
+        CrateRoot, ImplBlock, SymbolKind, SymbolNode,
+    },
+};
+
+use super::{
+    progress::ProgressReporter,
+    registry::{CustomTool, ToolContext},
+    schema::validate_arguments,
+    server::RustAnalyzerMCPServer,
+    tools::get_tools,
 };
 
-use super::server::RustAnalyzerMCPServer;
+/// The most positions one batched tool call may ask about.
+const MAX_BATCH_POSITIONS: usize = 200;
+
+/// The most tool calls one `rust_analyzer_batch` call may make.
+const MAX_BATCH_CALLS: usize = 50;
+
+/// The most items `rust_analyzer_usage_stats` counts references to in one call.
+const MAX_USAGE_ITEMS: usize = 100;
+
+/// The most feature sets one `rust_analyzer_feature_matrix` call checks.
+const MAX_FEATURE_SETS: usize = 8;
+
+/// How many levels of calls `rust_analyzer_call_hierarchy` follows at most.
+const MAX_CALL_HIERARCHY_DEPTH: u64 = 5;
+
+/// The most calls `rust_analyzer_call_hierarchy` lists in one direction.
+const MAX_CALL_HIERARCHY_CALLS: usize = 500;
+
+/// Rounds `rust_analyzer_benchmark` runs unless told otherwise, and the most
+/// it runs.
+const DEFAULT_BENCHMARK_ITERATIONS: u64 = 5;
+const MAX_BENCHMARK_ITERATIONS: u64 = 100;
 
 /// Helper struct for extracting common tool parameters.
 struct ToolParams;
 
+/// One position of a batched query.
+struct BatchPosition {
+    file_path: String,
+    line: u32,
+    character: u32,
+}
+
 impl ToolParams {
     fn extract_file_path(args: &Value) -> Result<String> {
         let Some(file_path) = args["file_path"].as_str() else {
-            return Err(anyhow!("Missing file_path"));
+            return Err(ToolError::missing("file_path").into());
         };
         Ok(file_path.to_string())
     }
 
-    fn extract_position(args: &Value) -> Result<(u32, u32)> {
-        let Some(line) = args["line"].as_u64() else {
-            return Err(anyhow!("Missing line"));
-        };
-        let Some(character) = args["character"].as_u64() else {
-            return Err(anyhow!("Missing character"));
-        };
-        Ok((line as u32, character as u32))
-    }
+    fn extract_position(args: &Value) -> Result<(u32, u32)> {
+        let Some(line) = args["line"].as_u64() else {
+            return Err(ToolError::missing("line").into());
+        };
+        let Some(character) = args["character"].as_u64() else {
+            return Err(ToolError::missing("character").into());
+        };
+        Ok((line as u32, character as u32))
+    }
+
+    /// Reads the optional `timeout_ms` argument, capped at `max`.
+    fn extract_timeout(args: &Value, max: Duration) -> Result<Option<Duration>> {
+        let Some(timeout_ms) = args.get("timeout_ms") else {
+            return Ok(None);
+        };
+        match timeout_ms.as_u64() {
+            Some(millis) if millis > 0 => Ok(Some(Duration::from_millis(millis).min(max))),
+            _ => Err(ToolError::invalid(
+                "timeout_ms",
+                "timeout_ms must be a positive number of milliseconds",
+            )
+            .into()),
+        }
+    }
+
+    /// Whether the optional `render` argument of the diagnostics tools asks for
+    /// rustc-style text instead of JSON.
+    fn extract_text_render(args: &Value) -> Result<bool> {
+        match args["render"].as_str() {
+            None | Some("json") => Ok(false),
+            Some("text") => Ok(true),
+            Some(other) => Err(ToolError::invalid(
+                "render",
+                format!("Unknown render mode '{}'; use json or text", other),
+            )
+            .into()),
+        }
+    }
+
+    /// Reads the optional `max_results` argument of tools with long results.
+    fn extract_max_results(args: &Value) -> Result<Option<usize>> {
+        let Some(max_results) = args.get("max_results") else {
+            return Ok(None);
+        };
+        match max_results.as_u64() {
+            Some(max) if max > 0 => Ok(Some(max as usize)),
+            _ => Err(
+                ToolError::invalid("max_results", "max_results must be a positive number").into(),
+            ),
+        }
+    }
+
+    /// Reads the `positions` of a batched query; positions without a
+    /// `file_path` use the top-level one.
+    fn extract_positions(args: &Value) -> Result<Vec<BatchPosition>> {
+        let Some(positions) = args["positions"].as_array() else {
+            return Err(ToolError::missing("positions").into());
+        };
+        if positions.len() > MAX_BATCH_POSITIONS {
+            return Err(ToolError::invalid(
+                "positions",
+                format!(
+                    "Too many positions: {} (at most {})",
+                    positions.len(),
+                    MAX_BATCH_POSITIONS
+                ),
+            )
+            .into());
+        }
+        positions
+            .iter()
+            .enumerate()
+            .map(|(index, position)| {
+                let Some(file_path) = position["file_path"]
+                    .as_str()
+                    .or(args["file_path"].as_str())
+                else {
+                    return Err(ToolError::invalid(
+                        "positions",
+                        format!("Missing file_path for position {}", index),
+                    )
+                    .into());
+                };
+                let (line, character) = Self::extract_position(position).map_err(|e| {
+                    ToolError::invalid("positions", format!("{} for position {}", e, index))
+                })?;
+                Ok(BatchPosition {
+                    file_path: file_path.to_string(),
+                    line,
+                    character,
+                })
+            })
+            .collect()
+    }
+
+    fn extract_range(args: &Value) -> Result<(u32, u32, u32, u32)> {
+        let (line, character) = Self::extract_position(args)?;
+        let Some(end_line) = args["end_line"].as_u64() else {
+            return Err(ToolError::missing("end_line").into());
+        };
+        let Some(end_character) = args["end_character"].as_u64() else {
+            return Err(ToolError::missing("end_character").into());
+        };
+        Ok((line, character, end_line as u32, end_character as u32))
+    }
+}
+
+pub async fn handle_tool_call(
+    server: &mut RustAnalyzerMCPServer,
+    tool_name: &str,
+    args: Value,
+    progress: Option<ProgressReporter>,
+) -> Result<ToolResult> {
+    if !server.tool_enabled(tool_name) {
+        return Err(ToolError::Disabled {
+            tool: tool_name.to_string(),
+        }
+        .into());
+    }
+    let schema = get_tools(server.tools.definitions())
+        .into_iter()
+        .find(|tool| tool.name == tool_name)
+        .map(|tool| tool.input_schema);
+    if let Some(schema) = schema {
+        validate_arguments(&schema, &args)?;
+    }
+    server.ensure_client_started().await?;
+    if let Some(capability) = server.missing_capability(tool_name) {
+        return Err(ToolError::Unsupported {
+            tool: tool_name.to_string(),
+            capability: capability.to_string(),
+        }
+        .into());
+    }
+
+    // Calls made from within another call, as in a batch, report no progress
+    // of their own and leave the outer call's reporter in place afterwards.
+    let outer = std::mem::replace(&mut server.progress, progress);
+    let result = call_with_timeout(server, tool_name, args).await;
+    server.progress = outer;
+    result
+}
+
+async fn call_with_timeout(
+    server: &mut RustAnalyzerMCPServer,
+    tool_name: &str,
+    args: Value,
+) -> Result<ToolResult> {
+    let Some(timeout) = ToolParams::extract_timeout(&args, server.config.max_tool_timeout())?
+    else {
+        return dispatch_tool_call(server, tool_name, args).await;
+    };
+
+    // The budget replaces the default limits on waiting for rust-analyzer's
+    // responses and for cargo. Nothing is cut off midway, so messages to
+    // rust-analyzer and file writes always complete.
+    let deadline = Instant::now() + timeout;
+    let result = with_deadline(deadline, dispatch_tool_call(server, tool_name, args)).await;
+    match result {
+        Err(_) if Instant::now() >= deadline => Err(ToolError::Timeout(format!(
+            "{} timed out after {} ms",
+            tool_name,
+            timeout.as_millis()
+        ))
+        .into()),
+        result => result,
+    }
+}
+
+async fn dispatch_tool_call(
+    server: &mut RustAnalyzerMCPServer,
+    tool_name: &str,
+    args: Value,
+) -> Result<ToolResult> {
+    match tool_name {
+        "rust_analyzer_hover" => handle_hover(server, args).await,
+        "rust_analyzer_hover_batch" => handle_hover_batch(server, args).await,
+        "rust_analyzer_definition" => handle_definition(server, args).await,
+        "rust_analyzer_definition_batch" => handle_definition_batch(server, args).await,
+        "rust_analyzer_references" => handle_references(server, args).await,
+        "rust_analyzer_call_hierarchy" => handle_call_hierarchy(server, args).await,
+        "rust_analyzer_highlights" => handle_highlights(server, args).await,
+        "rust_analyzer_open_docs" => handle_open_docs(server, args).await,
+        "rust_analyzer_ssr_search" => handle_ssr_search(server, args).await,
+        "rust_analyzer_expand_macro" => handle_expand_macro(server, args).await,
+        "rust_analyzer_completion" => handle_completion(server, args).await,
+        "rust_analyzer_symbols" => handle_symbols(server, args).await,
+        "rust_analyzer_document_links" => handle_document_links(server, args).await,
+        "rust_analyzer_inlay_hints" => handle_inlay_hints(server, args).await,
+        "rust_analyzer_workspace_symbols" => handle_workspace_symbols(server, args).await,
+        "rust_analyzer_item_docs" => handle_item_docs(server, args).await,
+        "rust_analyzer_usage_stats" => handle_usage_stats(server, args).await,
+        "rust_analyzer_format" => handle_format(server, args).await,
+        "rust_analyzer_code_actions" => handle_code_actions(server, args).await,
+        "rust_analyzer_preview_edit" => handle_preview_edit(server, args).await,
+        "rust_analyzer_rename" => handle_rename(server, args).await,
+        "rust_analyzer_prepare_rename" => handle_prepare_rename(server, args).await,
+        "rust_analyzer_rename_file" => handle_rename_file(server, args).await,
+        "rust_analyzer_rename_module" => handle_rename_module(server, args).await,
+        "rust_analyzer_set_workspace" => handle_set_workspace(server, args).await,
+        "rust_analyzer_diagnostics" => handle_diagnostics(server, args).await,
+        "rust_analyzer_inactive_code" => handle_inactive_code(server, args).await,
+        "rust_analyzer_cfg_at" => handle_cfg_at(server, args).await,
+        "rust_analyzer_workspace_diagnostics" => handle_workspace_diagnostics(server, args).await,
+        "rust_analyzer_diagnostics_snapshot" => handle_diagnostics_snapshot(server, args).await,
+        "rust_analyzer_diagnostics_diff" => handle_diagnostics_diff(server, args).await,
+        "rust_analyzer_cargo_check" => handle_cargo_check(server, args).await,
+        "rust_analyzer_run_check" => handle_run_check(server, args).await,
+        "rust_analyzer_feature_matrix" => handle_feature_matrix(server, args).await,
+        "rust_analyzer_cargo_build" => handle_cargo_build(server, args).await,
+        "rust_analyzer_dead_code" => handle_dead_code(server, args).await,
+        "rust_toolchain_info" => handle_toolchain_info(server, args).await,
+        "rust_project_overview" => handle_project_overview(server, args).await,
+        "rust_list_workspace_members" => handle_list_workspace_members(server, args).await,
+        "rust_cargo_dependencies" => handle_cargo_dependencies(server, args).await,
+        "rust_analyzer_status" => handle_status(server, args).await,
+        "rust_analyzer_server_stats" => handle_server_stats(server, args).await,
+        "rust_analyzer_open_documents" => handle_open_documents(server, args).await,
+        "rust_analyzer_benchmark" => handle_benchmark(server, args).await,
+        "rust_analyzer_logs" => handle_logs(server, args).await,
+        "rust_analyzer_public_api" => handle_public_api(server, args).await,
+        "rust_analyzer_outline" => handle_outline(server, args).await,
+        "rust_analyzer_outline_markdown" => handle_outline_markdown(server, args).await,
+        "rust_analyzer_enclosing_items" => handle_enclosing_items(server, args).await,
+        "rust_analyzer_qualified_name" => handle_qualified_name(server, args).await,
+        "rust_analyzer_convert_position" => handle_convert_position(server, args).await,
+        "rust_analyzer_impls" => handle_impls(server, args).await,
+        "rust_analyzer_implementors" => handle_implementors(server, args).await,
+        "rust_analyzer_trait_surface" => handle_trait_surface(server, args).await,
+        "rust_analyzer_batch" => handle_batch(server, args).await,
+        _ => match server.tools.get(tool_name) {
+            Some(tool) => handle_custom_tool(server, tool, args).await,
+            None => Err(ToolError::invalid("name", format!("Unknown tool: {}", tool_name)).into()),
+        },
+    }
+}
+
+async fn handle_batch(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
+    let Some(calls) = args["calls"].as_array() else {
+        return Err(ToolError::missing("calls").into());
+    };
+    if calls.len() > MAX_BATCH_CALLS {
+        return Err(ToolError::invalid(
+            "calls",
+            format!(
+                "Too many calls: {} (at most {})",
+                calls.len(),
+                MAX_BATCH_CALLS
+            ),
+        )
+        .into());
+    }
+    let stop_on_error = args["stop_on_error"].as_bool().unwrap_or(false);
+
+    // Check every call before making any, so a malformed batch does nothing.
+    let mut invocations = Vec::with_capacity(calls.len());
+    for (index, call) in calls.iter().enumerate() {
+        let Some(name) = call["name"].as_str() else {
+            return Err(
+                ToolError::invalid("calls", format!("Missing name for call {}", index)).into(),
+            );
+        };
+        if name == "rust_analyzer_batch" {
+            return Err(ToolError::invalid(
+                "calls",
+                format!("Call {}: batches cannot be nested", index),
+            )
+            .into());
+        }
+        let arguments = call.get("arguments").cloned().unwrap_or_else(|| json!({}));
+        invocations.push((name, arguments));
+    }
+
+    let total = invocations.len();
+    let mut results = Vec::with_capacity(total);
+    for (index, (name, arguments)) in invocations.into_iter().enumerate() {
+        server.report_progress(index, Some(total), &format!("Calling {}", name));
+        // Boxed: the call may come back through this handler's dispatch.
+        match Box::pin(server.call_tool(name, arguments, None)).await {
+            Ok(result) => results.push(json!({ "name": name, "content": result.content })),
+            Err(e) => {
+                results.push(json!({ "name": name, "error": e.to_string() }));
+                if stop_on_error {
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&results)?)],
+    })
+}
+
+async fn handle_custom_tool(
+    server: &mut RustAnalyzerMCPServer,
+    tool: Arc<dyn CustomTool>,
+    mut args: Value,
+) -> Result<ToolResult> {
+    if let Some(args) = args.as_object_mut() {
+        args.remove("timeout_ms");
+    }
+    let Some(client) = server.client.as_mut() else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+    let context = ToolContext {
+        workspace_root: &server.workspace_root,
+        client,
+    };
+    tool.call(context, args).await
+}
+
+async fn handle_hover(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
+    let file_path = ToolParams::extract_file_path(&args)?;
+    let (line, character) = ToolParams::extract_position(&args)?;
+    let format = HoverFormat::parse(args["format"].as_str().unwrap_or("json"))?;
+
+    let uri = server.open_document_if_needed(&file_path).await?;
+
+    let Some(client) = &mut server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+
+    let result = match (args["end_line"].as_u64(), args["end_character"].as_u64()) {
+        (None, None) => client.hover(&uri, line, character).await?,
+        _ => {
+            let (_, _, end_line, end_character) = ToolParams::extract_range(&args)?;
+            client
+                .hover_range(&uri, (line, character), (end_line, end_character))
+                .await?
+        }
+    };
+    let follow_ups = result.as_ref().map(hover_follow_ups).unwrap_or_default();
+    let text = match format {
+        HoverFormat::Json => {
+            let mut output = serde_json::to_value(&result)?;
+            if !follow_ups.is_empty() {
+                output["follow_ups"] = json!(follow_ups);
+            }
+            serde_json::to_string_pretty(&output)?
+        }
+        _ => {
+            let mut text = result
+                .as_ref()
+                .and_then(|hover| render_hover(hover, format))
+                .unwrap_or_else(|| "No hover information available".to_string());
+            // Signature and docs formats are asked for to get only that part.
+            let whole = matches!(format, HoverFormat::Markdown | HoverFormat::PlainText);
+            if whole && !follow_ups.is_empty() {
+                text.push_str("\n\n");
+                text.push_str(&follow_ups_text(&follow_ups));
+            }
+            text
+        }
+    };
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(text)],
+    })
+}
+
+async fn handle_definition(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
+    let file_path = ToolParams::extract_file_path(&args)?;
+    let (line, character) = ToolParams::extract_position(&args)?;
+
+    let uri = server.open_document_if_needed(&file_path).await?;
+
+    let Some(client) = &mut server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+
+    let result = client.definition(&uri, line, character).await?;
+    let links = resource_links(server, result.iter().map(|location| location.uri.as_str()));
+
+    let mut content = vec![ContentItem::text(serde_json::to_string_pretty(&result)?)];
+    content.extend(links);
+    Ok(ToolResult { content })
+}
+
+async fn handle_hover_batch(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
+    let positions = ToolParams::extract_positions(&args)?;
+    let format = HoverFormat::parse(args["format"].as_str().unwrap_or("json"))?;
+
+    let uris = open_batch_documents(server, &positions).await;
+
+    let Some(client) = &server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+
+    let results = join_all(positions.iter().map(|position| async {
+        let uri = batch_uri(&uris, position)?;
+        let hover = client.hover(uri, position.line, position.character).await?;
+        Ok(match format {
+            HoverFormat::Json => serde_json::to_value(&hover)?,
+            _ => json!(hover.as_ref().and_then(|hover| render_hover(hover, format))),
+        })
+    }))
+    .await;
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(
+            &batch_entries(&positions, results),
+        )?)],
+    })
+}
+
+async fn handle_definition_batch(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    let positions = ToolParams::extract_positions(&args)?;
+
+    let uris = open_batch_documents(server, &positions).await;
+
+    let Some(client) = &server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+
+    let results = join_all(positions.iter().map(|position| async {
+        let uri = batch_uri(&uris, position)?;
+        let locations = client
+            .definition(uri, position.line, position.character)
+            .await?;
+        Ok(serde_json::to_value(locations)?)
+    }))
+    .await;
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(
+            &batch_entries(&positions, results),
+        )?)],
+    })
+}
+
+/// Opens each file a batch asks about once, keeping the error for files that
+/// cannot be opened so only their positions fail.
+async fn open_batch_documents(
+    server: &mut RustAnalyzerMCPServer,
+    positions: &[BatchPosition],
+) -> HashMap<String, std::result::Result<String, String>> {
+    let mut uris = HashMap::new();
+    for position in positions {
+        if !uris.contains_key(&position.file_path) {
+            let uri = server
+                .open_document_if_needed(&position.file_path)
+                .await
+                .map_err(|e| e.to_string());
+            uris.insert(position.file_path.clone(), uri);
+        }
+    }
+    uris
+}
+
+fn batch_uri<'a>(
+    uris: &'a HashMap<String, std::result::Result<String, String>>,
+    position: &BatchPosition,
+) -> Result<&'a str> {
+    match &uris[&position.file_path] {
+        Ok(uri) => Ok(uri),
+        Err(e) => Err(anyhow!("{}", e)),
+    }
+}
+
+/// Pairs each position of a batch with its `result`, or the `error` that
+/// query failed with.
+fn batch_entries(positions: &[BatchPosition], results: Vec<Result<Value>>) -> Value {
+    positions
+        .iter()
+        .zip(results)
+        .map(|(position, result)| {
+            let mut entry = json!({
+                "file_path": position.file_path,
+                "line": position.line,
+                "character": position.character
+            });
+            match result {
+                Ok(value) => entry["result"] = value,
+                Err(e) => entry["error"] = json!(e.to_string()),
+            }
+            entry
+        })
+        .collect()
+}
+
+async fn handle_references(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
+    let file_path = ToolParams::extract_file_path(&args)?;
+    let (line, character) = ToolParams::extract_position(&args)?;
+    let max_results = ToolParams::extract_max_results(&args)?;
+
+    let uri = server.open_document_if_needed(&file_path).await?;
+
+    let Some(client) = &mut server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+
+    let references = client.references(&uri, line, character).await?;
+    let items = references
+        .iter()
+        .map(serde_json::to_value)
+        .collect::<serde_json::Result<Vec<_>>>()?;
+    let result = server.search_result(items, max_results);
+    let listed = references.iter().take(max_results.unwrap_or(usize::MAX));
+    let links = resource_links(server, listed.map(|location| location.uri.as_str()));
+
+    let mut content = vec![ContentItem::text(serde_json::to_string_pretty(&result)?)];
+    content.extend(links);
+    Ok(ToolResult { content })
+}
+
+async fn handle_call_hierarchy(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    let file_path = ToolParams::extract_file_path(&args)?;
+    let (line, character) = ToolParams::extract_position(&args)?;
+    let directions: &[CallDirection] = match args["direction"].as_str().unwrap_or("both") {
+        "incoming" => &[CallDirection::Incoming],
+        "outgoing" => &[CallDirection::Outgoing],
+        "both" => &[CallDirection::Incoming, CallDirection::Outgoing],
+        other => {
+            return Err(ToolError::invalid(
+                "direction",
+                format!(
+                    "Unknown direction '{}'; use incoming, outgoing or both",
+                    other
+                ),
+            )
+            .into())
+        }
+    };
+    let depth = match args.get("depth") {
+        None => 1,
+        Some(depth) => match depth.as_u64() {
+            Some(depth) if depth > 0 => depth.min(MAX_CALL_HIERARCHY_DEPTH),
+            _ => return Err(ToolError::invalid("depth", "depth must be a positive number").into()),
+        },
+    };
+
+    let uri = server.open_document_if_needed(&file_path).await?;
+
+    let Some(client) = &server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+
+    let items = client.prepare_call_hierarchy(&uri, line, character).await?;
+    let Some(item) = items.first() else {
+        return Err(anyhow!(
+            "No function or method at {}:{}:{}",
+            file_path,
+            line,
+            character
+        ));
+    };
+
+    let mut output = json!({
+        "item": call_item_json(item),
+        "depth": depth,
+        "truncated": false
+    });
+    for &direction in directions {
+        let (calls, truncated) = call_tree(client, item, direction, depth).await?;
+        output[direction.name()] = json!(calls);
+        if truncated {
+            output["truncated"] = json!(true);
+        }
+    }
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&output)?)],
+    })
+}
+
+#[derive(Debug, Clone, Copy)]
+enum CallDirection {
+    Incoming,
+    Outgoing,
+}
+
+impl CallDirection {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Incoming => "incoming",
+            Self::Outgoing => "outgoing",
+        }
+    }
+}
+
+/// A caller or callee found while walking a call hierarchy.
+struct CallNode {
+    item: CallHierarchyItem,
+    /// Where the calls are: in the caller's file for incoming calls and in the
+    /// parent's file for outgoing ones.
+    call_sites: Vec<Range>,
+    parent: Option<usize>,
+    /// Reached before, e.g. through recursion, so its calls are not followed
+    /// again.
+    repeated: bool,
+}
+
+/// The calls of `root` in `direction`, `depth` levels deep and breadth first,
+/// as a tree; and whether `MAX_CALL_HIERARCHY_CALLS` cut it short.
+async fn call_tree(
+    client: &RustAnalyzerClient,
+    root: &CallHierarchyItem,
+    direction: CallDirection,
+    depth: u64,
+) -> Result<(Vec<Value>, bool)> {
+    let key = |item: &CallHierarchyItem| (item.uri.clone(), item.selection_range.start);
+    let mut seen = BTreeSet::from([key(root)]);
+    let mut nodes: Vec<CallNode> = Vec::new();
+    let mut frontier = vec![(None, root.clone())];
+    let mut truncated = false;
+
+    'levels: for _ in 0..depth {
+        let mut next = Vec::new();
+        for (parent, item) in frontier {
+            let calls = match direction {
+                CallDirection::Incoming => client
+                    .incoming_calls(&item)
+                    .await?
+                    .into_iter()
+                    .map(|call| (call.from, call.from_ranges))
+                    .collect::<Vec<_>>(),
+                CallDirection::Outgoing => client
+                    .outgoing_calls(&item)
+                    .await?
+                    .into_iter()
+                    .map(|call| (call.to, call.from_ranges))
+                    .collect(),
+            };
+            for (item, call_sites) in calls {
+                if nodes.len() >= MAX_CALL_HIERARCHY_CALLS {
+                    truncated = true;
+                    break 'levels;
+                }
+                let repeated = !seen.insert(key(&item));
+                if !repeated {
+                    next.push((Some(nodes.len()), item.clone()));
+                }
+                nodes.push(CallNode {
+                    item,
+                    call_sites,
+                    parent,
+                    repeated,
+                });
+            }
+        }
+        frontier = next;
+    }
+
+    Ok((call_tree_json(&nodes, None), truncated))
+}
+
+fn call_tree_json(nodes: &[CallNode], parent: Option<usize>) -> Vec<Value> {
+    nodes
+        .iter()
+        .enumerate()
+        .filter(|(_, node)| node.parent == parent)
+        .map(|(index, node)| {
+            let mut call = call_item_json(&node.item);
+            call["call_sites"] = json!(node.call_sites);
+            if node.repeated {
+                call["repeated"] = json!(true);
+            }
+            let calls = call_tree_json(nodes, Some(index));
+            if !calls.is_empty() {
+                call["calls"] = json!(calls);
+            }
+            call
+        })
+        .collect()
+}
+
+fn call_item_json(item: &CallHierarchyItem) -> Value {
+    let detail = item.detail.as_deref().unwrap_or_default();
+    json!({
+        "name": item.name,
+        "kind": item_kind_name(item.kind, detail),
+        "detail": item.detail,
+        "file": item.uri,
+        "range": item.selection_range
+    })
+}
+
+async fn handle_highlights(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
+    let file_path = ToolParams::extract_file_path(&args)?;
+    let (line, character) = ToolParams::extract_position(&args)?;
+
+    let uri = server.open_document_if_needed(&file_path).await?;
+    let content = tokio::fs::read_to_string(server.resolve_file_path(&file_path)).await?;
+
+    let Some(client) = &server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+
+    let highlights = client.document_highlights(&uri, line, character).await?;
+    let result: Vec<Value> = highlights
+        .iter()
+        .map(|highlight| {
+            let start = offset_at(&content, highlight.range.start);
+            let end = offset_at(&content, highlight.range.end);
+            let text = match (start, end) {
+                (Some(start), Some(end)) if start <= end => &content[start..end],
+                _ => "",
+            };
+            let kind = match highlight.kind {
+                Some(2) => "read",
+                Some(3) => "write",
+                _ => "text",
+            };
+            json!({ "range": highlight.range, "kind": kind, "text": text })
+        })
+        .collect();
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&result)?)],
+    })
+}
+
+async fn handle_ssr_search(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
+    let Some(pattern) = args["pattern"].as_str() else {
+        return Err(ToolError::missing("pattern").into());
+    };
+    let query = ssr_search_query(pattern)?;
+    let file_path = ToolParams::extract_file_path(&args)?;
+    let line = args["line"].as_u64().unwrap_or(0) as u32;
+    let character = args["character"].as_u64().unwrap_or(0) as u32;
+
+    let uri = server.open_document_if_needed(&file_path).await?;
+
+    let Some(client) = &server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+
+    let Some(edit) = client.ssr(&query, &uri, line, character).await? else {
+        return Err(anyhow!("rust-analyzer rejected the pattern '{}'", pattern));
+    };
+
+    // Every match is replaced by itself, so the edit ranges are the matches.
+    let mut matches = Vec::new();
+    let mut files = 0;
+    for (uri, edits) in edit.text_edits() {
+        let Some(path) = path_from_uri(uri) else {
+            continue;
+        };
+        let content = tokio::fs::read_to_string(&path).await.unwrap_or_default();
+        files += 1;
+        for edit in edits {
+            let start = offset_at(&content, edit.range.start);
+            let end = offset_at(&content, edit.range.end);
+            let text = match (start, end) {
+                (Some(start), Some(end)) if start <= end => &content[start..end],
+                _ => "",
+            };
+            matches.push(json!({
+                "file": path.strip_prefix(&server.workspace_root).unwrap_or(&path),
+                "range": edit.range,
+                "text": text
+            }));
+        }
+    }
+
+    let result = json!({
+        "pattern": pattern,
+        "total": matches.len(),
+        "files": files,
+        "matches": matches
+    });
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&result)?)],
+    })
+}
+
+async fn handle_open_docs(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
+    let file_path = ToolParams::extract_file_path(&args)?;
+    let (line, character) = ToolParams::extract_position(&args)?;
+    let check_local = args["local"].as_bool().unwrap_or(false);
+
+    let uri = server.open_document_if_needed(&file_path).await?;
+
+    let Some(client) = &server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+
+    let docs = client
+        .external_docs(&uri, line, character)
+        .await?
+        .unwrap_or_default();
+    let mut result = json!({ "web": docs.web });
+    // rust-analyzer computes where `cargo doc` would put the page without
+    // checking that it was generated.
+    if check_local {
+        let local = docs.local.as_deref().and_then(|url| {
+            let page = url.split('#').next().unwrap_or(url);
+            let path = path_from_uri(page).filter(|path| path.is_file())?;
+            Some(json!({ "path": path, "url": url }))
+        });
+        result["local"] = local.unwrap_or(Value::Null);
+    }
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&result)?)],
+    })
+}
+
+async fn handle_expand_macro(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    let file_path = ToolParams::extract_file_path(&args)?;
+    let (line, character) = ToolParams::extract_position(&args)?;
+    let pretty = args["pretty"].as_bool().unwrap_or(false);
+    // `rust-analyzer/expandMacro` only expands fully.
+    if args["recursive"].as_bool() == Some(false) {
+        return Err(ToolError::invalid(
+            "recursive",
+            "rust-analyzer only expands macros recursively; expanding a single step is not supported",
+        )
+        .into());
+    }
+
+    let uri = server.open_document_if_needed(&file_path).await?;
+
+    let Some(client) = &server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+
+    let Some(expanded) = client.expand_macro(&uri, line, character).await? else {
+        return Err(anyhow!(
+            "No macro call or derive at {}:{}:{}",
+            file_path,
+            line,
+            character
+        ));
+    };
+
+    let mut result = json!({
+        "name": expanded.name,
+        "expansion": expanded.expansion
+    });
+    // Derive output in particular is hard to read unformatted; when rustfmt
+    // cannot parse the expansion it is returned as rust-analyzer printed it.
+    if pretty {
+        match format_snippet(&server.workspace_root, &expanded.expansion).await {
+            Ok(formatted) => {
+                result["expansion"] = json!(formatted);
+                result["formatted"] = json!(true);
+            }
+            Err(e) => {
+                result["formatted"] = json!(false);
+                result["format_error"] = json!(e.to_string());
+            }
+        }
+    }
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&result)?)],
+    })
+}
+
+async fn handle_completion(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
+    let file_path = ToolParams::extract_file_path(&args)?;
+    let (line, character) = ToolParams::extract_position(&args)?;
+
+    let uri = server.open_document_if_needed(&file_path).await?;
+
+    let Some(client) = &mut server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+
+    let result = client.completion(&uri, line, character).await?;
+    let result = match completion_filter(&args)? {
+        Some(filter) => {
+            let mut selected = select_completions(&result, &filter);
+            // rust-analyzer adds the imports of auto-import completions on
+            // resolve; only the items returned are resolved.
+            if filter.plain_text {
+                for item in &mut selected.items {
+                    if needs_import_resolve(item) {
+                        *item = client.resolve_completion(item).await?;
+                    }
+                }
+            }
+            selected.render(&filter)
+        }
+        None => result,
+    };
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&result)?)],
+    })
+}
+
+/// Reads the optional `prefix`, `kinds`, `limit` and `plain_text` completion
+/// arguments. Returns `None` when none are given, so the full response is
+/// passed through.
+fn completion_filter(args: &Value) -> Result<Option<CompletionFilter>> {
+    if ["prefix", "kinds", "limit", "max_results", "plain_text"]
+        .iter()
+        .all(|key| args[key].is_null())
+    {
+        return Ok(None);
+    }
+
+    let mut kinds = Vec::new();
+    for kind in args["kinds"].as_array().into_iter().flatten() {
+        let Some(kind) = kind.as_str().filter(|kind| is_completion_kind(kind)) else {
+            return Err(
+                ToolError::invalid("kinds", format!("Unknown completion kind: {}", kind)).into(),
+            );
+        };
+        kinds.push(kind.to_string());
+    }
+
+    Ok(Some(CompletionFilter {
+        prefix: args["prefix"].as_str().map(str::to_string),
+        kinds,
+        limit: match args["limit"].as_u64() {
+            Some(limit) => Some(limit as usize),
+            None => ToolParams::extract_max_results(args)?,
+        },
+        plain_text: args["plain_text"].as_bool().unwrap_or(false),
+    }))
+}
+
+async fn handle_symbols(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
+    let file_path = ToolParams::extract_file_path(&args)?;
+
+    debug!("Getting symbols for file: {}", file_path);
+    let uri = server.open_document_if_needed(&file_path).await?;
+    debug!("Document opened with URI: {}", uri);
+
+    let Some(client) = &mut server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+
+    let result = client.document_symbols(&uri).await?;
+    debug!("Document symbols result: {:?}", result);
+
+    let result = if args["qualified"].as_bool().unwrap_or(false) {
+        let roots = crate_roots(&cargo_metadata(&server.workspace_root, true).await?);
+        let (crate_name, prefix) = module_prefix(&roots, &server.resolve_file_path(&file_path));
+        json!({
+            "file": uri,
+            "crate": crate_name,
+            "symbols": qualified_symbols(&build_symbol_tree(&result), &prefix)
+        })
+    } else {
+        result
+    };
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&result)?)],
+    })
+}
+
+async fn handle_document_links(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    let file_path = ToolParams::extract_file_path(&args)?;
+
+    let uri = server.open_document_if_needed(&file_path).await?;
+
+    let Some(client) = &server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+
+    let result = client.document_links(&uri).await?;
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&result)?)],
+    })
+}
+
+async fn handle_inlay_hints(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
+    let file_path = ToolParams::extract_file_path(&args)?;
+
+    let uri = server.open_document_if_needed(&file_path).await?;
+    let content = tokio::fs::read_to_string(server.resolve_file_path(&file_path)).await?;
+    let lines: Vec<&str> = content.lines().collect();
+    let last_line = lines.len().saturating_sub(1) as u32;
+    let start_line = args["start_line"].as_u64().unwrap_or(0) as u32;
+    let end_line = args["end_line"]
+        .as_u64()
+        .map_or(last_line, |line| (line as u32).min(last_line));
+    if start_line > end_line {
+        return Err(ToolError::invalid_arguments(format!(
+            "start_line {} is after end_line {}",
+            start_line, end_line
+        ))
+        .into());
+    }
+
+    let Some(client) = &server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+
+    let hints: Vec<Value> = client
+        .inlay_hints(&uri, start_line, end_line)
+        .await?
+        .iter()
+        .filter(|hint| hint.position.line <= end_line)
+        .map(|hint| {
+            json!({
+                "line": hint.position.line,
+                "character": hint.position.character,
+                "kind": inlay_hint_kind(hint, &lines),
+                "label": inlay_hint_label(hint),
+                "tooltip": inlay_hint_tooltip(hint)
+            })
+        })
+        .collect();
+
+    let output = json!({
+        "file": file_path,
+        "start_line": start_line,
+        "end_line": end_line,
+        "count": hints.len(),
+        "hints": hints
+    });
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&output)?)],
+    })
+}
+
+async fn handle_workspace_symbols(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    let Some(query) = args["query"].as_str() else {
+        return Err(ToolError::missing("query").into());
+    };
+    let qualified = args["qualified"].as_bool().unwrap_or(false);
+    let max_results = ToolParams::extract_max_results(&args)?;
+    let package = package_filter(&server.workspace_root, &args).await?;
+
+    let roots = if qualified {
+        crate_roots(&cargo_metadata(&server.workspace_root, true).await?)
+    } else {
+        Vec::new()
+    };
+
+    let Some(client) = &mut server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+
+    let mut result = client.workspace_symbols(query).await?;
+    if let Some((members, package)) = &package {
+        result.retain(|symbol| in_package(members, package, &symbol.location.uri));
+    }
+    let result = if qualified {
+        let mut trees = HashMap::new();
+        let mut symbols = Vec::new();
+        for symbol in &result {
+            let uri = symbol.location.uri.as_str();
+            let start = symbol.location.range.start;
+            let position = (start.line, start.character);
+
+            if !trees.contains_key(uri) {
+                let tree = build_symbol_tree(&client.document_symbols(uri).await?);
+                trees.insert(uri.to_string(), tree);
+            }
+            let (crate_name, prefix) = match path_from_uri(uri) {
+                Some(path) => module_prefix(&roots, &path),
+                None => (None, String::new()),
+            };
+
+            symbols.push(json!({
+                "path": qualified_path_at(&trees[uri], &prefix, position),
+                "name": symbol.name,
+                "kind": item_kind_name(symbol.kind, ""),
+                "crate": crate_name,
+                "file": uri,
+                "range": symbol.location.range
+            }));
+        }
+        symbols
+    } else {
+        result
+            .iter()
+            .map(serde_json::to_value)
+            .collect::<serde_json::Result<Vec<_>>>()?
+    };
+    let result = server.search_result(result, max_results);
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&result)?)],
+    })
+}
+
+async fn handle_item_docs(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
+    let Some(path) = args["path"].as_str() else {
+        return Err(ToolError::missing("path").into());
+    };
+
+    let Some(client) = &mut server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+    let item = locate_item(client, &mut SourceCache::default(), path, true).await?;
+
+    server
+        .open_document_if_needed(&item.file.to_string_lossy())
+        .await?;
+    let Some(client) = &server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+    let hover = client.hover(&item.uri, item.line, item.character).await?;
+    let signature = hover
+        .as_ref()
+        .and_then(|hover| render_hover(hover, HoverFormat::SignatureOnly));
+    let docs = hover
+        .as_ref()
+        .and_then(|hover| render_hover(hover, HoverFormat::DocsOnly));
+
+    let result = json!({
+        "path": path,
+        "name": item.name,
+        "kind": item_kind_name(item.kind, signature.as_deref().unwrap_or_default()),
+        "file": item.file,
+        "line": item.line,
+        "character": item.character,
+        "signature": signature,
+        "docs": docs,
+        "other_matches": item.others
+    });
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&result)?)],
+    })
+}
+
+async fn handle_usage_stats(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
+    // Each item path comes with its name's position, or the error explaining
+    // why the path could not be resolved.
+    let mut targets: Vec<(String, Result<UsageTarget>)> = Vec::new();
+    if let Some(paths) = args["symbols"].as_array() {
+        let Some(client) = &mut server.client else {
+            return Err(ToolError::client_not_initialized().into());
+        };
+        let mut sources = SourceCache::default();
+        for path in paths {
+            let Some(path) = path.as_str() else {
+                return Err(ToolError::invalid("symbols", "symbols must be item paths").into());
+            };
+            let item = locate_item(client, &mut sources, path, false)
+                .await
+                .map(|item| UsageTarget {
+                    kind: item_kind_name(item.kind, "").to_string(),
+                    uri: item.uri,
+                    line: item.line,
+                    character: item.character,
+                });
+            targets.push((path.to_string(), item));
+        }
+    } else if args["file_path"].is_string() {
+        let file_path = ToolParams::extract_file_path(&args)?;
+        let file = server.resolve_file_path(&file_path);
+        let roots = crate_roots(&cargo_metadata(&server.workspace_root, true).await?);
+        let (_, prefix) = module_prefix(&roots, &file);
+        if prefix.is_empty() {
+            return Err(anyhow!("{} is not part of a workspace crate", file_path));
+        }
+
+        let uri = server.open_document_if_needed(&file_path).await?;
+        let content = tokio::fs::read_to_string(&file).await?;
+        let Some(client) = &mut server.client else {
+            return Err(ToolError::client_not_initialized().into());
+        };
+        let tree = build_symbol_tree(&client.document_symbols(&uri).await?);
+        let lines: Vec<&str> = content.lines().collect();
+        let api = collect_public_items(&tree, &lines, &prefix, &file_path);
+        for item in api.items {
+            let path = item["path"].as_str().unwrap_or_default().to_string();
+            let line = item["line"].as_u64().unwrap_or_default() as u32;
+            let name = path.rsplit("::").next().unwrap_or_default();
+            let character = lines
+                .get(line as usize)
+                .and_then(|text| identifier_column(text, name))
+                .unwrap_or_default();
+            let target = UsageTarget {
+                kind: item["kind"].as_str().unwrap_or_default().to_string(),
+                uri: uri.clone(),
+                line,
+                character,
+            };
+            targets.push((path, Ok(target)));
+        }
+    } else {
+        return Err(ToolError::missing("symbols or file_path").into());
+    }
+    if targets.len() > MAX_USAGE_ITEMS {
+        return Err(ToolError::invalid(
+            "symbols",
+            format!(
+                "Too many items: {} (at most {})",
+                targets.len(),
+                MAX_USAGE_ITEMS
+            ),
+        )
+        .into());
+    }
+
+    let Some(client) = &mut server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+    let mut items = Vec::new();
+    let mut unused = Vec::new();
+    for (path, target) in targets {
+        let UsageTarget {
+            kind,
+            uri,
+            line,
+            character,
+        } = match target {
+            Ok(target) => target,
+            Err(e) => {
+                items.push(json!({ "path": path, "error": e.to_string() }));
+                continue;
+            }
+        };
+        // References include the declaration itself.
+        let references: Vec<Location> = client
+            .references(&uri, line, character)
+            .await?
+            .into_iter()
+            .filter(|location| {
+                let start = location.range.start;
+                location.uri != uri || (start.line, start.character) != (line, character)
+            })
+            .collect();
+        let files: HashSet<&str> = references
+            .iter()
+            .map(|location| location.uri.as_str())
+            .collect();
+        let outside_file = references
+            .iter()
+            .filter(|location| location.uri != uri)
+            .count();
+        if references.is_empty() {
+            unused.push(path.clone());
+        }
+        items.push(json!({
+            "path": path,
+            "kind": kind,
+            "file": path_from_uri(&uri),
+            "line": line,
+            "references": references.len(),
+            "files": files.len(),
+            "references_outside_file": outside_file
+        }));
+    }
+
+    let result = json!({
+        "items": items,
+        "unused": unused
+    });
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&result)?)],
+    })
+}
+
+/// An item whose references `rust_analyzer_usage_stats` counts, with the
+/// position of its name.
+struct UsageTarget {
+    kind: String,
+    uri: String,
+    line: u32,
+    character: u32,
+}
+
+/// An item found from its path by [`locate_item`].
+struct LocatedItem {
+    name: String,
+    kind: u64,
+    uri: String,
+    file: PathBuf,
+    /// Position of the item's name.
+    line: u32,
+    character: u32,
+    /// Locations of other items matching the path equally well.
+    others: Vec<String>,
+}
+
+/// Finds the item an item path such as `tokio::sync::Mutex::lock` names.
+/// Symbols named like the last segment are ranked by how much of the path
+/// their location and container account for; a leading `crate` is ignored.
+async fn locate_item(
+    client: &mut RustAnalyzerClient,
+    sources: &mut SourceCache,
+    path: &str,
+    include_dependencies: bool,
+) -> Result<LocatedItem> {
+    let segments: Vec<&str> = path
+        .split("::")
+        .map(str::trim)
+        .skip_while(|segment| *segment == "crate")
+        .collect();
+    let Some(&name) = segments.last().filter(|name| !name.is_empty()) else {
+        return Err(ToolError::invalid_arguments(format!("Invalid item path '{}'", path)).into());
+    };
+
+    let symbols = client.search_symbols(name, include_dependencies).await?;
+    let mut ranked = Vec::new();
+    for symbol in &symbols {
+        let Some(file) = path_from_uri(&symbol.location.uri) else {
+            continue;
+        };
+        if symbol.name != name {
+            continue;
+        }
+        let line = symbol.location.range.start.line as usize;
+        let is_reexport = sources
+            .get(client, &symbol.location.uri)
+            .await?
+            .and_then(|(_, content)| content.lines().nth(line))
+            .is_some_and(is_use_statement);
+        if is_reexport {
+            continue;
+        }
+        let components =
+            symbol_path_components(&file, symbol.container_name.as_deref(), &symbol.name);
+        ranked.push((matched_path_suffix(&segments, &components), file, symbol));
+    }
+    // Stable, so equally good matches keep rust-analyzer's order.
+    ranked.sort_by_key(|(matched, _, _)| std::cmp::Reverse(*matched));
+    let Some((matched, file, symbol)) = ranked.first() else {
+        return Err(anyhow!("No item named '{}' found", name));
+    };
+    if *matched < segments.len().min(2) {
+        return Err(anyhow!("No item matching '{}' found", path));
+    }
+    let others = ranked[1..]
+        .iter()
+        .take_while(|(other, _, _)| other == matched)
+        .map(|(_, file, symbol)| format!("{}:{}", file.display(), symbol.location.range.start.line))
+        .collect();
+
+    let uri = symbol.location.uri.clone();
+    let start = symbol.location.range.start;
+    let (line, character) =
+        declaration_position(client, sources, &uri, (start.line, start.character), name).await?;
+    Ok(LocatedItem {
+        name: name.to_string(),
+        kind: symbol.kind,
+        uri,
+        file: file.clone(),
+        line,
+        character,
+        others,
+    })
+}
+
+async fn handle_qualified_name(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    let file_path = ToolParams::extract_file_path(&args)?;
+    let (line, character) = ToolParams::extract_position(&args)?;
+
+    let uri = server.open_document_if_needed(&file_path).await?;
+    // Without cargo metadata every item is named from its hover instead.
+    let roots = match cargo_metadata(&server.workspace_root, true).await {
+        Ok(metadata) => crate_roots(&metadata),
+        Err(e) => {
+            debug!("No crate roots for qualified names: {}", e);
+            Vec::new()
+        }
+    };
+
+    let Some(client) = &mut server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+
+    // References resolve to the item they name; on a declaration the
+    // definition is the declaration itself.
+    let (target_uri, target) = match client.definition(&uri, line, character).await?.first() {
+        Some(location) => {
+            let start = location.range.start;
+            (location.uri.clone(), (start.line, start.character))
+        }
+        None => (uri.clone(), (line, character)),
+    };
+    let target_file = path_from_uri(&target_uri);
+    let content = match &target_file {
+        Some(path) => tokio::fs::read_to_string(path).await.unwrap_or_default(),
+        None => String::new(),
+    };
+    let lines: Vec<&str> = content.lines().collect();
+
+    let tree = build_symbol_tree(&client.document_symbols(&target_uri).await?);
+    let node = enclosing_symbols(&tree, target)
+        .pop()
+        .filter(|node| {
+            let name = lines
+                .get(target.0 as usize)
+                .and_then(|text| word_at(text, target.1));
+            name.is_none_or(|name| name == node.name)
+        })
+        .ok_or_else(|| {
+            anyhow!(
+                "No item at {}:{}:{}; locals and parameters have no path",
+                file_path,
+                line,
+                character
+            )
+        })?;
+
+    let (crate_name, prefix) = match &target_file {
+        Some(path) => module_prefix(&roots, path),
+        None => (None, String::new()),
+    };
+    let relative = qualified_path_at(&tree, &prefix, target).unwrap_or(node.name.clone());
+    let (path, crate_path, crate_name) = match crate_name {
+        Some(name) => {
+            let path = format!("{}{}", name, &relative["crate".len()..]);
+            (path, Some(relative), Some(name))
+        }
+        None => {
+            let hover = client.hover(&uri, line, character).await?;
+            match hover.as_ref().and_then(hover_container) {
+                Some(container) => {
+                    let path = join_container_path(&container, &relative);
+                    let name = path.split("::").next().map(str::to_string);
+                    (path, None, name)
+                }
+                None => (relative, None, None),
+            }
+        }
+    };
+    let signature = item_header(&lines, node).map(|header| header.signature);
+
+    let output = json!({
+        "path": path,
+        "crate_path": crate_path,
+        "crate": crate_name,
+        "name": node.name,
+        "kind": item_kind_name(node.kind, signature.as_deref().unwrap_or("")),
+        "file": target_uri,
+        "range": node.range_json()
+    });
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&output)?)],
+    })
+}
+
+/// Returns the crate a file belongs to and the `crate::...` path of its module.
+/// Files outside the workspace crates get no crate and an empty prefix, so their
+/// symbol paths are relative to the file.
+fn module_prefix(roots: &[CrateRoot], file: &Path) -> (Option<String>, String) {
+    let Some(root) = crate_root_for_file(roots, file) else {
+        return (None, String::new());
+    };
+    let Some(segments) = module_path_for_file(&root.src_path, file) else {
+        return (None, String::new());
+    };
+
+    let prefix = std::iter::once("crate".to_string())
+        .chain(segments)
+        .collect::<Vec<_>>()
+        .join("::");
+    (Some(root.name.clone()), prefix)
+}
+
+async fn handle_format(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
+    let file_path = ToolParams::extract_file_path(&args)?;
+
+    let uri = server.open_document_if_needed(&file_path).await?;
+
+    let Some(client) = &mut server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+
+    let result = client.formatting(&uri).await?;
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&result)?)],
+    })
+}
+
+async fn handle_code_actions(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    let file_path = ToolParams::extract_file_path(&args)?;
+    let (line, character, end_line, end_character) = ToolParams::extract_range(&args)?;
+
+    let uri = server.open_document_if_needed(&file_path).await?;
+
+    let Some(client) = &mut server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+
+    let result = client
+        .code_actions(&uri, line, character, end_line, end_character)
+        .await?;
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&result)?)],
+    })
+}
+
+async fn handle_preview_edit(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    let edit: WorkspaceEdit = match &args["edit"] {
+        Value::String(text) => serde_json::from_str(text),
+        Value::Object(_) => serde_json::from_value(args["edit"].clone()),
+        _ => return Err(ToolError::missing("edit").into()),
+    }
+    .map_err(|e| ToolError::invalid("edit", format!("Invalid WorkspaceEdit: {}", e)))?;
+    let context = args["context_lines"].as_u64().unwrap_or(3) as usize;
+
+    let plan = EditPlan::new(&edit).await?;
+    let output = edit_summary(&server.workspace_root, &plan, context);
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&output)?)],
+    })
+}
+
+async fn handle_rename(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
+    let file_path = ToolParams::extract_file_path(&args)?;
+    let (line, character) = ToolParams::extract_position(&args)?;
+    let Some(new_name) = args["new_name"].as_str() else {
+        return Err(ToolError::missing("new_name").into());
+    };
+
+    let uri = server.open_document_if_needed(&file_path).await?;
+
+    let Some(client) = &mut server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+
+    let Some(edit) = client.rename(&uri, line, character, new_name).await? else {
+        return Err(anyhow!(
+            "Nothing to rename at {}:{}:{}",
+            file_path,
+            line,
+            character
+        ));
+    };
+
+    // Group the edits by file, each with the text it replaces, so every
+    // affected location can be checked before the edit is applied.
+    let mut files: Vec<(String, Vec<Value>)> = Vec::new();
+    for (uri, edits) in edit.text_edits() {
+        let path = path_from_uri(uri);
+        let content = match &path {
+            Some(path) => tokio::fs::read_to_string(path).await.unwrap_or_default(),
+            None => String::new(),
+        };
+        let file = match &path {
+            Some(path) => path
+                .strip_prefix(&server.workspace_root)
+                .unwrap_or(path)
+                .display()
+                .to_string(),
+            None => uri.to_string(),
+        };
+        let entries = edits.iter().map(|edit| {
+            let start = offset_at(&content, edit.range.start);
+            let end = offset_at(&content, edit.range.end);
+            let old_text = match (start, end) {
+                (Some(start), Some(end)) if start <= end => &content[start..end],
+                _ => "",
+            };
+            json!({
+                "range": edit.range,
+                "old_text": old_text,
+                "new_text": edit.new_text
+            })
+        });
+        match files.iter_mut().find(|(existing, _)| *existing == file) {
+            Some((_, grouped)) => grouped.extend(entries),
+            None => files.push((file, entries.collect())),
+        }
+    }
+    let file_operations: Vec<&Value> = edit
+        .document_changes
+        .iter()
+        .flatten()
+        .filter_map(|change| match change {
+            DocumentChange::Operation(operation) => Some(operation),
+            DocumentChange::Edit(_) => None,
+        })
+        .collect();
+
+    let total: usize = files.iter().map(|(_, edits)| edits.len()).sum();
+    let output = json!({
+        "new_name": new_name,
+        "files": files
+            .iter()
+            .map(|(file, edits)| json!({ "file": file, "count": edits.len(), "edits": edits }))
+            .collect::<Vec<_>>(),
+        "file_operations": file_operations,
+        "summary": { "files": files.len(), "edits": total },
+        "workspace_edit": edit
+    });
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&output)?)],
+    })
+}
+
+async fn handle_prepare_rename(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    let file_path = ToolParams::extract_file_path(&args)?;
+    let (line, character) = ToolParams::extract_position(&args)?;
+
+    let uri = server.open_document_if_needed(&file_path).await?;
+    let content = tokio::fs::read_to_string(server.resolve_file_path(&file_path)).await?;
+
+    let Some(client) = &mut server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+
+    // rust-analyzer refuses positions it cannot rename with an error naming
+    // why; only errors from it being busy fail the call.
+    let prepared = match client.prepare_rename(&uri, line, character).await {
+        Ok(prepared) => prepared,
+        Err(e) => match e.downcast_ref::<LspError>() {
+            Some(error) if !error.is_transient() => {
+                let output = json!({
+                    "renameable": false,
+                    "reason": error.message
+                });
+                return Ok(ToolResult {
+                    content: vec![ContentItem::text(serde_json::to_string_pretty(&output)?)],
+                });
+            }
+            _ => return Err(e),
+        },
+    };
+
+    let text_at = |range: &Range| {
+        let start = offset_at(&content, range.start)?;
+        let end = offset_at(&content, range.end)?;
+        content.get(start..end).map(str::to_string)
+    };
+    let (range, placeholder) = match prepared {
+        Some(PrepareRename::RangeWithPlaceholder { range, placeholder }) => {
+            (Some(range), Some(placeholder))
+        }
+        Some(PrepareRename::Range(range)) => (Some(range), text_at(&range)),
+        Some(PrepareRename::DefaultBehavior { .. }) => {
+            let word = content
+                .lines()
+                .nth(line as usize)
+                .and_then(|text| word_at(text, character));
+            (None, word)
+        }
+        None => (None, None),
+    };
+    let renameable = range.is_some() || placeholder.is_some();
+
+    let output = json!({
+        "renameable": renameable,
+        "range": range,
+        "placeholder": placeholder,
+        "reason": (!renameable).then_some("Nothing to rename at this position")
+    });
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&output)?)],
+    })
+}
+
+async fn handle_rename_file(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
+    let Some(old_path) = args["old_path"].as_str() else {
+        return Err(ToolError::missing("old_path").into());
+    };
+    let Some(new_path) = args["new_path"].as_str() else {
+        return Err(ToolError::missing("new_path").into());
+    };
+    let context = args["context_lines"].as_u64().unwrap_or(3) as usize;
+
+    let old_path = server.resolve_file_path(old_path);
+    let new_path = server.resolve_file_path(new_path);
+    for path in [&old_path, &new_path] {
+        if path.extension().and_then(|ext| ext.to_str()) != Some("rs") {
+            return Err(anyhow!("{} is not a .rs file", path.display()));
+        }
+        if !path.starts_with(&server.workspace_root) {
+            return Err(anyhow!("{} is outside the workspace", path.display()));
+        }
+    }
+    if !old_path.is_file() {
+        return Err(anyhow!("{} does not exist", old_path.display()));
+    }
+    let old_uri = format!("file://{}", old_path.display());
+    let new_uri = format!("file://{}", new_path.display());
+
+    let Some(client) = &mut server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+
+    // Fix up references while they still point at the old module, then move
+    // the file itself.
+    let edit = client
+        .will_rename_file(&old_uri, &new_uri)
+        .await?
+        .unwrap_or_default();
+    let mut plan = EditPlan::new(&edit).await?;
+    plan.rename_file(&old_path, &new_path).await?;
+    merge_pre_save_edits(client, &mut plan).await?;
+    plan.apply().await?;
+    client.did_rename_file(&old_uri, &new_uri).await?;
+    client.refresh_open_documents().await?;
+
+    let output = edit_summary(&server.workspace_root, &plan, context);
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&output)?)],
+    })
+}
+
+async fn handle_rename_module(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    let file_path = ToolParams::extract_file_path(&args)?;
+    let Some(new_name) = args["new_name"].as_str() else {
+        return Err(ToolError::missing("new_name").into());
+    };
+    if !is_module_name(new_name) {
+        return Err(anyhow!("{} is not a valid module name", new_name));
+    }
+    let context = args["context_lines"].as_u64().unwrap_or(3) as usize;
+
+    let uri = server.open_document_if_needed(&file_path).await?;
+    let Some(client) = &mut server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+    // Crate roots have no `mod` declaration; their parent is Cargo.toml.
+    let Some(declaration) = client
+        .parent_module(&uri)
+        .await?
+        .into_iter()
+        .find(|location| location.uri.ends_with(".rs"))
+    else {
+        return Err(anyhow!(
+            "No `mod` declaration found for {}; crate roots cannot be renamed",
+            file_path
+        ));
+    };
+
+    // Renaming the module at its declaration moves the file and rewrites the
+    // paths that use it.
+    let declaration_uri = server.open_document_if_needed(&declaration.uri).await?;
+    let Some(client) = &mut server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+    let start = declaration.range.start;
+    let Some(edit) = client
+        .rename(&declaration_uri, start.line, start.character, new_name)
+        .await?
+    else {
+        return Err(anyhow!(
+            "rust-analyzer could not rename the module in {}",
+            file_path
+        ));
+    };
+    let mut plan = EditPlan::new(&edit).await?;
+    merge_pre_save_edits(client, &mut plan).await?;
+    plan.apply().await?;
+    for file in plan.files() {
+        if file.operation() == FileOperation::Rename {
+            let old_uri = format!("file://{}", file.original_path.display());
+            let new_uri = format!("file://{}", file.path.display());
+            client.did_rename_file(&old_uri, &new_uri).await?;
+        }
+    }
+    client.refresh_open_documents().await?;
+
+    let output = edit_summary(&server.workspace_root, &plan, context);
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&output)?)],
+    })
+}
+
+/// Asks rust-analyzer, through `willSaveWaitUntil`, for the edits it wants
+/// in each file the plan modifies in place, as an editor does before saving,
+/// and adds them to the plan. Each file is synced with its planned content
+/// first, since the returned edits are relative to that.
+async fn merge_pre_save_edits(client: &mut RustAnalyzerClient, plan: &mut EditPlan) -> Result<()> {
+    if !client.wants_will_save_wait_until() {
+        return Ok(());
+    }
+    let modified: Vec<(String, String)> = plan
+        .files()
+        .filter(|file| file.operation() == FileOperation::Modify)
+        .filter_map(|file| Some((uri_from_path(&file.path), file.content.clone()?)))
+        .collect();
+    for (uri, content) in modified {
+        client.open_document(&uri, &content).await?;
+        let edits = client.will_save_wait_until(&uri).await?;
+        if !edits.is_empty() {
+            plan.edit(&uri, &edits).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether `name` is a plain Rust identifier, usable as a module name.
+fn is_module_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) if first.is_alphabetic() || first == '_' => {}
+        _ => return false,
+    }
+    name != "_" && chars.all(|ch| ch.is_alphanumeric() || ch == '_')
+}
+
+/// Per-file diffs of an edit plan with paths relative to `root`, and totals.
+fn edit_summary(root: &Path, plan: &EditPlan, context: usize) -> Value {
+    let relative = |path: &Path| {
+        path.strip_prefix(root)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    };
+    let mut files = Vec::new();
+    let (mut insertions, mut deletions) = (0, 0);
+    for file in plan.files() {
+        let diff = file.diff(root, context);
+        insertions += diff.insertions;
+        deletions += diff.deletions;
+        let operation = file.operation();
+        let mut entry = json!({
+            "path": relative(&file.path),
+            "operation": operation.name(),
+            "insertions": diff.insertions,
+            "deletions": diff.deletions,
+            "diff": diff.text
+        });
+        if operation == FileOperation::Rename {
+            entry["old_path"] = json!(relative(&file.original_path));
+        }
+        files.push(entry);
+    }
+
+    json!({
+        "files": files,
+        "summary": {
+            "files": files.len(),
+            "insertions": insertions,
+            "deletions": deletions
+        }
+    })
+}
+
+async fn handle_set_workspace(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    let Some(workspace_path) = args["workspace_path"].as_str() else {
+        return Err(ToolError::missing("workspace_path").into());
+    };
+
+    // Shutdown existing client.
+    if let Some(client) = &mut server.client {
+        client.shutdown().await?;
+    }
+    server.client = None;
+    server.session.diagnostics_snapshots.clear();
+    server.rust_src = None;
+
+    // Set new workspace with proper absolute path handling.
+    let workspace_root = PathBuf::from(workspace_path);
+    server.workspace_root = workspace_root.canonicalize().unwrap_or_else(|_| {
+        if workspace_root.is_absolute() {
+            workspace_root.clone()
+        } else {
+            std::env::current_dir()
+                .unwrap_or_else(|_| PathBuf::from("."))
+                .join(&workspace_root)
+        }
+    });
+
+    // Start the new client automatically.
+    server.ensure_client_started().await?;
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(format!(
+            "Workspace set to: {}",
+            server.workspace_root.display()
+        ))],
+    })
+}
+
+async fn handle_diagnostics(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
+    let file_path = ToolParams::extract_file_path(&args)?;
+
+    let uri = server.open_document_if_needed(&file_path).await?;
+
+    // Poll for diagnostics - rust-analyzer needs time to run cargo check.
+    // For files with expected errors (like diagnostics_test.rs), poll longer.
+    let should_poll = file_path.contains("diagnostics_test") || file_path.contains("simple_error");
+
+    let Some(client) = &mut server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+
+    let mut result = Vec::new();
+    if should_poll {
+        let start = std::time::Instant::now();
+        let timeout = tokio::time::Duration::from_secs(8); // Less than test timeout.
+        let poll_interval = tokio::time::Duration::from_millis(500);
+
+        while start.elapsed() < timeout {
+            result = client.diagnostics(&uri).await?;
+            if !result.is_empty() {
+                // We got diagnostics, stop polling.
+                break;
+            }
+            tokio::time::sleep(poll_interval).await;
+        }
+    } else {
+        // For clean files, just wait a bit and check once.
+        tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+        result = client.diagnostics(&uri).await?;
+    }
+
+    let blocked = client.readiness().await.blocked;
+    let mut diagnostics = format_diagnostics(&file_path, &result);
+    let mut shown = BTreeMap::from([(uri.clone(), result)]);
+    if let Some(blocked) = &blocked {
+        diagnostics["check_status"] = json!(blocked);
+    }
+    let mut omitted = 0;
+    if let Some(max) = ToolParams::extract_max_results(&args)? {
+        (shown, omitted) = most_severe_diagnostics(&shown, max);
+        let kept = shown.get(&uri).map(Vec::as_slice).unwrap_or_default();
+        diagnostics["diagnostics"] = format_diagnostics(&file_path, kept)["diagnostics"].take();
+        diagnostics["summary"]["omitted"] = json!(omitted);
+    }
+
+    let text = if ToolParams::extract_text_render(&args)? {
+        diagnostics_text(&server.workspace_root, &shown, omitted, blocked.as_deref())
+    } else {
+        serde_json::to_string_pretty(&diagnostics)?
+    };
+    let mut content = vec![ContentItem::text(text)];
+    content.extend(resource_links(server, [uri.as_str()]));
+    Ok(ToolResult { content })
+}
+
+async fn handle_inactive_code(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    let file_path = ToolParams::extract_file_path(&args)?;
+    let uri = server.open_document_if_needed(&file_path).await?;
+
+    let Some(client) = &mut server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+
+    // Inactive code is reported by rust-analyzer itself rather than by
+    // cargo check, so it is known as soon as the file is analyzed.
+    let result = client.diagnostics(&uri).await?;
+    let report = inactive_code_report(&file_path, &result);
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&report)?)],
+    })
+}
+
+async fn handle_cfg_at(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
+    let file_path = ToolParams::extract_file_path(&args)?;
+    let (line, character) = ToolParams::extract_position(&args)?;
+
+    let uri = server.open_document_if_needed(&file_path).await?;
+    let content = tokio::fs::read_to_string(server.resolve_file_path(&file_path)).await?;
+
+    let Some(client) = &mut server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+
+    let tree = build_symbol_tree(&client.document_symbols(&uri).await?);
+    let diagnostics = client.diagnostics(&uri).await?;
+    let lines: Vec<&str> = content.lines().collect();
+    let items: Vec<Value> = enclosing_symbols(&tree, (line, character))
+        .into_iter()
+        .map(|node| {
+            let start = Position {
+                line: node.start.0,
+                character: node.start.1,
+            };
+            json!({
+                "name": node.name,
+                "range": node.range_json(),
+                "cfgs": cfg_attributes(&lines, node.start.0 as usize),
+                "active": inactive_reason_at(&diagnostics, start).is_none()
+            })
+        })
+        .collect();
+    let file_cfgs: Vec<String> = cfg_attributes(&lines, 0)
+        .into_iter()
+        .filter(|attribute| attribute.starts_with("#!["))
+        .collect();
+    let inactive_reason = inactive_reason_at(&diagnostics, Position { line, character });
+
+    let output = json!({
+        "file": file_path,
+        "position": { "line": line, "character": character },
+        "active": inactive_reason.is_none(),
+        "inactive_reason": inactive_reason,
+        "file_cfgs": file_cfgs,
+        "items": items
+    });
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&output)?)],
+    })
+}
+
+async fn handle_workspace_diagnostics(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    let package = package_filter(&server.workspace_root, &args).await?;
+    let max_results = ToolParams::extract_max_results(&args)?;
+
+    let Some(client) = &mut server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+
+    let mut result = client.workspace_diagnostics(&server.cancellation).await?;
+    let blocked = client.readiness().await.blocked;
+    if let Some((members, package)) = &package {
+        result.retain(|uri, _| in_package(members, package, uri));
+    }
+
+    // Format workspace diagnostics; the summary counts all of them.
+    let mut formatted = format_workspace_diagnostics(&server.workspace_root, &result);
+    let mut omitted = 0;
+    if let Some(max) = max_results {
+        let limited;
+        (limited, omitted) = most_severe_diagnostics(&result, max);
+        let summary = formatted["summary"].take();
+        formatted = format_workspace_diagnostics(&server.workspace_root, &limited);
+        formatted["summary"] = summary;
+        formatted["summary"]["omitted"] = json!(omitted);
+        result = limited;
+    }
+    if let Some(blocked) = &blocked {
+        formatted["check_status"] = json!(blocked);
+    }
+    let listed = result
+        .iter()
+        .filter(|(_, diagnostics)| !diagnostics.is_empty())
+        .map(|(uri, _)| uri.as_str());
+
+    let text = if ToolParams::extract_text_render(&args)? {
+        diagnostics_text(&server.workspace_root, &result, omitted, blocked.as_deref())
+    } else {
+        serde_json::to_string_pretty(&formatted)?
+    };
+    let mut content = vec![ContentItem::text(text)];
+    content.extend(resource_links(server, listed));
+    Ok(ToolResult { content })
+}
+
+async fn handle_run_check(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
+    let package = package_filter(&server.workspace_root, &args).await?;
+
+    let Some(client) = &mut server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+
+    let mut result = client.run_check(&server.cancellation).await?;
+    if let Some((members, package)) = &package {
+        result.retain(|uri, _| in_package(members, package, uri));
+    }
+
+    let text = if ToolParams::extract_text_render(&args)? {
+        diagnostics_text(&server.workspace_root, &result, 0, None)
+    } else {
+        let formatted = format_workspace_diagnostics(&server.workspace_root, &result);
+        serde_json::to_string_pretty(&formatted)?
+    };
+    Ok(ToolResult {
+        content: vec![ContentItem::text(text)],
+    })
+}
+
+const DEFAULT_SNAPSHOT_NAME: &str = "default";
+
+async fn handle_diagnostics_snapshot(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    let name = args["name"]
+        .as_str()
+        .unwrap_or(DEFAULT_SNAPSHOT_NAME)
+        .to_string();
+
+    let snapshot = capture_diagnostics_snapshot(server).await?;
+    let output = json!({
+        "snapshot": name,
+        "summary": {
+            "total_files": snapshot.file_count(),
+            "total_diagnostics": snapshot.len()
+        }
+    });
+    server.session.diagnostics_snapshots.insert(name, snapshot);
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&output)?)],
+    })
+}
+
+async fn handle_diagnostics_diff(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    let name = args["name"].as_str().unwrap_or(DEFAULT_SNAPSHOT_NAME);
+    if !server.session.diagnostics_snapshots.contains_key(name) {
+        return Err(anyhow!(
+            "No diagnostics snapshot named '{}'; call rust_analyzer_diagnostics_snapshot first",
+            name
+        ));
+    }
+
+    let current = capture_diagnostics_snapshot(server).await?;
+    let mut output = server.session.diagnostics_snapshots[name].diff(&current);
+    output["snapshot"] = json!(name);
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&output)?)],
+    })
+}
+
+async fn capture_diagnostics_snapshot(
+    server: &mut RustAnalyzerMCPServer,
+) -> Result<DiagnosticsSnapshot> {
+    let Some(client) = &mut server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+
+    // Pick up edits made on disk since the documents were opened.
+    client.refresh_open_documents().await?;
+    let result = client.workspace_diagnostics(&server.cancellation).await?;
+
+    Ok(DiagnosticsSnapshot::from_workspace(&result))
+}
+
+async fn handle_cargo_check(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
+    if let Some(target) = args["target"].as_str() {
+        return check_for_target(server, &args, target).await;
+    }
+
+    let mut cargo_args = vec!["check".to_string()];
+    if let Some(package) = args["package"].as_str() {
+        cargo_args.push("--package".to_string());
+        cargo_args.push(package.to_string());
+    }
+    if args["all_targets"].as_bool().unwrap_or(true) {
+        cargo_args.push("--all-targets".to_string());
+    }
+
+    let run = run_cargo_json(&server.workspace_root, &cargo_args).await?;
+    let output = format_cargo_run(&cargo_args, run, false);
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&output)?)],
+    })
+}
+
+/// Runs rust-analyzer's check with its `cargo.target` set to `target`, so
+/// the `cfg` branches of that platform are analyzed, then restores the
+/// settings the client started with.
+async fn check_for_target(
+    server: &mut RustAnalyzerMCPServer,
+    args: &Value,
+    target: &str,
+) -> Result<ToolResult> {
+    let package = package_filter(&server.workspace_root, args).await?;
+    let settings = json!({
+        "cargo": { "target": target },
+        "checkOnSave": { "allTargets": args["all_targets"].as_bool().unwrap_or(true) }
+    });
+
+    let Some(client) = &mut server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+    let checked = match client.reconfigure(&settings, &server.cancellation).await {
+        Ok(()) => client.run_check(&server.cancellation).await,
+        Err(e) => Err(e),
+    };
+    // Restoring is not cancelled along with the check.
+    client
+        .reconfigure(&json!({}), &CancellationToken::new())
+        .await?;
+    let mut result = checked?;
+    if let Some((members, package)) = &package {
+        result.retain(|uri, _| in_package(members, package, uri));
+    }
+
+    let mut output = format_workspace_diagnostics(&server.workspace_root, &result);
+    output["target"] = json!(target);
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&output)?)],
+    })
+}
+
+async fn handle_feature_matrix(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    let Some(feature_sets) = args["feature_sets"].as_array() else {
+        return Err(ToolError::missing("feature_sets").into());
+    };
+    if feature_sets.is_empty() || feature_sets.len() > MAX_FEATURE_SETS {
+        return Err(ToolError::invalid(
+            "feature_sets",
+            format!(
+                "Expected between 1 and {} feature sets, got {}",
+                MAX_FEATURE_SETS,
+                feature_sets.len()
+            ),
+        )
+        .into());
+    }
+    let feature_sets = feature_sets
+        .iter()
+        .map(|set| {
+            set.as_array()
+                .and_then(|features| {
+                    features
+                        .iter()
+                        .map(|feature| feature.as_str().map(str::to_string))
+                        .collect::<Option<Vec<String>>>()
+                })
+                .ok_or_else(|| {
+                    ToolError::invalid(
+                        "feature_sets",
+                        "Each feature set must be an array of feature names",
+                    )
+                    .into()
+                })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let no_default_features = args["no_default_features"].as_bool().unwrap_or(false);
+    let package = package_filter(&server.workspace_root, &args).await?;
+
+    let mut settings = json!({
+        "cargo": { "noDefaultFeatures": no_default_features },
+        "checkOnSave": { "allTargets": args["all_targets"].as_bool().unwrap_or(true) }
+    });
+    if let Some(target) = args["target"].as_str() {
+        settings["cargo"]["target"] = json!(target);
+    }
+
+    if server.client.is_none() {
+        return Err(ToolError::client_not_initialized().into());
+    }
+
+    // rust-analyzer's check runs with the features it loaded the workspace
+    // with, so each set reloads the workspace before checking it.
+    let mut runs = Vec::new();
+    let mut summaries = Vec::new();
+    let mut outcome = Ok(());
+    for (index, features) in feature_sets.iter().enumerate() {
+        let label = match (features.is_empty(), no_default_features) {
+            (true, false) => "default".to_string(),
+            (true, true) => "none".to_string(),
+            (false, _) => features.join(","),
+        };
+        settings["cargo"]["features"] = json!(features);
+
+        server.report_progress(
+            index,
+            Some(feature_sets.len()),
+            &format!("Checking features: {}", label),
+        );
+        let Some(client) = &mut server.client else {
+            break;
+        };
+        let checked = match client.reconfigure(&settings, &server.cancellation).await {
+            Ok(()) => client.run_check(&server.cancellation).await,
+            Err(e) => Err(e),
+        };
+        let mut result = match checked {
+            Ok(result) => result,
+            Err(e) => {
+                outcome = Err(e);
+                break;
+            }
+        };
+        if let Some((members, package)) = &package {
+            result.retain(|uri, _| in_package(members, package, uri));
+        }
+
+        let count = |severity| {
+            result
+                .values()
+                .flatten()
+                .filter(|diagnostic| diagnostic.severity == Some(severity))
+                .count()
+        };
+        summaries.push(json!({
+            "features": label,
+            "settings": settings["cargo"],
+            "errors": count(1),
+            "warnings": count(2)
+        }));
+        runs.push((label, result));
+    }
+
+    // Restoring is not cancelled along with the checks.
+    if let Some(client) = &mut server.client {
+        client
+            .reconfigure(&json!({}), &CancellationToken::new())
+            .await?;
+    }
+    outcome?;
+
+    let mut output = feature_matrix_report(&runs);
+    output["feature_sets"] = json!(summaries);
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&output)?)],
+    })
+}
+
+async fn handle_dead_code(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
+    let mut cargo_args = vec!["check".to_string()];
+    if let Some(package) = args["package"].as_str() {
+        cargo_args.push("--package".to_string());
+        cargo_args.push(package.to_string());
+    }
+    if args["all_targets"].as_bool().unwrap_or(true) {
+        cargo_args.push("--all-targets".to_string());
+    }
+
+    let run = run_cargo_json(&server.workspace_root, &cargo_args).await?;
+    let roots = crate_roots(&cargo_metadata(&server.workspace_root, true).await?);
+
+    let mut output = dead_code_report(&run.messages.diagnostics, &roots);
+    output["command"] = json!(format!("cargo {}", cargo_args.join(" ")));
+    output["success"] = json!(run.success);
+    // Unused-code lints are only reported for crates that compile.
+    if !run.success {
+        output["errors"] = json!(run.messages.errors);
+        if run.messages.errors == 0 {
+            output["stderr"] = json!(run.stderr.trim());
+        }
+    }
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&output)?)],
+    })
+}
+
+async fn handle_cargo_build(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
+    let mut cargo_args = vec!["build".to_string()];
+    if args["release"].as_bool().unwrap_or(false) {
+        cargo_args.push("--release".to_string());
+    }
+    if let Some(package) = args["package"].as_str() {
+        cargo_args.push("--package".to_string());
+        cargo_args.push(package.to_string());
+    }
+    if args["lib"].as_bool().unwrap_or(false) {
+        cargo_args.push("--lib".to_string());
+    }
+    for (arg, flag) in [("bin", "--bin"), ("example", "--example")] {
+        if let Some(name) = args[arg].as_str() {
+            cargo_args.push(flag.to_string());
+            cargo_args.push(name.to_string());
+        }
+    }
+    if args["all_targets"].as_bool().unwrap_or(false) {
+        cargo_args.push("--all-targets".to_string());
+    }
+
+    let run = run_cargo_json(&server.workspace_root, &cargo_args).await?;
+    let output = format_cargo_run(&cargo_args, run, true);
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&output)?)],
+    })
+}
+
+async fn handle_toolchain_info(
+    server: &mut RustAnalyzerMCPServer,
+    _args: Value,
+) -> Result<ToolResult> {
+    let mut info = toolchain_info(&server.workspace_root).await;
+    info["rust_analyzer"] = server
+        .client
+        .as_ref()
+        .and_then(|client| client.server_info())
+        .cloned()
+        .unwrap_or(json!(null));
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&info)?)],
+    })
+}
+
+async fn handle_project_overview(
+    server: &mut RustAnalyzerMCPServer,
+    _args: Value,
+) -> Result<ToolResult> {
+    let metadata = cargo_metadata(&server.workspace_root, true).await?;
+    let overview = project_overview(&metadata);
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&overview)?)],
+    })
+}
+
+async fn handle_list_workspace_members(
+    server: &mut RustAnalyzerMCPServer,
+    _args: Value,
+) -> Result<ToolResult> {
+    let metadata = cargo_metadata(&server.workspace_root, true).await?;
+    let members = workspace_members(&metadata);
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&members)?)],
+    })
+}
+
+async fn handle_cargo_dependencies(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    let package_filter = args["package"].as_str();
+    let metadata = cargo_metadata(&server.workspace_root, true).await?;
+    let workspace_root = Path::new(metadata["workspace_root"].as_str().unwrap_or_default());
+
+    let mut members = Vec::new();
+    for package in metadata["packages"].as_array().into_iter().flatten() {
+        if package_filter.is_some_and(|filter| package["name"].as_str() != Some(filter)) {
+            continue;
+        }
+        let manifest_path = package["manifest_path"].as_str().unwrap_or_default();
+        let manifest = tokio::fs::read_to_string(manifest_path)
+            .await
+            .map_err(|e| anyhow!("Failed to read {}: {}", manifest_path, e))?;
+        members.push(member_dependencies(package, workspace_root, &manifest));
+    }
+    if let (Some(package), true) = (package_filter, members.is_empty()) {
+        return Err(anyhow!("No workspace member named '{}'", package));
+    }
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(
+            &json!({ "members": members }),
+        )?)],
+    })
+}
+
+async fn handle_status(server: &mut RustAnalyzerMCPServer, _args: Value) -> Result<ToolResult> {
+    let status = json!({
+        "workspace": server.workspace_root.display().to_string(),
+        "rust_analyzer": {
+            "running": server.client.is_some(),
+            "server_info": server.client.as_ref().and_then(|client| client.server_info()),
+            "readiness": server.readiness().await
+        },
+        "rust_src": server.rust_src
+    });
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&status)?)],
+    })
+}
+
+async fn handle_open_documents(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    let mut targets: Vec<String> = args["close"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .map(|path| format!("file://{}", server.resolve_file_path(path).display()))
+        .collect();
+    let idle_limit = args["close_idle_secs"].as_u64().map(Duration::from_secs);
+
+    let Some(client) = &mut server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
+
+    if let Some(limit) = idle_limit {
+        for document in client.open_documents().await {
+            if document.idle >= limit {
+                targets.push(document.uri);
+            }
+        }
+    }
+    let (mut closed, mut not_open) = (Vec::new(), Vec::new());
+    for uri in targets {
+        if closed.contains(&uri) {
+            continue;
+        }
+        if client.is_document_open(&uri).await {
+            client.close_document(&uri).await?;
+            closed.push(uri);
+        } else {
+            not_open.push(uri);
+        }
+    }
+
+    let documents = client.open_documents().await;
+    let total_bytes: usize = documents.iter().map(|document| document.size).sum();
+    let documents: Vec<Value> = documents
+        .iter()
+        .map(|document| {
+            let file = path_from_uri(&document.uri).map(|path| {
+                path.strip_prefix(&server.workspace_root)
+                    .unwrap_or(&path)
+                    .display()
+                    .to_string()
+            });
+            json!({
+                "uri": document.uri,
+                "file": file,
+                "version": document.version,
+                "size_bytes": document.size,
+                "read_only": document.read_only,
+                "idle_secs": document.idle.as_secs()
+            })
+        })
+        .collect();
+
+    let output = json!({
+        "count": documents.len(),
+        "total_bytes": total_bytes,
+        "documents": documents,
+        "closed": closed,
+        "not_open": not_open
+    });
 
-    fn extract_range(args: &Value) -> Result<(u32, u32, u32, u32)> {
-        let (line, character) = Self::extract_position(args)?;
-        let Some(end_line) = args["end_line"].as_u64() else {
-            return Err(anyhow!("Missing end_line"));
-        };
-        let Some(end_character) = args["end_character"].as_u64() else {
-            return Err(anyhow!("Missing end_character"));
-        };
-        Ok((line, character, end_line as u32, end_character as u32))
-    }
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&output)?)],
+    })
 }
 
-pub async fn handle_tool_call(
+async fn handle_server_stats(
     server: &mut RustAnalyzerMCPServer,
-    tool_name: &str,
-    args: Value,
+    _args: Value,
 ) -> Result<ToolResult> {
-    server.ensure_client_started().await?;
+    let stats = server.metrics.snapshot();
 
-    match tool_name {
-        "rust_analyzer_hover" => handle_hover(server, args).await,
-        "rust_analyzer_definition" => handle_definition(server, args).await,
-        "rust_analyzer_references" => handle_references(server, args).await,
-        "rust_analyzer_completion" => handle_completion(server, args).await,
-        "rust_analyzer_symbols" => handle_symbols(server, args).await,
-        "rust_analyzer_format" => handle_format(server, args).await,
-        "rust_analyzer_code_actions" => handle_code_actions(server, args).await,
-        "rust_analyzer_set_workspace" => handle_set_workspace(server, args).await,
-        "rust_analyzer_diagnostics" => handle_diagnostics(server, args).await,
-        "rust_analyzer_workspace_diagnostics" => handle_workspace_diagnostics(server, args).await,
-        _ => Err(anyhow!("Unknown tool: {}", tool_name)),
-    }
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&stats)?)],
+    })
 }
 
-async fn handle_hover(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
+async fn handle_benchmark(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
     let file_path = ToolParams::extract_file_path(&args)?;
-    let (line, character) = ToolParams::extract_position(&args)?;
+    let iterations = match args.get("iterations") {
+        None => DEFAULT_BENCHMARK_ITERATIONS,
+        Some(value) => match value.as_u64() {
+            Some(n) if (1..=MAX_BENCHMARK_ITERATIONS).contains(&n) => n,
+            _ => {
+                return Err(ToolError::invalid(
+                    "iterations",
+                    format!(
+                        "iterations must be a number from 1 to {}",
+                        MAX_BENCHMARK_ITERATIONS
+                    ),
+                )
+                .into())
+            }
+        },
+    };
 
     let uri = server.open_document_if_needed(&file_path).await?;
-
+    let progress = server.progress.clone();
     let Some(client) = &mut server.client else {
-        return Err(anyhow!("Client not initialized"));
+        return Err(ToolError::client_not_initialized().into());
+    };
+
+    // Without a position, query the file's first symbol so that hover and
+    // navigation have something to resolve.
+    let (line, character) = match ToolParams::extract_position(&args) {
+        Ok(position) => position,
+        Err(_) => first_symbol_position(&client.document_symbols(&uri).await?)
+            .ok_or_else(|| anyhow!("No symbols in {}; pass line and character", file_path))?,
     };
 
-    let result = client.hover(&uri, line, character).await?;
+    let mut samples: BTreeMap<&str, (Vec<Duration>, usize)> = BTreeMap::new();
+    let mut record = |operation, started: Instant, ok: bool| {
+        let (durations, errors) = samples.entry(operation).or_default();
+        durations.push(started.elapsed());
+        if !ok {
+            *errors += 1;
+        }
+    };
+    for round in 0..iterations {
+        // Cached answers would time the cache rather than rust-analyzer.
+        client.clear_cached_queries().await;
+        let started = Instant::now();
+        let ok = client.hover(&uri, line, character).await.is_ok();
+        record("hover", started, ok);
+        let started = Instant::now();
+        let ok = client.definition(&uri, line, character).await.is_ok();
+        record("definition", started, ok);
+        let started = Instant::now();
+        let ok = client.references(&uri, line, character).await.is_ok();
+        record("references", started, ok);
+        let started = Instant::now();
+        let ok = client.diagnostics(&uri).await.is_ok();
+        record("diagnostics", started, ok);
+
+        if let Some(progress) = &progress {
+            progress.report(
+                round as usize + 1,
+                Some(iterations as usize),
+                "Benchmarking",
+            );
+        }
+    }
+
+    let operations: serde_json::Map<String, Value> = samples
+        .into_iter()
+        .map(|(operation, (durations, errors))| {
+            let stats = json!({ "errors": errors, "latency_ms": latency_summary(&durations) });
+            (operation.to_string(), stats)
+        })
+        .collect();
+    let report = json!({
+        "file_path": file_path,
+        "position": { "line": line, "character": character },
+        "iterations": iterations,
+        "rust_analyzer": client.server_info(),
+        "operations": operations
+    });
 
     Ok(ToolResult {
-        content: vec![ContentItem {
-            content_type: "text".to_string(),
-            text: serde_json::to_string_pretty(&result)?,
-        }],
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&report)?)],
     })
 }
 
-async fn handle_definition(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
-    let file_path = ToolParams::extract_file_path(&args)?;
-    let (line, character) = ToolParams::extract_position(&args)?;
+/// Where the first symbol of a `documentSymbol` response is named, for nested
+/// document symbols and flat symbol information alike.
+fn first_symbol_position(symbols: &Value) -> Option<(u32, u32)> {
+    let symbol = symbols.as_array()?.first()?;
+    let start = if symbol["selectionRange"].is_object() {
+        &symbol["selectionRange"]["start"]
+    } else {
+        &symbol["location"]["range"]["start"]
+    };
+    Some((
+        start["line"].as_u64()? as u32,
+        start["character"].as_u64()? as u32,
+    ))
+}
 
-    let uri = server.open_document_if_needed(&file_path).await?;
+async fn handle_logs(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
+    let limit = args["lines"].as_u64().unwrap_or(100) as usize;
+    let filter = args["contains"].as_str();
 
-    let Some(client) = &mut server.client else {
-        return Err(anyhow!("Client not initialized"));
+    let Some(client) = &server.client else {
+        return Err(ToolError::client_not_initialized().into());
     };
 
-    let result = client.definition(&uri, line, character).await?;
+    let lines = client.recent_stderr(limit, filter).await;
+    let output = json!({
+        "count": lines.len(),
+        "lines": lines
+    });
 
     Ok(ToolResult {
-        content: vec![ContentItem {
-            content_type: "text".to_string(),
-            text: serde_json::to_string_pretty(&result)?,
-        }],
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&output)?)],
     })
 }
 
-async fn handle_references(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
-    let file_path = ToolParams::extract_file_path(&args)?;
-    let (line, character) = ToolParams::extract_position(&args)?;
+async fn handle_public_api(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
+    let Some(crate_name) = args["crate_name"].as_str() else {
+        return Err(ToolError::missing("crate_name").into());
+    };
 
-    let uri = server.open_document_if_needed(&file_path).await?;
+    let (root_file, crate_ident) = crate_root_file(&server.workspace_root, crate_name).await?;
+
+    let mut items = Vec::new();
+    let mut reexports = Vec::new();
+    let mut files = 0;
+    let mut pending = vec![(root_file, crate_ident.clone())];
+    while let Some((file, module_path)) = pending.pop() {
+        let file_str = file.display().to_string();
+        let content = tokio::fs::read_to_string(&file)
+            .await
+            .map_err(|e| anyhow!("Failed to read {}: {}", file_str, e))?;
+        let uri = server.open_document_if_needed(&file_str).await?;
+        let Some(client) = &mut server.client else {
+            return Err(ToolError::client_not_initialized().into());
+        };
+        let symbols = client.document_symbols(&uri).await?;
 
-    let Some(client) = &mut server.client else {
-        return Err(anyhow!("Client not initialized"));
-    };
+        let lines: Vec<&str> = content.lines().collect();
+        let tree = build_symbol_tree(&symbols);
+        let api = collect_public_items(&tree, &lines, &module_path, &file_str);
+        files += 1;
+
+        for (child_path, _) in api.out_of_line_modules.iter().rev() {
+            match module_file(&file, &module_path, child_path) {
+                Some(child_file) => pending.push((child_file, child_path.clone())),
+                None => debug!("No source file found for module {}", child_path),
+            }
+        }
+        items.extend(api.items);
+        reexports.extend(api.reexports);
+    }
 
-    let result = client.references(&uri, line, character).await?;
+    let output = json!({
+        "crate": crate_ident,
+        "files_scanned": files,
+        "items": items,
+        "reexports": reexports
+    });
 
     Ok(ToolResult {
-        content: vec![ContentItem {
-            content_type: "text".to_string(),
-            text: serde_json::to_string_pretty(&result)?,
-        }],
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&output)?)],
     })
 }
 
-async fn handle_completion(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
+async fn handle_outline(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
     let file_path = ToolParams::extract_file_path(&args)?;
-    let (line, character) = ToolParams::extract_position(&args)?;
 
     let uri = server.open_document_if_needed(&file_path).await?;
+    let content = tokio::fs::read_to_string(server.resolve_file_path(&file_path)).await?;
 
     let Some(client) = &mut server.client else {
-        return Err(anyhow!("Client not initialized"));
+        return Err(ToolError::client_not_initialized().into());
     };
 
-    let result = client.completion(&uri, line, character).await?;
+    let symbols = client.document_symbols(&uri).await?;
+    let lines: Vec<&str> = content.lines().collect();
+    let output = json!({
+        "file": uri,
+        "outline": outline(&build_symbol_tree(&symbols), &lines)
+    });
 
     Ok(ToolResult {
-        content: vec![ContentItem {
-            content_type: "text".to_string(),
-            text: serde_json::to_string_pretty(&result)?,
-        }],
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&output)?)],
     })
 }
 
-async fn handle_symbols(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
+async fn handle_outline_markdown(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
     let file_path = ToolParams::extract_file_path(&args)?;
+    let max_depth = args["max_depth"].as_u64().map(|depth| depth as usize);
+    let token_budget = args["token_budget"].as_u64().unwrap_or(40) as usize;
 
-    debug!("Getting symbols for file: {}", file_path);
     let uri = server.open_document_if_needed(&file_path).await?;
-    debug!("Document opened with URI: {}", uri);
+    let content = tokio::fs::read_to_string(server.resolve_file_path(&file_path)).await?;
 
     let Some(client) = &mut server.client else {
-        return Err(anyhow!("Client not initialized"));
+        return Err(ToolError::client_not_initialized().into());
     };
 
-    let result = client.document_symbols(&uri).await?;
-    debug!("Document symbols result: {:?}", result);
+    let symbols = client.document_symbols(&uri).await?;
+    let lines: Vec<&str> = content.lines().collect();
+    let text = format!(
+        "# {}\n\n{}",
+        file_path,
+        outline_markdown(
+            &build_symbol_tree(&symbols),
+            &lines,
+            max_depth,
+            token_budget
+        )
+    );
 
     Ok(ToolResult {
-        content: vec![ContentItem {
-            content_type: "text".to_string(),
-            text: serde_json::to_string_pretty(&result)?,
-        }],
+        content: vec![ContentItem::text(text)],
     })
 }
 
-async fn handle_format(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
+async fn handle_enclosing_items(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
     let file_path = ToolParams::extract_file_path(&args)?;
+    let (line, character) = ToolParams::extract_position(&args)?;
 
     let uri = server.open_document_if_needed(&file_path).await?;
+    let content = tokio::fs::read_to_string(server.resolve_file_path(&file_path)).await?;
 
     let Some(client) = &mut server.client else {
-        return Err(anyhow!("Client not initialized"));
+        return Err(ToolError::client_not_initialized().into());
     };
 
-    let result = client.formatting(&uri).await?;
+    let tree = build_symbol_tree(&client.document_symbols(&uri).await?);
+    let lines: Vec<&str> = content.lines().collect();
+    let items: Vec<Value> = enclosing_symbols(&tree, (line, character))
+        .into_iter()
+        .map(|node| {
+            let signature = item_header(&lines, node).map(|header| header.signature);
+            json!({
+                "name": node.name,
+                "kind": item_kind_name(node.kind, signature.as_deref().unwrap_or("")),
+                "signature": signature,
+                "range": node.range_json()
+            })
+        })
+        .collect();
+
+    let output = json!({
+        "file": uri,
+        "position": { "line": line, "character": character },
+        "innermost": items.last().cloned(),
+        "items": items
+    });
 
     Ok(ToolResult {
-        content: vec![ContentItem {
-            content_type: "text".to_string(),
-            text: serde_json::to_string_pretty(&result)?,
-        }],
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&output)?)],
     })
 }
 
-async fn handle_code_actions(
+async fn handle_convert_position(
     server: &mut RustAnalyzerMCPServer,
     args: Value,
 ) -> Result<ToolResult> {
     let file_path = ToolParams::extract_file_path(&args)?;
-    let (line, character, end_line, end_character) = ToolParams::extract_range(&args)?;
+    let number = |name: &str| args[name].as_u64().map(|value| value as u32);
+    let input = match (args["offset"].as_u64(), number("line")) {
+        (Some(offset), None) => PositionInput::Offset(offset as usize),
+        (None, Some(line)) => match (number("utf8_column"), number("character")) {
+            (Some(_), Some(_)) => {
+                return Err(anyhow!("Give either utf8_column or character, not both"))
+            }
+            (Some(column), None) => PositionInput::Utf8Column { line, column },
+            (None, character) => PositionInput::Utf16Column {
+                line,
+                character: character.unwrap_or(0),
+            },
+        },
+        (Some(_), Some(_)) => return Err(anyhow!("Give either offset or line, not both")),
+        (None, None) => return Err(ToolError::missing("offset or line").into()),
+    };
 
-    let uri = server.open_document_if_needed(&file_path).await?;
+    let content = tokio::fs::read_to_string(server.resolve_file_path(&file_path)).await?;
+    let position = convert_position(&content, input)?;
+
+    let output = json!({
+        "file": file_path,
+        "offset": position.offset,
+        "line": position.line,
+        "utf8_column": position.utf8_column,
+        "character": position.utf16_column,
+        "line_text": position.line_text,
+        "line_count": content.lines().count()
+    });
+
+    Ok(ToolResult {
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&output)?)],
+    })
+}
+
+async fn handle_impls(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
+    let (uri, line, character) = resolve_item_position(
+        server,
+        &args,
+        "type_name",
+        &[SymbolKind::Struct, SymbolKind::Enum, SymbolKind::TypeAlias],
+    )
+    .await?;
 
     let Some(client) = &mut server.client else {
-        return Err(anyhow!("Client not initialized"));
+        return Err(ToolError::client_not_initialized().into());
     };
 
-    let result = client
-        .code_actions(&uri, line, character, end_line, end_character)
-        .await?;
+    let mut inherent = Vec::new();
+    let mut trait_impls = Vec::new();
+    for (impl_uri, block) in implementation_blocks(client, &uri, line, character).await? {
+        if block.trait_name.is_some() {
+            trait_impls.push(block.to_json(&impl_uri));
+        } else {
+            inherent.push(block.to_json(&impl_uri));
+        }
+    }
+
+    let output = json!({
+        "type": { "file": uri, "line": line, "character": character },
+        "inherent_impls": inherent,
+        "trait_impls": trait_impls
+    });
 
     Ok(ToolResult {
-        content: vec![ContentItem {
-            content_type: "text".to_string(),
-            text: serde_json::to_string_pretty(&result)?,
-        }],
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&output)?)],
     })
 }
 
-async fn handle_set_workspace(
+async fn handle_implementors(
     server: &mut RustAnalyzerMCPServer,
     args: Value,
 ) -> Result<ToolResult> {
-    let Some(workspace_path) = args["workspace_path"].as_str() else {
-        return Err(anyhow!("Missing workspace_path"));
+    let (uri, line, character) =
+        resolve_item_position(server, &args, "trait_name", &[SymbolKind::Trait]).await?;
+
+    let Some(client) = &mut server.client else {
+        return Err(ToolError::client_not_initialized().into());
     };
 
-    // Shutdown existing client.
-    if let Some(client) = &mut server.client {
-        client.shutdown().await?;
-    }
-    server.client = None;
+    let implementors: Vec<Value> = implementation_blocks(client, &uri, line, character)
+        .await?
+        .into_iter()
+        .map(|(impl_uri, block)| block.to_json(&impl_uri))
+        .collect();
 
-    // Set new workspace with proper absolute path handling.
-    let workspace_root = PathBuf::from(workspace_path);
-    server.workspace_root = workspace_root.canonicalize().unwrap_or_else(|_| {
-        if workspace_root.is_absolute() {
-            workspace_root.clone()
-        } else {
-            std::env::current_dir()
-                .unwrap_or_else(|_| PathBuf::from("."))
-                .join(&workspace_root)
-        }
+    let output = json!({
+        "trait": { "file": uri, "line": line, "character": character },
+        "count": implementors.len(),
+        "implementors": implementors
     });
 
-    // Start the new client automatically.
-    server.ensure_client_started().await?;
-
     Ok(ToolResult {
-        content: vec![ContentItem {
-            content_type: "text".to_string(),
-            text: format!("Workspace set to: {}", server.workspace_root.display()),
-        }],
+        content: vec![ContentItem::text(serde_json::to_string_pretty(&output)?)],
     })
 }
 
-async fn handle_diagnostics(server: &mut RustAnalyzerMCPServer, args: Value) -> Result<ToolResult> {
-    let file_path = ToolParams::extract_file_path(&args)?;
+async fn handle_trait_surface(
+    server: &mut RustAnalyzerMCPServer,
+    args: Value,
+) -> Result<ToolResult> {
+    let (uri, line, character) =
+        resolve_item_position(server, &args, "trait_name", &[SymbolKind::Trait]).await?;
 
-    let uri = server.open_document_if_needed(&file_path).await?;
+    let Some(client) = &mut server.client else {
+        return Err(ToolError::client_not_initialized().into());
+    };
 
-    // Poll for diagnostics - rust-analyzer needs time to run cargo check.
-    // For files with expected errors (like diagnostics_test.rs), poll longer.
-    let should_poll = file_path.contains("diagnostics_test") || file_path.contains("simple_error");
+    // The position may be on a use of the trait, such as an impl header; follow
+    // it to the definition in that case.
+    let mut targets = vec![(uri.clone(), (line, character))];
+    let definition = client.definition(&uri, line, character).await?;
+    targets.extend(location_starts(&definition));
+
+    let mut sources = SourceCache::default();
+    for (target_uri, position) in targets {
+        let Some((tree, content)) = sources.get(client, &target_uri).await? else {
+            continue;
+        };
+        let Some(trait_node) = enclosing_symbols(tree, position)
+            .into_iter()
+            .rev()
+            .find(|node| node.kind_is(SymbolKind::Trait))
+        else {
+            continue;
+        };
+
+        let lines: Vec<&str> = content.lines().collect();
+        let mut output = trait_surface(trait_node, &lines);
+        output["file"] = json!(target_uri);
+        output["range"] = trait_node.range_json();
+
+        return Ok(ToolResult {
+            content: vec![ContentItem::text(serde_json::to_string_pretty(&output)?)],
+        });
+    }
+
+    Err(anyhow!("No trait found at {}:{}:{}", uri, line, character))
+}
+
+/// Runs `textDocument/implementation` and resolves each result to the impl
+/// block it points into.
+async fn implementation_blocks(
+    client: &mut RustAnalyzerClient,
+    uri: &str,
+    line: u32,
+    character: u32,
+) -> Result<Vec<(String, ImplBlock)>> {
+    let locations = client.implementation(uri, line, character).await?;
+
+    let mut blocks = Vec::new();
+    let mut sources = SourceCache::default();
+    for (impl_uri, position) in location_starts(&locations) {
+        let Some((tree, content)) = sources.get(client, &impl_uri).await? else {
+            continue;
+        };
+        let lines: Vec<&str> = content.lines().collect();
+        if let Some(block) = impl_block_at(tree, &lines, position) {
+            blocks.push((impl_uri, block));
+        }
+    }
+    Ok(blocks)
+}
+
+/// Resolves an item either from a `file_path`/`line`/`character` position or by
+/// looking up the name in `name_key` with a workspace symbol search. Returns the
+/// position of the item's name, suitable for LSP requests.
+async fn resolve_item_position(
+    server: &mut RustAnalyzerMCPServer,
+    args: &Value,
+    name_key: &str,
+    kinds: &[SymbolKind],
+) -> Result<(String, u32, u32)> {
+    if args["file_path"].is_string() {
+        let file_path = ToolParams::extract_file_path(args)?;
+        let (line, character) = ToolParams::extract_position(args)?;
+        let uri = server.open_document_if_needed(&file_path).await?;
+        return Ok((uri, line, character));
+    }
 
+    let Some(name) = args[name_key].as_str() else {
+        return Err(ToolError::MissingArgument {
+            argument: name_key.to_string(),
+            message: format!("Missing {} (or file_path, line and character)", name_key),
+        }
+        .into());
+    };
     let Some(client) = &mut server.client else {
-        return Err(anyhow!("Client not initialized"));
+        return Err(ToolError::client_not_initialized().into());
     };
 
-    let mut result = json!([]);
-    if should_poll {
-        let start = std::time::Instant::now();
-        let timeout = tokio::time::Duration::from_secs(8); // Less than test timeout.
-        let poll_interval = tokio::time::Duration::from_millis(500);
+    let symbols = client.workspace_symbols(name).await?;
+    let mut sources = SourceCache::default();
+    let mut matches = Vec::new();
+    for symbol in &symbols {
+        if symbol.name != name || !kinds.contains(&SymbolKind::from_lsp(symbol.kind)) {
+            continue;
+        }
+        // rust-analyzer also reports `pub use` re-exports under the item's name.
+        let line = symbol.location.range.start.line as usize;
+        let is_reexport = sources
+            .get(client, &symbol.location.uri)
+            .await?
+            .and_then(|(_, content)| content.lines().nth(line))
+            .is_some_and(is_use_statement);
+        if !is_reexport {
+            matches.push(symbol);
+        }
+    }
+    let symbol = match matches.as_slice() {
+        [] => return Err(anyhow!("No item named '{}' found in the workspace", name)),
+        [symbol] => *symbol,
+        _ => {
+            let candidates: Vec<String> = matches
+                .iter()
+                .map(|symbol| {
+                    format!(
+                        "{}:{}",
+                        symbol.location.uri, symbol.location.range.start.line
+                    )
+                })
+                .collect();
+            return Err(anyhow!(
+                "Multiple items named '{}': {}; pass file_path, line and character instead",
+                name,
+                candidates.join(", ")
+            ));
+        }
+    };
 
-        while start.elapsed() < timeout {
-            result = client.diagnostics(&uri).await?;
-            let Some(diag_array) = result.as_array() else {
-                tokio::time::sleep(poll_interval).await;
-                continue;
-            };
+    let uri = symbol.location.uri.as_str();
+    let start = symbol.location.range.start;
+    let (line, character) = declaration_position(
+        client,
+        &mut sources,
+        uri,
+        (start.line, start.character),
+        name,
+    )
+    .await?;
+    Ok((uri.to_string(), line, character))
+}
 
-            if !diag_array.is_empty() {
-                // We got diagnostics, stop polling.
-                break;
+/// Moves a workspace symbol's start to its name: symbol ranges may start at
+/// attributes or doc comments, while LSP requests need a position on the name
+/// itself.
+async fn declaration_position(
+    client: &mut RustAnalyzerClient,
+    sources: &mut SourceCache,
+    uri: &str,
+    start: (u32, u32),
+    name: &str,
+) -> Result<(u32, u32)> {
+    if let Some((tree, content)) = sources.get(client, uri).await? {
+        let lines: Vec<&str> = content.lines().collect();
+        let declaration = enclosing_symbols(tree, start)
+            .last()
+            .and_then(|node| item_header(&lines, node));
+        if let Some(header) = declaration {
+            let line = lines[header.line as usize];
+            if let Some(column) = identifier_column(line, name) {
+                return Ok((header.line, column));
             }
-            tokio::time::sleep(poll_interval).await;
         }
-    } else {
-        // For clean files, just wait a bit and check once.
-        tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-        result = client.diagnostics(&uri).await?;
     }
+    Ok(start)
+}
 
-    let diagnostics = format_diagnostics(&file_path, &result);
+fn is_use_statement(line: &str) -> bool {
+    let line = line.trim_start();
+    let line = match line.strip_prefix("pub") {
+        Some(rest) if rest.starts_with('(') => rest.split_once(')').map_or(rest, |(_, rest)| rest),
+        Some(rest) => rest,
+        None => line,
+    };
+    line.trim_start().starts_with("use ")
+}
 
-    Ok(ToolResult {
-        content: vec![ContentItem {
-            content_type: "text".to_string(),
-            text: serde_json::to_string_pretty(&diagnostics)?,
-        }],
-    })
+/// Caches symbol trees and file contents for the files referenced by a set of
+/// locations.
+#[derive(Default)]
+struct SourceCache {
+    files: HashMap<String, Option<(Vec<SymbolNode>, String)>>,
 }
 
-async fn handle_workspace_diagnostics(
-    server: &mut RustAnalyzerMCPServer,
-    _args: Value,
-) -> Result<ToolResult> {
-    let Some(client) = &mut server.client else {
-        return Err(anyhow!("Client not initialized"));
+impl SourceCache {
+    async fn get(
+        &mut self,
+        client: &mut RustAnalyzerClient,
+        uri: &str,
+    ) -> Result<Option<(&[SymbolNode], &str)>> {
+        if !self.files.contains_key(uri) {
+            let content = match path_from_uri(uri) {
+                Some(path) => tokio::fs::read_to_string(path).await.ok(),
+                None => None,
+            };
+            let entry = match content {
+                Some(content) => {
+                    let tree = build_symbol_tree(&client.document_symbols(uri).await?);
+                    Some((tree, content))
+                }
+                None => None,
+            };
+            self.files.insert(uri.to_string(), entry);
+        }
+
+        Ok(self.files[uri]
+            .as_ref()
+            .map(|(tree, content)| (tree.as_slice(), content.as_str())))
+    }
+}
+
+/// Extracts `(uri, start position)` pairs from locations.
+fn location_starts(locations: &[Location]) -> Vec<(String, (u32, u32))> {
+    locations
+        .iter()
+        .map(|location| {
+            let start = location.range.start;
+            (location.uri.clone(), (start.line, start.character))
+        })
+        .collect()
+}
+
+/// Finds the root source file of a workspace package, preferring its library
+/// target, and returns it with the crate's identifier.
+async fn crate_root_file(workspace_root: &Path, crate_name: &str) -> Result<(PathBuf, String)> {
+    let metadata = cargo_metadata(workspace_root, true).await?;
+    let roots = crate_roots(&metadata);
+    let wanted = crate_name.replace('-', "_");
+
+    let Some(root) = roots
+        .iter()
+        .filter(|root| root.kind == "bin" || root.is_lib())
+        .find(|root| root.package.replace('-', "_") == wanted)
+    else {
+        let mut names: Vec<&str> = roots.iter().map(|root| root.package.as_str()).collect();
+        names.sort_unstable();
+        names.dedup();
+        return Err(ToolError::UnknownPackage(format!(
+            "Crate '{}' is not a workspace member (available: {})",
+            crate_name,
+            names.join(", ")
+        ))
+        .into());
+    };
+
+    Ok((root.src_path.clone(), root.name.clone()))
+}
+
+/// Resolves the optional `package` argument of a workspace-wide tool to the
+/// members' directories and the member's name as cargo reports it.
+async fn package_filter(
+    workspace_root: &Path,
+    args: &Value,
+) -> Result<Option<(MemberDirs, String)>> {
+    let Some(package) = args["package"].as_str() else {
+        return Ok(None);
+    };
+
+    let members = MemberDirs::from_metadata(&cargo_metadata(workspace_root, true).await?);
+    let Some(name) = members.find(package).map(str::to_string) else {
+        return Err(ToolError::UnknownPackage(format!(
+            "Package '{}' is not a workspace member (available: {})",
+            package,
+            members.names().join(", ")
+        ))
+        .into());
     };
 
-    let result = client.workspace_diagnostics().await?;
+    Ok(Some((members, name)))
+}
 
-    // Format workspace diagnostics.
-    let formatted = format_workspace_diagnostics(&server.workspace_root, &result);
+/// Whether a document belongs to the given workspace member.
+fn in_package(members: &MemberDirs, package: &str, uri: &str) -> bool {
+    path_from_uri(uri).is_some_and(|path| members.owner(&path) == Some(package))
+}
 
-    Ok(ToolResult {
-        content: vec![ContentItem {
-            content_type: "text".to_string(),
-            text: serde_json::to_string_pretty(&formatted)?,
-        }],
-    })
+/// Resolves an out-of-line `mod` declaration to `name.rs` or `name/mod.rs`,
+/// relative to the directory that owns the declaring file's submodules.
+fn module_file(
+    declaring_file: &Path,
+    declaring_module: &str,
+    child_module: &str,
+) -> Option<PathBuf> {
+    let parent = declaring_file.parent()?;
+    let owns_directory = matches!(
+        declaring_file.file_name().and_then(|name| name.to_str()),
+        Some("mod.rs" | "lib.rs" | "main.rs")
+    ) || !declaring_module.contains("::");
+    let mut dir = if owns_directory {
+        parent.to_path_buf()
+    } else {
+        parent.join(declaring_file.file_stem()?)
+    };
+
+    let relative = child_module
+        .strip_prefix(declaring_module)?
+        .trim_start_matches("::");
+    let mut segments: Vec<&str> = relative.split("::").collect();
+    let name = segments.pop()?;
+    for segment in segments {
+        dir.push(segment);
+    }
+
+    [
+        dir.join(format!("{}.rs", name)),
+        dir.join(name).join("mod.rs"),
+    ]
+    .into_iter()
+    .find(|candidate| candidate.is_file())
+}
+
+fn format_cargo_run(cargo_args: &[String], run: CargoRun, include_artifacts: bool) -> Value {
+    let mut output = json!({
+        "command": format!("cargo {}", cargo_args.join(" ")),
+        "success": run.success,
+        "diagnostics": run.messages.diagnostics,
+        "summary": {
+            "errors": run.messages.errors,
+            "warnings": run.messages.warnings
+        }
+    });
+
+    if include_artifacts {
+        output["artifacts"] = json!(run.messages.artifacts);
+    }
+
+    // Failures without compiler messages (e.g. a broken manifest) are only explained on stderr.
+    if !run.success && run.messages.errors == 0 {
+        output["stderr"] = json!(run.stderr.trim());
+    }
+
+    output
+}
+
+/// The `render: "text"` output of the diagnostics tools, with notes on what
+/// `max_results` left out and on a check that cannot run.
+fn diagnostics_text(
+    root: &Path,
+    files: &BTreeMap<String, Vec<Diagnostic>>,
+    omitted: usize,
+    blocked: Option<&str>,
+) -> String {
+    let mut text = render_diagnostics_text(root, files);
+    if omitted > 0 {
+        text.push_str(&format!(
+            "note: {} less severe diagnostics omitted\n",
+            omitted
+        ));
+    }
+    if let Some(blocked) = blocked {
+        text.push_str(&format!("note: {}\n", blocked));
+    }
+    text
+}
+
+/// `resource_link` items for the files at `uris`, each once and in order and
+/// named by their path in the workspace, so that clients reading resources can
+/// fetch the files a result points into.
+fn resource_links<'a>(
+    server: &RustAnalyzerMCPServer,
+    uris: impl IntoIterator<Item = &'a str>,
+) -> Vec<ContentItem> {
+    let mut seen = HashSet::new();
+    uris.into_iter()
+        .filter(|uri| seen.insert(*uri))
+        .filter_map(|uri| {
+            let path = path_from_uri(uri)?;
+            let name = path.strip_prefix(&server.workspace_root).unwrap_or(&path);
+            Some(ContentItem::resource_link(uri, name.display().to_string()))
+        })
+        .collect()
 }
 
-fn format_workspace_diagnostics(workspace_root: &Path, result: &Value) -> Value {
+fn format_workspace_diagnostics(
+    workspace_root: &Path,
+    result: &BTreeMap<String, Vec<Diagnostic>>,
+) -> Value {
     let mut output = json!({
         "workspace": workspace_root.display().to_string(),
         "files": {},
@@ -333,38 +3421,8 @@ fn format_workspace_diagnostics(workspace_root: &Path, result: &Value) -> Value
     let mut totals = WorkspaceDiagnosticTotals::default();
     let mut file_count = 0;
 
-    let Some(obj) = result.as_object() else {
-        return output;
-    };
-
-    if let Some(items) = obj.get("items").and_then(|value| value.as_array()) {
-        for item in items {
-            let Some(uri) = item.get("uri").and_then(|value| value.as_str()) else {
-                continue;
-            };
-            let empty_diagnostics = Value::Array(Vec::new());
-            let diagnostics = item
-                .get("items")
-                .or_else(|| item.get("diagnostics"))
-                .unwrap_or(&empty_diagnostics);
-            add_workspace_file_diagnostics(
-                &mut output,
-                uri,
-                diagnostics,
-                &mut file_count,
-                &mut totals,
-            );
-        }
-    } else {
-        for (uri, diagnostics) in obj {
-            add_workspace_file_diagnostics(
-                &mut output,
-                uri,
-                diagnostics,
-                &mut file_count,
-                &mut totals,
-            );
-        }
+    for (uri, diagnostics) in result {
+        add_workspace_file_diagnostics(&mut output, uri, diagnostics, &mut file_count, &mut totals);
     }
 
     output["summary"]["total_files"] = json!(file_count);
@@ -387,15 +3445,11 @@ struct WorkspaceDiagnosticTotals {
 fn add_workspace_file_diagnostics(
     output: &mut Value,
     uri: &str,
-    diagnostics: &Value,
+    diagnostics: &[Diagnostic],
     file_count: &mut u64,
     totals: &mut WorkspaceDiagnosticTotals,
 ) {
-    let Some(diag_array) = diagnostics.as_array() else {
-        return;
-    };
-
-    if diag_array.is_empty() {
+    if diagnostics.is_empty() {
         return;
     }
 
@@ -405,8 +3459,8 @@ fn add_workspace_file_diagnostics(
     let mut file_information = 0;
     let mut file_hints = 0;
 
-    for diag in diag_array {
-        match workspace_diagnostic_severity(diag) {
+    for diag in diagnostics {
+        match diag.severity {
             Some(1) => {
                 file_errors += 1;
                 totals.errors += 1;
@@ -437,17 +3491,3 @@ fn add_workspace_file_diagnostics(
         }
     });
 }
-
-fn workspace_diagnostic_severity(diagnostic: &Value) -> Option<u64> {
-    if let Some(severity) = diagnostic.get("severity").and_then(|value| value.as_u64()) {
-        return Some(severity);
-    }
-
-    match diagnostic.get("severity").and_then(|value| value.as_str()) {
-        Some("error") => Some(1),
-        Some("warning") => Some(2),
-        Some("information") => Some(3),
-        Some("hint") => Some(4),
-        _ => None,
-    }
-}
@@ -0,0 +1,106 @@
+use serde_json::{json, Value};
+
+use crate::lsp::WindowMessage;
+
+/// MCP log levels (RFC 5424 severities), from least to most severe.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Debug,
+    #[default]
+    Info,
+    Notice,
+    Warning,
+    Error,
+    Critical,
+    Alert,
+    Emergency,
+}
+
+impl LogLevel {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "debug" => Some(Self::Debug),
+            "info" => Some(Self::Info),
+            "notice" => Some(Self::Notice),
+            "warning" => Some(Self::Warning),
+            "error" => Some(Self::Error),
+            "critical" => Some(Self::Critical),
+            "alert" => Some(Self::Alert),
+            "emergency" => Some(Self::Emergency),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Debug => "debug",
+            Self::Info => "info",
+            Self::Notice => "notice",
+            Self::Warning => "warning",
+            Self::Error => "error",
+            Self::Critical => "critical",
+            Self::Alert => "alert",
+            Self::Emergency => "emergency",
+        }
+    }
+
+    /// Maps an LSP `MessageType` (1 = error, 2 = warning, 3 = info, 4 = log,
+    /// 5 = debug) to the MCP level.
+    pub fn from_lsp_message_type(message_type: u64) -> Self {
+        match message_type {
+            1 => Self::Error,
+            2 => Self::Warning,
+            3 => Self::Info,
+            _ => Self::Debug,
+        }
+    }
+}
+
+/// Builds the `notifications/message` notification forwarding a rust-analyzer
+/// `window/showMessage` or `window/logMessage`, or `None` when the message is
+/// below `min_level`.
+pub fn window_message_notification(message: &WindowMessage, min_level: LogLevel) -> Option<Value> {
+    let level = LogLevel::from_lsp_message_type(message.message_type);
+    if level < min_level {
+        return None;
+    }
+
+    Some(json!({
+        "jsonrpc": "2.0",
+        "method": "notifications/message",
+        "params": {
+            "level": level.name(),
+            "logger": "rust-analyzer",
+            "data": {
+                "message": message.text,
+                "source": message.method
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{window_message_notification, LogLevel};
+    use crate::lsp::WindowMessage;
+
+    #[test]
+    fn test_window_message_notification_maps_and_filters_levels() {
+        let warning = WindowMessage {
+            message_type: 2,
+            method: "window/showMessage".to_string(),
+            text: "Failed to load workspace".to_string(),
+        };
+
+        let notification = window_message_notification(&warning, LogLevel::Info).unwrap();
+
+        assert_eq!(notification["method"], "notifications/message");
+        assert_eq!(notification["params"]["level"], "warning");
+        assert_eq!(
+            notification["params"]["data"]["message"],
+            "Failed to load workspace"
+        );
+        assert!(window_message_notification(&warning, LogLevel::Error).is_none());
+        assert_eq!(LogLevel::from_lsp_message_type(4), LogLevel::Debug);
+    }
+}
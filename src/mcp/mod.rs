@@ -1,6 +1,20 @@
+mod completions;
+mod envelope;
+mod errors;
 mod handlers;
+mod logging;
+mod observer;
+mod progress;
+mod prompts;
+mod queue;
+mod registry;
+mod schema;
 mod server;
+mod session;
+mod signal;
 mod tools;
 mod transport;
 
+pub use observer::MessageObserver;
+pub use registry::{CommandTool, CustomTool, ToolContext};
 pub use server::RustAnalyzerMCPServer;
@@ -0,0 +1,18 @@
+use serde_json::Value;
+
+use crate::protocol::mcp::{MCPRequest, MCPResponse};
+
+/// Sees the MCP traffic of a [`RustAnalyzerMCPServer`](super::RustAnalyzerMCPServer),
+/// e.g. to show it in a GUI or record it in a daemon's log. Methods are called
+/// on the server's task, so they should return quickly.
+pub trait MessageObserver: Send + Sync {
+    /// A request or notification from the client, just before it is handled.
+    fn on_request(&self, _request: &MCPRequest) {}
+
+    /// A response, just before it is written to the client.
+    fn on_response(&self, _response: &MCPResponse) {}
+
+    /// A notification the server sends on its own, such as a forwarded
+    /// rust-analyzer log message.
+    fn on_notification(&self, _notification: &Value) {}
+}
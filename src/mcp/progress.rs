@@ -0,0 +1,156 @@
+use serde_json::{json, Value};
+use tokio::sync::mpsc::UnboundedSender;
+
+/// Results with more items than this are streamed in chunks of this size when
+/// the call carries a progress token.
+pub(super) const PARTIAL_RESULT_CHUNK_SIZE: usize = 100;
+
+/// Sends `notifications/progress` for a tool call whose request asked for
+/// them with a `_meta.progressToken`.
+#[derive(Debug, Clone)]
+pub(super) struct ProgressReporter {
+    token: Value,
+    notifications: UnboundedSender<Value>,
+}
+
+impl ProgressReporter {
+    pub fn new(token: Value, notifications: UnboundedSender<Value>) -> Self {
+        Self {
+            token,
+            notifications,
+        }
+    }
+
+    /// Reports that `progress` of `total` steps are done. Progress is dropped
+    /// once the client has gone away.
+    pub fn report(&self, progress: usize, total: Option<usize>, message: &str) {
+        let notification = progress_notification(&self.token, progress, total, message);
+        let _ = self.notifications.send(notification);
+    }
+
+    /// Sends `items` as `notifications/partialResult` chunks, each followed by
+    /// a progress report, and returns how many chunks were sent.
+    pub fn stream_items(&self, items: &[Value]) -> usize {
+        let mut sent = 0;
+        for (index, chunk) in items.chunks(PARTIAL_RESULT_CHUNK_SIZE).enumerate() {
+            let notification = json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/partialResult",
+                "params": {
+                    "progressToken": self.token,
+                    "chunk": index,
+                    "items": chunk
+                }
+            });
+            let _ = self.notifications.send(notification);
+            sent += chunk.len();
+            self.report(sent, Some(items.len()), "Streaming results");
+        }
+        items.len().div_ceil(PARTIAL_RESULT_CHUNK_SIZE)
+    }
+}
+
+/// The progress token of `tools/call` params: a string or an integer.
+pub(super) fn progress_token(params: &Value) -> Option<Value> {
+    let token = &params["_meta"]["progressToken"];
+    (token.is_string() || token.is_i64() || token.is_u64()).then(|| token.clone())
+}
+
+fn progress_notification(
+    token: &Value,
+    progress: usize,
+    total: Option<usize>,
+    message: &str,
+) -> Value {
+    let mut params = json!({
+        "progressToken": token,
+        "progress": progress,
+        "message": message
+    });
+    if let Some(total) = total {
+        params["total"] = json!(total);
+    }
+    json!({
+        "jsonrpc": "2.0",
+        "method": "notifications/progress",
+        "params": params
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use tokio::sync::mpsc;
+
+    use super::{
+        progress_notification, progress_token, ProgressReporter, PARTIAL_RESULT_CHUNK_SIZE,
+    };
+
+    #[test]
+    fn test_progress_token_accepts_strings_and_integers() {
+        assert_eq!(
+            progress_token(&json!({ "_meta": { "progressToken": "abc" } })),
+            Some(json!("abc"))
+        );
+        assert_eq!(
+            progress_token(&json!({ "_meta": { "progressToken": 7 } })),
+            Some(json!(7))
+        );
+        assert_eq!(
+            progress_token(&json!({ "_meta": { "progressToken": 1.5 } })),
+            None
+        );
+        assert_eq!(
+            progress_token(&json!({ "name": "rust_analyzer_hover" })),
+            None
+        );
+    }
+
+    #[test]
+    fn test_progress_notification() {
+        let notification = progress_notification(&json!("abc"), 2, Some(5), "Checking tls");
+
+        assert_eq!(notification["method"], "notifications/progress");
+        assert_eq!(
+            notification["params"],
+            json!({ "progressToken": "abc", "progress": 2, "total": 5, "message": "Checking tls" })
+        );
+        assert!(progress_notification(&json!(1), 1, None, "")["params"]
+            .get("total")
+            .is_none());
+    }
+
+    #[test]
+    fn test_stream_items_sends_chunks_then_progress() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let reporter = ProgressReporter::new(json!("refs"), sender);
+        let items: Vec<_> = (0..PARTIAL_RESULT_CHUNK_SIZE + 1)
+            .map(|i| json!(i))
+            .collect();
+
+        assert_eq!(reporter.stream_items(&items), 2);
+
+        let mut messages = Vec::new();
+        while let Ok(message) = receiver.try_recv() {
+            messages.push(message);
+        }
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[0]["method"], "notifications/partialResult");
+        assert_eq!(messages[0]["params"]["progressToken"], "refs");
+        assert_eq!(
+            messages[0]["params"]["items"].as_array().unwrap().len(),
+            PARTIAL_RESULT_CHUNK_SIZE
+        );
+        assert_eq!(messages[1]["params"]["progress"], PARTIAL_RESULT_CHUNK_SIZE);
+        assert_eq!(messages[2]["params"]["chunk"], 1);
+        assert_eq!(
+            messages[2]["params"]["items"],
+            json!([PARTIAL_RESULT_CHUNK_SIZE])
+        );
+        assert_eq!(
+            messages[3]["params"]["progress"],
+            messages[3]["params"]["total"]
+        );
+    }
+}
@@ -0,0 +1,194 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+
+use crate::{
+    error::ToolError,
+    protocol::mcp::{ContentItem, PromptArgument, PromptDefinition},
+};
+
+use super::server::RustAnalyzerMCPServer;
+
+/// A guided refactor: what to ask the model to do with the code at a position.
+struct RefactorPrompt {
+    name: &'static str,
+    description: &'static str,
+    instructions: &'static str,
+}
+
+const REFACTOR_PROMPTS: [RefactorPrompt; 3] = [
+    RefactorPrompt {
+        name: "extract_function",
+        description: "Extract the selected code into a new function",
+        instructions: "Extract the selected code into a new function. If rust-analyzer offers an \
+                       `Extract into function` assist below, apply it with \
+                       rust_analyzer_code_actions; otherwise write the function by hand, passing \
+                       what it reads as parameters and returning what later code uses. Name it \
+                       after what it does and keep the behavior unchanged.",
+    },
+    RefactorPrompt {
+        name: "make_async",
+        description: "Turn the function at the position into an async function",
+        instructions: "Make the function enclosing the position `async`. Replace blocking calls \
+                       inside it with their async counterparts, then find its callers with \
+                       rust_analyzer_references and add `.await` where they call it, making them \
+                       async in turn where needed. Check the result with \
+                       rust_analyzer_diagnostics.",
+    },
+    RefactorPrompt {
+        name: "add_error_handling",
+        description: "Replace panics at the position with propagated errors",
+        instructions: "Replace the `unwrap`, `expect` and `panic!` calls at the position with \
+                       error handling: return a `Result` from the enclosing function, using the \
+                       error type the surrounding code already uses, and propagate failures with \
+                       `?` and context on what failed. Update callers the new signature breaks.",
+    },
+];
+
+/// The prompts listed by `prompts/list`.
+pub(super) fn prompt_definitions() -> Vec<PromptDefinition> {
+    let argument = |name: &str, description: &str, required: bool| PromptArgument {
+        name: name.to_string(),
+        description: description.to_string(),
+        required,
+    };
+    REFACTOR_PROMPTS
+        .iter()
+        .map(|prompt| PromptDefinition {
+            name: prompt.name.to_string(),
+            description: prompt.description.to_string(),
+            arguments: vec![
+                argument("file_path", "Path to the Rust file", true),
+                argument("line", "Line number (0-based)", true),
+                argument("character", "Character position (0-based)", true),
+                argument(
+                    "end_line",
+                    "End line of the selection (default: line)",
+                    false,
+                ),
+                argument(
+                    "end_character",
+                    "End character of the selection (default: character)",
+                    false,
+                ),
+            ],
+        })
+        .collect()
+}
+
+/// Answers `prompts/get`: the prompt's instructions followed by the items
+/// enclosing the position, the file's diagnostics and the assists rust-analyzer
+/// offers for the selection, as one user message.
+pub(super) async fn get_prompt(
+    server: &mut RustAnalyzerMCPServer,
+    params: &Value,
+) -> Result<Value> {
+    let Some(name) = params["name"].as_str() else {
+        return Err(ToolError::missing("name").into());
+    };
+    let Some(prompt) = REFACTOR_PROMPTS.iter().find(|prompt| prompt.name == name) else {
+        return Err(ToolError::invalid("name", format!("Unknown prompt '{}'", name)).into());
+    };
+
+    let arguments = &params["arguments"];
+    let Some(file_path) = arguments["file_path"].as_str() else {
+        return Err(ToolError::missing("file_path").into());
+    };
+    let Some(line) = position_argument(arguments, "line")? else {
+        return Err(ToolError::missing("line").into());
+    };
+    let Some(character) = position_argument(arguments, "character")? else {
+        return Err(ToolError::missing("character").into());
+    };
+    let end_line = position_argument(arguments, "end_line")?.unwrap_or(line);
+    let end_character = position_argument(arguments, "end_character")?.unwrap_or(character);
+
+    let position = json!({ "file_path": file_path, "line": line, "character": character });
+    let mut selection = position.clone();
+    selection["end_line"] = json!(end_line);
+    selection["end_character"] = json!(end_character);
+    let sections = [
+        ("Enclosing items", "rust_analyzer_enclosing_items", position),
+        (
+            "Diagnostics",
+            "rust_analyzer_diagnostics",
+            json!({ "file_path": file_path }),
+        ),
+        ("Available assists", "rust_analyzer_code_actions", selection),
+    ];
+
+    let mut text = format!(
+        "{}\n\nTarget: `{}`, lines {}:{} to {}:{} (0-based).\n",
+        prompt.instructions, file_path, line, character, end_line, end_character
+    );
+    for (title, tool, args) in sections {
+        let output = match server.call_tool(tool, args, None).await {
+            Ok(result) => result
+                .content
+                .into_iter()
+                .filter_map(ContentItem::into_text)
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Err(e) => format!("Unavailable: {}", e),
+        };
+        text.push_str(&format!("\n## {}\n\n```json\n{}\n```\n", title, output));
+    }
+
+    Ok(json!({
+        "description": prompt.description,
+        "messages": [{
+            "role": "user",
+            "content": { "type": "text", "text": text }
+        }]
+    }))
+}
+
+/// A 0-based position argument; prompt arguments arrive as strings.
+fn position_argument(arguments: &Value, name: &str) -> Result<Option<u64>> {
+    let value = match &arguments[name] {
+        Value::Null => return Ok(None),
+        Value::String(text) => text.trim().parse().ok(),
+        value => value.as_u64(),
+    };
+    value.map(Some).ok_or_else(|| {
+        ToolError::invalid(name, format!("{} must be a non-negative integer", name)).into()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{position_argument, prompt_definitions};
+
+    #[test]
+    fn test_prompt_definitions_take_a_position() {
+        let prompts = prompt_definitions();
+        let names: Vec<&str> = prompts.iter().map(|prompt| prompt.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["extract_function", "make_async", "add_error_handling"]
+        );
+        assert!(prompts[0]
+            .arguments
+            .iter()
+            .any(|argument| argument.name == "line" && argument.required));
+    }
+
+    #[test]
+    fn test_position_argument_accepts_strings_and_numbers() {
+        let arguments = json!({ "line": "12", "character": 3, "end_line": "x" });
+
+        assert_eq!(position_argument(&arguments, "line").unwrap(), Some(12));
+        assert_eq!(position_argument(&arguments, "character").unwrap(), Some(3));
+        assert_eq!(
+            position_argument(&arguments, "end_character").unwrap(),
+            None
+        );
+        assert_eq!(
+            position_argument(&arguments, "end_line")
+                .unwrap_err()
+                .to_string(),
+            "end_line must be a non-negative integer"
+        );
+    }
+}
@@ -0,0 +1,529 @@
+use anyhow::Result;
+use log::{debug, error};
+use serde_json::Value;
+use std::collections::VecDeque;
+
+use crate::{
+    config::ServerConfig,
+    protocol::mcp::{MCPRequest, MCPResponse},
+};
+
+use super::transport::MessageFraming;
+
+/// Tools that scan the whole workspace or run cargo. They yield to interactive
+/// tools that arrived while they were queued.
+const BATCH_TOOLS: [&str; 9] = [
+    "rust_analyzer_workspace_diagnostics",
+    "rust_analyzer_diagnostics_snapshot",
+    "rust_analyzer_diagnostics_diff",
+    "rust_analyzer_cargo_check",
+    "rust_analyzer_run_check",
+    "rust_analyzer_cargo_build",
+    "rust_analyzer_dead_code",
+    "rust_analyzer_feature_matrix",
+    "rust_analyzer_public_api",
+];
+
+/// Tools that change what later requests refer to, so nothing received after
+/// them may run before them.
+const BARRIER_TOOLS: [&str; 3] = [
+    "rust_analyzer_set_workspace",
+    "rust_analyzer_rename_file",
+    "rust_analyzer_rename_module",
+];
+
+/// Protocol methods that end the session. They are handled after everything
+/// received before them, and nothing received after them runs first.
+const LIFECYCLE_METHODS: [&str; 2] = ["shutdown", "exit"];
+
+/// Tools whose identical calls, received while one is pending, share its answer.
+const COALESCED_TOOLS: [&str; 2] = ["rust_analyzer_hover", "rust_analyzer_definition"];
+
+/// Scheduling class of a request, most urgent first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum RequestPriority {
+    /// Protocol requests and notifications such as `initialize` or `ping`.
+    Control,
+    /// Tools answering a question about a position or file, e.g. hover.
+    Interactive,
+    /// Workspace-wide scans and cargo runs.
+    Batch,
+    /// `shutdown` and `exit`, which wait for everything sent before them.
+    Lifecycle,
+}
+
+#[derive(Debug)]
+pub(super) struct QueuedRequest {
+    pub request: MCPRequest,
+    pub framing: MessageFraming,
+    priority: RequestPriority,
+    barrier: bool,
+}
+
+/// A request turned away because its client already has too much work waiting,
+/// with the server-busy error to answer it with.
+#[derive(Debug)]
+pub(super) struct RejectedRequest {
+    pub request: MCPRequest,
+    pub framing: MessageFraming,
+    pub response: MCPResponse,
+}
+
+/// How much work one client may have waiting, so a runaway agent loop cannot
+/// pile up requests in front of rust-analyzer. Unlimited when unset.
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct QueueLimits {
+    /// Requests read but not yet handled.
+    pub max_queued_requests: Option<usize>,
+    /// Tool calls queued or being handled.
+    pub max_pending_tool_calls: Option<usize>,
+}
+
+impl QueueLimits {
+    pub fn from_config(config: &ServerConfig) -> Self {
+        Self {
+            max_queued_requests: config.max_queued_requests,
+            max_pending_tool_calls: config.max_pending_tool_calls,
+        }
+    }
+}
+
+/// Requests read from the client but not yet handled. Messages keep arriving
+/// while a tool runs, and the next one handled is the most urgent, so a hover
+/// sent behind a workspace diagnostics scan does not wait for the scans queued
+/// before it.
+#[derive(Debug)]
+pub(super) struct RequestQueue {
+    requests: VecDeque<QueuedRequest>,
+    rejected: Vec<RejectedRequest>,
+    limits: QueueLimits,
+    /// Whether the request handed out last is a tool call, which counts
+    /// towards the pending tool calls until the next one is taken.
+    handling_tool_call: bool,
+    closed: bool,
+    last_framing: MessageFraming,
+}
+
+impl RequestQueue {
+    pub fn new(limits: QueueLimits) -> Self {
+        Self {
+            requests: VecDeque::new(),
+            rejected: Vec::new(),
+            limits,
+            handling_tool_call: false,
+            closed: false,
+            last_framing: MessageFraming::JsonLine,
+        }
+    }
+
+    /// Queues a message read from the transport. End of input and read errors
+    /// close the queue; requests already queued are still handed out.
+    pub fn accept(&mut self, message: Result<Option<(String, MessageFraming)>>) {
+        let (request_text, framing) = match message {
+            Ok(Some(message)) => message,
+            Ok(None) => {
+                self.closed = true;
+                return;
+            }
+            Err(e) => {
+                error!("Error reading MCP message: {e}");
+                self.closed = true;
+                return;
+            }
+        };
+        self.last_framing = framing;
+
+        let request_text = request_text.trim();
+        if request_text.is_empty() {
+            return;
+        }
+
+        let Ok(request) = serde_json::from_str::<MCPRequest>(request_text) else {
+            debug!("Failed to parse request: {request_text}");
+            return;
+        };
+
+        debug!("Received request: {}", request.method);
+        if let Some(response) = self.refusal(&request) {
+            debug!(
+                "Refusing {}: client has too much work waiting",
+                request.method
+            );
+            self.rejected.push(RejectedRequest {
+                request,
+                framing,
+                response,
+            });
+            return;
+        }
+        self.requests.push_back(QueuedRequest {
+            priority: request_priority(&request),
+            barrier: is_barrier(&request),
+            request,
+            framing,
+        });
+    }
+
+    /// Takes the most urgent request, oldest first among equals. Requests
+    /// behind a barrier wait until the barrier has been handed out.
+    pub fn pop(&mut self) -> Option<QueuedRequest> {
+        let candidates = self
+            .requests
+            .iter()
+            .position(|queued| queued.barrier)
+            .map_or(self.requests.len(), |barrier| barrier + 1);
+        let index = self
+            .requests
+            .iter()
+            .take(candidates)
+            .enumerate()
+            .min_by_key(|(index, queued)| (queued.priority, *index))
+            .map(|(index, _)| index);
+        self.take(index)
+    }
+
+    /// Takes the oldest protocol request, leaving tool calls queued. Used while
+    /// tools are held back during warm-up.
+    pub fn pop_control(&mut self) -> Option<QueuedRequest> {
+        let index = self.requests.iter().position(|queued| {
+            matches!(
+                queued.priority,
+                RequestPriority::Control | RequestPriority::Lifecycle
+            )
+        });
+        self.take(index)
+    }
+
+    fn take(&mut self, index: Option<usize>) -> Option<QueuedRequest> {
+        let queued = index.and_then(|index| self.requests.remove(index));
+        self.handling_tool_call = queued
+            .as_ref()
+            .is_some_and(|queued| is_tool_call(&queued.request));
+        queued
+    }
+
+    /// Takes the requests refused since the last call, to be answered right away.
+    pub fn take_rejected(&mut self) -> Vec<RejectedRequest> {
+        std::mem::take(&mut self.rejected)
+    }
+
+    /// The server-busy error for a request that would exceed the limits, if
+    /// any. Notifications and `shutdown` or `exit` are never refused.
+    fn refusal(&self, request: &MCPRequest) -> Option<MCPResponse> {
+        if request.id.is_none() || ends_session(request) {
+            return None;
+        }
+        if let Some(max) = self.limits.max_queued_requests {
+            if self.requests.len() >= max {
+                return Some(MCPResponse::server_busy(
+                    request.id.clone(),
+                    "max_queued_requests",
+                    max,
+                ));
+            }
+        }
+        if let Some(max) = self.limits.max_pending_tool_calls {
+            let pending = self
+                .requests
+                .iter()
+                .filter(|queued| is_tool_call(&queued.request))
+                .count()
+                + usize::from(self.handling_tool_call);
+            if is_tool_call(request) && pending >= max {
+                return Some(MCPResponse::server_busy(
+                    request.id.clone(),
+                    "max_pending_tool_calls",
+                    max,
+                ));
+            }
+        }
+        None
+    }
+
+    /// Removes queued requests identical to a request with `key` that was just
+    /// handled, so they can share its response. Requests behind a barrier are
+    /// left alone since their answer may differ.
+    pub fn take_duplicates(&mut self, key: &CoalescingKey) -> Vec<QueuedRequest> {
+        let barrier = self
+            .requests
+            .iter()
+            .position(|queued| queued.barrier)
+            .unwrap_or(self.requests.len());
+
+        let mut duplicates = Vec::new();
+        let mut kept = VecDeque::with_capacity(self.requests.len());
+        for (index, queued) in self.requests.drain(..).enumerate() {
+            if index < barrier && coalescing_key(&queued.request).as_ref() == Some(key) {
+                duplicates.push(queued);
+            } else {
+                kept.push_back(queued);
+            }
+        }
+        self.requests = kept;
+        duplicates
+    }
+
+    /// Takes all queued requests, oldest first.
+    pub fn drain(&mut self) -> impl Iterator<Item = QueuedRequest> + '_ {
+        self.requests.drain(..)
+    }
+
+    /// Whether the client closed its end of the connection.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Framing of the most recent client message, used for notifications.
+    pub fn last_framing(&self) -> MessageFraming {
+        self.last_framing
+    }
+}
+
+fn is_tool_call(request: &MCPRequest) -> bool {
+    request.method == "tools/call"
+}
+
+fn tool_name(request: &MCPRequest) -> Option<&str> {
+    if !is_tool_call(request) {
+        return None;
+    }
+    request.params.as_ref()?.get("name")?.as_str()
+}
+
+/// Identifies calls that produce the same answer: the tool name and its arguments.
+pub(super) type CoalescingKey = (String, Value);
+
+/// The key under which identical hover and definition calls are coalesced, or
+/// `None` for requests that are always handled individually.
+pub(super) fn coalescing_key(request: &MCPRequest) -> Option<CoalescingKey> {
+    let name = tool_name(request).filter(|name| COALESCED_TOOLS.contains(name))?;
+    let arguments = request.params.as_ref()?.get("arguments").cloned()?;
+    Some((name.to_string(), arguments))
+}
+
+/// Whether `request` asks the server to stop serving the session.
+pub(super) fn ends_session(request: &MCPRequest) -> bool {
+    LIFECYCLE_METHODS.contains(&request.method.as_str())
+}
+
+fn request_priority(request: &MCPRequest) -> RequestPriority {
+    if ends_session(request) {
+        return RequestPriority::Lifecycle;
+    }
+    if !is_tool_call(request) {
+        return RequestPriority::Control;
+    }
+    match tool_name(request) {
+        Some(name) if BATCH_TOOLS.contains(&name) || BARRIER_TOOLS.contains(&name) => {
+            RequestPriority::Batch
+        }
+        _ => RequestPriority::Interactive,
+    }
+}
+
+fn is_barrier(request: &MCPRequest) -> bool {
+    ends_session(request) || tool_name(request).is_some_and(|name| BARRIER_TOOLS.contains(&name))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::{coalescing_key, MessageFraming, QueueLimits, RequestQueue};
+
+    fn tool_call(id: u64, name: &str) -> String {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": { "name": name, "arguments": {} }
+        })
+        .to_string()
+    }
+
+    fn drain(queue: &mut RequestQueue) -> Vec<u64> {
+        std::iter::from_fn(|| queue.pop())
+            .map(|queued| queued.request.id.unwrap().as_u64().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_interactive_requests_jump_ahead_of_batch_work() {
+        let mut queue = RequestQueue::new(QueueLimits::default());
+        for message in [
+            tool_call(1, "rust_analyzer_workspace_diagnostics"),
+            tool_call(2, "rust_analyzer_hover"),
+            tool_call(3, "rust_analyzer_cargo_check"),
+            json!({ "jsonrpc": "2.0", "id": 4, "method": "ping" }).to_string(),
+            tool_call(5, "rust_analyzer_definition"),
+        ] {
+            queue.accept(Ok(Some((message, MessageFraming::JsonLine))));
+        }
+
+        assert_eq!(drain(&mut queue), [4, 2, 5, 1, 3]);
+    }
+
+    #[test]
+    fn test_requests_do_not_pass_a_workspace_change() {
+        let mut queue = RequestQueue::new(QueueLimits::default());
+        for message in [
+            tool_call(1, "rust_analyzer_cargo_check"),
+            tool_call(2, "rust_analyzer_set_workspace"),
+            tool_call(3, "rust_analyzer_hover"),
+        ] {
+            queue.accept(Ok(Some((message, MessageFraming::ContentLength))));
+        }
+        queue.accept(Ok(None));
+
+        assert_eq!(drain(&mut queue), [1, 2, 3]);
+        assert!(queue.is_closed());
+        assert_eq!(queue.last_framing(), MessageFraming::ContentLength);
+    }
+
+    #[test]
+    fn test_writes_wait_for_batch_work_sent_before_them() {
+        let mut queue = RequestQueue::new(QueueLimits::default());
+        for message in [
+            tool_call(1, "rust_analyzer_diagnostics_snapshot"),
+            tool_call(2, "rust_analyzer_rename_file"),
+            tool_call(3, "rust_analyzer_diagnostics_diff"),
+            tool_call(4, "rust_analyzer_hover"),
+            tool_call(5, "rust_analyzer_workspace_diagnostics"),
+            tool_call(6, "rust_analyzer_rename_module"),
+            tool_call(7, "rust_analyzer_symbols"),
+        ] {
+            queue.accept(Ok(Some((message, MessageFraming::JsonLine))));
+        }
+
+        assert_eq!(drain(&mut queue), [1, 2, 4, 3, 5, 6, 7]);
+    }
+
+    #[test]
+    fn test_shutdown_waits_for_requests_sent_before_it() {
+        let mut queue = RequestQueue::new(QueueLimits::default());
+        for message in [
+            tool_call(1, "rust_analyzer_cargo_check"),
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "shutdown" }).to_string(),
+            json!({ "jsonrpc": "2.0", "id": 3, "method": "ping" }).to_string(),
+            tool_call(4, "rust_analyzer_hover"),
+        ] {
+            queue.accept(Ok(Some((message, MessageFraming::JsonLine))));
+        }
+
+        assert_eq!(drain(&mut queue), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_pop_control_holds_back_tool_calls() {
+        let mut queue = RequestQueue::new(QueueLimits::default());
+        for message in [
+            tool_call(1, "rust_analyzer_hover"),
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "ping" }).to_string(),
+            tool_call(3, "rust_analyzer_set_workspace"),
+            json!({ "jsonrpc": "2.0", "id": 4, "method": "tools/list" }).to_string(),
+        ] {
+            queue.accept(Ok(Some((message, MessageFraming::JsonLine))));
+        }
+
+        let control: Vec<u64> = std::iter::from_fn(|| queue.pop_control())
+            .map(|queued| queued.request.id.unwrap().as_u64().unwrap())
+            .collect();
+        assert_eq!(control, [2, 4]);
+        assert_eq!(drain(&mut queue), [1, 3]);
+    }
+
+    #[test]
+    fn test_requests_beyond_the_limits_are_refused() {
+        let mut queue = RequestQueue::new(QueueLimits {
+            max_queued_requests: Some(3),
+            max_pending_tool_calls: Some(2),
+        });
+        let accept = |queue: &mut RequestQueue, message: String| {
+            queue.accept(Ok(Some((message, MessageFraming::JsonLine))));
+        };
+
+        accept(&mut queue, tool_call(1, "rust_analyzer_hover"));
+        assert_eq!(queue.pop().unwrap().request.id, Some(json!(1)));
+        // The hover is still being handled, so one more tool call fits.
+        accept(&mut queue, tool_call(2, "rust_analyzer_hover"));
+        accept(&mut queue, tool_call(3, "rust_analyzer_hover"));
+        accept(
+            &mut queue,
+            json!({ "jsonrpc": "2.0", "id": 4, "method": "ping" }).to_string(),
+        );
+        accept(
+            &mut queue,
+            json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }).to_string(),
+        );
+        accept(
+            &mut queue,
+            json!({ "jsonrpc": "2.0", "id": 5, "method": "tools/list" }).to_string(),
+        );
+        accept(
+            &mut queue,
+            json!({ "jsonrpc": "2.0", "id": 6, "method": "shutdown" }).to_string(),
+        );
+
+        let rejected: Vec<(u64, Value)> = queue
+            .take_rejected()
+            .into_iter()
+            .map(|rejected| {
+                let response = serde_json::to_value(&rejected.response).unwrap();
+                (
+                    rejected.request.id.unwrap().as_u64().unwrap(),
+                    response["error"]["data"]["limit"].clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            rejected,
+            [
+                (3, json!("max_pending_tool_calls")),
+                (5, json!("max_queued_requests")),
+            ]
+        );
+        assert!(queue.take_rejected().is_empty());
+    }
+
+    #[test]
+    fn test_take_duplicates_of_a_handled_hover() {
+        let hover = |id: u64, line: u64| {
+            json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": "tools/call",
+                "params": {
+                    "name": "rust_analyzer_hover",
+                    "arguments": { "file_path": "src/main.rs", "line": line, "character": 4 }
+                }
+            })
+            .to_string()
+        };
+        let mut queue = RequestQueue::new(QueueLimits::default());
+        for message in [
+            hover(1, 12),
+            hover(2, 12),
+            hover(3, 13),
+            hover(4, 12),
+            tool_call(5, "rust_analyzer_set_workspace"),
+            hover(6, 12),
+        ] {
+            queue.accept(Ok(Some((message, MessageFraming::JsonLine))));
+        }
+
+        let handled = queue.pop().unwrap();
+        let key = coalescing_key(&handled.request).unwrap();
+        let duplicates: Vec<u64> = queue
+            .take_duplicates(&key)
+            .into_iter()
+            .map(|queued| queued.request.id.unwrap().as_u64().unwrap())
+            .collect();
+
+        assert_eq!(duplicates, [2, 4]);
+        assert_eq!(drain(&mut queue), [3, 5, 6]);
+        assert!(coalescing_key(
+            &serde_json::from_str(&tool_call(7, "rust_analyzer_symbols")).unwrap()
+        )
+        .is_none());
+    }
+}
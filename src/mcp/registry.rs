@@ -0,0 +1,199 @@
+use anyhow::{anyhow, Result};
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{collections::BTreeMap, path::Path, sync::Arc};
+
+use crate::{
+    cargo::command_output,
+    error::ToolError,
+    lsp::RustAnalyzerClient,
+    protocol::mcp::{ContentItem, ToolDefinition, ToolResult},
+};
+
+use super::tools::is_builtin_tool;
+
+/// What a [`CustomTool`] can use while handling a call.
+pub struct ToolContext<'a> {
+    pub workspace_root: &'a Path,
+    /// The started rust-analyzer client, for tools built on LSP requests.
+    pub client: &'a mut RustAnalyzerClient,
+}
+
+/// A tool registered with [`RustAnalyzerMCPServer::register_tool`] that is
+/// listed and dispatched next to the built-in ones.
+///
+/// [`RustAnalyzerMCPServer::register_tool`]: super::RustAnalyzerMCPServer::register_tool
+pub trait CustomTool: Send + Sync {
+    /// The name, description and input schema shown in `tools/list`. The
+    /// schema gets the same `timeout_ms` property as the built-in tools.
+    fn definition(&self) -> ToolDefinition;
+
+    /// Whether the tool only reads the workspace and stays available in
+    /// read-only mode.
+    fn read_only(&self) -> bool {
+        false
+    }
+
+    /// Handles a `tools/call`; `args` are the call arguments without `timeout_ms`.
+    fn call<'a>(
+        &'a self,
+        context: ToolContext<'a>,
+        args: Value,
+    ) -> BoxFuture<'a, Result<ToolResult>>;
+}
+
+/// Custom tools by name.
+#[derive(Default)]
+pub(super) struct ToolRegistry {
+    tools: BTreeMap<String, Arc<dyn CustomTool>>,
+}
+
+impl ToolRegistry {
+    pub fn register(&mut self, tool: Arc<dyn CustomTool>) -> Result<()> {
+        let definition = tool.definition();
+        if is_builtin_tool(&definition.name) || self.tools.contains_key(&definition.name) {
+            return Err(anyhow!("Tool '{}' is already registered", definition.name));
+        }
+        if !definition.input_schema.is_object() {
+            return Err(anyhow!(
+                "Tool '{}' needs an object input schema",
+                definition.name
+            ));
+        }
+        self.tools.insert(definition.name, tool);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn CustomTool>> {
+        self.tools.get(name).cloned()
+    }
+
+    pub fn definitions(&self) -> Vec<ToolDefinition> {
+        self.tools.values().map(|tool| tool.definition()).collect()
+    }
+}
+
+/// A tool declared in a `--tools-file`, answered by running a command in the
+/// workspace.
+///
+/// Command arguments of the form `{name}` are replaced with the call argument
+/// `name`; the tool result reports the exit code, stdout and stderr.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CommandTool {
+    pub name: String,
+    pub description: String,
+    /// The program followed by its arguments.
+    pub command: Vec<String>,
+    #[serde(default = "empty_schema")]
+    pub input_schema: Value,
+    /// Keeps the tool available in read-only mode.
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+fn empty_schema() -> Value {
+    json!({ "type": "object", "properties": {} })
+}
+
+impl CommandTool {
+    /// Reads a JSON array of tool declarations.
+    pub fn load(path: &Path) -> Result<Vec<Self>> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read tools file {}: {}", path.display(), e))?;
+        let tools: Vec<Self> = serde_json::from_str(&text)
+            .map_err(|e| anyhow!("Invalid tools file {}: {}", path.display(), e))?;
+        if let Some(tool) = tools.iter().find(|tool| tool.command.is_empty()) {
+            return Err(anyhow!("Tool '{}' has an empty command", tool.name));
+        }
+        Ok(tools)
+    }
+
+    fn command_line(&self, args: &Value) -> Result<Vec<String>> {
+        self.command
+            .iter()
+            .map(|part| {
+                let Some(name) = part.strip_prefix('{').and_then(|p| p.strip_suffix('}')) else {
+                    return Ok(part.clone());
+                };
+                match &args[name] {
+                    Value::Null => Err(ToolError::MissingArgument {
+                        argument: name.to_string(),
+                        message: format!("Missing argument '{}'", name),
+                    }
+                    .into()),
+                    Value::String(value) => Ok(value.clone()),
+                    value => Ok(value.to_string()),
+                }
+            })
+            .collect()
+    }
+}
+
+impl CustomTool for CommandTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            input_schema: self.input_schema.clone(),
+        }
+    }
+
+    fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn call<'a>(
+        &'a self,
+        context: ToolContext<'a>,
+        args: Value,
+    ) -> BoxFuture<'a, Result<ToolResult>> {
+        Box::pin(async move {
+            let command = self.command_line(&args)?;
+            let output = command_output(context.workspace_root, &command[0], &command[1..]).await?;
+
+            let result = json!({
+                "command": command,
+                "success": output.status.success(),
+                "exit_code": output.status.code(),
+                "stdout": String::from_utf8_lossy(&output.stdout),
+                "stderr": String::from_utf8_lossy(&output.stderr)
+            });
+            Ok(ToolResult {
+                content: vec![ContentItem::text(serde_json::to_string_pretty(&result)?)],
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::CommandTool;
+
+    #[test]
+    fn test_command_line_substitutes_arguments() {
+        let tool: CommandTool = serde_json::from_value(json!({
+            "name": "lint_file",
+            "description": "Lint one file",
+            "command": ["cargo", "xtask", "lint", "{file_path}", "--level={level}", "{level}"]
+        }))
+        .unwrap();
+
+        assert_eq!(
+            tool.command_line(&json!({ "file_path": "src/lib.rs", "level": 2 }))
+                .unwrap(),
+            [
+                "cargo",
+                "xtask",
+                "lint",
+                "src/lib.rs",
+                "--level={level}",
+                "2"
+            ]
+        );
+        assert!(tool.command_line(&json!({ "level": 2 })).is_err());
+        assert_eq!(tool.input_schema["type"], "object");
+    }
+}
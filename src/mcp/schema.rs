@@ -0,0 +1,176 @@
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::error::ToolError;
+
+/// Checks tool call arguments against the tool's input schema before the call
+/// reaches rust-analyzer, naming the field at fault: `Missing line`, or
+/// `character must be a number, got string`.
+///
+/// Covers the parts of JSON Schema the tool schemas use: `type`, `required`,
+/// `properties`, `items` and `enum`. Optional properties set to `null` count
+/// as absent, and properties the schema does not declare are left alone.
+pub(super) fn validate_arguments(schema: &Value, args: &Value) -> Result<()> {
+    if !args.is_object() {
+        return Err(ToolError::invalid_arguments(format!(
+            "arguments must be an object, got {}",
+            type_name(args)
+        ))
+        .into());
+    }
+    validate(schema, args, "")
+}
+
+fn validate(schema: &Value, value: &Value, path: &str) -> Result<()> {
+    if let Some(expected) = schema["type"].as_str() {
+        if !has_type(value, expected) {
+            return Err(ToolError::invalid(
+                path,
+                format!(
+                    "{} must be {} {}, got {}",
+                    path,
+                    article(expected),
+                    expected,
+                    type_name(value)
+                ),
+            )
+            .into());
+        }
+    }
+    if let Some(allowed) = schema["enum"].as_array() {
+        if !allowed.contains(value) {
+            let allowed: Vec<String> = allowed.iter().map(Value::to_string).collect();
+            return Err(ToolError::invalid(
+                path,
+                format!(
+                    "{} must be one of {}, got {}",
+                    path,
+                    allowed.join(", "),
+                    value
+                ),
+            )
+            .into());
+        }
+    }
+
+    match value {
+        Value::Object(object) => {
+            let present = |name: &str| object.get(name).is_some_and(|value| !value.is_null());
+            for name in schema["required"].as_array().into_iter().flatten() {
+                if let Some(name) = name.as_str().filter(|name| !present(name)) {
+                    return Err(ToolError::missing(&join(path, name)).into());
+                }
+            }
+            for (name, property) in schema["properties"].as_object().into_iter().flatten() {
+                if let Some(value) = object.get(name).filter(|value| !value.is_null()) {
+                    validate(property, value, &join(path, name))?;
+                }
+            }
+        }
+        Value::Array(items) if schema["items"].is_object() => {
+            for (index, item) in items.iter().enumerate() {
+                validate(&schema["items"], item, &format!("{}[{}]", path, index))?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn has_type(value: &Value, expected: &str) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn article(type_name: &str) -> &'static str {
+    if type_name.starts_with(['a', 'e', 'i', 'o', 'u']) {
+        "an"
+    } else {
+        "a"
+    }
+}
+
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", path, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::validate_arguments;
+
+    #[test]
+    fn test_validate_arguments_names_the_field_at_fault() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "file_path": { "type": "string" },
+                "line": { "type": "number" },
+                "character": { "type": "number" },
+                "format": { "type": "string", "enum": ["markdown", "json"] },
+                "positions": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": { "line": { "type": "number" } },
+                        "required": ["line"]
+                    }
+                }
+            },
+            "required": ["file_path", "line", "character"]
+        });
+        let error = |args| validate_arguments(&schema, &args).unwrap_err().to_string();
+
+        assert!(validate_arguments(
+            &schema,
+            &json!({ "file_path": "src/lib.rs", "line": 1, "character": 2, "format": null })
+        )
+        .is_ok());
+        assert_eq!(
+            error(json!({ "file_path": "src/lib.rs", "character": 2 })),
+            "Missing line"
+        );
+        assert_eq!(
+            error(json!({ "file_path": "src/lib.rs", "line": 1, "character": "2" })),
+            "character must be a number, got string"
+        );
+        assert_eq!(
+            error(json!({ "file_path": "a.rs", "line": 1, "character": 2, "format": "html" })),
+            r#"format must be one of "markdown", "json", got "html""#
+        );
+        assert_eq!(
+            error(json!({
+                "file_path": "a.rs", "line": 1, "character": 2, "positions": [{ "line": 1 }, {}]
+            })),
+            "Missing positions[1].line"
+        );
+        assert_eq!(
+            error(json!(["src/lib.rs"])),
+            "arguments must be an object, got array"
+        );
+    }
+}
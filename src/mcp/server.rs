@@ -1,20 +1,62 @@
 use anyhow::Result;
-use log::{debug, error, info};
-use serde_json::json;
-use std::{path::PathBuf, sync::Arc};
+use log::{debug, error, info, warn};
+use serde_json::{json, Value};
+use std::{
+    future::Future,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tokio::{
     io::{AsyncRead, AsyncWrite},
-    sync::Mutex,
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
 };
 
 use crate::{
-    lsp::RustAnalyzerClient,
-    protocol::mcp::{MCPError, MCPRequest, MCPResponse},
+    cargo::{detect_rust_src, ensure_rust_src, RustSrcStatus},
+    config::{ServerConfig, ISOLATED_TARGET_DIR, WARM_UP_POLL_MILLIS, WARM_UP_TIMEOUT_SECS},
+    error::ToolError,
+    lsp::{
+        path_from_uri, CancellationToken, LspTracer, Readiness, RustAnalyzerClient, WindowMessage,
+    },
+    metrics::{CallOutcome, Metrics},
+    protocol::mcp::{
+        MCPError, MCPRequest, MCPResponse, ToolDefinition, ToolResult, METHOD_NOT_FOUND,
+    },
+};
+
+use super::{
+    envelope::envelope,
+    errors::tool_error,
+    logging::{window_message_notification, LogLevel},
+    observer::MessageObserver,
+    progress::{progress_token, ProgressReporter, PARTIAL_RESULT_CHUNK_SIZE},
+    queue::{coalescing_key, ends_session, QueueLimits, QueuedRequest, RequestQueue},
+    registry::{CustomTool, ToolRegistry},
+    session::Session,
+    signal::{deadline_elapsed, shutdown_drain_deadline, shutdown_signal},
+    transport::{MessageFraming, StdioTransport},
 };
 
 pub struct RustAnalyzerMCPServer {
     pub(super) client: Option<RustAnalyzerClient>,
     pub(super) workspace_root: PathBuf,
+    pub(super) config: ServerConfig,
+    pub(super) rust_src: Option<RustSrcStatus>,
+    pub(super) metrics: Arc<Metrics>,
+    /// State of the client being served; swapped per request when several
+    /// sessions share the server.
+    pub(super) session: Session,
+    /// Tool calls are held back until rust-analyzer is ready.
+    warming_up: bool,
+    window_messages_tx: UnboundedSender<WindowMessage>,
+    pub(super) window_messages_rx: Option<UnboundedReceiver<WindowMessage>>,
+    pub(super) observers: Vec<Arc<dyn MessageObserver>>,
+    pub(super) tools: ToolRegistry,
+    /// Cancelled when a shutdown starts, so long workspace scans stop early.
+    pub(super) cancellation: CancellationToken,
+    /// Reports progress of the tool call being handled, when its client asked.
+    pub(super) progress: Option<ProgressReporter>,
 }
 
 impl Default for RustAnalyzerMCPServer {
@@ -25,13 +67,29 @@ impl Default for RustAnalyzerMCPServer {
 
 impl RustAnalyzerMCPServer {
     pub fn new() -> Self {
+        let (window_messages_tx, window_messages_rx) = mpsc::unbounded_channel();
         Self {
             client: None,
             workspace_root: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+            config: ServerConfig::default(),
+            rust_src: None,
+            metrics: Arc::new(Metrics::new()),
+            session: Session::default(),
+            warming_up: false,
+            window_messages_tx,
+            window_messages_rx: Some(window_messages_rx),
+            observers: Vec::new(),
+            tools: ToolRegistry::default(),
+            cancellation: CancellationToken::new(),
+            progress: None,
         }
     }
 
     pub fn with_workspace(workspace_root: PathBuf) -> Self {
+        Self::with_config(workspace_root, ServerConfig::default())
+    }
+
+    pub fn with_config(workspace_root: PathBuf, config: ServerConfig) -> Self {
         // Ensure the workspace root is absolute.
         let workspace_root = workspace_root.canonicalize().unwrap_or_else(|_| {
             // If canonicalize fails, try to make it absolute.
@@ -44,115 +102,545 @@ impl RustAnalyzerMCPServer {
             }
         });
 
+        let (window_messages_tx, window_messages_rx) = mpsc::unbounded_channel();
         Self {
             client: None,
             workspace_root,
+            config,
+            rust_src: None,
+            metrics: Arc::new(Metrics::new()),
+            session: Session::default(),
+            warming_up: false,
+            window_messages_tx,
+            window_messages_rx: Some(window_messages_rx),
+            observers: Vec::new(),
+            tools: ToolRegistry::default(),
+            cancellation: CancellationToken::new(),
+            progress: None,
+        }
+    }
+
+    /// Reports every request, response and notification to `observer`.
+    pub fn with_observer(mut self, observer: Arc<dyn MessageObserver>) -> Self {
+        self.observers.push(observer);
+        self
+    }
+
+    /// Adds a tool to `tools/list` and dispatches its calls. Fails when a
+    /// tool with the same name exists.
+    pub fn register_tool(&mut self, tool: Arc<dyn CustomTool>) -> Result<()> {
+        self.tools.register(tool)
+    }
+
+    /// Whether the server configuration lets `name` be listed and called.
+    pub(super) fn tool_enabled(&self, name: &str) -> bool {
+        let read_only = match self.tools.get(name) {
+            Some(tool) => tool.read_only(),
+            None => super::tools::is_read_only_tool(name),
+        };
+        self.config.tool_enabled(name, read_only)
+    }
+
+    /// The tools `tools/list` shows: the enabled ones, without those the
+    /// started rust-analyzer lacks the capability for.
+    fn listed_tools(&self) -> Vec<ToolDefinition> {
+        super::tools::get_tools(self.tools.definitions())
+            .into_iter()
+            .filter(|tool| {
+                self.tool_enabled(&tool.name) && self.missing_capability(&tool.name).is_none()
+            })
+            .collect()
+    }
+
+    /// The server capability tool `name` needs that the running rust-analyzer
+    /// does not advertise, if any.
+    pub(super) fn missing_capability(&self, name: &str) -> Option<&'static str> {
+        let capability = super::tools::required_capability(name)?;
+        let client = self.client.as_ref()?;
+        (!client.supports(capability)).then_some(capability)
+    }
+
+    /// Sends `notifications/tools/list_changed` once the tools differ from
+    /// those last listed to the client.
+    fn notify_tool_list_changes(&mut self) {
+        let Some(listed) = &self.session.listed_tools else {
+            return;
+        };
+        let tools: Vec<String> = self
+            .listed_tools()
+            .into_iter()
+            .map(|tool| tool.name)
+            .collect();
+        if *listed == tools {
+            return;
+        }
+        if let Some(notifications) = &self.session.notifications {
+            let _ = notifications.send(json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/tools/list_changed"
+            }));
+        }
+        self.session.listed_tools = Some(tools);
+    }
+
+    fn observe(&self, event: impl Fn(&dyn MessageObserver)) {
+        for observer in &self.observers {
+            event(observer.as_ref());
         }
     }
 
     pub(super) async fn ensure_client_started(&mut self) -> Result<()> {
         if self.client.is_none() {
-            let mut client = RustAnalyzerClient::new(self.workspace_root.clone());
-            client.start().await?;
+            // rust-analyzer only discovers the sysroot sources at startup, so make sure
+            // they are in place before spawning it. The mock and replay
+            // backends need no toolchain.
+            if self.config.spawns_rust_analyzer() {
+                if self.config.install_rust_src && self.config.offline {
+                    info!("Not installing rust-src in offline mode");
+                }
+                let rust_src = if self.config.install_rust_src && !self.config.offline {
+                    ensure_rust_src(&self.workspace_root).await
+                } else {
+                    detect_rust_src(&self.workspace_root).await
+                };
+                if !rust_src.installed {
+                    warn!("rust-src component not found; std definitions will be unavailable");
+                }
+                self.rust_src = Some(rust_src);
+            }
+
+            let mut builder = RustAnalyzerClient::builder(self.workspace_root.clone())
+                .backend(self.config.lsp_backend())
+                .retry_policy(self.config.retry_policy())
+                .check_on_save(!self.config.check_on_demand)
+                .offline(self.config.offline)
+                .proc_macros(!self.config.no_proc_macros)
+                .cache_priming(!self.config.no_cache_priming)
+                .metrics(Arc::clone(&self.metrics))
+                .window_messages(self.window_messages_tx.clone());
+            if let Some(parallelism) = self.config.diagnostics_parallelism {
+                builder = builder.diagnostics_parallelism(parallelism);
+            }
+            if let Some(millis) = self.config.save_debounce_ms {
+                builder = builder.save_debounce(Duration::from_millis(millis));
+            }
+            if self.config.isolated_target_dir {
+                builder = builder.target_dir(self.workspace_root.join(ISOLATED_TARGET_DIR));
+            }
+            if let Some(toolchain) = &self.config.toolchain {
+                builder = builder.toolchain(toolchain);
+            }
+            if let Some(sysroot) = &self.config.sysroot {
+                builder = builder.sysroot(sysroot);
+            }
+            if let Some(source) = &self.config.rustc_source {
+                builder = builder.rustc_source(source);
+            }
+            if let Some(threads) = self.config.num_threads {
+                builder = builder.num_threads(threads);
+            }
+            if let Some(capacity) = self.config.lru_capacity {
+                builder = builder.lru_capacity(capacity);
+            }
+            if let Some(threads) = self.config.cache_priming_threads {
+                builder = builder.cache_priming_threads(threads);
+            }
+            if let Some(path) = &self.config.proc_macro_server {
+                builder = builder.proc_macro_server(path);
+            }
+            for (krate, name) in &self.config.ignored_proc_macros {
+                builder = builder.ignore_proc_macro(krate, name);
+            }
+            for dir in &self.config.exclude_dirs {
+                builder = builder.exclude_dir(dir);
+            }
+            for (name, value) in &self.config.lsp_env {
+                builder = builder.env(name, value);
+            }
+            if let Some(trace_path) = &self.config.trace_lsp {
+                builder = builder.lsp_trace(Arc::new(LspTracer::open(trace_path)?));
+            }
+            let client = builder.start().await?;
+            self.metrics.record_rust_analyzer_start();
             self.client = Some(client);
         }
         Ok(())
     }
 
-    pub(super) async fn open_document_if_needed(&mut self, file_path: &str) -> Result<String> {
-        let absolute_path = self.workspace_root.join(file_path);
+    /// Resolves a tool `file_path` argument to an absolute path. Accepts paths
+    /// relative to the workspace, absolute paths, and `file://` URIs as returned
+    /// by definition and reference results.
+    pub(super) fn resolve_file_path(&self, file_path: &str) -> PathBuf {
+        let path = path_from_uri(file_path).unwrap_or_else(|| PathBuf::from(file_path));
+        let absolute_path = self.workspace_root.join(path);
         // Ensure we have an absolute path for the URI.
-        let absolute_path = absolute_path
-            .canonicalize()
-            .unwrap_or_else(|_| absolute_path.clone());
+        canonicalize_lenient(&absolute_path)
+    }
+
+    /// Runs a tool and records the call in the server metrics.
+    pub(super) async fn call_tool(
+        &mut self,
+        tool_name: &str,
+        args: Value,
+        progress: Option<ProgressReporter>,
+    ) -> Result<ToolResult> {
+        let started = Instant::now();
+        let result = super::handlers::handle_tool_call(self, tool_name, args, progress).await;
+        let outcome = match &result {
+            Ok(_) => CallOutcome::Ok,
+            Err(e) if is_timeout_error(e) => CallOutcome::Timeout,
+            Err(_) => CallOutcome::Error,
+        };
+        self.metrics
+            .record_tool_call(tool_name, started.elapsed(), outcome);
+        result
+    }
+
+    /// The result of a tool that can return many items. When the call carries a
+    /// progress token and the items do not fit in one chunk, they are streamed
+    /// as partial-result notifications and only a summary is returned.
+    pub(super) fn stream_large_result(&self, items: Vec<Value>) -> Value {
+        match &self.progress {
+            Some(reporter) if items.len() > PARTIAL_RESULT_CHUNK_SIZE => {
+                let chunks = reporter.stream_items(&items);
+                json!({ "streamed": true, "total": items.len(), "chunks": chunks })
+            }
+            _ => Value::Array(items),
+        }
+    }
+
+    /// The result of a search tool, cut to `max_results` if given. A cut
+    /// result is wrapped with the total number of results and how many were
+    /// omitted.
+    pub(super) fn search_result(&self, mut items: Vec<Value>, max_results: Option<usize>) -> Value {
+        let Some(max) = max_results else {
+            return self.stream_large_result(items);
+        };
+        let total = items.len();
+        items.truncate(max);
+        let omitted = total - items.len();
+
+        let mut result = match self.stream_large_result(items) {
+            Value::Array(items) => json!({ "results": items }),
+            summary => summary,
+        };
+        result["total"] = json!(total);
+        result["omitted"] = json!(omitted);
+        result
+    }
+
+    /// Reports progress of the running tool call if its client asked for it.
+    pub(super) fn report_progress(&self, progress: usize, total: Option<usize>, message: &str) {
+        if let Some(reporter) = &self.progress {
+            reporter.report(progress, total, message);
+        }
+    }
+
+    pub(super) async fn open_document_if_needed(&mut self, file_path: &str) -> Result<String> {
+        let absolute_path = self.resolve_file_path(file_path);
         let uri = format!("file://{}", absolute_path.display());
         let content = tokio::fs::read_to_string(&absolute_path)
             .await
-            .map_err(|e| anyhow::anyhow!("Failed to read file {}: {}", file_path, e))?;
+            .map_err(|e| ToolError::FileNotFound {
+                path: file_path.to_string(),
+                reason: e.to_string(),
+            })?;
 
         let Some(client) = &mut self.client else {
-            return Err(anyhow::anyhow!("Client not initialized"));
+            return Err(ToolError::client_not_initialized().into());
         };
 
-        client.open_document(&uri, &content).await?;
+        // Files outside the workspace (dependencies, std) are only browsed, never saved.
+        if absolute_path.starts_with(&self.workspace_root) {
+            client.open_document(&uri, &content).await?;
+        } else {
+            client.open_read_only_document(&uri, &content).await?;
+        }
         Ok(uri)
     }
 
+    /// Serves MCP over stdin and stdout until the client disconnects, sends
+    /// `shutdown` or `exit`, or the process receives Ctrl-C.
     pub async fn run(&mut self) -> Result<()> {
-        let stdin = tokio::io::stdin();
-        let stdout = tokio::io::stdout();
-        self.run_with_streams(stdin, stdout).await
+        self.run_until(tokio::io::stdin(), tokio::io::stdout(), shutdown_signal())
+            .await
     }
 
+    /// Serves MCP over any pair of byte streams, such as a socket or an
+    /// in-memory pipe, until the client closes `reader` or sends `shutdown` or
+    /// `exit`. Messages may use newline-delimited JSON or `Content-Length`
+    /// framing.
     pub async fn run_with_streams<R, W>(&mut self, reader: R, writer: W) -> Result<()>
     where
         R: AsyncRead + Unpin,
         W: AsyncWrite + Unpin,
+    {
+        self.run_until(reader, writer, std::future::pending()).await
+    }
+
+    /// Like [`run_with_streams`](Self::run_with_streams), but also stops when
+    /// `shutdown` completes. A request being handled at that moment is
+    /// answered first. rust-analyzer is shut down in either case.
+    pub async fn run_until<R, W, S>(&mut self, reader: R, writer: W, shutdown: S) -> Result<()>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+        S: Future<Output = ()>,
     {
         info!("Starting rust-analyzer MCP server");
+        let result = self.serve_requests(reader, writer, shutdown).await;
 
-        let mut transport = super::transport::StdioTransport::new(reader, writer);
+        // rust-analyzer must not outlive the server, however serving ended.
+        info!("Shutting down");
+        self.shutdown_client().await;
+        result
+    }
 
-        // Handle shutdown signals.
-        let running = Arc::new(Mutex::new(true));
-        let running_clone = Arc::clone(&running);
+    async fn serve_requests<R, W, S>(&mut self, reader: R, writer: W, shutdown: S) -> Result<()>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+        S: Future<Output = ()>,
+    {
+        let mut transport = StdioTransport::new(reader, writer);
+        let mut window_messages = self.window_messages_rx.take();
+        let (notifications_tx, mut notifications) = mpsc::unbounded_channel();
+        self.session.notifications = Some(notifications_tx);
+        let mut queue = RequestQueue::new(QueueLimits::from_config(&self.config));
+        let observers = self.observers.clone();
+        tokio::pin!(shutdown);
+        let mut stopping = false;
 
-        tokio::spawn(async move {
-            let _ = tokio::signal::ctrl_c().await;
-            info!("Received shutdown signal");
-            *running_clone.lock().await = false;
-        });
+        // In warm-up mode rust-analyzer starts right away, while messages keep
+        // being read so `initialize` is answered as soon as it is running.
+        let warm_up_deadline = Instant::now() + Duration::from_secs(WARM_UP_TIMEOUT_SECS);
+        if self.config.warm_up {
+            info!("Warming up rust-analyzer before serving tools");
+            let started = {
+                let starting = self.ensure_client_started();
+                tokio::pin!(starting);
+                loop {
+                    tokio::select! {
+                        started = &mut starting => break started,
+                        message = transport.read_message(), if !queue.is_closed() => {
+                            queue.accept(message);
+                        }
+                    }
+                }
+            };
+            match started {
+                Ok(()) => self.warming_up = true,
+                Err(e) => error!("Failed to start rust-analyzer for warm-up: {e}"),
+            }
+        }
 
-        loop {
-            // Check if we should stop.
-            if !*running.lock().await {
+        'requests: loop {
+            if stopping || !answer_rejected(&mut transport, &mut queue, &observers).await {
                 break;
             }
+            if self.warming_up {
+                if self.client_ready().await {
+                    info!("rust-analyzer is ready; serving tools");
+                    self.warming_up = false;
+                } else if Instant::now() >= warm_up_deadline {
+                    warn!(
+                        "rust-analyzer not ready after {WARM_UP_TIMEOUT_SECS}s; serving tools anyway"
+                    );
+                    self.warming_up = false;
+                }
+            }
 
-            let Some((request_text, framing)) = (match transport.read_message().await {
-                Ok(message) => message,
-                Err(e) => {
-                    error!("Error reading MCP message: {e}");
+            let next = if self.warming_up {
+                queue.pop_control()
+            } else {
+                queue.pop()
+            };
+            let Some(QueuedRequest {
+                request, framing, ..
+            }) = next
+            else {
+                if queue.is_closed() && !self.warming_up {
                     break;
                 }
-            }) else {
-                break;
+
+                tokio::select! {
+                    message = transport.read_message(), if !queue.is_closed() => {
+                        queue.accept(message);
+                    }
+                    _ = tokio::time::sleep(Duration::from_millis(WARM_UP_POLL_MILLIS)),
+                        if self.warming_up => {}
+                    _ = &mut shutdown => stopping = true,
+                    Some(window_message) = next_window_message(&mut window_messages) => {
+                        let Some(notification) =
+                            window_message_notification(&window_message, self.session.log_level)
+                        else {
+                            continue;
+                        };
+                        self.observe(|observer| observer.on_notification(&notification));
+                        if let Err(err) = transport
+                            .write_message(&notification.to_string(), queue.last_framing())
+                            .await
+                        {
+                            error!("Error writing MCP notification: {err}");
+                            break;
+                        }
+                    }
+                }
+                continue;
             };
 
-            let request_text = request_text.trim();
-            if request_text.is_empty() {
+            self.observe(|observer| observer.on_request(&request));
+            let ends_session = ends_session(&request);
+
+            // requests without an id are notifications and must not receive a response!
+            if request.id.is_none() {
+                stopping |= ends_session;
                 continue;
             }
 
-            let Ok(request) = serde_json::from_str::<MCPRequest>(request_text) else {
-                debug!("Failed to parse request: {request_text}");
-                continue;
+            let key = coalescing_key(&request);
+            let id = request.id.clone();
+
+            // Keep reading while the request is handled so that urgent requests
+            // arriving meanwhile are scheduled ahead of queued batch work. Once
+            // shutting down, the request gets a while to finish and no more
+            // messages are read.
+            let mut drain_deadline = None;
+            let cancellation = self.cancellation.clone();
+            let response = {
+                let handling = self.handle_request(request);
+                tokio::pin!(handling);
+                loop {
+                    tokio::select! {
+                        response = &mut handling => break response,
+                        message = transport.read_message(), if !queue.is_closed() && !stopping => {
+                            queue.accept(message);
+                            if !answer_rejected(&mut transport, &mut queue, &observers).await {
+                                break 'requests;
+                            }
+                        }
+                        Some(notification) = notifications.recv() => {
+                            let forwarded = forward_notification(
+                                &mut transport,
+                                &observers,
+                                &notification,
+                                framing,
+                            );
+                            if !forwarded.await {
+                                break 'requests;
+                            }
+                        }
+                        _ = &mut shutdown, if !stopping => {
+                            stopping = true;
+                            cancellation.cancel();
+                            drain_deadline = Some(shutdown_drain_deadline());
+                        }
+                        _ = deadline_elapsed(drain_deadline) => {
+                            warn!("Cancelling an unfinished request to shut down");
+                            break MCPResponse::shutting_down(id);
+                        }
+                    }
+                }
             };
 
-            debug!("Received request: {}", request.method);
-            log::debug!("{request:#?}");
+            // Progress reported right before the call finished goes out first.
+            while let Ok(notification) = notifications.try_recv() {
+                if !forward_notification(&mut transport, &observers, &notification, framing).await {
+                    break 'requests;
+                }
+            }
 
-            // requests without an id are notifications and must not receive a response!
-            if request.id.is_some() {
-                let response = self.handle_request(request).await;
+            self.observe(|observer| observer.on_response(&response));
+            let response_json = serde_json::to_string(&response)?;
+            if let Err(err) = transport.write_message(&response_json, framing).await {
+                error!("Error writing MCP response: {err}");
+                break;
+            }
+            if ends_session {
+                info!("Client asked the server to stop");
+                stopping = true;
+                continue;
+            }
+
+            // Identical calls that arrived meanwhile get the same answer instead
+            // of another round trip to rust-analyzer.
+            let Some(key) = key else {
+                continue;
+            };
+            let duplicates = queue.take_duplicates(&key);
+            if duplicates.is_empty() {
+                continue;
+            }
+            debug!("Answering {} duplicate {} calls", duplicates.len(), key.0);
+            for duplicate in duplicates {
+                self.observe(|observer| observer.on_request(&duplicate.request));
+                let response = response.with_id(duplicate.request.id.clone());
+                self.observe(|observer| observer.on_response(&response));
+                let response_json = serde_json::to_string(&response)?;
+                if let Err(err) = transport
+                    .write_message(&response_json, duplicate.framing)
+                    .await
+                {
+                    error!("Error writing MCP response: {err}");
+                    break 'requests;
+                }
+            }
+        }
+
+        if stopping {
+            // Requests already read are answered rather than left hanging.
+            for queued in queue.drain() {
+                self.observe(|observer| observer.on_request(&queued.request));
+                if queued.request.id.is_none() {
+                    continue;
+                }
+                let response = MCPResponse::shutting_down(queued.request.id);
+                self.observe(|observer| observer.on_response(&response));
                 let response_json = serde_json::to_string(&response)?;
-                if let Err(err) = transport.write_message(&response_json, framing).await {
+                if let Err(err) = transport
+                    .write_message(&response_json, queued.framing)
+                    .await
+                {
                     error!("Error writing MCP response: {err}");
                     break;
                 }
             }
         }
 
-        // Cleanup.
-        info!("Shutting down");
+        Ok(())
+    }
+
+    /// Shuts down rust-analyzer, if it was started, and waits for it to exit.
+    pub(super) async fn shutdown_client(&mut self) {
         if let Some(client) = &mut self.client {
-            let _ = client.shutdown().await;
+            if let Err(e) = client.shutdown().await {
+                warn!("Failed to shut down rust-analyzer: {e}");
+            }
+        }
+    }
+
+    async fn client_ready(&self) -> bool {
+        match &self.client {
+            Some(client) => client.is_ready().await,
+            None => false,
         }
+    }
 
-        Ok(())
+    /// rust-analyzer's indexing progress, or not ready if it was not started yet.
+    pub(super) async fn readiness(&self) -> Readiness {
+        match &self.client {
+            Some(client) => client.readiness().await,
+            None => Readiness::not_started(),
+        }
+    }
+
+    pub(super) async fn handle_request(&mut self, request: MCPRequest) -> MCPResponse {
+        let response = self.respond(request).await;
+        self.notify_tool_list_changes();
+        response
     }
 
-    async fn handle_request(&mut self, request: MCPRequest) -> MCPResponse {
+    async fn respond(&mut self, request: MCPRequest) -> MCPResponse {
         log::debug!("{request:#?}");
         match request.method.as_str() {
             "initialize" => {
@@ -162,6 +650,7 @@ impl RustAnalyzerMCPServer {
                     .and_then(|params| params.get("protocolVersion"))
                     .and_then(|version| version.as_str())
                     .unwrap_or("2024-11-05");
+                let readiness = self.readiness().await;
 
                 MCPResponse::Success {
                     jsonrpc: "2.0".to_string(),
@@ -173,23 +662,101 @@ impl RustAnalyzerMCPServer {
                             "version": env!("CARGO_PKG_VERSION")
                         },
                         "capabilities": {
-                            "tools": {}
+                            "tools": { "listChanged": true },
+                            "logging": {},
+                            "completions": {},
+                            "prompts": {}
+                        },
+                        "readiness": {
+                            "ready": readiness.ready,
+                            "percentage": readiness.percentage,
+                            "warmingUp": self.warming_up
                         }
                     }),
                 }
             }
+            "logging/setLevel" => {
+                let level = request
+                    .params
+                    .as_ref()
+                    .and_then(|params| params["level"].as_str())
+                    .and_then(LogLevel::parse);
+                let Some(level) = level else {
+                    return MCPResponse::Error {
+                        jsonrpc: "2.0".to_string(),
+                        id: request.id,
+                        error: MCPError {
+                            code: -32602,
+                            message: "Invalid log level".to_string(),
+                            data: None,
+                        },
+                    };
+                };
+
+                self.session.log_level = level;
+                MCPResponse::Success {
+                    jsonrpc: "2.0".to_string(),
+                    id: request.id,
+                    result: json!({}),
+                }
+            }
+            "completion/complete" => {
+                let params = request.params.unwrap_or_default();
+                match super::completions::complete_argument(self, &params).await {
+                    Ok(result) => MCPResponse::Success {
+                        jsonrpc: "2.0".to_string(),
+                        id: request.id,
+                        result,
+                    },
+                    Err(e) => MCPResponse::Error {
+                        jsonrpc: "2.0".to_string(),
+                        id: request.id,
+                        error: tool_error("completion/complete", &e, &self.workspace_root),
+                    },
+                }
+            }
+            "prompts/list" => MCPResponse::Success {
+                jsonrpc: "2.0".to_string(),
+                id: request.id,
+                result: json!({ "prompts": super::prompts::prompt_definitions() }),
+            },
+            "prompts/get" => {
+                let params = request.params.unwrap_or_default();
+                match super::prompts::get_prompt(self, &params).await {
+                    Ok(result) => MCPResponse::Success {
+                        jsonrpc: "2.0".to_string(),
+                        id: request.id,
+                        result,
+                    },
+                    Err(e) => MCPResponse::Error {
+                        jsonrpc: "2.0".to_string(),
+                        id: request.id,
+                        error: tool_error("prompts/get", &e, &self.workspace_root),
+                    },
+                }
+            }
             "ping" => MCPResponse::Success {
                 jsonrpc: "2.0".to_string(),
                 id: request.id,
                 result: json!({}),
             },
-            "tools/list" => MCPResponse::Success {
+            // The serving loop stops after answering; rust-analyzer is shut
+            // down on the way out.
+            "shutdown" | "exit" => MCPResponse::Success {
                 jsonrpc: "2.0".to_string(),
                 id: request.id,
-                result: json!({
-                    "tools": super::tools::get_tools()
-                }),
+                result: Value::Null,
             },
+            "tools/list" => {
+                let tools = self.listed_tools();
+                let names = tools.iter().map(|tool| tool.name.clone()).collect();
+                self.session.listed_tools = Some(names);
+                MCPResponse::Success {
+                    jsonrpc: "2.0".to_string(),
+                    id: request.id,
+                    result: json!({ "tools": tools }),
+                }
+            }
             "tools/call" => {
                 let Some(params) = request.params else {
                     return MCPResponse::Error {
@@ -219,8 +786,18 @@ impl RustAnalyzerMCPServer {
                     .get("arguments")
                     .cloned()
                     .unwrap_or_else(|| json!({}));
+                let progress = progress_token(&params)
+                    .zip(self.session.notifications.clone())
+                    .map(|(token, notifications)| ProgressReporter::new(token, notifications));
 
-                match super::handlers::handle_tool_call(self, tool_name, args).await {
+                let result = self.call_tool(tool_name, args, progress).await;
+                let result = match result {
+                    Ok(result) if self.config.response_envelope => {
+                        Ok(envelope(tool_name, &self.workspace_root, result))
+                    }
+                    result => result,
+                };
+                match result {
                     Ok(result) => MCPResponse::Success {
                         jsonrpc: "2.0".to_string(),
                         id: request.id,
@@ -231,11 +808,7 @@ impl RustAnalyzerMCPServer {
                         MCPResponse::Error {
                             jsonrpc: "2.0".to_string(),
                             id: request.id,
-                            error: MCPError {
-                                code: -1,
-                                message: e.to_string(),
-                                data: None,
-                            },
+                            error: tool_error(tool_name, &e, &self.workspace_root),
                         }
                     }
                 }
@@ -244,7 +817,7 @@ impl RustAnalyzerMCPServer {
                 jsonrpc: "2.0".to_string(),
                 id: request.id,
                 error: MCPError {
-                    code: -32601,
+                    code: METHOD_NOT_FOUND,
                     message: format!("Method not found: {}", request.method),
                     data: None,
                 },
@@ -253,15 +826,155 @@ impl RustAnalyzerMCPServer {
     }
 }
 
+/// Waits for the next forwarded rust-analyzer message; never resolves once the
+/// receiver has been taken by another run.
+pub(super) async fn next_window_message(
+    receiver: &mut Option<UnboundedReceiver<WindowMessage>>,
+) -> Option<WindowMessage> {
+    match receiver {
+        Some(receiver) => receiver.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Writes a notification raised while a request was handled, returning
+/// whether the transport can still be written to.
+async fn forward_notification<R, W>(
+    transport: &mut StdioTransport<R, W>,
+    observers: &[Arc<dyn MessageObserver>],
+    notification: &Value,
+    framing: MessageFraming,
+) -> bool
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    for observer in observers {
+        observer.on_notification(notification);
+    }
+    match transport
+        .write_message(&notification.to_string(), framing)
+        .await
+    {
+        Ok(()) => true,
+        Err(err) => {
+            error!("Error writing MCP notification: {err}");
+            false
+        }
+    }
+}
+
+/// Answers the requests `queue` refused because the client has too much work
+/// waiting, returning whether the transport can still be written to.
+pub(super) async fn answer_rejected<R, W>(
+    transport: &mut StdioTransport<R, W>,
+    queue: &mut RequestQueue,
+    observers: &[Arc<dyn MessageObserver>],
+) -> bool
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    for rejected in queue.take_rejected() {
+        for observer in observers {
+            observer.on_request(&rejected.request);
+            observer.on_response(&rejected.response);
+        }
+        let response_json = match serde_json::to_string(&rejected.response) {
+            Ok(json) => json,
+            Err(err) => {
+                error!("Error serializing MCP response: {err}");
+                return false;
+            }
+        };
+        if let Err(err) = transport
+            .write_message(&response_json, rejected.framing)
+            .await
+        {
+            error!("Error writing MCP response: {err}");
+            return false;
+        }
+    }
+    true
+}
+
+/// Canonicalizes `path` even when it does not exist yet, as for the target of a
+/// rename: the nearest existing ancestor is canonicalized, resolving symlinks,
+/// and the missing components are appended with `..` and `.` applied. The
+/// result has no `..` left, so `starts_with` checks against it are sound.
+fn canonicalize_lenient(path: &Path) -> PathBuf {
+    let Some((ancestor, canonical)) = path
+        .ancestors()
+        .find_map(|ancestor| Some((ancestor, ancestor.canonicalize().ok()?)))
+    else {
+        return path.to_path_buf();
+    };
+    let mut resolved = canonical;
+    for component in path.strip_prefix(ancestor).unwrap_or(path).components() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::Normal(name) => resolved.push(name),
+            _ => {}
+        }
+    }
+    resolved
+}
+
+/// Whether a tool failed because an LSP request or a cargo command timed out.
+fn is_timeout_error(error: &anyhow::Error) -> bool {
+    matches!(
+        error.downcast_ref::<ToolError>(),
+        Some(ToolError::Timeout(_))
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use anyhow::{anyhow, Result};
     use serde_json::{json, Value};
-    use std::time::Duration;
+    use std::{sync::Arc, time::Duration};
     use tokio::io::{duplex, split, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
     use tokio::time::timeout;
 
-    use super::RustAnalyzerMCPServer;
+    use super::{canonicalize_lenient, RustAnalyzerMCPServer};
+    use crate::{
+        config::ServerConfig,
+        lsp::WindowMessage,
+        mcp::MessageObserver,
+        protocol::mcp::{MCPRequest, MCPResponse, INVALID_PARAMS},
+    };
+
+    #[test]
+    fn test_canonicalize_lenient_resolves_paths_that_do_not_exist_yet() -> Result<()> {
+        let root = tempfile::tempdir()?;
+        let root = root.path().canonicalize()?;
+        let workspace = root.join("ws");
+        std::fs::create_dir_all(workspace.join("src"))?;
+
+        assert_eq!(
+            canonicalize_lenient(&workspace.join("src/new.rs")),
+            workspace.join("src/new.rs")
+        );
+        assert_eq!(
+            canonicalize_lenient(&workspace.join("../escape.rs")),
+            root.join("escape.rs")
+        );
+        assert_eq!(
+            canonicalize_lenient(&workspace.join("missing/../../escape.rs")),
+            root.join("escape.rs")
+        );
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(&root, workspace.join("link"))?;
+            assert_eq!(
+                canonicalize_lenient(&workspace.join("link/escape.rs")),
+                root.join("escape.rs")
+            );
+        }
+        Ok(())
+    }
 
     #[tokio::test]
     async fn test_content_length_requests_are_handled_without_eof() -> Result<()> {
@@ -380,16 +1093,1439 @@ mod tests {
         Ok(())
     }
 
-    async fn write_content_length_message<W>(writer: &mut W, body: &str) -> Result<()>
-    where
-        W: AsyncWrite + Unpin,
-    {
-        let frame = format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
-        writer.write_all(frame.as_bytes()).await?;
-        writer.flush().await?;
+    /// Records the method of each request and the id of each response.
+    #[derive(Default)]
+    struct RecordingObserver {
+        events: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl MessageObserver for RecordingObserver {
+        fn on_request(&self, request: &MCPRequest) {
+            self.events.lock().unwrap().push(request.method.clone());
+        }
+
+        fn on_response(&self, response: &MCPResponse) {
+            let id = match response {
+                MCPResponse::Success { id, .. } | MCPResponse::Error { id, .. } => id,
+            };
+            let id = id.clone().unwrap_or_default();
+            self.events.lock().unwrap().push(format!("response {id}"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_embedded_server_reports_traffic_and_stops_on_shutdown() -> Result<()> {
+        let (client_io, server_io) = duplex(16 * 1024);
+        let (server_reader, server_writer) = split(server_io);
+        let observer = Arc::new(RecordingObserver::default());
+        let mut server = RustAnalyzerMCPServer::new().with_observer(observer.clone());
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+
+        let server_task = tokio::spawn(async move {
+            server
+                .run_until(server_reader, server_writer, async {
+                    let _ = stopped.await;
+                })
+                .await
+        });
+
+        let (mut client_reader, mut client_writer) = split(client_io);
+        for message in [
+            json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }),
+            json!({ "jsonrpc": "2.0", "id": 3, "method": "ping" }),
+        ] {
+            write_content_length_message(&mut client_writer, &message.to_string()).await?;
+        }
+        let ping = timeout(Duration::from_secs(1), read_response(&mut client_reader)).await??;
+        assert_eq!(ping["id"], 3);
+
+        // The client keeps its end open; the shutdown future alone ends the run.
+        stop.send(()).map_err(|_| anyhow!("server stopped early"))?;
+        timeout(Duration::from_secs(1), server_task).await???;
+
+        assert_eq!(
+            *observer.events.lock().unwrap(),
+            ["notifications/initialized", "ping", "response 3"]
+        );
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_shutdown_request_stops_serving_without_eof() -> Result<()> {
+        let (client_io, server_io) = duplex(16 * 1024);
+        let (server_reader, server_writer) = split(server_io);
+        let mut server = RustAnalyzerMCPServer::new();
+
+        let server_task =
+            tokio::spawn(
+                async move { server.run_with_streams(server_reader, server_writer).await },
+            );
+
+        let (mut client_reader, mut client_writer) = split(client_io);
+        for message in [
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "ping" }),
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "shutdown" }),
+        ] {
+            write_content_length_message(&mut client_writer, &message.to_string()).await?;
+        }
+
+        let ping = timeout(Duration::from_secs(1), read_response(&mut client_reader)).await??;
+        assert_eq!(ping["id"], 1);
+        let shutdown = timeout(Duration::from_secs(1), read_response(&mut client_reader)).await??;
+        assert_eq!(shutdown["id"], 2);
+        assert!(shutdown["result"].is_null(), "{shutdown}");
+
+        // The client still holds its end open; the shutdown request alone ends serving.
+        timeout(Duration::from_secs(5), server_task).await???;
+        drop(client_writer);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_window_messages_are_forwarded_as_log_notifications() -> Result<()> {
+        let (client_io, server_io) = duplex(16 * 1024);
+        let (server_reader, server_writer) = split(server_io);
+        let mut server = RustAnalyzerMCPServer::new();
+        let window_messages = server.window_messages_tx.clone();
+
+        let server_task =
+            tokio::spawn(
+                async move { server.run_with_streams(server_reader, server_writer).await },
+            );
+
+        let (mut client_reader, mut client_writer) = split(client_io);
+
+        let set_level = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "logging/setLevel",
+            "params": { "level": "warning" }
+        });
+        write_content_length_message(&mut client_writer, &set_level.to_string()).await?;
+        let response = timeout(
+            Duration::from_secs(1),
+            read_content_length_message(&mut client_reader),
+        )
+        .await??;
+        let response: Value = serde_json::from_str(&response)?;
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["result"], json!({}));
+
+        for (message_type, text) in [(3, "Indexing"), (1, "Failed to load workspace")] {
+            window_messages.send(WindowMessage {
+                message_type,
+                method: "window/showMessage".to_string(),
+                text: text.to_string(),
+            })?;
+        }
+
+        let notification = timeout(
+            Duration::from_secs(1),
+            read_content_length_message(&mut client_reader),
+        )
+        .await??;
+        let notification: Value = serde_json::from_str(&notification)?;
+        assert_eq!(notification["method"], "notifications/message");
+        assert_eq!(notification["params"]["level"], "error");
+        assert_eq!(
+            notification["params"]["data"]["message"],
+            "Failed to load workspace"
+        );
+
+        client_writer.shutdown().await?;
+        drop(client_writer);
+        drop(client_reader);
+        server_task.await??;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_identical_pending_hovers_share_one_call() -> Result<()> {
+        let workspace = tempfile::tempdir()?;
+        let (client_io, server_io) = duplex(16 * 1024);
+        let (server_reader, server_writer) = split(server_io);
+        let config = ServerConfig {
+            mock: true,
+            ..ServerConfig::default()
+        };
+        let mut server = RustAnalyzerMCPServer::with_config(workspace.path().to_path_buf(), config);
+
+        let server_task =
+            tokio::spawn(
+                async move { server.run_with_streams(server_reader, server_writer).await },
+            );
+
+        let (mut client_reader, mut client_writer) = split(client_io);
+
+        for id in [1, 2] {
+            let hover = json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": "tools/call",
+                "params": {
+                    "name": "rust_analyzer_hover",
+                    "arguments": { "file_path": "src/missing.rs", "line": 0, "character": 0 }
+                }
+            });
+            write_content_length_message(&mut client_writer, &hover.to_string()).await?;
+        }
+
+        let mut ids = Vec::new();
+        for _ in 0..2 {
+            let response =
+                timeout(Duration::from_secs(30), read_response(&mut client_reader)).await??;
+            ids.push(response["id"].as_u64().unwrap());
+        }
+        ids.sort_unstable();
+        assert_eq!(ids, [1, 2]);
+
+        let stats = json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "method": "tools/call",
+            "params": { "name": "rust_analyzer_server_stats", "arguments": {} }
+        });
+        write_content_length_message(&mut client_writer, &stats.to_string()).await?;
+        let response = timeout(Duration::from_secs(5), read_response(&mut client_reader)).await??;
+        let stats: Value =
+            serde_json::from_str(response["result"]["content"][0]["text"].as_str().unwrap())?;
+        assert_eq!(stats["tools"]["rust_analyzer_hover"]["calls"], 1);
+
+        client_writer.shutdown().await?;
+        drop(client_writer);
+        drop(client_reader);
+        server_task.await??;
+
+        Ok(())
+    }
+
+    /// A workspace with one source file, served by a server on the mock
+    /// backend.
+    #[cfg(feature = "mock")]
+    struct MockServer {
+        workspace: tempfile::TempDir,
+        reader: tokio::io::ReadHalf<tokio::io::DuplexStream>,
+        writer: tokio::io::WriteHalf<tokio::io::DuplexStream>,
+        task: tokio::task::JoinHandle<Result<()>>,
+    }
+
+    #[cfg(feature = "mock")]
+    impl MockServer {
+        fn start() -> Result<Self> {
+            let workspace = tempfile::tempdir()?;
+            std::fs::create_dir(workspace.path().join("src"))?;
+            std::fs::write(
+                workspace.path().join("src/lib.rs"),
+                "pub fn answer() -> u32 { 42 }\n",
+            )?;
+
+            let (client_io, server_io) = duplex(16 * 1024);
+            let (server_reader, server_writer) = split(server_io);
+            let config = ServerConfig {
+                mock: true,
+                ..ServerConfig::default()
+            };
+            let mut server =
+                RustAnalyzerMCPServer::with_config(workspace.path().to_path_buf(), config);
+            let task =
+                tokio::spawn(
+                    async move { server.run_with_streams(server_reader, server_writer).await },
+                );
+            let (reader, writer) = split(client_io);
+            Ok(Self {
+                workspace,
+                reader,
+                writer,
+                task,
+            })
+        }
+
+        /// Calls a tool and parses the JSON text of its first content item.
+        async fn call_json(&mut self, name: &str, arguments: Value) -> Result<Value> {
+            let response = call_tool(&mut self.writer, &mut self.reader, name, arguments).await?;
+            let text = response["result"]["content"][0]["text"]
+                .as_str()
+                .ok_or_else(|| anyhow!("No text content in {response}"))?;
+            Ok(serde_json::from_str(text)?)
+        }
+
+        async fn stop(mut self) -> Result<()> {
+            self.writer.shutdown().await?;
+            drop(self.writer);
+            drop(self.reader);
+            self.task.await?
+        }
+    }
+
+    /// Sends a `tools/call` request and returns the response.
+    #[cfg(feature = "mock")]
+    async fn call_tool<W, R>(
+        writer: &mut W,
+        reader: &mut R,
+        name: &str,
+        arguments: Value,
+    ) -> Result<Value>
+    where
+        W: AsyncWrite + Unpin,
+        R: AsyncRead + Unpin,
+    {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": { "name": name, "arguments": arguments }
+        });
+        write_content_length_message(writer, &request.to_string()).await?;
+        timeout(Duration::from_secs(5), read_response(reader)).await?
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_mock_hover_offers_follow_ups() -> Result<()> {
+        let mut server = MockServer::start()?;
+        let response = call_tool(
+            &mut server.writer,
+            &mut server.reader,
+            "rust_analyzer_hover",
+            json!({ "file_path": "src/lib.rs", "line": 0, "character": 7 }),
+        )
+        .await?;
+        let text = response["result"]["content"][0]["text"].as_str().unwrap();
+        assert!(text.contains("fn mock_function() -> u32"), "{text}");
+        let hover: Value = serde_json::from_str(text)?;
+        assert_eq!(hover["follow_ups"][0]["tool"], "rust_analyzer_references");
+        assert_eq!(hover["follow_ups"][0]["count"], 1);
+        server.stop().await
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_mock_document_links() -> Result<()> {
+        let mut server = MockServer::start()?;
+        let links = server
+            .call_json(
+                "rust_analyzer_document_links",
+                json!({ "file_path": "src/lib.rs" }),
+            )
+            .await?;
+        assert_eq!(links[0]["target"], "https://docs.rs/mock/latest/mock/");
+        assert_eq!(links[0]["range"]["start"]["character"], 4);
+        server.stop().await
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_mock_highlights() -> Result<()> {
+        let mut server = MockServer::start()?;
+        let highlights = server
+            .call_json(
+                "rust_analyzer_highlights",
+                json!({ "file_path": "src/lib.rs", "line": 0, "character": 7 }),
+            )
+            .await?;
+        assert_eq!(highlights[0]["kind"], "text");
+        assert_eq!(highlights[0]["range"]["start"]["character"], 7);
+        server.stop().await
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_mock_item_docs() -> Result<()> {
+        let mut server = MockServer::start()?;
+        let docs = server
+            .call_json(
+                "rust_analyzer_item_docs",
+                json!({ "path": "crate::lib::mock_function" }),
+            )
+            .await?;
+        assert_eq!(docs["signature"], "fn mock_function() -> u32");
+        assert_eq!(docs["kind"], "function");
+        assert_eq!(docs["line"], 0);
+        server.stop().await
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_mock_usage_stats() -> Result<()> {
+        let mut server = MockServer::start()?;
+        let usage = server
+            .call_json(
+                "rust_analyzer_usage_stats",
+                json!({ "symbols": ["mock_function", "crate::missing"] }),
+            )
+            .await?;
+        assert_eq!(usage["items"][0]["references"], 0);
+        assert!(usage["items"][1]["error"].is_string());
+        assert_eq!(usage["unused"], json!(["mock_function"]));
+        server.stop().await
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_mock_ssr_search() -> Result<()> {
+        let mut server = MockServer::start()?;
+        let ssr = server
+            .call_json(
+                "rust_analyzer_ssr_search",
+                json!({ "pattern": "answer()", "file_path": "src/lib.rs" }),
+            )
+            .await?;
+        assert_eq!(ssr["total"], 1);
+        assert_eq!(ssr["matches"][0]["file"], "src/lib.rs");
+        assert_eq!(ssr["matches"][0]["text"], "answer");
+        server.stop().await
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_mock_prepare_rename() -> Result<()> {
+        let mut server = MockServer::start()?;
+        let prepare = server
+            .call_json(
+                "rust_analyzer_prepare_rename",
+                json!({ "file_path": "src/lib.rs", "line": 0, "character": 9 }),
+            )
+            .await?;
+        assert_eq!(prepare["renameable"], true);
+        assert_eq!(prepare["placeholder"], "answer");
+        assert_eq!(prepare["range"]["end"]["character"], 13);
+        server.stop().await
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_mock_rename_previews_without_writing() -> Result<()> {
+        let mut server = MockServer::start()?;
+        let rename = server
+            .call_json(
+                "rust_analyzer_rename",
+                json!({
+                    "file_path": "src/lib.rs",
+                    "line": 0,
+                    "character": 7,
+                    "new_name": "solution"
+                }),
+            )
+            .await?;
+        assert_eq!(rename["summary"]["edits"], 1);
+        assert_eq!(rename["files"][0]["file"], "src/lib.rs");
+        assert_eq!(rename["files"][0]["edits"][0]["old_text"], "answer");
+        assert_eq!(rename["files"][0]["edits"][0]["new_text"], "solution");
+        assert_eq!(
+            std::fs::read_to_string(server.workspace.path().join("src/lib.rs"))?,
+            "pub fn answer() -> u32 { 42 }\n"
+        );
+        server.stop().await
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_mock_completion_honours_max_results() -> Result<()> {
+        let mut server = MockServer::start()?;
+        let completion = server
+            .call_json(
+                "rust_analyzer_completion",
+                json!({
+                    "file_path": "src/lib.rs",
+                    "line": 0,
+                    "character": 7,
+                    "max_results": 1
+                }),
+            )
+            .await?;
+        assert_eq!(completion["items"].as_array().unwrap().len(), 1);
+        assert_eq!(completion["omitted"], 1);
+        server.stop().await
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_mock_missing_file_is_reported_as_not_found() -> Result<()> {
+        let mut server = MockServer::start()?;
+        let response = call_tool(
+            &mut server.writer,
+            &mut server.reader,
+            "rust_analyzer_hover",
+            json!({ "file_path": "src/missing.rs", "line": 0, "character": 0 }),
+        )
+        .await?;
+        assert_eq!(response["error"]["data"]["kind"], "file_not_found");
+        assert_eq!(response["error"]["data"]["argument"], "file_path");
+        server.stop().await
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_mock_single_step_expansion_is_rejected() -> Result<()> {
+        let mut server = MockServer::start()?;
+        let response = call_tool(
+            &mut server.writer,
+            &mut server.reader,
+            "rust_analyzer_expand_macro",
+            json!({ "file_path": "src/lib.rs", "line": 0, "character": 0, "recursive": false }),
+        )
+        .await?;
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+        assert_eq!(response["error"]["data"]["argument"], "recursive");
+        server.stop().await
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_mock_benchmark() -> Result<()> {
+        let mut server = MockServer::start()?;
+        let report = server
+            .call_json(
+                "rust_analyzer_benchmark",
+                json!({ "file_path": "src/lib.rs", "iterations": 2 }),
+            )
+            .await?;
+        assert_eq!(report["iterations"], 2);
+        for operation in ["hover", "definition", "references", "diagnostics"] {
+            assert_eq!(report["operations"][operation]["errors"], 0, "{}", report);
+            assert!(report["operations"][operation]["latency_ms"]["p50"].is_u64());
+        }
+        server.stop().await
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_mock_open_documents_closes_documents() -> Result<()> {
+        let mut server = MockServer::start()?;
+        server
+            .call_json(
+                "rust_analyzer_hover",
+                json!({ "file_path": "src/lib.rs", "line": 0, "character": 7 }),
+            )
+            .await?;
+        let documents = server
+            .call_json(
+                "rust_analyzer_open_documents",
+                json!({ "close": ["src/lib.rs", "src/other.rs"] }),
+            )
+            .await?;
+        assert_eq!(documents["count"], 0, "{documents}");
+        assert_eq!(documents["closed"].as_array().unwrap().len(), 1);
+        assert!(documents["not_open"][0]
+            .as_str()
+            .unwrap()
+            .ends_with("src/other.rs"));
+        server.stop().await
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_mock_inlay_hints() -> Result<()> {
+        let mut server = MockServer::start()?;
+        let hints = server
+            .call_json(
+                "rust_analyzer_inlay_hints",
+                json!({ "file_path": "src/lib.rs", "start_line": 0, "end_line": 4 }),
+            )
+            .await?;
+        assert_eq!(hints["end_line"], 0, "{hints}");
+        assert_eq!(hints["hints"][0]["kind"], "type");
+        assert_eq!(hints["hints"][0]["label"], "u32");
+        assert_eq!(hints["hints"][0]["tooltip"], "The answer");
+        server.stop().await
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_mock_references_link_their_files() -> Result<()> {
+        let mut server = MockServer::start()?;
+        let response = call_tool(
+            &mut server.writer,
+            &mut server.reader,
+            "rust_analyzer_references",
+            json!({ "file_path": "src/lib.rs", "line": 0, "character": 7 }),
+        )
+        .await?;
+        let link = &response["result"]["content"][1];
+        assert_eq!(link["type"], "resource_link", "{response}");
+        assert_eq!(link["name"], "src/lib.rs");
+        assert!(link["uri"].as_str().unwrap().ends_with("/src/lib.rs"));
+        server.stop().await
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_mock_call_hierarchy() -> Result<()> {
+        let mut server = MockServer::start()?;
+        let calls = server
+            .call_json(
+                "rust_analyzer_call_hierarchy",
+                json!({ "file_path": "src/lib.rs", "line": 0, "character": 7, "depth": 3 }),
+            )
+            .await?;
+        assert_eq!(calls["item"]["name"], "mock_function", "{calls}");
+        let caller = &calls["incoming"][0];
+        assert_eq!(caller["name"], "main");
+        assert_eq!(caller["kind"], "function");
+        assert_eq!(caller["call_sites"][0]["start"]["line"], 1);
+        assert_eq!(caller["calls"][0]["repeated"], true);
+        assert_eq!(calls["outgoing"], json!([]));
+        server.stop().await
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_mock_open_docs_finds_local_pages() -> Result<()> {
+        let mut server = MockServer::start()?;
+        let page = server
+            .workspace
+            .path()
+            .join("target/doc/mock/fn.mock_function.html");
+        std::fs::create_dir_all(page.parent().unwrap())?;
+        std::fs::write(&page, "<html></html>")?;
+        let open_docs = server
+            .call_json(
+                "rust_analyzer_open_docs",
+                json!({ "file_path": "src/lib.rs", "line": 0, "character": 7, "local": true }),
+            )
+            .await?;
+        assert_eq!(
+            open_docs["web"],
+            "https://docs.rs/mock/latest/mock/fn.mock_function.html"
+        );
+        assert!(open_docs["local"]["path"]
+            .as_str()
+            .unwrap()
+            .ends_with("target/doc/mock/fn.mock_function.html"));
+        server.stop().await
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_mock_status_names_the_mock_backend() -> Result<()> {
+        let mut server = MockServer::start()?;
+        let status = server.call_json("rust_analyzer_status", json!({})).await?;
+        assert_eq!(
+            status["rust_analyzer"]["server_info"]["name"],
+            "rust-analyzer-mcp mock backend"
+        );
+        assert_eq!(status["rust_analyzer"]["readiness"]["ready"], true);
+        server.stop().await
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_tool_call_reports_progress_when_asked() -> Result<()> {
+        let workspace = tempfile::tempdir()?;
+        std::fs::create_dir(workspace.path().join("src"))?;
+        std::fs::write(
+            workspace.path().join("src/lib.rs"),
+            "pub fn answer() -> u32 { 42 }\n",
+        )?;
+
+        let (client_io, server_io) = duplex(16 * 1024);
+        let (server_reader, server_writer) = split(server_io);
+        let config = ServerConfig {
+            mock: true,
+            ..ServerConfig::default()
+        };
+        let mut server = RustAnalyzerMCPServer::with_config(workspace.path().to_path_buf(), config);
+        let server_task =
+            tokio::spawn(
+                async move { server.run_with_streams(server_reader, server_writer).await },
+            );
+        let (mut client_reader, mut client_writer) = split(client_io);
+
+        let hover = json!({
+            "name": "rust_analyzer_hover",
+            "arguments": { "file_path": "src/lib.rs", "line": 0, "character": 7 }
+        });
+        let batch = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": {
+                "name": "rust_analyzer_batch",
+                "arguments": { "calls": [hover, hover] },
+                "_meta": { "progressToken": "batch-1" }
+            }
+        });
+        write_content_length_message(&mut client_writer, &batch.to_string()).await?;
+
+        let mut progress = Vec::new();
+        let response = loop {
+            let message: Value = serde_json::from_str(
+                &timeout(
+                    Duration::from_secs(5),
+                    read_content_length_message(&mut client_reader),
+                )
+                .await??,
+            )?;
+            if message.get("id").is_some() {
+                break message;
+            }
+            if message["method"] == "notifications/progress" {
+                progress.push(message["params"].clone());
+            }
+        };
+
+        assert!(response["result"].is_object(), "{response}");
+        assert_eq!(progress.len(), 2, "{progress:?}");
+        assert_eq!(progress[0]["progressToken"], "batch-1");
+        assert_eq!(progress[0]["progress"], 0);
+        assert_eq!(progress[1]["progress"], 1);
+        assert_eq!(progress[1]["total"], 2);
+
+        client_writer.shutdown().await?;
+        drop(client_writer);
+        drop(client_reader);
+        server_task.await??;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_completion_complete_suggests_files_and_symbols() -> Result<()> {
+        let workspace = tempfile::tempdir()?;
+        std::fs::create_dir(workspace.path().join("src"))?;
+        std::fs::write(
+            workspace.path().join("src/lib.rs"),
+            "pub fn mock_function() {}\n",
+        )?;
+        std::fs::write(workspace.path().join("src/main.rs"), "fn main() {}\n")?;
+        let config = ServerConfig {
+            mock: true,
+            ..ServerConfig::default()
+        };
+        let mut server = RustAnalyzerMCPServer::with_config(workspace.path().to_path_buf(), config);
+        let complete = |id: u64, name: &str, value: &str| -> Result<MCPRequest> {
+            Ok(serde_json::from_value(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": "completion/complete",
+                "params": {
+                    "ref": { "type": "ref/tool", "name": "rust_analyzer_hover" },
+                    "argument": { "name": name, "value": value }
+                }
+            }))?)
+        };
+
+        let files = server
+            .handle_request(complete(1, "file_path", "src/l")?)
+            .await;
+        let MCPResponse::Success { result, .. } = files else {
+            return Err(anyhow!("file completion failed: {:?}", files));
+        };
+        assert_eq!(result["completion"]["values"], json!(["src/lib.rs"]));
+        assert_eq!(result["completion"]["hasMore"], false);
+
+        let symbols = server.handle_request(complete(2, "query", "mock")?).await;
+        let MCPResponse::Success { result, .. } = symbols else {
+            return Err(anyhow!("symbol completion failed: {:?}", symbols));
+        };
+        let values = result["completion"]["values"].as_array().unwrap();
+        assert!(values.contains(&json!("mock_function")), "{}", result);
+
+        let other = server.handle_request(complete(3, "line", "1")?).await;
+        let MCPResponse::Success { result, .. } = other else {
+            return Err(anyhow!("completion failed: {:?}", other));
+        };
+        assert_eq!(result["completion"]["total"], 0);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_run_check_waits_for_the_requested_check() -> Result<()> {
+        let workspace = tempfile::tempdir()?;
+        std::fs::create_dir(workspace.path().join("src"))?;
+        std::fs::write(
+            workspace.path().join("src/lib.rs"),
+            "pub fn answer() -> u32 { 42 }\n",
+        )?;
+        let config = ServerConfig {
+            mock: true,
+            check_on_demand: true,
+            ..ServerConfig::default()
+        };
+        let mut server = RustAnalyzerMCPServer::with_config(workspace.path().to_path_buf(), config);
+
+        let check = server
+            .handle_request(serde_json::from_value(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "tools/call",
+                "params": { "name": "rust_analyzer_run_check", "arguments": {} }
+            }))?)
+            .await;
+        let MCPResponse::Success { result, .. } = check else {
+            return Err(anyhow!("rust_analyzer_run_check failed: {:?}", check));
+        };
+        let text = result["content"][0]["text"].as_str().unwrap();
+        let diagnostics: Value = serde_json::from_str(text)?;
+        assert_eq!(diagnostics["summary"]["total_errors"], 0);
+        let client = server.client.as_ref().unwrap();
+        assert!(!client.options().check_on_save);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_feature_matrix_restores_the_settings_it_checked_with() -> Result<()> {
+        let workspace = tempfile::tempdir()?;
+        std::fs::create_dir(workspace.path().join("src"))?;
+        std::fs::write(
+            workspace.path().join("src/lib.rs"),
+            "pub fn answer() -> u32 { 42 }\n",
+        )?;
+        let config = ServerConfig {
+            mock: true,
+            ..ServerConfig::default()
+        };
+        let mut server = RustAnalyzerMCPServer::with_config(workspace.path().to_path_buf(), config);
+        server.ensure_client_started().await?;
+        let started_with = server.client.as_ref().unwrap().settings().await;
+
+        let matrix = server
+            .handle_request(serde_json::from_value(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "tools/call",
+                "params": {
+                    "name": "rust_analyzer_feature_matrix",
+                    "arguments": { "feature_sets": [[], ["tls"]], "target": "wasm32-unknown-unknown" }
+                }
+            }))?)
+            .await;
+        let MCPResponse::Success { result, .. } = matrix else {
+            return Err(anyhow!("rust_analyzer_feature_matrix failed: {:?}", matrix));
+        };
+        let text = result["content"][0]["text"].as_str().unwrap();
+        let report: Value = serde_json::from_str(text)?;
+        assert_eq!(report["feature_sets"][0]["features"], "default");
+        assert_eq!(report["feature_sets"][1]["features"], "tls");
+        assert_eq!(
+            report["feature_sets"][1]["settings"],
+            json!({ "features": ["tls"], "noDefaultFeatures": false, "target": "wasm32-unknown-unknown" })
+        );
+        let settings = server.client.as_ref().unwrap().settings().await;
+        assert_eq!(settings, started_with);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_cargo_check_for_a_target_goes_through_rust_analyzer() -> Result<()> {
+        let workspace = tempfile::tempdir()?;
+        std::fs::create_dir(workspace.path().join("src"))?;
+        std::fs::write(
+            workspace.path().join("src/lib.rs"),
+            "pub fn answer() -> u32 { 42 }\n",
+        )?;
+        let config = ServerConfig {
+            mock: true,
+            ..ServerConfig::default()
+        };
+        let mut server = RustAnalyzerMCPServer::with_config(workspace.path().to_path_buf(), config);
+        server.ensure_client_started().await?;
+        let started_with = server.client.as_ref().unwrap().settings().await;
+
+        let check = server
+            .handle_request(serde_json::from_value(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "tools/call",
+                "params": {
+                    "name": "rust_analyzer_cargo_check",
+                    "arguments": { "target": "wasm32-unknown-unknown" }
+                }
+            }))?)
+            .await;
+        let MCPResponse::Success { result, .. } = check else {
+            return Err(anyhow!("rust_analyzer_cargo_check failed: {:?}", check));
+        };
+        let text = result["content"][0]["text"].as_str().unwrap();
+        let diagnostics: Value = serde_json::from_str(text)?;
+        assert_eq!(diagnostics["target"], "wasm32-unknown-unknown");
+        assert_eq!(diagnostics["summary"]["total_errors"], 0);
+        let settings = server.client.as_ref().unwrap().settings().await;
+        assert_eq!(settings, started_with);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_refactor_prompt_gathers_tool_outputs() -> Result<()> {
+        let workspace = tempfile::tempdir()?;
+        std::fs::create_dir(workspace.path().join("src"))?;
+        std::fs::write(
+            workspace.path().join("src/lib.rs"),
+            "pub fn answer() -> u32 { 42 }\n",
+        )?;
+        let config = ServerConfig {
+            mock: true,
+            ..ServerConfig::default()
+        };
+        let mut server = RustAnalyzerMCPServer::with_config(workspace.path().to_path_buf(), config);
+
+        let get = server
+            .handle_request(serde_json::from_value(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "prompts/get",
+                "params": {
+                    "name": "extract_function",
+                    "arguments": { "file_path": "src/lib.rs", "line": "0", "character": "25" }
+                }
+            }))?)
+            .await;
+        let MCPResponse::Success { result, .. } = get else {
+            return Err(anyhow!("prompts/get failed: {:?}", get));
+        };
+        let text = result["messages"][0]["content"]["text"].as_str().unwrap();
+        assert!(text.starts_with("Extract the selected code"), "{}", text);
+        for section in [
+            "## Enclosing items",
+            "## Diagnostics",
+            "## Available assists",
+        ] {
+            assert!(text.contains(section), "{}", text);
+        }
+
+        let unknown = server
+            .handle_request(serde_json::from_value(json!({
+                "jsonrpc": "2.0",
+                "id": 2,
+                "method": "prompts/get",
+                "params": { "name": "inline_everything", "arguments": {} }
+            }))?)
+            .await;
+        let MCPResponse::Error { error, .. } = unknown else {
+            return Err(anyhow!("unknown prompt was accepted: {:?}", unknown));
+        };
+        assert_eq!(error.code, -32602);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_batched_hovers_answer_each_position_in_order() -> Result<()> {
+        let workspace = tempfile::tempdir()?;
+        std::fs::create_dir(workspace.path().join("src"))?;
+        std::fs::write(
+            workspace.path().join("src/lib.rs"),
+            "pub fn answer() -> u32 { 42 }\n",
+        )?;
+        let config = ServerConfig {
+            mock: true,
+            ..ServerConfig::default()
+        };
+        let mut server = RustAnalyzerMCPServer::with_config(workspace.path().to_path_buf(), config);
+
+        let call = server
+            .handle_request(serde_json::from_value(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "tools/call",
+                "params": {
+                    "name": "rust_analyzer_hover_batch",
+                    "arguments": {
+                        "file_path": "src/lib.rs",
+                        "format": "signature_only",
+                        "positions": [
+                            { "line": 0, "character": 7 },
+                            { "file_path": "src/missing.rs", "line": 0, "character": 0 },
+                            { "line": 0, "character": 25 }
+                        ]
+                    }
+                }
+            }))?)
+            .await;
+        let MCPResponse::Success { result, .. } = call else {
+            return Err(anyhow!("hover batch failed: {:?}", call));
+        };
+        let entries: Value = serde_json::from_str(result["content"][0]["text"].as_str().unwrap())?;
+        let entries = entries.as_array().unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0]["character"], 7);
+        assert_eq!(entries[0]["result"], "fn mock_function() -> u32");
+        assert_eq!(entries[1]["file_path"], "src/missing.rs");
+        assert!(entries[1]["error"].is_string(), "{}", entries[1]);
+        assert_eq!(entries[2]["character"], 25);
+        assert!(entries[2]["result"].is_string());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_batch_runs_calls_in_order() -> Result<()> {
+        let workspace = tempfile::tempdir()?;
+        std::fs::create_dir(workspace.path().join("src"))?;
+        std::fs::write(
+            workspace.path().join("src/lib.rs"),
+            "pub fn answer() -> u32 { 42 }\n",
+        )?;
+        let config = ServerConfig {
+            mock: true,
+            ..ServerConfig::default()
+        };
+        let mut server = RustAnalyzerMCPServer::with_config(workspace.path().to_path_buf(), config);
+        let hover = json!({
+            "name": "rust_analyzer_hover",
+            "arguments": { "file_path": "src/lib.rs", "line": 0, "character": 7, "format": "signature_only" }
+        });
+        let batch = |id: u64, arguments: Value| {
+            serde_json::from_value::<MCPRequest>(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": "tools/call",
+                "params": { "name": "rust_analyzer_batch", "arguments": arguments }
+            }))
+        };
+
+        let call = server
+            .handle_request(batch(
+                1,
+                json!({ "calls": [hover, { "name": "no_such_tool" }, hover] }),
+            )?)
+            .await;
+        let MCPResponse::Success { result, .. } = call else {
+            return Err(anyhow!("batch failed: {:?}", call));
+        };
+        let results: Value = serde_json::from_str(result["content"][0]["text"].as_str().unwrap())?;
+        let results = results.as_array().unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(
+            results[0]["content"][0]["text"],
+            "fn mock_function() -> u32"
+        );
+        assert!(results[1]["error"]
+            .as_str()
+            .unwrap()
+            .contains("Unknown tool"));
+        assert_eq!(results[2]["name"], "rust_analyzer_hover");
+
+        let call = server
+            .handle_request(batch(
+                2,
+                json!({ "calls": [{ "name": "no_such_tool" }, hover], "stop_on_error": true }),
+            )?)
+            .await;
+        let MCPResponse::Success { result, .. } = call else {
+            return Err(anyhow!("batch failed: {:?}", call));
+        };
+        let results: Value = serde_json::from_str(result["content"][0]["text"].as_str().unwrap())?;
+        assert_eq!(results.as_array().unwrap().len(), 1);
+
+        let nested = server
+            .handle_request(batch(
+                3,
+                json!({ "calls": [{ "name": "rust_analyzer_batch" }] }),
+            )?)
+            .await;
+        let MCPResponse::Error { error, .. } = nested else {
+            return Err(anyhow!("nested batch succeeded: {:?}", nested));
+        };
+        assert_eq!(error.code, INVALID_PARAMS);
+        assert_eq!(error.data.unwrap()["argument"], "calls");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_disabled_tools_are_hidden_and_rejected() -> Result<()> {
+        let config = ServerConfig {
+            denied_tools: vec!["rust_analyzer_hover".to_string()],
+            read_only: true,
+            ..ServerConfig::default()
+        };
+        let mut server = RustAnalyzerMCPServer::with_config(std::env::temp_dir(), config);
+
+        let list = server
+            .handle_request(serde_json::from_value(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "tools/list",
+                "params": {}
+            }))?)
+            .await;
+        let MCPResponse::Success { result, .. } = list else {
+            return Err(anyhow!("tools/list failed: {:?}", list));
+        };
+        let names: Vec<&str> = result["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|tool| tool["name"].as_str())
+            .collect();
+        assert!(names.contains(&"rust_analyzer_definition"));
+        assert!(!names.contains(&"rust_analyzer_hover"));
+        assert!(!names.contains(&"rust_analyzer_cargo_build"));
+
+        let call = server
+            .handle_request(serde_json::from_value(json!({
+                "jsonrpc": "2.0",
+                "id": 2,
+                "method": "tools/call",
+                "params": { "name": "rust_analyzer_cargo_build", "arguments": {} }
+            }))?)
+            .await;
+        let MCPResponse::Error { error, .. } = call else {
+            return Err(anyhow!("cargo build was not rejected: {:?}", call));
+        };
+        assert!(error.message.contains("disabled"), "{}", error.message);
+        assert!(server.client.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tool_list_changes_are_notified() -> Result<()> {
+        let config = ServerConfig::default();
+        let mut server = RustAnalyzerMCPServer::with_config(std::env::temp_dir(), config);
+        let (notifications, mut received) = tokio::sync::mpsc::unbounded_channel();
+        server.session.notifications = Some(notifications);
+        let request = |id: u64, method: &str| -> Result<MCPRequest> {
+            Ok(serde_json::from_value(
+                json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": {} }),
+            )?)
+        };
+
+        server.handle_request(request(1, "ping")?).await;
+        assert!(
+            received.try_recv().is_err(),
+            "notified before tools were listed"
+        );
+
+        server.handle_request(request(2, "tools/list")?).await;
+        server.handle_request(request(3, "ping")?).await;
+        assert!(received.try_recv().is_err(), "notified without a change");
+
+        server.config.read_only = true;
+        server.handle_request(request(4, "ping")?).await;
+        let notification = received.try_recv()?;
+        assert_eq!(notification["method"], "notifications/tools/list_changed");
+
+        server.handle_request(request(5, "ping")?).await;
+        assert!(
+            received.try_recv().is_err(),
+            "notified twice for one change"
+        );
+
+        Ok(())
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_custom_tools_are_listed_and_dispatched() -> Result<()> {
+        use futures::future::BoxFuture;
+
+        use crate::{
+            mcp::{CommandTool, CustomTool, ToolContext},
+            protocol::mcp::{ContentItem, ToolDefinition, ToolResult},
+        };
+
+        struct WorkspaceTool;
+
+        impl CustomTool for WorkspaceTool {
+            fn definition(&self) -> ToolDefinition {
+                ToolDefinition {
+                    name: "site_workspace".to_string(),
+                    description: "Report the workspace root".to_string(),
+                    input_schema: json!({ "type": "object", "properties": {} }),
+                }
+            }
+
+            fn call<'a>(
+                &'a self,
+                context: ToolContext<'a>,
+                args: Value,
+            ) -> BoxFuture<'a, Result<ToolResult>> {
+                Box::pin(async move {
+                    Ok(ToolResult {
+                        content: vec![ContentItem::text(format!(
+                            "{} {}",
+                            context.workspace_root.display(),
+                            args
+                        ))],
+                    })
+                })
+            }
+        }
+
+        let workspace = tempfile::tempdir()?;
+        let config = ServerConfig {
+            mock: true,
+            ..ServerConfig::default()
+        };
+        let mut server = RustAnalyzerMCPServer::with_config(workspace.path().to_path_buf(), config);
+        server.register_tool(Arc::new(WorkspaceTool))?;
+        let echo: CommandTool = serde_json::from_value(json!({
+            "name": "site_echo",
+            "description": "Echo a message",
+            "command": ["echo", "{message}"]
+        }))?;
+        server.register_tool(Arc::new(echo))?;
+        assert!(server.register_tool(Arc::new(WorkspaceTool)).is_err());
+        let workspace_root = server.workspace_root.display().to_string();
+
+        let (client_io, server_io) = duplex(16 * 1024);
+        let (server_reader, server_writer) = split(server_io);
+        let server_task =
+            tokio::spawn(
+                async move { server.run_with_streams(server_reader, server_writer).await },
+            );
+        let (mut client_reader, mut client_writer) = split(client_io);
+
+        let list = json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/list", "params": {} });
+        write_content_length_message(&mut client_writer, &list.to_string()).await?;
+        let response = timeout(Duration::from_secs(5), read_response(&mut client_reader)).await??;
+        let tools = response["result"]["tools"].as_array().unwrap();
+        let custom = tools
+            .iter()
+            .find(|tool| tool["name"] == "site_workspace")
+            .unwrap();
+        assert!(custom["inputSchema"]["properties"]["timeout_ms"].is_object());
+        assert!(tools.iter().any(|tool| tool["name"] == "site_echo"));
+
+        let call = json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "tools/call",
+            "params": {
+                "name": "site_workspace",
+                "arguments": { "verbose": true, "timeout_ms": 5000 }
+            }
+        });
+        write_content_length_message(&mut client_writer, &call.to_string()).await?;
+        let response = timeout(Duration::from_secs(5), read_response(&mut client_reader)).await??;
+        assert_eq!(
+            response["result"]["content"][0]["text"],
+            format!("{} {{\"verbose\":true}}", workspace_root)
+        );
+
+        let call = json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "method": "tools/call",
+            "params": { "name": "site_echo", "arguments": { "message": "hello" } }
+        });
+        write_content_length_message(&mut client_writer, &call.to_string()).await?;
+        let response = timeout(Duration::from_secs(5), read_response(&mut client_reader)).await??;
+        let output: Value =
+            serde_json::from_str(response["result"]["content"][0]["text"].as_str().unwrap())?;
+        assert_eq!(output["success"], true);
+        assert_eq!(output["stdout"], "hello\n");
+
+        client_writer.shutdown().await?;
+        drop(client_writer);
+        drop(client_reader);
+        server_task.await??;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_queued_requests_are_answered_on_shutdown() -> Result<()> {
+        use crate::mcp::CommandTool;
+
+        let workspace = tempfile::tempdir()?;
+        let config = ServerConfig {
+            mock: true,
+            ..ServerConfig::default()
+        };
+        let mut server = RustAnalyzerMCPServer::with_config(workspace.path().to_path_buf(), config);
+        let sleep: CommandTool = serde_json::from_value(json!({
+            "name": "site_sleep",
+            "description": "Sleep for a while",
+            "command": ["sleep", "{seconds}"]
+        }))?;
+        server.register_tool(Arc::new(sleep))?;
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+
+        let (client_io, server_io) = duplex(16 * 1024);
+        let (server_reader, server_writer) = split(server_io);
+        let server_task = tokio::spawn(async move {
+            server
+                .run_until(server_reader, server_writer, async {
+                    let _ = stopped.await;
+                })
+                .await
+        });
+        let (mut client_reader, mut client_writer) = split(client_io);
+
+        for id in [1, 2] {
+            let call = json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": "tools/call",
+                "params": { "name": "site_sleep", "arguments": { "seconds": "0.5" } }
+            });
+            write_content_length_message(&mut client_writer, &call.to_string()).await?;
+        }
+        // Stop while the first call runs and the second one waits behind it.
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        stop.send(()).map_err(|_| anyhow!("server stopped early"))?;
+
+        let first = timeout(Duration::from_secs(5), read_response(&mut client_reader)).await??;
+        assert_eq!(first["id"], 1);
+        assert!(first["result"]["content"].is_array(), "{first}");
+        let second = timeout(Duration::from_secs(5), read_response(&mut client_reader)).await??;
+        assert_eq!(second["id"], 2);
+        assert_eq!(second["error"]["code"], -32000);
+        timeout(Duration::from_secs(5), server_task).await???;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_tool_calls_beyond_the_limit_are_refused_right_away() -> Result<()> {
+        use crate::mcp::CommandTool;
+
+        let workspace = tempfile::tempdir()?;
+        let config = ServerConfig {
+            mock: true,
+            max_pending_tool_calls: Some(1),
+            ..ServerConfig::default()
+        };
+        let mut server = RustAnalyzerMCPServer::with_config(workspace.path().to_path_buf(), config);
+        let sleep: CommandTool = serde_json::from_value(json!({
+            "name": "site_sleep",
+            "description": "Sleep for a while",
+            "command": ["sleep", "{seconds}"]
+        }))?;
+        server.register_tool(Arc::new(sleep))?;
+
+        let (client_io, server_io) = duplex(16 * 1024);
+        let (server_reader, server_writer) = split(server_io);
+        let server_task =
+            tokio::spawn(
+                async move { server.run_with_streams(server_reader, server_writer).await },
+            );
+        let (mut client_reader, mut client_writer) = split(client_io);
+
+        for id in [1, 2] {
+            let call = json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": "tools/call",
+                "params": { "name": "site_sleep", "arguments": { "seconds": "1" } }
+            });
+            write_content_length_message(&mut client_writer, &call.to_string()).await?;
+        }
+
+        // The refusal does not wait for the running call.
+        let busy = timeout(
+            Duration::from_millis(500),
+            read_response(&mut client_reader),
+        )
+        .await??;
+        assert_eq!(busy["id"], 2);
+        assert_eq!(busy["error"]["code"], -32001);
+        assert_eq!(busy["error"]["data"]["limit"], "max_pending_tool_calls");
+        let first = timeout(Duration::from_secs(5), read_response(&mut client_reader)).await??;
+        assert_eq!(first["id"], 1);
+        assert!(first["result"]["content"].is_array(), "{first}");
+
+        drop(client_writer);
+        drop(client_reader);
+        timeout(Duration::from_secs(5), server_task).await???;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_replay_answers_from_a_recorded_session() -> Result<()> {
+        let workspace = tempfile::tempdir()?;
+        std::fs::create_dir(workspace.path().join("src"))?;
+        std::fs::write(
+            workspace.path().join("src/lib.rs"),
+            "pub fn answer() -> u32 { 42 }\n",
+        )?;
+        let recording = workspace.path().join("session.jsonl");
+
+        let record = ServerConfig {
+            mock: true,
+            trace_lsp: Some(recording.clone()),
+            ..ServerConfig::default()
+        };
+        let replay = ServerConfig {
+            replay: Some(recording.clone()),
+            ..ServerConfig::default()
+        };
+
+        let mut hovers = Vec::new();
+        for config in [record, replay] {
+            let (client_io, server_io) = duplex(16 * 1024);
+            let (server_reader, server_writer) = split(server_io);
+            let mut server =
+                RustAnalyzerMCPServer::with_config(workspace.path().to_path_buf(), config);
+            let server_task =
+                tokio::spawn(
+                    async move { server.run_with_streams(server_reader, server_writer).await },
+                );
+            let (mut client_reader, mut client_writer) = split(client_io);
+
+            let hover = json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "tools/call",
+                "params": {
+                    "name": "rust_analyzer_hover",
+                    "arguments": { "file_path": "src/lib.rs", "line": 0, "character": 7 }
+                }
+            });
+            write_content_length_message(&mut client_writer, &hover.to_string()).await?;
+            let response =
+                timeout(Duration::from_secs(5), read_response(&mut client_reader)).await??;
+            hovers.push(response["result"].clone());
+
+            client_writer.shutdown().await?;
+            drop(client_writer);
+            drop(client_reader);
+            server_task.await??;
+        }
+
+        assert!(hovers[0]["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("mock_function"));
+        assert_eq!(hovers[0], hovers[1]);
+
+        Ok(())
+    }
+
+    async fn write_content_length_message<W>(writer: &mut W, body: &str) -> Result<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let frame = format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+        writer.write_all(frame.as_bytes()).await?;
+        writer.flush().await?;
+        Ok(())
+    }
+
+    /// Reads the next response, skipping log notifications from rust-analyzer.
+    async fn read_response<R>(reader: &mut R) -> Result<Value>
+    where
+        R: AsyncRead + Unpin,
+    {
+        loop {
+            let message: Value = serde_json::from_str(&read_content_length_message(reader).await?)?;
+            if message.get("id").is_some() {
+                return Ok(message);
+            }
+        }
+    }
+
     async fn read_content_length_message<R>(reader: &mut R) -> Result<String>
     where
         R: AsyncRead + Unpin,
@@ -0,0 +1,790 @@
+use anyhow::{Context, Result};
+use log::{debug, error, info, warn};
+use serde_json::Value;
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    net::SocketAddr,
+    sync::Arc,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpListener,
+    sync::{
+        mpsc::{self, UnboundedReceiver, UnboundedSender},
+        oneshot, watch,
+    },
+    task::JoinSet,
+};
+
+use crate::{
+    diagnostics::DiagnosticsSnapshot,
+    protocol::mcp::{MCPError, MCPRequest, MCPResponse, INVALID_PARAMS, SESSION_ENDED},
+};
+
+use super::{
+    logging::{window_message_notification, LogLevel},
+    observer::MessageObserver,
+    queue::{coalescing_key, ends_session, QueueLimits, QueuedRequest, RequestQueue},
+    server::{answer_rejected, next_window_message, RustAnalyzerMCPServer},
+    signal::{deadline_elapsed, shutdown_drain_deadline, shutdown_signal},
+    transport::{MessageFraming, StdioTransport},
+};
+
+/// What the server keeps for one connected MCP client.
+#[derive(Default)]
+pub(super) struct Session {
+    /// Minimum level of rust-analyzer messages forwarded as MCP log notifications.
+    pub log_level: LogLevel,
+    pub diagnostics_snapshots: HashMap<String, DiagnosticsSnapshot>,
+    /// Documents this session's requests opened or used in rust-analyzer.
+    pub open_documents: HashSet<String>,
+    /// Notifications sent to this session's client while its requests run.
+    pub notifications: Option<UnboundedSender<Value>>,
+    /// Names of the tools last listed to this session's client, to tell it
+    /// when they change.
+    pub listed_tools: Option<Vec<String>>,
+}
+
+/// A connected session as seen by the server.
+struct SessionHandle {
+    state: Session,
+    notifications: UnboundedSender<Value>,
+}
+
+enum SessionEvent {
+    Request {
+        session: u64,
+        request: MCPRequest,
+        reply: oneshot::Sender<MCPResponse>,
+    },
+    Closed {
+        session: u64,
+    },
+}
+
+impl RustAnalyzerMCPServer {
+    /// Accepts MCP clients on `addr` and serves each connection as its own
+    /// session until interrupted; see [`serve_sessions`](Self::serve_sessions).
+    pub async fn listen(&mut self, addr: SocketAddr) -> Result<()> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("Failed to listen on {}", addr))?;
+        info!(
+            "Listening for MCP connections on {}",
+            listener.local_addr()?
+        );
+
+        let (connections_tx, connections) = mpsc::unbounded_channel();
+        let accepting = tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, peer)) => {
+                        info!("Accepted MCP connection from {}", peer);
+                        if connections_tx.send(stream.into_split()).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => warn!("Failed to accept MCP connection: {}", e),
+                }
+            }
+        });
+
+        let result = self.serve_sessions(connections, shutdown_signal()).await;
+        accepting.abort();
+        result
+    }
+
+    /// Serves every reader/writer pair received on `connections` as a separate
+    /// MCP session, until `shutdown` completes or `connections` is closed and
+    /// the last session has ended.
+    ///
+    /// On shutdown no more connections are accepted and sessions stop reading.
+    /// The request being handled gets a while to finish; requests the sessions
+    /// already read are answered with a shutting-down error. A client sending
+    /// `shutdown` or `exit` ends only its own session.
+    ///
+    /// The sessions share one rust-analyzer client and take turns using it.
+    /// Each has its own log level, diagnostics snapshots and request queue;
+    /// documents it used are closed when it disconnects unless another
+    /// session used them too. `rust_analyzer_set_workspace` is refused while
+    /// other sessions are connected.
+    pub async fn serve_sessions<R, W, S>(
+        &mut self,
+        mut connections: UnboundedReceiver<(R, W)>,
+        shutdown: S,
+    ) -> Result<()>
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+        W: AsyncWrite + Unpin + Send + 'static,
+        S: Future<Output = ()>,
+    {
+        info!("Starting rust-analyzer MCP server for multiple sessions");
+        if self.config.warm_up {
+            info!("Starting rust-analyzer before serving sessions");
+            if let Err(e) = self.ensure_client_started().await {
+                error!("Failed to start rust-analyzer for warm-up: {e}");
+            }
+        }
+
+        let mut window_messages = self.window_messages_rx.take();
+        let (events_tx, mut events) = mpsc::unbounded_channel();
+        let mut sessions: HashMap<u64, SessionHandle> = HashMap::new();
+        let mut tasks = JoinSet::new();
+        let mut next_session = 0;
+        let mut accepting = true;
+        let (stopping_tx, stopping) = watch::channel(false);
+        let mut drain_deadline = None;
+        tokio::pin!(shutdown);
+
+        loop {
+            tokio::select! {
+                _ = &mut shutdown, if drain_deadline.is_none() => {
+                    accepting = false;
+                    stopping_tx.send_replace(true);
+                    drain_deadline = Some(shutdown_drain_deadline());
+                    if sessions.is_empty() {
+                        break;
+                    }
+                }
+                _ = deadline_elapsed(drain_deadline) => {
+                    warn!("Sessions did not finish in time; closing them");
+                    break;
+                }
+                connection = connections.recv(), if accepting => {
+                    let Some((reader, writer)) = connection else {
+                        accepting = false;
+                        if sessions.is_empty() {
+                            break;
+                        }
+                        continue;
+                    };
+                    next_session += 1;
+                    let (notifications_tx, notifications) = mpsc::unbounded_channel();
+                    sessions.insert(
+                        next_session,
+                        SessionHandle {
+                            state: Session {
+                                notifications: Some(notifications_tx.clone()),
+                                ..Session::default()
+                            },
+                            notifications: notifications_tx,
+                        },
+                    );
+                    tasks.spawn(run_session(
+                        next_session,
+                        StdioTransport::new(reader, writer),
+                        events_tx.clone(),
+                        notifications,
+                        stopping.clone(),
+                        QueueLimits::from_config(&self.config),
+                        self.observers.clone(),
+                    ));
+                    info!("Session {} started ({} active)", next_session, sessions.len());
+                }
+                Some(event) = events.recv() => match event {
+                    SessionEvent::Request { session, request, reply } => {
+                        if drain_deadline.is_some() {
+                            let _ = reply.send(MCPResponse::shutting_down(request.id));
+                            continue;
+                        }
+                        let id = request.id.clone();
+                        let cancellation = self.cancellation.clone();
+                        let handling = self.handle_session_request(&mut sessions, session, request);
+                        tokio::pin!(handling);
+                        let response = loop {
+                            tokio::select! {
+                                response = &mut handling => break response,
+                                _ = &mut shutdown, if drain_deadline.is_none() => {
+                                    accepting = false;
+                                    stopping_tx.send_replace(true);
+                                    cancellation.cancel();
+                                    drain_deadline = Some(shutdown_drain_deadline());
+                                }
+                                _ = deadline_elapsed(drain_deadline) => {
+                                    warn!("Cancelling an unfinished request to shut down");
+                                    break MCPResponse::shutting_down(id);
+                                }
+                            }
+                        };
+                        let _ = reply.send(response);
+                    }
+                    SessionEvent::Closed { session } => {
+                        self.close_session(&mut sessions, session).await;
+                        info!("Session {} ended ({} active)", session, sessions.len());
+                        if !accepting && sessions.is_empty() {
+                            break;
+                        }
+                    }
+                },
+                Some(window_message) = next_window_message(&mut window_messages) => {
+                    for handle in sessions.values() {
+                        if let Some(notification) =
+                            window_message_notification(&window_message, handle.state.log_level)
+                        {
+                            let _ = handle.notifications.send(notification);
+                        }
+                    }
+                }
+            }
+        }
+
+        // Cleanup.
+        info!("Shutting down");
+        tasks.shutdown().await;
+        self.shutdown_client().await;
+
+        Ok(())
+    }
+
+    /// Handles a request with the session's state in place of the server's.
+    async fn handle_session_request(
+        &mut self,
+        sessions: &mut HashMap<u64, SessionHandle>,
+        session: u64,
+        request: MCPRequest,
+    ) -> MCPResponse {
+        if changes_workspace(&request) && sessions.len() > 1 {
+            return MCPResponse::Error {
+                jsonrpc: "2.0".to_string(),
+                id: request.id,
+                error: MCPError {
+                    code: INVALID_PARAMS,
+                    message:
+                        "The workspace cannot be changed while other sessions share the server"
+                            .to_string(),
+                    data: None,
+                },
+            };
+        }
+        let Some(handle) = sessions.get_mut(&session) else {
+            return MCPResponse::Error {
+                jsonrpc: "2.0".to_string(),
+                id: request.id,
+                error: MCPError {
+                    code: SESSION_ENDED,
+                    message: "Session has ended".to_string(),
+                    data: None,
+                },
+            };
+        };
+
+        if let Some(client) = &mut self.client {
+            client.take_synced_documents();
+        }
+        std::mem::swap(&mut self.session, &mut handle.state);
+        let response = self.handle_request(request).await;
+        std::mem::swap(&mut self.session, &mut handle.state);
+        if let Some(client) = &mut self.client {
+            handle
+                .state
+                .open_documents
+                .extend(client.take_synced_documents());
+        }
+        response
+    }
+
+    /// Forgets a session and closes the documents only it had opened.
+    async fn close_session(&mut self, sessions: &mut HashMap<u64, SessionHandle>, session: u64) {
+        let Some(handle) = sessions.remove(&session) else {
+            return;
+        };
+        let Some(client) = &mut self.client else {
+            return;
+        };
+        for uri in &handle.state.open_documents {
+            if sessions
+                .values()
+                .any(|other| other.state.open_documents.contains(uri))
+            {
+                continue;
+            }
+            if let Err(e) = client.close_document(uri).await {
+                warn!("Failed to close {}: {}", uri, e);
+            }
+        }
+    }
+}
+
+/// Reads a session's requests and hands them to the server one at a time,
+/// most urgent first, writing back responses and log notifications. Requests
+/// beyond `limits` are answered with a server-busy error right away.
+///
+/// Once `stopping` is set, or the client sends `shutdown` or `exit`, the
+/// session stops reading and answers the requests it still holds with a
+/// shutting-down error. A client leaving that way ends only its own session.
+async fn run_session<R, W>(
+    session: u64,
+    mut transport: StdioTransport<R, W>,
+    events: UnboundedSender<SessionEvent>,
+    mut notifications: UnboundedReceiver<Value>,
+    mut stopping: watch::Receiver<bool>,
+    limits: QueueLimits,
+    observers: Vec<Arc<dyn MessageObserver>>,
+) where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let observe = |event: &dyn Fn(&dyn MessageObserver)| {
+        for observer in &observers {
+            event(observer.as_ref());
+        }
+    };
+    let mut queue = RequestQueue::new(limits);
+    let mut ending = false;
+
+    'requests: loop {
+        if *stopping.borrow() || !answer_rejected(&mut transport, &mut queue, &observers).await {
+            break;
+        }
+        let Some(QueuedRequest {
+            request, framing, ..
+        }) = queue.pop()
+        else {
+            if queue.is_closed() {
+                break;
+            }
+            tokio::select! {
+                message = transport.read_message() => queue.accept(message),
+                _ = stopped(&mut stopping) => {}
+                Some(notification) = notifications.recv() => {
+                    observe(&|observer| observer.on_notification(&notification));
+                    if !write(&mut transport, &notification, queue.last_framing()).await {
+                        break;
+                    }
+                }
+            }
+            continue;
+        };
+
+        observe(&|observer| observer.on_request(&request));
+        ending = ends_session(&request);
+
+        // requests without an id are notifications and must not receive a response!
+        if request.id.is_none() {
+            if ending {
+                break;
+            }
+            continue;
+        }
+
+        // Pings and lifecycle requests are answered right away rather than
+        // behind other sessions' work.
+        if request.method == "ping" || ending {
+            let response = MCPResponse::Success {
+                jsonrpc: "2.0".to_string(),
+                id: request.id,
+                result: if ending {
+                    Value::Null
+                } else {
+                    serde_json::json!({})
+                },
+            };
+            observe(&|observer| observer.on_response(&response));
+            if !write(&mut transport, &response, framing).await || ending {
+                break;
+            }
+            continue;
+        }
+
+        let key = coalescing_key(&request);
+        let (reply, response) = oneshot::channel();
+        let event = SessionEvent::Request {
+            session,
+            request,
+            reply,
+        };
+        if events.send(event).is_err() {
+            break;
+        }
+
+        // Keep reading and forwarding notifications while the server is busy.
+        tokio::pin!(response);
+        let response = loop {
+            tokio::select! {
+                response = &mut response => break response,
+                message = transport.read_message(),
+                    if !queue.is_closed() && !*stopping.borrow() => {
+                    queue.accept(message);
+                    if !answer_rejected(&mut transport, &mut queue, &observers).await {
+                        break 'requests;
+                    }
+                }
+                Some(notification) = notifications.recv() => {
+                    observe(&|observer| observer.on_notification(&notification));
+                    if !write(&mut transport, &notification, queue.last_framing()).await {
+                        break 'requests;
+                    }
+                }
+            }
+        };
+        let Ok(response) = response else {
+            break;
+        };
+
+        observe(&|observer| observer.on_response(&response));
+        if !write(&mut transport, &response, framing).await {
+            break;
+        }
+
+        let Some(key) = key else {
+            continue;
+        };
+        for duplicate in queue.take_duplicates(&key) {
+            debug!("Answering duplicate {} call in session {}", key.0, session);
+            observe(&|observer| observer.on_request(&duplicate.request));
+            let response = response.with_id(duplicate.request.id.clone());
+            observe(&|observer| observer.on_response(&response));
+            if !write(&mut transport, &response, duplicate.framing).await {
+                break 'requests;
+            }
+        }
+    }
+
+    if ending || *stopping.borrow() {
+        for queued in queue.drain() {
+            observe(&|observer| observer.on_request(&queued.request));
+            if queued.request.id.is_none() {
+                continue;
+            }
+            let response = MCPResponse::shutting_down(queued.request.id);
+            observe(&|observer| observer.on_response(&response));
+            if !write(&mut transport, &response, queued.framing).await {
+                break;
+            }
+        }
+    }
+
+    let _ = events.send(SessionEvent::Closed { session });
+}
+
+/// Completes once the server is shutting down.
+async fn stopped(stopping: &mut watch::Receiver<bool>) {
+    // An error means the server is gone, which is just as final.
+    let _ = stopping.wait_for(|stop| *stop).await;
+}
+
+/// Writes a message, returning whether the session can continue.
+async fn write<R, W>(
+    transport: &mut StdioTransport<R, W>,
+    message: &impl serde::Serialize,
+    framing: MessageFraming,
+) -> bool
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let text = match serde_json::to_string(message) {
+        Ok(text) => text,
+        Err(err) => {
+            error!("Error serializing MCP message: {err}");
+            return false;
+        }
+    };
+    match transport.write_message(&text, framing).await {
+        Ok(()) => true,
+        Err(err) => {
+            error!("Error writing MCP message: {err}");
+            false
+        }
+    }
+}
+
+fn changes_workspace(request: &MCPRequest) -> bool {
+    request.method == "tools/call"
+        && request
+            .params
+            .as_ref()
+            .and_then(|params| params["name"].as_str())
+            == Some("rust_analyzer_set_workspace")
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use anyhow::{anyhow, Result};
+    use serde_json::{json, Value};
+    use std::{collections::HashMap, sync::Arc, time::Duration};
+    use tokio::{
+        io::{
+            duplex, split, AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream, ReadHalf,
+            WriteHalf,
+        },
+        sync::{mpsc, oneshot},
+        time::timeout,
+    };
+
+    use super::{Session, SessionHandle};
+    use crate::{
+        config::ServerConfig,
+        lsp::uri_from_path,
+        mcp::{CommandTool, RustAnalyzerMCPServer},
+    };
+
+    struct TestClient {
+        reader: BufReader<ReadHalf<DuplexStream>>,
+        writer: WriteHalf<DuplexStream>,
+    }
+
+    impl TestClient {
+        fn new(stream: DuplexStream) -> Self {
+            let (reader, writer) = split(stream);
+            Self {
+                reader: BufReader::new(reader),
+                writer,
+            }
+        }
+
+        async fn call_tool(&mut self, id: u64, name: &str, arguments: Value) -> Result<Value> {
+            self.send_call(id, name, arguments).await?;
+            self.response(id).await
+        }
+
+        async fn send_call(&mut self, id: u64, name: &str, arguments: Value) -> Result<()> {
+            let request = json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": "tools/call",
+                "params": { "name": name, "arguments": arguments }
+            });
+            self.writer
+                .write_all(format!("{request}\n").as_bytes())
+                .await?;
+            self.writer.flush().await?;
+            Ok(())
+        }
+
+        async fn response(&mut self, id: u64) -> Result<Value> {
+            timeout(Duration::from_secs(10), async {
+                loop {
+                    let mut line = String::new();
+                    if self.reader.read_line(&mut line).await? == 0 {
+                        return Err(anyhow!("connection closed"));
+                    }
+                    let message: Value = serde_json::from_str(&line)?;
+                    if message["id"] == id {
+                        return Ok(message);
+                    }
+                }
+            })
+            .await?
+        }
+    }
+
+    fn mock_workspace() -> Result<tempfile::TempDir> {
+        let workspace = tempfile::tempdir()?;
+        std::fs::create_dir(workspace.path().join("src"))?;
+        std::fs::write(workspace.path().join("src/lib.rs"), "pub fn answer() {}\n")?;
+        std::fs::write(workspace.path().join("src/other.rs"), "pub fn other() {}\n")?;
+        Ok(workspace)
+    }
+
+    fn mock_server(workspace: &tempfile::TempDir) -> RustAnalyzerMCPServer {
+        let config = ServerConfig {
+            mock: true,
+            ..ServerConfig::default()
+        };
+        RustAnalyzerMCPServer::with_config(workspace.path().to_path_buf(), config)
+    }
+
+    #[tokio::test]
+    async fn test_sessions_share_the_client_but_keep_their_own_state() -> Result<()> {
+        let workspace = mock_workspace()?;
+        let mut server = mock_server(&workspace);
+
+        let (connections_tx, connections) = mpsc::unbounded_channel();
+        let (first, first_server) = duplex(16 * 1024);
+        let (second, second_server) = duplex(16 * 1024);
+        connections_tx.send(split(first_server))?;
+        connections_tx.send(split(second_server))?;
+        drop(connections_tx);
+        let server_task = tokio::spawn(async move {
+            server
+                .serve_sessions(connections, std::future::pending())
+                .await
+        });
+        let mut first = TestClient::new(first);
+        let mut second = TestClient::new(second);
+
+        let hover = first
+            .call_tool(
+                1,
+                "rust_analyzer_hover",
+                json!({ "file_path": "src/lib.rs", "line": 0, "character": 7 }),
+            )
+            .await?;
+        assert!(hover["result"]["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("mock_function"));
+
+        let snapshot = first
+            .call_tool(2, "rust_analyzer_diagnostics_snapshot", json!({}))
+            .await?;
+        assert!(snapshot["result"].is_object(), "{snapshot}");
+        let diff = second
+            .call_tool(1, "rust_analyzer_diagnostics_diff", json!({}))
+            .await?;
+        assert!(diff["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("No diagnostics snapshot"));
+
+        let workspace_path = workspace.path().display().to_string();
+        let set_workspace = second
+            .call_tool(
+                2,
+                "rust_analyzer_set_workspace",
+                json!({ "workspace_path": workspace_path }),
+            )
+            .await?;
+        assert!(set_workspace["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("other sessions"));
+
+        drop(first);
+        drop(second);
+        timeout(Duration::from_secs(5), server_task).await???;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_request_ends_only_its_own_session() -> Result<()> {
+        let workspace = mock_workspace()?;
+        let mut server = mock_server(&workspace);
+
+        let (connections_tx, connections) = mpsc::unbounded_channel();
+        let (first, first_server) = duplex(16 * 1024);
+        let (second, second_server) = duplex(16 * 1024);
+        connections_tx.send(split(first_server))?;
+        connections_tx.send(split(second_server))?;
+        drop(connections_tx);
+        let server_task = tokio::spawn(async move {
+            server
+                .serve_sessions(connections, std::future::pending())
+                .await
+        });
+        let mut first = TestClient::new(first);
+        let mut second = TestClient::new(second);
+
+        let shutdown = json!({ "jsonrpc": "2.0", "id": 1, "method": "shutdown" });
+        first
+            .writer
+            .write_all(format!("{shutdown}\n").as_bytes())
+            .await?;
+        first.writer.flush().await?;
+        let response = first.response(1).await?;
+        assert!(response["result"].is_null(), "{response}");
+        let mut line = String::new();
+        assert_eq!(first.reader.read_line(&mut line).await?, 0);
+
+        let hover = second
+            .call_tool(
+                1,
+                "rust_analyzer_hover",
+                json!({ "file_path": "src/lib.rs", "line": 0, "character": 7 }),
+            )
+            .await?;
+        assert!(hover["result"].is_object(), "{hover}");
+
+        drop(second);
+        timeout(Duration::from_secs(5), server_task).await???;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_answers_requests_sessions_already_sent() -> Result<()> {
+        let workspace = mock_workspace()?;
+        let mut server = mock_server(&workspace);
+        let sleep: CommandTool = serde_json::from_value(json!({
+            "name": "site_sleep",
+            "description": "Sleep for a while",
+            "command": ["sleep", "{seconds}"]
+        }))?;
+        server.register_tool(Arc::new(sleep))?;
+        let (stop, stopped) = oneshot::channel::<()>();
+
+        let (connections_tx, connections) = mpsc::unbounded_channel();
+        let (client, client_server) = duplex(16 * 1024);
+        connections_tx.send(split(client_server))?;
+        let server_task = tokio::spawn(async move {
+            server
+                .serve_sessions(connections, async {
+                    let _ = stopped.await;
+                })
+                .await
+        });
+        let mut client = TestClient::new(client);
+
+        for id in [1, 2] {
+            client
+                .send_call(id, "site_sleep", json!({ "seconds": "0.5" }))
+                .await?;
+        }
+        // Stop while the first call runs and the second one waits behind it.
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let _ = stop.send(());
+
+        let first = client.response(1).await?;
+        assert!(first["result"]["content"].is_array(), "{first}");
+        let second = client.response(2).await?;
+        assert_eq!(second["error"]["code"], -32000);
+        // The connection is still open; shutdown alone ends serving.
+        timeout(Duration::from_secs(5), server_task).await???;
+        drop(connections_tx);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_documents_are_closed_with_the_last_session_using_them() -> Result<()> {
+        let workspace = mock_workspace()?;
+        let mut server = mock_server(&workspace);
+        let mut sessions = HashMap::new();
+        for id in [1, 2] {
+            let (notifications, _) = mpsc::unbounded_channel();
+            sessions.insert(
+                id,
+                SessionHandle {
+                    state: Session::default(),
+                    notifications,
+                },
+            );
+        }
+        let hover = |file: &str| {
+            serde_json::from_value(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "tools/call",
+                "params": {
+                    "name": "rust_analyzer_hover",
+                    "arguments": { "file_path": file, "line": 0, "character": 7 }
+                }
+            }))
+            .unwrap()
+        };
+
+        server
+            .handle_session_request(&mut sessions, 1, hover("src/lib.rs"))
+            .await;
+        server
+            .handle_session_request(&mut sessions, 2, hover("src/lib.rs"))
+            .await;
+        server
+            .handle_session_request(&mut sessions, 2, hover("src/other.rs"))
+            .await;
+
+        let lib = uri_from_path(&server.workspace_root.join("src/lib.rs"));
+        let other = uri_from_path(&server.workspace_root.join("src/other.rs"));
+        assert_eq!(sessions[&2].state.open_documents.len(), 2);
+
+        server.close_session(&mut sessions, 2).await;
+        let client = server.client.as_ref().unwrap();
+        assert!(client.is_document_open(&lib).await);
+        assert!(!client.is_document_open(&other).await);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+use log::{info, warn};
+use std::{
+    future::pending,
+    time::{Duration, Instant},
+};
+
+use crate::config::SHUTDOWN_DRAIN_TIMEOUT_SECS;
+
+/// Completes when the server is asked to stop: on Ctrl-C, on SIGTERM or
+/// SIGHUP on Unix, and on Windows on Ctrl-Break, when the console window is
+/// closed, or when the user logs off or the system shuts down.
+pub(super) async fn shutdown_signal() {
+    let signal = next_signal().await;
+    info!("Received {signal}; shutting down");
+}
+
+#[cfg(unix)]
+async fn next_signal() -> &'static str {
+    use tokio::signal::unix::{signal, Signal, SignalKind};
+
+    fn listen(kind: SignalKind, name: &str) -> Option<Signal> {
+        signal(kind)
+            .inspect_err(|e| warn!("Cannot listen for {name}: {e}"))
+            .ok()
+    }
+
+    let mut terminate = listen(SignalKind::terminate(), "SIGTERM");
+    let mut hangup = listen(SignalKind::hangup(), "SIGHUP");
+    tokio::select! {
+        Ok(()) = tokio::signal::ctrl_c() => "Ctrl-C",
+        Some(()) = recv(terminate.as_mut().map(Signal::recv)) => "SIGTERM",
+        Some(()) = recv(hangup.as_mut().map(Signal::recv)) => "SIGHUP",
+        else => pending().await,
+    }
+}
+
+#[cfg(windows)]
+async fn next_signal() -> &'static str {
+    use tokio::signal::windows::{ctrl_break, ctrl_close, ctrl_logoff, ctrl_shutdown};
+
+    let mut ctrl_breaks = ctrl_break()
+        .inspect_err(|e| warn!("Cannot listen for Ctrl-Break: {e}"))
+        .ok();
+    let mut close = ctrl_close()
+        .inspect_err(|e| warn!("Cannot listen for console close events: {e}"))
+        .ok();
+    let mut logoff = ctrl_logoff()
+        .inspect_err(|e| warn!("Cannot listen for logoff events: {e}"))
+        .ok();
+    let mut shutdown = ctrl_shutdown()
+        .inspect_err(|e| warn!("Cannot listen for system shutdown events: {e}"))
+        .ok();
+    tokio::select! {
+        Ok(()) = tokio::signal::ctrl_c() => "Ctrl-C",
+        Some(()) = recv(ctrl_breaks.as_mut().map(|signal| signal.recv())) => "Ctrl-Break",
+        Some(()) = recv(close.as_mut().map(|signal| signal.recv())) => "console close event",
+        Some(()) = recv(logoff.as_mut().map(|signal| signal.recv())) => "logoff event",
+        Some(()) = recv(shutdown.as_mut().map(|signal| signal.recv())) => "system shutdown event",
+        else => pending().await,
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+async fn next_signal() -> &'static str {
+    match tokio::signal::ctrl_c().await {
+        Ok(()) => "Ctrl-C",
+        Err(e) => {
+            warn!("Cannot listen for Ctrl-C: {e}");
+            pending().await
+        }
+    }
+}
+
+/// When requests still being handled at shutdown are cancelled.
+pub(super) fn shutdown_drain_deadline() -> Instant {
+    Instant::now() + Duration::from_secs(SHUTDOWN_DRAIN_TIMEOUT_SECS)
+}
+
+/// Completes at `deadline`, or never without one.
+pub(super) async fn deadline_elapsed(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+        None => pending().await,
+    }
+}
+
+/// Waits for the next event of a listener that may have failed to install.
+async fn recv(next: Option<impl std::future::Future<Output = Option<()>>>) -> Option<()> {
+    match next {
+        Some(next) => next.await,
+        None => None,
+    }
+}
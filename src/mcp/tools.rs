@@ -1,11 +1,102 @@
 use crate::protocol::mcp::ToolDefinition;
 use serde_json::json;
 
-pub fn get_tools() -> Vec<ToolDefinition> {
+/// The built-in tools followed by `custom` ones.
+pub fn get_tools(custom: Vec<ToolDefinition>) -> Vec<ToolDefinition> {
+    let mut tools = tool_definitions();
+    tools.extend(custom);
+    for tool in &mut tools {
+        tool.input_schema["properties"]["timeout_ms"] = json!({
+            "type": "number",
+            "description": "Time limit for this call in milliseconds, capped by the server's configured maximum (default: 30000 per LSP request)"
+        });
+        if SEARCH_TOOLS.contains(&tool.name.as_str()) {
+            tool.input_schema["properties"]["max_results"] = json!({
+                "type": "number",
+                "description": "Return at most this many results; the response tells how many were omitted"
+            });
+        }
+    }
+    tools
+}
+
+/// Built-in tools whose results can be long; they accept `max_results`.
+const SEARCH_TOOLS: [&str; 5] = [
+    "rust_analyzer_references",
+    "rust_analyzer_workspace_symbols",
+    "rust_analyzer_completion",
+    "rust_analyzer_diagnostics",
+    "rust_analyzer_workspace_diagnostics",
+];
+
+/// Built-in tools that run cargo builds, and with them build scripts and
+/// proc macros; they are unavailable in read-only mode.
+const EXECUTING_TOOLS: [&str; 5] = [
+    "rust_analyzer_cargo_check",
+    "rust_analyzer_run_check",
+    "rust_analyzer_cargo_build",
+    "rust_analyzer_dead_code",
+    "rust_analyzer_feature_matrix",
+];
+
+/// Built-in tools that write to the workspace; also unavailable in read-only
+/// mode.
+const WRITING_TOOLS: [&str; 2] = ["rust_analyzer_rename_file", "rust_analyzer_rename_module"];
+
+/// Built-in tools resting on one LSP request, with the server capability that
+/// advertises it. They are hidden, and their calls refused, when the running
+/// rust-analyzer does not advertise it.
+const CAPABILITY_TOOLS: [(&str, &str); 25] = [
+    ("rust_analyzer_hover", "hoverProvider"),
+    ("rust_analyzer_hover_batch", "hoverProvider"),
+    ("rust_analyzer_definition", "definitionProvider"),
+    ("rust_analyzer_definition_batch", "definitionProvider"),
+    ("rust_analyzer_references", "referencesProvider"),
+    ("rust_analyzer_call_hierarchy", "callHierarchyProvider"),
+    ("rust_analyzer_highlights", "documentHighlightProvider"),
+    ("rust_analyzer_completion", "completionProvider"),
+    ("rust_analyzer_symbols", "documentSymbolProvider"),
+    ("rust_analyzer_outline", "documentSymbolProvider"),
+    ("rust_analyzer_outline_markdown", "documentSymbolProvider"),
+    ("rust_analyzer_enclosing_items", "documentSymbolProvider"),
+    ("rust_analyzer_cfg_at", "documentSymbolProvider"),
+    ("rust_analyzer_qualified_name", "definitionProvider"),
+    ("rust_analyzer_public_api", "documentSymbolProvider"),
+    ("rust_analyzer_document_links", "documentLinkProvider"),
+    ("rust_analyzer_inlay_hints", "inlayHintProvider"),
+    ("rust_analyzer_workspace_symbols", "workspaceSymbolProvider"),
+    ("rust_analyzer_format", "documentFormattingProvider"),
+    ("rust_analyzer_code_actions", "codeActionProvider"),
+    ("rust_analyzer_rename", "renameProvider"),
+    ("rust_analyzer_prepare_rename", "renameProvider"),
+    ("rust_analyzer_trait_surface", "definitionProvider"),
+    ("rust_analyzer_impls", "implementationProvider"),
+    ("rust_analyzer_implementors", "implementationProvider"),
+];
+
+/// The server capability the built-in tool `name` needs, if it rests on one.
+pub(super) fn required_capability(name: &str) -> Option<&'static str> {
+    CAPABILITY_TOOLS
+        .iter()
+        .find(|(tool, _)| *tool == name)
+        .map(|(_, capability)| *capability)
+}
+
+pub(super) fn is_builtin_tool(name: &str) -> bool {
+    tool_definitions().iter().any(|tool| tool.name == name)
+}
+
+pub(super) fn is_read_only_tool(name: &str) -> bool {
+    !EXECUTING_TOOLS.contains(&name) && !WRITING_TOOLS.contains(&name)
+}
+
+fn tool_definitions() -> Vec<ToolDefinition> {
     vec![
         ToolDefinition {
-            name: "rust_analyzer_hover".to_string(),
-            description: "Get hover information for a symbol at a specific position in a Rust file"
+            name: "rust_analyzer_highlights".to_string(),
+            description: "Find the ranges related to a position: other uses of a variable, or \
+                          the exit points of a function from `return`/`?`/`fn` and its yield \
+                          points from `.await`/`async`"
                 .to_string(),
             input_schema: json!({
                 "type": "object",
@@ -17,6 +108,58 @@ pub fn get_tools() -> Vec<ToolDefinition> {
                 "required": ["file_path", "line", "character"]
             }),
         },
+        ToolDefinition {
+            name: "rust_analyzer_hover".to_string(),
+            description: "Get hover information for a symbol at a specific position in a Rust \
+                          file, or the type of a selected expression, with follow-up queries \
+                          for the types, implementations and references it mentions"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file_path": { "type": "string", "description": "Path to the Rust file" },
+                    "line": { "type": "number", "description": "Line number (0-based)" },
+                    "character": { "type": "number", "description": "Character position (0-based)" },
+                    "end_line": { "type": "number", "description": "End line of a selection to get the type of (0-based)" },
+                    "end_character": { "type": "number", "description": "End character of the selection (0-based)" },
+                    "format": {
+                        "type": "string",
+                        "enum": ["markdown", "plaintext", "signature_only", "docs_only", "json"],
+                        "description": "Output format: the markdown text, plain text, only the declaration, only the doc comment as plain text, or the raw LSP response (default: json)"
+                    }
+                },
+                "required": ["file_path", "line", "character"]
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_hover_batch".to_string(),
+            description: "Get hover information for many positions in one call".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "positions": {
+                        "type": "array",
+                        "description": "Positions to query, answered in the same order",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "file_path": { "type": "string", "description": "Path to the Rust file (default: the top-level file_path)" },
+                                "line": { "type": "number", "description": "Line number (0-based)" },
+                                "character": { "type": "number", "description": "Character position (0-based)" }
+                            },
+                            "required": ["line", "character"]
+                        }
+                    },
+                    "file_path": { "type": "string", "description": "Rust file for positions that do not name one" },
+                    "format": {
+                        "type": "string",
+                        "enum": ["markdown", "plaintext", "signature_only", "docs_only", "json"],
+                        "description": "Output format of each result, as for rust_analyzer_hover (default: json)"
+                    }
+                },
+                "required": ["positions"]
+            }),
+        },
         ToolDefinition {
             name: "rust_analyzer_definition".to_string(),
             description: "Go to definition of a symbol at a specific position".to_string(),
@@ -30,6 +173,31 @@ pub fn get_tools() -> Vec<ToolDefinition> {
                 "required": ["file_path", "line", "character"]
             }),
         },
+        ToolDefinition {
+            name: "rust_analyzer_definition_batch".to_string(),
+            description: "Go to the definitions of the symbols at many positions in one call"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "positions": {
+                        "type": "array",
+                        "description": "Positions to query, answered in the same order",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "file_path": { "type": "string", "description": "Path to the Rust file (default: the top-level file_path)" },
+                                "line": { "type": "number", "description": "Line number (0-based)" },
+                                "character": { "type": "number", "description": "Character position (0-based)" }
+                            },
+                            "required": ["line", "character"]
+                        }
+                    },
+                    "file_path": { "type": "string", "description": "Rust file for positions that do not name one" }
+                },
+                "required": ["positions"]
+            }),
+        },
         ToolDefinition {
             name: "rust_analyzer_references".to_string(),
             description: "Find all references to a symbol at a specific position".to_string(),
@@ -43,6 +211,74 @@ pub fn get_tools() -> Vec<ToolDefinition> {
                 "required": ["file_path", "line", "character"]
             }),
         },
+        ToolDefinition {
+            name: "rust_analyzer_call_hierarchy".to_string(),
+            description: "List the callers and callees of the function or method at a \
+                          position, optionally several levels deep, to see what a change to \
+                          it affects"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file_path": { "type": "string", "description": "Path to the Rust file" },
+                    "line": { "type": "number", "description": "Line number (0-based)" },
+                    "character": { "type": "number", "description": "Character position (0-based)" },
+                    "direction": {
+                        "type": "string",
+                        "enum": ["incoming", "outgoing", "both"],
+                        "description": "List callers (incoming), callees (outgoing) or both (default: both)"
+                    },
+                    "depth": {
+                        "type": "integer",
+                        "description": "Levels of calls to follow, at most 5 (default: 1)"
+                    }
+                },
+                "required": ["file_path", "line", "character"]
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_expand_macro".to_string(),
+            description: "Expand the macro call or derive at a position, recursively".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file_path": { "type": "string", "description": "Path to the Rust file" },
+                    "line": { "type": "number", "description": "Line number (0-based)" },
+                    "character": { "type": "number", "description": "Character position on the macro name (0-based)" },
+                    "pretty": { "type": "boolean", "description": "Format the expansion with rustfmt (default: false)" },
+                    "recursive": { "type": "boolean", "description": "Expand macros used by the expansion too (default: true); rust-analyzer cannot expand a single step, so false is rejected" }
+                },
+                "required": ["file_path", "line", "character"]
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_open_docs".to_string(),
+            description: "Get the documentation URL of the item at a position, and optionally its locally generated page".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file_path": { "type": "string", "description": "Path to the Rust file" },
+                    "line": { "type": "number", "description": "Line number (0-based)" },
+                    "character": { "type": "number", "description": "Character position (0-based)" },
+                    "local": { "type": "boolean", "description": "Also return the page under target/doc if `cargo doc` has generated it (default: false)" }
+                },
+                "required": ["file_path", "line", "character"]
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_ssr_search".to_string(),
+            description: "Find the code matching a structural search pattern such as `$a.unwrap()` across the workspace, without editing".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "pattern": { "type": "string", "description": "Structural search pattern, e.g. `$a.unwrap()` or `Foo::new(${x:kind(literal)})`" },
+                    "file_path": { "type": "string", "description": "Rust file from which paths in the pattern are resolved" },
+                    "line": { "type": "number", "description": "Line whose scope resolves paths in the pattern (0-based, default: 0)" },
+                    "character": { "type": "number", "description": "Character position on that line (0-based, default: 0)" }
+                },
+                "required": ["pattern", "file_path"]
+            }),
+        },
         ToolDefinition {
             name: "rust_analyzer_completion".to_string(),
             description: "Get code completion suggestions at a specific position".to_string(),
@@ -51,7 +287,15 @@ pub fn get_tools() -> Vec<ToolDefinition> {
                 "properties": {
                     "file_path": { "type": "string", "description": "Path to the Rust file" },
                     "line": { "type": "number", "description": "Line number (0-based)" },
-                    "character": { "type": "number", "description": "Character position (0-based)" }
+                    "character": { "type": "number", "description": "Character position (0-based)" },
+                    "prefix": { "type": "string", "description": "Only return items starting with this text (case-insensitive)" },
+                    "kinds": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Only return items of these kinds, e.g. method, field, function, variable, keyword, snippet"
+                    },
+                    "limit": { "type": "number", "description": "Maximum number of items to return, most relevant first" },
+                    "plain_text": { "type": "boolean", "description": "Include the text each item inserts with snippet placeholders resolved, plus the imports it needs" }
                 },
                 "required": ["file_path", "line", "character"]
             }),
@@ -60,6 +304,23 @@ pub fn get_tools() -> Vec<ToolDefinition> {
             name: "rust_analyzer_symbols".to_string(),
             description: "Get document symbols (functions, structs, etc.) for a Rust file"
                 .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file_path": { "type": "string", "description": "Path to the Rust file" },
+                    "qualified": {
+                        "type": "boolean",
+                        "description": "Return a flat list with fully qualified paths (crate::module::Type::method) instead of the raw LSP response (default: false)"
+                    }
+                },
+                "required": ["file_path"]
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_document_links".to_string(),
+            description: "List the links in a Rust file, such as links in doc comments, with \
+                          their ranges and targets"
+                .to_string(),
             input_schema: json!({
                 "type": "object",
                 "properties": {
@@ -68,6 +329,72 @@ pub fn get_tools() -> Vec<ToolDefinition> {
                 "required": ["file_path"]
             }),
         },
+        ToolDefinition {
+            name: "rust_analyzer_inlay_hints".to_string(),
+            description: "List the inlay hints rust-analyzer shows for a range of lines: \
+                          inferred types, parameter names and the types along method chains"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file_path": { "type": "string", "description": "Path to the Rust file" },
+                    "start_line": {
+                        "type": "integer",
+                        "description": "First line (0-based, default: 0)"
+                    },
+                    "end_line": {
+                        "type": "integer",
+                        "description": "Last line, included (0-based, default: the last line)"
+                    }
+                },
+                "required": ["file_path"]
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_workspace_symbols".to_string(),
+            description: "Search symbols across the workspace by name".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string", "description": "Fuzzy symbol name query" },
+                    "qualified": {
+                        "type": "boolean",
+                        "description": "Return each match with its fully qualified path (crate::module::Type::method) (default: false)"
+                    },
+                    "package": {
+                        "type": "string",
+                        "description": "Only return symbols defined in this workspace member"
+                    }
+                },
+                "required": ["query"]
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_item_docs".to_string(),
+            description: "Look up the signature and documentation of an item by its path, in the workspace or its dependencies".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "Item path, e.g. tokio::sync::Mutex::lock or crate::config::Config" }
+                },
+                "required": ["path"]
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_usage_stats".to_string(),
+            description: "Count the references to items across the workspace, to find what can be deleted or deprecated".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "symbols": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Item paths to count, e.g. crate::config::Config::load"
+                    },
+                    "file_path": { "type": "string", "description": "Count every public item of this module file instead" }
+                }
+            }),
+        },
         ToolDefinition {
             name: "rust_analyzer_format".to_string(),
             description: "Format a Rust file using rust-analyzer".to_string(),
@@ -94,6 +421,85 @@ pub fn get_tools() -> Vec<ToolDefinition> {
                 "required": ["file_path", "line", "character", "end_line", "end_character"]
             }),
         },
+        ToolDefinition {
+            name: "rust_analyzer_rename".to_string(),
+            description: "Compute the rename of the symbol at a position across the workspace \
+                          without applying it: every edit grouped by file with the text it \
+                          replaces, plus the WorkspaceEdit itself"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file_path": { "type": "string", "description": "Path to the Rust file" },
+                    "line": { "type": "number", "description": "Line number (0-based)" },
+                    "character": { "type": "number", "description": "Character position (0-based)" },
+                    "new_name": { "type": "string", "description": "The symbol's new name" }
+                },
+                "required": ["file_path", "line", "character", "new_name"]
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_prepare_rename".to_string(),
+            description: "Check whether the symbol at a position can be renamed, returning the \
+                          range a rename would change and its current name, or why it cannot"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file_path": { "type": "string", "description": "Path to the Rust file" },
+                    "line": { "type": "number", "description": "Line number (0-based)" },
+                    "character": { "type": "number", "description": "Character position (0-based)" }
+                },
+                "required": ["file_path", "line", "character"]
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_preview_edit".to_string(),
+            description: "Render an LSP WorkspaceEdit, such as one from a rename or code action, \
+                          as per-file unified diffs without applying it"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "edit": {
+                        "type": "object",
+                        "description": "The WorkspaceEdit, with `changes` or `documentChanges` (also accepted as a JSON string)"
+                    },
+                    "context_lines": { "type": "number", "description": "Unchanged lines shown around each change (default: 3)" }
+                },
+                "required": ["edit"]
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_rename_file".to_string(),
+            description: "Rename or move a Rust source file, updating the `mod` declarations and \
+                          paths that refer to its module"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "old_path": { "type": "string", "description": "Path to the .rs file to rename" },
+                    "new_path": { "type": "string", "description": "Path the file moves to; must not exist" },
+                    "context_lines": { "type": "number", "description": "Unchanged lines shown around each change (default: 3)" }
+                },
+                "required": ["old_path", "new_path"]
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_rename_module".to_string(),
+            description: "Rename a module: its file, the parent `mod` declaration and every path \
+                          that uses it"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file_path": { "type": "string", "description": "Path to the module's .rs file" },
+                    "new_name": { "type": "string", "description": "New module name, a Rust identifier" },
+                    "context_lines": { "type": "number", "description": "Unchanged lines shown around each change (default: 3)" }
+                },
+                "required": ["file_path", "new_name"]
+            }),
+        },
         ToolDefinition {
             name: "rust_analyzer_set_workspace".to_string(),
             description: "Set the workspace root directory for rust-analyzer".to_string(),
@@ -109,6 +515,25 @@ pub fn get_tools() -> Vec<ToolDefinition> {
             name: "rust_analyzer_diagnostics".to_string(),
             description: "Get compiler diagnostics (errors, warnings, hints) for a Rust file"
                 .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file_path": { "type": "string", "description": "Path to the Rust file" },
+                    "render": {
+                        "type": "string",
+                        "enum": ["json", "text"],
+                        "description": "json for structured output, or text for rustc-style messages (default: json)"
+                    }
+                },
+                "required": ["file_path"]
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_inactive_code".to_string(),
+            description: "List the regions of a Rust file disabled by #[cfg] attributes under the \
+                          current target and features, with the cfg that disables each; edits \
+                          there are not analyzed and show no errors"
+                .to_string(),
             input_schema: json!({
                 "type": "object",
                 "properties": {
@@ -117,13 +542,465 @@ pub fn get_tools() -> Vec<ToolDefinition> {
                 "required": ["file_path"]
             }),
         },
+        ToolDefinition {
+            name: "rust_analyzer_cfg_at".to_string(),
+            description: "Show the #[cfg] and #[cfg_attr] attributes on the items enclosing a \
+                          position and whether the code there is active under the current target \
+                          and features"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file_path": { "type": "string", "description": "Path to the Rust file" },
+                    "line": { "type": "number", "description": "Line number (0-based)" },
+                    "character": { "type": "number", "description": "Character position (0-based)" }
+                },
+                "required": ["file_path", "line", "character"]
+            }),
+        },
         ToolDefinition {
             name: "rust_analyzer_workspace_diagnostics".to_string(),
             description: "Get all compiler diagnostics across the entire workspace".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "package": {
+                        "type": "string",
+                        "description": "Only report diagnostics for files of this workspace member"
+                    },
+                    "render": {
+                        "type": "string",
+                        "enum": ["json", "text"],
+                        "description": "json for structured output, or text for rustc-style messages (default: json)"
+                    }
+                }
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_diagnostics_snapshot".to_string(),
+            description: "Capture the current workspace diagnostics under a name so a later \
+                          rust_analyzer_diagnostics_diff can report what changed"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string", "description": "Snapshot name (default: \"default\")" }
+                }
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_diagnostics_diff".to_string(),
+            description: "Compare the current workspace diagnostics against a snapshot and \
+                          report which diagnostics appeared or disappeared"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string", "description": "Snapshot name (default: \"default\")" }
+                }
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_cargo_check".to_string(),
+            description: "Run `cargo check` in the workspace and return the parsed compiler \
+                          diagnostics, including ones rust-analyzer has not published yet"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "package": { "type": "string", "description": "Only check this package" },
+                    "all_targets": { "type": "boolean", "description": "Check all targets including tests and examples (default: true)" },
+                    "target": { "type": "string", "description": "Check for this target triple instead of the host, e.g. wasm32-unknown-unknown or x86_64-pc-windows-msvc, by setting rust-analyzer's cargo.target for the check and returning the diagnostics it publishes" }
+                }
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_run_check".to_string(),
+            description: "Have rust-analyzer run its `cargo check` of the workspace now and \
+                          return the diagnostics it published. Use it with --check-on-demand, \
+                          where checks no longer run on save"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "package": { "type": "string", "description": "Only return diagnostics for this workspace member" },
+                    "render": {
+                        "type": "string",
+                        "enum": ["json", "text"],
+                        "description": "json for structured output, or text for rustc-style messages (default: json)"
+                    }
+                }
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_cargo_build".to_string(),
+            description: "Run `cargo build` in the workspace and report whether it compiled and \
+                          linked, with parsed compiler errors and produced artifacts"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "release": { "type": "boolean", "description": "Build with the release profile (default: false)" },
+                    "package": { "type": "string", "description": "Only build this package" },
+                    "lib": { "type": "boolean", "description": "Only build the library target" },
+                    "bin": { "type": "string", "description": "Only build the named binary target" },
+                    "example": { "type": "string", "description": "Only build the named example target" },
+                    "all_targets": { "type": "boolean", "description": "Build all targets including tests and examples (default: false)" }
+                }
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_dead_code".to_string(),
+            description: "Run `cargo check` and report `dead_code` and `unused_*` warnings \
+                          across the workspace, grouped by crate and module"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "package": { "type": "string", "description": "Only check this package" },
+                    "all_targets": { "type": "boolean", "description": "Check all targets including tests and examples (default: true)" }
+                }
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_feature_matrix".to_string(),
+            description: "Check the workspace under several feature combinations by setting \
+                          rust-analyzer's cargo.features and running its check for each, and \
+                          report which errors and warnings appear only under some of them"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "feature_sets": {
+                        "type": "array",
+                        "description": "Feature combinations to check, e.g. [[], [\"tls\"], [\"tls\", \"json\"]]; an empty list checks the default features",
+                        "items": { "type": "array", "items": { "type": "string" } }
+                    },
+                    "no_default_features": { "type": "boolean", "description": "Disable default features in every combination (default: false)" },
+                    "package": { "type": "string", "description": "Only report diagnostics in this package" },
+                    "all_targets": { "type": "boolean", "description": "Check all targets including tests and examples (default: true)" },
+                    "target": { "type": "string", "description": "Check for this target triple instead of the host" }
+                },
+                "required": ["feature_sets"]
+            }),
+        },
+        ToolDefinition {
+            name: "rust_toolchain_info".to_string(),
+            description: "Get the rustc, cargo, and rust-analyzer versions, the active toolchain \
+                          and channel, and the editions used by workspace members"
+                .to_string(),
             input_schema: json!({
                 "type": "object",
                 "properties": {}
             }),
         },
+        ToolDefinition {
+            name: "rust_project_overview".to_string(),
+            description: "Get a snapshot of the workspace: its member crates with their editions, \
+                          targets and features, the dependencies between them, and the external \
+                          crates they use"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {}
+            }),
+        },
+        ToolDefinition {
+            name: "rust_list_workspace_members".to_string(),
+            description: "List the workspace members with the directory each is rooted at and \
+                          its targets; their names are what the `package` argument of workspace \
+                          tools takes"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {}
+            }),
+        },
+        ToolDefinition {
+            name: "rust_cargo_dependencies".to_string(),
+            description: "List the dependencies declared in the workspace's Cargo.toml files with \
+                          their version requirements, features, kinds and sources (registry, \
+                          path or git, and whether inherited from the workspace)"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "package": { "type": "string", "description": "Only list this workspace member's dependencies" }
+                }
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_status".to_string(),
+            description: "Get the status of the rust-analyzer server, including whether the \
+                          rust-src component needed for std definitions is installed"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {}
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_server_stats".to_string(),
+            description: "Get per-tool call counts and latency percentiles, LSP request timings \
+                          and timeouts, and how often rust-analyzer was restarted"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {}
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_open_documents".to_string(),
+            description: "List the documents open in rust-analyzer with their version, size and \
+                          idle time, optionally closing some first to free memory"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "close": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Paths or URIs of documents to close"
+                    },
+                    "close_idle_secs": {
+                        "type": "number",
+                        "description": "Close every document not used for at least this many seconds"
+                    }
+                }
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_benchmark".to_string(),
+            description: "Time hover, definition, references and diagnostics on a file over \
+                          several rounds and report latency statistics per operation"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file_path": { "type": "string", "description": "Path to the Rust file to benchmark" },
+                    "line": { "type": "number", "description": "Line number (0-based) to query (default: the file's first symbol)" },
+                    "character": { "type": "number", "description": "Character position (0-based) to query (default: the file's first symbol)" },
+                    "iterations": { "type": "number", "description": "Rounds to run, from 1 to 100 (default: 5)" }
+                },
+                "required": ["file_path"]
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_logs".to_string(),
+            description: "Get recent rust-analyzer stderr output, where panics and proc-macro \
+                          server errors are reported"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "lines": { "type": "number", "description": "Maximum number of most recent lines to return (default: 100)" },
+                    "contains": { "type": "string", "description": "Only return lines containing this text, e.g. \"panicked\"" }
+                }
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_public_api".to_string(),
+            description: "List the public API of a workspace crate: public functions, types, \
+                          traits, constants and inherent methods with their signatures and \
+                          module paths, plus `pub use` re-exports"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "crate_name": {
+                        "type": "string",
+                        "description": "Name of a workspace package (dashes and underscores are interchangeable)"
+                    }
+                },
+                "required": ["crate_name"]
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_outline".to_string(),
+            description: "Get a nested outline of a file (modules, types, impls, functions) \
+                          with ranges and the first line of each doc comment"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file_path": {
+                        "type": "string",
+                        "description": "Path to the Rust file"
+                    }
+                },
+                "required": ["file_path"]
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_outline_markdown".to_string(),
+            description: "Render a file's items as a compact markdown list of signatures and doc \
+                          summaries, sized for an LLM context window"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file_path": { "type": "string", "description": "Path to the Rust file" },
+                    "max_depth": { "type": "number", "description": "Levels of nesting to list; deeper items are counted on their parent (default: all)" },
+                    "token_budget": { "type": "number", "description": "Approximate tokens per item for its signature and doc summary (default: 40)" }
+                },
+                "required": ["file_path"]
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_enclosing_items".to_string(),
+            description: "Get the chain of items (module, impl, function, ...) enclosing a \
+                          position, outermost first"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file_path": { "type": "string", "description": "Path to the Rust file" },
+                    "line": { "type": "number", "description": "Line number (0-based)" },
+                    "character": { "type": "number", "description": "Character position (0-based)" }
+                },
+                "required": ["file_path", "line", "character"]
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_qualified_name".to_string(),
+            description: "Get the fully qualified path (e.g. my_crate::net::Client::connect), \
+                          crate and kind of the item at a position or referenced there, for \
+                          writing imports and cross-file references"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file_path": { "type": "string", "description": "Path to the Rust file" },
+                    "line": { "type": "number", "description": "Line number (0-based)" },
+                    "character": { "type": "number", "description": "Character position (0-based)" }
+                },
+                "required": ["file_path", "line", "character"]
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_convert_position".to_string(),
+            description: "Convert a position in a file between byte offsets, UTF-8 byte columns \
+                          and the UTF-16 columns LSP tools take, and get the text of its line. \
+                          Give either offset, or line with utf8_column or character"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "file_path": { "type": "string", "description": "Path to the file" },
+                    "offset": {
+                        "type": "number",
+                        "description": "Byte offset from the start of the file"
+                    },
+                    "line": { "type": "number", "description": "Line number (0-based)" },
+                    "utf8_column": {
+                        "type": "number",
+                        "description": "Column in UTF-8 bytes (0-based)"
+                    },
+                    "character": {
+                        "type": "number",
+                        "description": "Column in UTF-16 code units (0-based, default: 0)"
+                    }
+                },
+                "required": ["file_path"]
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_impls".to_string(),
+            description: "List the impl blocks of a type across the workspace: inherent impls \
+                          and trait impls (including derives), with trait names and locations. \
+                          Address the type by name or by position"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "type_name": { "type": "string", "description": "Name of the struct, enum or type alias" },
+                    "file_path": { "type": "string", "description": "Path to a file containing the type (alternative to type_name)" },
+                    "line": { "type": "number", "description": "Line of the type name (0-based)" },
+                    "character": { "type": "number", "description": "Character of the type name (0-based)" }
+                }
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_implementors".to_string(),
+            description: "List every type in the workspace that implements a trait, including \
+                          derived impls. Address the trait by name or by position"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "trait_name": { "type": "string", "description": "Name of the trait" },
+                    "file_path": { "type": "string", "description": "Path to a file containing the trait (alternative to trait_name)" },
+                    "line": { "type": "number", "description": "Line of the trait name (0-based)" },
+                    "character": { "type": "number", "description": "Character of the trait name (0-based)" }
+                }
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_trait_surface".to_string(),
+            description: "List a trait's required and provided items (methods, associated types \
+                          and constants) with signatures, as needed to write a new impl block. \
+                          Address the trait by name or by position"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "trait_name": { "type": "string", "description": "Name of a workspace trait" },
+                    "file_path": { "type": "string", "description": "Path to a file containing the trait or a use of it (alternative to trait_name)" },
+                    "line": { "type": "number", "description": "Line of the trait name (0-based)" },
+                    "character": { "type": "number", "description": "Character of the trait name (0-based)" }
+                }
+            }),
+        },
+        ToolDefinition {
+            name: "rust_analyzer_batch".to_string(),
+            description: "Run several tool calls in one request and return their results. The \
+                          calls run one after another, in order, so later calls see the effects \
+                          of earlier ones; use the hover and definition batch tools to query \
+                          many positions concurrently"
+                .to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "calls": {
+                        "type": "array",
+                        "description": "The tool calls to make, in order",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "name": { "type": "string", "description": "Tool name" },
+                                "arguments": { "type": "object", "description": "Tool arguments" }
+                            },
+                            "required": ["name"]
+                        }
+                    },
+                    "stop_on_error": { "type": "boolean", "description": "Skip the remaining calls once one fails (default: false)" }
+                },
+                "required": ["calls"]
+            }),
+        },
     ]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{is_builtin_tool, required_capability};
+
+    #[test]
+    fn test_tools_are_gated_on_the_requests_they_send() {
+        // Each tool whose handler sends textDocument/implementation.
+        for tool in ["rust_analyzer_impls", "rust_analyzer_implementors"] {
+            assert!(is_builtin_tool(tool), "{tool}");
+            assert_eq!(
+                required_capability(tool),
+                Some("implementationProvider"),
+                "{tool}"
+            );
+        }
+        assert_eq!(
+            required_capability("rust_analyzer_trait_surface"),
+            Some("definitionProvider")
+        );
+        // Edit previews are rendered locally from the caller's WorkspaceEdit.
+        assert_eq!(required_capability("rust_analyzer_preview_edit"), None);
+    }
+}
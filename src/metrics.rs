@@ -0,0 +1,218 @@
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Number of recent latency samples kept per tool or LSP method for percentiles.
+const LATENCY_SAMPLES: usize = 1000;
+
+/// How a timed call ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallOutcome {
+    Ok,
+    Error,
+    Timeout,
+}
+
+/// Call counters and timings for tool calls and the LSP requests they make,
+/// shared between the MCP server and its rust-analyzer client.
+#[derive(Debug)]
+pub struct Metrics {
+    started: Instant,
+    state: Mutex<MetricsState>,
+}
+
+#[derive(Debug, Default)]
+struct MetricsState {
+    tools: BTreeMap<String, CallStats>,
+    lsp_requests: BTreeMap<String, CallStats>,
+    rust_analyzer_starts: u64,
+}
+
+#[derive(Debug, Default)]
+struct CallStats {
+    calls: u64,
+    errors: u64,
+    timeouts: u64,
+    total: Duration,
+    max: Duration,
+    recent: VecDeque<Duration>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            state: Mutex::new(MetricsState::default()),
+        }
+    }
+
+    pub fn record_tool_call(&self, tool: &str, elapsed: Duration, outcome: CallOutcome) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state
+            .tools
+            .entry(tool.to_string())
+            .or_default()
+            .record(elapsed, outcome);
+    }
+
+    pub fn record_lsp_request(&self, method: &str, elapsed: Duration, outcome: CallOutcome) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state
+            .lsp_requests
+            .entry(method.to_string())
+            .or_default()
+            .record(elapsed, outcome);
+    }
+
+    pub fn record_rust_analyzer_start(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.rust_analyzer_starts += 1;
+    }
+
+    /// Renders the collected metrics, with latencies in milliseconds.
+    pub fn snapshot(&self) -> Value {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let render = |stats: &BTreeMap<String, CallStats>| -> BTreeMap<String, Value> {
+            stats
+                .iter()
+                .map(|(name, stats)| (name.clone(), stats.to_json()))
+                .collect()
+        };
+
+        json!({
+            "uptime_secs": self.started.elapsed().as_secs(),
+            "rust_analyzer": {
+                "starts": state.rust_analyzer_starts,
+                "restarts": state.rust_analyzer_starts.saturating_sub(1)
+            },
+            "tools": render(&state.tools),
+            "lsp_requests": render(&state.lsp_requests)
+        })
+    }
+}
+
+impl CallStats {
+    fn record(&mut self, elapsed: Duration, outcome: CallOutcome) {
+        self.calls += 1;
+        match outcome {
+            CallOutcome::Ok => {}
+            CallOutcome::Error => self.errors += 1,
+            CallOutcome::Timeout => {
+                self.errors += 1;
+                self.timeouts += 1;
+            }
+        }
+        self.total += elapsed;
+        self.max = self.max.max(elapsed);
+        if self.recent.len() == LATENCY_SAMPLES {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(elapsed);
+    }
+
+    fn to_json(&self) -> Value {
+        let mut sorted: Vec<Duration> = self.recent.iter().copied().collect();
+        sorted.sort_unstable();
+
+        json!({
+            "calls": self.calls,
+            "errors": self.errors,
+            "timeouts": self.timeouts,
+            "latency_ms": {
+                "mean": millis(self.total / self.calls.max(1) as u32),
+                "p50": percentile(&sorted, 50),
+                "p90": percentile(&sorted, 90),
+                "p99": percentile(&sorted, 99),
+                "max": millis(self.max)
+            }
+        })
+    }
+}
+
+/// Latency statistics in milliseconds over `samples`: mean, min, p50, p90,
+/// p99 and max.
+pub fn latency_summary(samples: &[Duration]) -> Value {
+    let mut sorted = samples.to_vec();
+    sorted.sort_unstable();
+    let total: Duration = sorted.iter().sum();
+
+    json!({
+        "mean": millis(total / sorted.len().max(1) as u32),
+        "min": sorted.first().copied().map(millis),
+        "p50": percentile(&sorted, 50),
+        "p90": percentile(&sorted, 90),
+        "p99": percentile(&sorted, 99),
+        "max": sorted.last().copied().map(millis)
+    })
+}
+
+fn millis(duration: Duration) -> u64 {
+    (duration.as_secs_f64() * 1000.0).round() as u64
+}
+
+/// Nearest-rank percentile of sorted samples, in milliseconds.
+fn percentile(sorted: &[Duration], p: usize) -> Option<u64> {
+    let rank = (sorted.len() * p).div_ceil(100).max(1);
+    sorted.get(rank - 1).copied().map(millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{latency_summary, CallOutcome, Metrics};
+
+    #[test]
+    fn test_snapshot_reports_counts_and_percentiles() {
+        let metrics = Metrics::new();
+        for millis in 1..=100 {
+            metrics.record_tool_call(
+                "rust_analyzer_hover",
+                Duration::from_millis(millis),
+                CallOutcome::Ok,
+            );
+        }
+        metrics.record_lsp_request(
+            "textDocument/hover",
+            Duration::from_secs(30),
+            CallOutcome::Timeout,
+        );
+        metrics.record_rust_analyzer_start();
+        metrics.record_rust_analyzer_start();
+
+        let snapshot = metrics.snapshot();
+
+        let hover = &snapshot["tools"]["rust_analyzer_hover"];
+        assert_eq!(hover["calls"], 100);
+        assert_eq!(hover["errors"], 0);
+        assert_eq!(hover["latency_ms"]["p50"], 50);
+        assert_eq!(hover["latency_ms"]["p90"], 90);
+        assert_eq!(hover["latency_ms"]["p99"], 99);
+        assert_eq!(hover["latency_ms"]["max"], 100);
+        let request = &snapshot["lsp_requests"]["textDocument/hover"];
+        assert_eq!(request["timeouts"], 1);
+        assert_eq!(request["errors"], 1);
+        assert_eq!(snapshot["rust_analyzer"]["restarts"], 1);
+    }
+
+    #[test]
+    fn test_latency_summary() {
+        let samples: Vec<Duration> = [30, 10, 20].map(Duration::from_millis).to_vec();
+        let summary = latency_summary(&samples);
+
+        assert_eq!(summary["mean"], 20);
+        assert_eq!(summary["min"], 10);
+        assert_eq!(summary["p50"], 20);
+        assert_eq!(summary["max"], 30);
+        assert!(latency_summary(&[])["p50"].is_null());
+    }
+}
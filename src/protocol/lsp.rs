@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::BTreeMap;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LSPRequest {
@@ -16,3 +17,391 @@ pub struct LSPResponse {
     pub result: Option<Value>,
     pub error: Option<Value>,
 }
+
+/// A zero-based line and UTF-16 character offset in a document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Position {
+    pub line: u32,
+    pub character: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Location {
+    pub uri: String,
+    pub range: Range,
+}
+
+/// A definition target as sent to clients with `linkSupport`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocationLink {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin_selection_range: Option<Range>,
+    pub target_uri: String,
+    /// The whole target item, e.g. a function including its body.
+    pub target_range: Range,
+    /// The part of the target to highlight, e.g. the function's name.
+    pub target_selection_range: Range,
+}
+
+impl From<LocationLink> for Location {
+    fn from(link: LocationLink) -> Self {
+        Self {
+            uri: link.target_uri,
+            range: link.target_selection_range,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hover {
+    pub contents: HoverContents,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub range: Option<Range>,
+    /// Follow-up commands from rust-analyzer's `hoverActions` extension, such
+    /// as going to a type or listing implementations.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actions: Option<Vec<CommandLinkGroup>>,
+}
+
+/// A group of hover actions, e.g. `Go to` followed by one command per type.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommandLinkGroup {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub commands: Vec<Command>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum HoverContents {
+    Markup(MarkupContent),
+    Scalar(MarkedString),
+    Array(Vec<MarkedString>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MarkupContent {
+    /// `markdown` or `plaintext`.
+    pub kind: String,
+    pub value: String,
+}
+
+/// Deprecated hover markup: markdown text, or a code block in `language`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MarkedString {
+    String(String),
+    LanguageString { language: String, value: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum NumberOrString {
+    Number(i64),
+    String(String),
+}
+
+impl std::fmt::Display for NumberOrString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Number(number) => write!(f, "{}", number),
+            Self::String(string) => f.write_str(string),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostic {
+    pub range: Range,
+    /// 1 = error, 2 = warning, 3 = information, 4 = hint.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<u64>,
+    /// The lint or error code, e.g. `E0308` or `unused_variables`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<NumberOrString>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code_description: Option<CodeDescription>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    pub message: String,
+    /// 1 = unnecessary, 2 = deprecated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<u64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub related_information: Option<Vec<DiagnosticRelatedInformation>>,
+    /// Opaque data rust-analyzer needs back in code action requests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CodeDescription {
+    pub href: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiagnosticRelatedInformation {
+    pub location: Location,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SymbolInformation {
+    pub name: String,
+    /// LSP `SymbolKind`, e.g. 12 = function, 23 = struct.
+    pub kind: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<u64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<bool>,
+    pub location: Location,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container_name: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextEdit {
+    pub range: Range,
+    pub new_text: String,
+    /// Set on annotated edits inside a `WorkspaceEdit`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotation_id: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextDocumentEdit {
+    pub text_document: VersionedTextDocumentIdentifier,
+    pub edits: Vec<TextEdit>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionedTextDocumentIdentifier {
+    pub uri: String,
+    /// The document version the edits apply to; `None` for unopened files.
+    pub version: Option<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DocumentChange {
+    Edit(TextDocumentEdit),
+    /// A `create`, `rename` or `delete` file operation, kept as sent.
+    Operation(Value),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceEdit {
+    /// Edits by document URI.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changes: Option<BTreeMap<String, Vec<TextEdit>>>,
+    /// Versioned document edits and file operations, applied in order.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub document_changes: Option<Vec<DocumentChange>>,
+}
+
+impl WorkspaceEdit {
+    /// The text edits by document URI, from `documentChanges` when present and
+    /// `changes` otherwise; file operations are skipped.
+    pub fn text_edits(&self) -> Vec<(&str, &[TextEdit])> {
+        match (&self.document_changes, &self.changes) {
+            (Some(document_changes), _) => document_changes
+                .iter()
+                .filter_map(|change| match change {
+                    DocumentChange::Edit(edit) => {
+                        Some((edit.text_document.uri.as_str(), edit.edits.as_slice()))
+                    }
+                    DocumentChange::Operation(_) => None,
+                })
+                .collect(),
+            (None, Some(changes)) => changes
+                .iter()
+                .map(|(uri, edits)| (uri.as_str(), edits.as_slice()))
+                .collect(),
+            (None, None) => Vec::new(),
+        }
+    }
+}
+
+/// The answer to `textDocument/prepareRename` for a renameable position.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PrepareRename {
+    /// The range that would be renamed, with the text to start editing from.
+    RangeWithPlaceholder { range: Range, placeholder: String },
+    /// Only the range that would be renamed.
+    Range(Range),
+    /// The client is to find the identifier at the position itself.
+    DefaultBehavior {
+        #[serde(rename = "defaultBehavior")]
+        default_behavior: bool,
+    },
+}
+
+/// A function or method in a call hierarchy, from
+/// `textDocument/prepareCallHierarchy` or as a caller or callee of another.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallHierarchyItem {
+    pub name: String,
+    /// LSP `SymbolKind`, e.g. 6 = method, 12 = function.
+    pub kind: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub uri: String,
+    pub range: Range,
+    /// The item's name.
+    pub selection_range: Range,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+/// A caller of an item, with the ranges of its calls in the caller's file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallHierarchyIncomingCall {
+    pub from: CallHierarchyItem,
+    pub from_ranges: Vec<Range>,
+}
+
+/// A callee of an item, with the ranges of the calls in the item's own file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallHierarchyOutgoingCall {
+    pub to: CallHierarchyItem,
+    pub from_ranges: Vec<Range>,
+}
+
+/// The result of rust-analyzer's `rust-analyzer/expandMacro` extension.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExpandedMacro {
+    /// The macro's name, e.g. `vec` or `Debug` for a derive.
+    pub name: String,
+    /// The recursively expanded code.
+    pub expansion: String,
+}
+
+/// The result of rust-analyzer's `experimental/externalDocs` extension: the
+/// docs.rs page of an item and, when the client asks for `localDocs`, the page
+/// `cargo doc` would generate under `target/doc`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExternalDocs {
+    pub web: Option<String>,
+    pub local: Option<String>,
+}
+
+/// A range related to the symbol or keyword at a position: another use of a
+/// variable, or an exit or yield point of the enclosing function.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DocumentHighlight {
+    pub range: Range,
+    /// 1 for text, 2 for a read and 3 for a write access.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<u8>,
+}
+
+/// A clickable range in a document, such as a link in a doc comment.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DocumentLink {
+    pub range: Range,
+    /// Missing until resolved with `documentLink/resolve`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tooltip: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+/// An annotation rust-analyzer shows inline, such as an inferred type or the
+/// name of the parameter an argument is passed to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InlayHint {
+    pub position: Position,
+    pub label: InlayHintLabel,
+    /// 1 for a type and 2 for a parameter hint; other hints have none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<u8>,
+    /// Missing until resolved with `inlayHint/resolve`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tooltip: Option<InlayHintTooltip>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub padding_left: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub padding_right: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum InlayHintLabel {
+    String(String),
+    Parts(Vec<InlayHintLabelPart>),
+}
+
+/// A piece of a hint's label; type hints link each named type to its
+/// definition.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InlayHintLabelPart {
+    pub value: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tooltip: Option<InlayHintTooltip>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<Location>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum InlayHintTooltip {
+    String(String),
+    Markup(MarkupContent),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Command {
+    pub title: String,
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arguments: Option<Vec<Value>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CodeAction {
+    pub title: String,
+    /// E.g. `quickfix` or `refactor.extract`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diagnostics: Option<Vec<Diagnostic>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_preferred: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disabled: Option<CodeActionDisabled>,
+    /// Missing until resolved with `codeAction/resolve` when rust-analyzer
+    /// computes edits lazily.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edit: Option<WorkspaceEdit>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<Command>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CodeActionDisabled {
+    pub reason: String,
+}
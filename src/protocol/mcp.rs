@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{json, Value};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MCPRequest {
@@ -9,7 +9,7 @@ pub struct MCPRequest {
     pub params: Option<Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum MCPResponse {
     Success {
@@ -26,14 +26,94 @@ pub enum MCPResponse {
     },
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// JSON-RPC error code for requests left unhandled because the server is
+/// shutting down.
+pub const SHUTTING_DOWN: i32 = -32000;
+
+/// JSON-RPC error code for requests refused because the client already has as
+/// much work waiting as the server allows.
+pub const SERVER_BUSY: i32 = -32001;
+
+/// JSON-RPC error code for tool calls that ran past their timeout.
+pub const REQUEST_TIMEOUT: i32 = -32002;
+
+/// JSON-RPC error code for tool calls made while rust-analyzer is not running
+/// or could not be started.
+pub const RUST_ANALYZER_UNAVAILABLE: i32 = -32003;
+
+/// JSON-RPC error code for requests that arrive after their session has ended.
+pub const SESSION_ENDED: i32 = -32004;
+
+/// JSON-RPC error code for tools the running rust-analyzer lacks the LSP
+/// capability for.
+pub const TOOL_UNSUPPORTED: i32 = -32005;
+
+/// JSON-RPC error code for requests whose method the server does not know.
+pub const METHOD_NOT_FOUND: i32 = -32601;
+
+/// JSON-RPC error code for missing or invalid tool arguments.
+pub const INVALID_PARAMS: i32 = -32602;
+
+/// JSON-RPC error code for tool calls that failed for any other reason.
+pub const INTERNAL_ERROR: i32 = -32603;
+
+/// LSP error code for requests cancelled before they finished.
+pub const REQUEST_CANCELLED: i32 = -32800;
+
+impl MCPResponse {
+    /// The answer to a request the server will not handle, or stopped
+    /// handling, because it is shutting down.
+    pub fn shutting_down(id: Option<Value>) -> Self {
+        Self::Error {
+            jsonrpc: "2.0".to_string(),
+            id,
+            error: MCPError {
+                code: SHUTTING_DOWN,
+                message: "Request cancelled because the server is shutting down".to_string(),
+                data: None,
+            },
+        }
+    }
+
+    /// The answer to a request refused because the client reached `limit`,
+    /// which allows `max` requests. `data` names the limit so clients can back
+    /// off until earlier requests are answered.
+    pub fn server_busy(id: Option<Value>, limit: &str, max: usize) -> Self {
+        Self::Error {
+            jsonrpc: "2.0".to_string(),
+            id,
+            error: MCPError {
+                code: SERVER_BUSY,
+                message: format!(
+                    "Server busy: {limit} ({max}) reached; retry once earlier requests are answered"
+                ),
+                data: Some(json!({
+                    "reason": "server_busy",
+                    "limit": limit,
+                    "max": max
+                })),
+            },
+        }
+    }
+
+    /// The same response, addressed to the request with `id`.
+    pub fn with_id(&self, id: Option<Value>) -> Self {
+        let mut response = self.clone();
+        match &mut response {
+            Self::Success { id: current, .. } | Self::Error { id: current, .. } => *current = id,
+        }
+        response
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MCPError {
     pub code: i32,
     pub message: String,
     pub data: Option<Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolDefinition {
     pub name: String,
     pub description: String,
@@ -41,14 +121,71 @@ pub struct ToolDefinition {
     pub input_schema: Value,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptDefinition {
+    pub name: String,
+    pub description: String,
+    pub arguments: Vec<PromptArgument>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptArgument {
+    pub name: String,
+    pub description: String,
+    pub required: bool,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ToolResult {
     pub content: Vec<ContentItem>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ContentItem {
-    #[serde(rename = "type")]
-    pub content_type: String,
-    pub text: String,
+/// A `text` item, or a `resource_link` item pointing at a resource clients
+/// can fetch themselves.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentItem {
+    Text {
+        text: String,
+    },
+    ResourceLink {
+        uri: String,
+        name: String,
+        #[serde(rename = "mimeType", skip_serializing_if = "Option::is_none")]
+        mime_type: Option<String>,
+    },
+}
+
+impl ContentItem {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text { text: text.into() }
+    }
+
+    /// A link to the Rust source file at `uri`, shown as `name`.
+    pub fn resource_link(uri: impl Into<String>, name: impl Into<String>) -> Self {
+        Self::ResourceLink {
+            uri: uri.into(),
+            name: name.into(),
+            mime_type: Some("text/x-rust".to_string()),
+        }
+    }
+
+    /// The text of a `text` item; `None` for resource links.
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Self::Text { text } => Some(text),
+            Self::ResourceLink { .. } => None,
+        }
+    }
+
+    pub fn into_text(self) -> Option<String> {
+        match self {
+            Self::Text { text } => Some(text),
+            Self::ResourceLink { .. } => None,
+        }
+    }
+
+    pub fn is_text(&self) -> bool {
+        matches!(self, Self::Text { .. })
+    }
 }
@@ -1,5 +1,11 @@
 pub mod lsp;
 pub mod mcp;
 
-pub use lsp::{LSPRequest, LSPResponse};
+pub use lsp::{
+    CodeAction, CodeActionDisabled, CodeDescription, Command, Diagnostic,
+    DiagnosticRelatedInformation, DocumentChange, DocumentHighlight, DocumentLink, ExpandedMacro,
+    ExternalDocs, Hover, HoverContents, LSPRequest, LSPResponse, Location, LocationLink,
+    MarkedString, MarkupContent, NumberOrString, Position, Range, SymbolInformation,
+    TextDocumentEdit, TextEdit, VersionedTextDocumentIdentifier, WorkspaceEdit,
+};
 pub use mcp::{ContentItem, MCPError, MCPRequest, MCPResponse, ToolDefinition, ToolResult};
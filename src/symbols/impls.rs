@@ -0,0 +1,290 @@
+use serde_json::{json, Value};
+
+use super::{
+    enclosing_symbols, item_header, item_kind_name, source::word_at, tree::SymbolNode, Position,
+    SymbolKind,
+};
+
+/// An impl block found at an implementation location.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImplBlock {
+    /// Implemented trait, or `None` for inherent impls.
+    pub trait_name: Option<String>,
+    pub self_type: String,
+    /// The `impl ...` header, or the derive attribute for derived impls.
+    pub header: String,
+    pub start: Position,
+    pub end: Position,
+    /// Whether the impl comes from `#[derive(...)]` rather than an impl block.
+    pub derived: bool,
+}
+
+impl ImplBlock {
+    pub fn to_json(&self, uri: &str) -> Value {
+        json!({
+            "trait": self.trait_name,
+            "self_type": self.self_type,
+            "header": self.header,
+            "derived": self.derived,
+            "file": uri,
+            "range": {
+                "start": { "line": self.start.0, "character": self.start.1 },
+                "end": { "line": self.end.0, "character": self.end.1 }
+            }
+        })
+    }
+}
+
+/// Identifies the impl block that an implementation location points into.
+///
+/// rust-analyzer points at the self type in an impl header, or at the trait
+/// name inside `#[derive(...)]` for derived impls.
+pub fn impl_block_at(
+    nodes: &[SymbolNode],
+    lines: &[&str],
+    position: Position,
+) -> Option<ImplBlock> {
+    let chain = enclosing_symbols(nodes, position);
+
+    if let Some(node) = chain
+        .iter()
+        .rev()
+        .find(|node| node.kind_is(SymbolKind::Impl))
+    {
+        let header = item_header(lines, node)
+            .map(|header| header.signature)
+            .unwrap_or_else(|| node.name.clone());
+        let trait_name = node
+            .name
+            .strip_prefix("impl")
+            .and_then(|rest| rest.split_once(" for "))
+            .map(|(trait_part, _)| strip_generic_params(trait_part.trim()).to_string());
+        return Some(ImplBlock {
+            trait_name,
+            self_type: impl_self_type(&node.name).unwrap_or_default(),
+            header,
+            start: node.start,
+            end: node.end,
+            derived: false,
+        });
+    }
+
+    let line = lines.get(position.0 as usize)?;
+    if !line.contains("derive") {
+        return None;
+    }
+    let item = chain.last()?;
+    let trait_name = word_at(line, position.1)?;
+    Some(ImplBlock {
+        trait_name: Some(trait_name),
+        self_type: item.name.clone(),
+        header: line.trim().to_string(),
+        start: (position.0, 0),
+        end: (position.0, line.encode_utf16().count() as u32),
+        derived: true,
+    })
+}
+
+/// Splits a trait's associated items into required ones, which every impl must
+/// define, and provided ones that have a default body or value.
+pub fn trait_surface(node: &SymbolNode, lines: &[&str]) -> Value {
+    let header = item_header(lines, node);
+    let mut required = Vec::new();
+    let mut provided = Vec::new();
+
+    for member in &node.children {
+        let Some(member_header) = item_header(lines, member) else {
+            continue;
+        };
+        let has_default = match SymbolKind::from_lsp(member.kind) {
+            SymbolKind::TypeAlias => member_header.signature.contains('='),
+            _ => member_header.has_body,
+        };
+        let entry = json!({
+            "name": member.name,
+            "kind": item_kind_name(member.kind, &member_header.signature),
+            "signature": member_header.signature,
+            "doc": member_header.doc,
+            "line": member_header.line
+        });
+        if has_default {
+            provided.push(entry);
+        } else {
+            required.push(entry);
+        }
+    }
+
+    json!({
+        "name": node.name,
+        "signature": header.as_ref().map(|header| header.signature.as_str()),
+        "doc": header.and_then(|header| header.doc),
+        "required": required,
+        "provided": provided
+    })
+}
+
+/// Returns the self type of an inherent impl named like `impl Foo` or
+/// `impl<T> Foo<T>`, or `None` for trait impls.
+pub(super) fn inherent_impl_type(name: &str) -> Option<String> {
+    let rest = name.strip_prefix("impl")?.trim_start();
+    if rest.contains(" for ") {
+        return None;
+    }
+    let self_type = strip_generic_params(rest);
+    let self_type = self_type.split('<').next().unwrap_or(self_type).trim();
+    (!self_type.is_empty()).then(|| self_type.to_string())
+}
+
+/// Returns the self type of any impl, e.g. `Foo` for `impl Display for Foo`.
+pub(super) fn impl_self_type(name: &str) -> Option<String> {
+    match name.split_once(" for ") {
+        Some((_, self_type)) => {
+            let self_type = self_type.trim();
+            Some(
+                self_type
+                    .split('<')
+                    .next()
+                    .unwrap_or(self_type)
+                    .trim()
+                    .to_string(),
+            )
+        }
+        None => inherent_impl_type(name),
+    }
+}
+
+/// Drops a leading `<...>` parameter list, e.g. `<T> Display` -> `Display`.
+fn strip_generic_params(text: &str) -> &str {
+    if !text.starts_with('<') {
+        return text;
+    }
+    let mut depth = 0;
+    for (index, ch) in text.char_indices() {
+        match ch {
+            '<' => depth += 1,
+            '>' => {
+                depth -= 1;
+                if depth == 0 {
+                    return text[index + 1..].trim_start();
+                }
+            }
+            _ => {}
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{impl_block_at, inherent_impl_type, trait_surface};
+    use crate::symbols::build_symbol_tree;
+
+    #[test]
+    fn test_impl_block_at_classifies_impls() {
+        let source = [
+            "#[derive(Debug, Clone)]",
+            "pub struct Config {}",
+            "",
+            "impl Config {",
+            "    pub fn new() -> Self { Config {} }",
+            "}",
+            "",
+            "impl<T> From<T> for Config {",
+            "    fn from(_: T) -> Self { Config {} }",
+            "}",
+        ];
+        let flat = |name: &str, kind: u64, start: u32, end: u32| {
+            json!({
+                "name": name,
+                "kind": kind,
+                "location": { "uri": "file:///lib.rs", "range": {
+                    "start": { "line": start, "character": 0 },
+                    "end": { "line": end, "character": 1 }
+                }}
+            })
+        };
+        let tree = build_symbol_tree(&json!([
+            flat("Config", 23, 0, 1),
+            flat("impl Config", 19, 3, 5),
+            flat("new", 12, 4, 4),
+            flat("impl<T> From<T> for Config", 19, 7, 9),
+            flat("from", 6, 8, 8)
+        ]));
+
+        let inherent = impl_block_at(&tree, &source, (3, 5)).unwrap();
+        let from = impl_block_at(&tree, &source, (7, 21)).unwrap();
+        let derived = impl_block_at(&tree, &source, (0, 17)).unwrap();
+
+        assert_eq!(inherent.trait_name, None);
+        assert_eq!(inherent.header, "impl Config");
+        assert_eq!(from.trait_name.as_deref(), Some("From<T>"));
+        assert_eq!(from.self_type, "Config");
+        assert!(derived.derived);
+        assert_eq!(derived.trait_name.as_deref(), Some("Clone"));
+        assert_eq!(derived.self_type, "Config");
+    }
+
+    #[test]
+    fn test_inherent_impl_type() {
+        assert_eq!(
+            inherent_impl_type("impl Counter").as_deref(),
+            Some("Counter")
+        );
+        assert_eq!(
+            inherent_impl_type("impl<T> Stack<T>").as_deref(),
+            Some("Stack")
+        );
+        assert_eq!(inherent_impl_type("impl Display for Counter"), None);
+    }
+
+    #[test]
+    fn test_trait_surface_splits_required_and_provided() {
+        let source = [
+            "pub trait Shape: Debug {",
+            "    type Unit;",
+            "    const SIDES: u32 = 0;",
+            "    fn area(&self) -> f64;",
+            "    /// Doubles the area.",
+            "    fn double(&self) -> f64 {",
+            "        self.area() * 2.0",
+            "    }",
+            "}",
+        ];
+        let flat = |name: &str, kind: u64, start: u32, end: u32| {
+            json!({
+                "name": name,
+                "kind": kind,
+                "location": { "uri": "file:///lib.rs", "range": {
+                    "start": { "line": start, "character": 0 },
+                    "end": { "line": end, "character": 1 }
+                }}
+            })
+        };
+        let tree = build_symbol_tree(&json!([
+            flat("Shape", 11, 0, 8),
+            flat("Unit", 26, 1, 1),
+            flat("SIDES", 14, 2, 2),
+            flat("area", 12, 3, 3),
+            flat("double", 12, 4, 7)
+        ]));
+
+        let surface = trait_surface(&tree[0], &source);
+
+        assert_eq!(surface["signature"], "pub trait Shape: Debug");
+        let required: Vec<&str> = surface["required"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|member| member["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(required, ["Unit", "area"]);
+        assert_eq!(surface["provided"][0]["name"], "SIDES");
+        assert_eq!(
+            surface["provided"][1]["signature"],
+            "fn double(&self) -> f64"
+        );
+        assert_eq!(surface["provided"][1]["doc"], "Doubles the area.");
+    }
+}
@@ -0,0 +1,85 @@
+mod impls;
+mod outline;
+mod paths;
+mod public_api;
+mod source;
+mod tree;
+
+pub use impls::{impl_block_at, trait_surface, ImplBlock};
+pub use outline::{outline, outline_markdown};
+pub use paths::{
+    crate_root_for_file, crate_roots, join_container_path, matched_path_suffix,
+    module_path_for_file, qualified_path_at, qualified_symbols, symbol_path_components, CrateRoot,
+};
+pub use public_api::{collect_public_items, FilePublicApi};
+pub use source::{cfg_attributes, identifier_column, item_header, word_at, ItemHeader};
+pub use tree::{build_symbol_tree, enclosing_symbols, Position, SymbolNode};
+
+/// Rust item kinds as rust-analyzer reports them through LSP `SymbolKind`.
+///
+/// rust-analyzer folds several Rust kinds into one LSP kind: statics are
+/// reported as constants, type aliases as type parameters and `macro_rules!`
+/// definitions as functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Module,
+    Struct,
+    Enum,
+    Variant,
+    Trait,
+    Function,
+    Method,
+    Field,
+    Constant,
+    TypeAlias,
+    Impl,
+    Other,
+}
+
+impl SymbolKind {
+    pub fn from_lsp(kind: u64) -> Self {
+        match kind {
+            2 | 3 => Self::Module,
+            23 => Self::Struct,
+            10 => Self::Enum,
+            22 => Self::Variant,
+            11 => Self::Trait,
+            12 => Self::Function,
+            6 | 9 => Self::Method,
+            7 | 8 => Self::Field,
+            13 | 14 => Self::Constant,
+            26 => Self::TypeAlias,
+            5 | 19 => Self::Impl,
+            _ => Self::Other,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Module => "module",
+            Self::Struct => "struct",
+            Self::Enum => "enum",
+            Self::Variant => "variant",
+            Self::Trait => "trait",
+            Self::Function => "function",
+            Self::Method => "method",
+            Self::Field => "field",
+            Self::Constant => "constant",
+            Self::TypeAlias => "type_alias",
+            Self::Impl => "impl",
+            Self::Other => "other",
+        }
+    }
+}
+
+/// Names the kind of an item, using its signature to tell apart the Rust kinds
+/// that share an LSP kind.
+pub fn item_kind_name(kind: u64, signature: &str) -> &'static str {
+    let has_keyword = |keyword: &str| signature.split_whitespace().any(|word| word == keyword);
+    match SymbolKind::from_lsp(kind) {
+        SymbolKind::Constant if has_keyword("static") => "static",
+        SymbolKind::Function if signature.starts_with("macro_rules!") => "macro",
+        SymbolKind::Struct if has_keyword("union") => "union",
+        other => other.name(),
+    }
+}
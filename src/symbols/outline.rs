@@ -0,0 +1,201 @@
+use serde_json::{json, Map, Value};
+
+use super::{item_header, item_kind_name, tree::SymbolNode};
+
+/// Rough number of characters per token used for outline budgets.
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Renders a symbol tree as a nested outline with Rust item kinds, ranges and
+/// the first line of each item's doc comment.
+pub fn outline(nodes: &[SymbolNode], lines: &[&str]) -> Vec<Value> {
+    nodes.iter().map(|node| outline_node(node, lines)).collect()
+}
+
+fn outline_node(node: &SymbolNode, lines: &[&str]) -> Value {
+    let header = item_header(lines, node);
+    let signature = header
+        .as_ref()
+        .map(|header| header.signature.as_str())
+        .unwrap_or("");
+
+    let mut entry = Map::new();
+    entry.insert("name".to_string(), json!(node.name));
+    entry.insert(
+        "kind".to_string(),
+        json!(item_kind_name(node.kind, signature)),
+    );
+    entry.insert("range".to_string(), node.range_json());
+    if let Some(doc) = header.and_then(|header| header.doc) {
+        entry.insert("doc".to_string(), json!(doc));
+    }
+    if !node.children.is_empty() {
+        entry.insert(
+            "children".to_string(),
+            json!(outline(&node.children, lines)),
+        );
+    }
+    Value::Object(entry)
+}
+
+/// Renders a symbol tree as a compact markdown list for LLM context: one line
+/// per item with its signature, doc summary and 0-based line, e.g.
+/// ``- `pub fn add(a: i32) -> i32` — Adds. (L12)``.
+///
+/// Items nested deeper than `max_depth` are summarized as a count on their
+/// parent. Each line's signature and summary are cut to roughly
+/// `token_budget` tokens, dropping the summary before the signature.
+pub fn outline_markdown(
+    nodes: &[SymbolNode],
+    lines: &[&str],
+    max_depth: Option<usize>,
+    token_budget: usize,
+) -> String {
+    let mut markdown = String::new();
+    render_markdown(nodes, lines, 0, max_depth, token_budget, &mut markdown);
+    markdown
+}
+
+fn render_markdown(
+    nodes: &[SymbolNode],
+    lines: &[&str],
+    depth: usize,
+    max_depth: Option<usize>,
+    token_budget: usize,
+    markdown: &mut String,
+) {
+    for node in nodes {
+        let header = item_header(lines, node);
+        let (signature, doc, line) = match &header {
+            Some(header) => (header.signature.clone(), header.doc.as_deref(), header.line),
+            None => (
+                format!("{} {}", item_kind_name(node.kind, ""), node.name),
+                None,
+                node.start.0,
+            ),
+        };
+
+        let budget = token_budget.max(1) * CHARS_PER_TOKEN;
+        let signature = truncate_chars(&signature, budget);
+        let remaining = budget.saturating_sub(signature.chars().count());
+
+        markdown.push_str(&"  ".repeat(depth));
+        markdown.push_str(&format!("- `{}`", signature));
+        if let Some(doc) = doc.filter(|_| remaining > 0) {
+            markdown.push_str(&format!(" — {}", truncate_chars(doc, remaining)));
+        }
+        markdown.push_str(&format!(" (L{})", line));
+
+        let expand = max_depth.is_none_or(|max_depth| depth + 1 < max_depth);
+        if !node.children.is_empty() && !expand {
+            markdown.push_str(&format!(" [+{} nested]", node.children.len()));
+        }
+        markdown.push('\n');
+        if expand {
+            render_markdown(
+                &node.children,
+                lines,
+                depth + 1,
+                max_depth,
+                token_budget,
+                markdown,
+            );
+        }
+    }
+}
+
+/// Cuts `text` to at most `max_chars` characters, marking the cut with `…`.
+fn truncate_chars(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let kept: String = text.chars().take(max_chars.saturating_sub(1)).collect();
+    format!("{}…", kept.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{outline, outline_markdown};
+    use crate::symbols::build_symbol_tree;
+
+    #[test]
+    fn test_outline_nests_items_with_docs() {
+        let source = [
+            "/// Helpers.",
+            "pub mod util {",
+            "    /// Doubles a value.",
+            "    pub static SCALE: i32 = 2;",
+            "}",
+        ];
+        let symbols = json!([
+            {
+                "name": "util",
+                "kind": 2,
+                "location": { "uri": "file:///lib.rs", "range": {
+                    "start": { "line": 0, "character": 0 },
+                    "end": { "line": 4, "character": 1 }
+                }}
+            },
+            {
+                "name": "SCALE",
+                "kind": 14,
+                "location": { "uri": "file:///lib.rs", "range": {
+                    "start": { "line": 2, "character": 4 },
+                    "end": { "line": 3, "character": 30 }
+                }}
+            }
+        ]);
+
+        let outline = outline(&build_symbol_tree(&symbols), &source);
+
+        assert_eq!(outline[0]["kind"], "module");
+        assert_eq!(outline[0]["doc"], "Helpers.");
+        let child = &outline[0]["children"][0];
+        assert_eq!(child["name"], "SCALE");
+        assert_eq!(child["kind"], "static");
+        assert_eq!(child["doc"], "Doubles a value.");
+        assert!(child.get("children").is_none());
+    }
+
+    #[test]
+    fn test_outline_markdown_limits_depth_and_budget() {
+        let source = [
+            "/// Helpers for working with numbers.",
+            "pub mod util {",
+            "    /// Doubles a value and returns the result to the caller.",
+            "    pub fn double(value: i32) -> i32 { value * 2 }",
+            "}",
+        ];
+        let symbols = json!([
+            {
+                "name": "util",
+                "kind": 2,
+                "location": { "uri": "file:///lib.rs", "range": {
+                    "start": { "line": 0, "character": 0 },
+                    "end": { "line": 4, "character": 1 }
+                }}
+            },
+            {
+                "name": "double",
+                "kind": 12,
+                "location": { "uri": "file:///lib.rs", "range": {
+                    "start": { "line": 2, "character": 4 },
+                    "end": { "line": 3, "character": 50 }
+                }}
+            }
+        ]);
+        let tree = build_symbol_tree(&symbols);
+
+        assert_eq!(
+            outline_markdown(&tree, &source, None, 100),
+            "- `pub mod util` — Helpers for working with numbers. (L1)\n  \
+             - `pub fn double(value: i32) -> i32` — Doubles a value and returns the result to \
+             the caller. (L3)\n"
+        );
+        assert_eq!(
+            outline_markdown(&tree, &source, Some(1), 5),
+            "- `pub mod util` — Helpers… (L1) [+1 nested]\n"
+        );
+    }
+}
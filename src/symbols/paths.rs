@@ -0,0 +1,335 @@
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+use super::{impls::impl_self_type, item_kind_name, tree::SymbolNode, SymbolKind};
+
+/// The root source file of a workspace crate target.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrateRoot {
+    /// Crate name as written in paths (dashes replaced by underscores).
+    pub name: String,
+    pub package: String,
+    pub src_path: PathBuf,
+    /// Cargo target kind: `lib`, `bin`, `test`, `example`, ...
+    pub kind: String,
+}
+
+impl CrateRoot {
+    pub fn is_lib(&self) -> bool {
+        matches!(
+            self.kind.as_str(),
+            "lib" | "rlib" | "dylib" | "cdylib" | "proc-macro"
+        )
+    }
+
+    fn rank(&self) -> u8 {
+        match self.kind.as_str() {
+            _ if self.is_lib() => 0,
+            "bin" => 1,
+            _ => 2,
+        }
+    }
+}
+
+/// Lists the crate roots of every target in `cargo metadata` output, library
+/// targets first, then binaries.
+pub fn crate_roots(metadata: &Value) -> Vec<CrateRoot> {
+    let mut roots = Vec::new();
+    for package in metadata["packages"].as_array().into_iter().flatten() {
+        let package_name = package["name"].as_str().unwrap_or_default();
+        for target in package["targets"].as_array().into_iter().flatten() {
+            let (Some(name), Some(src_path)) =
+                (target["name"].as_str(), target["src_path"].as_str())
+            else {
+                continue;
+            };
+            roots.push(CrateRoot {
+                name: name.replace('-', "_"),
+                package: package_name.to_string(),
+                src_path: PathBuf::from(src_path),
+                kind: target["kind"][0].as_str().unwrap_or("lib").to_string(),
+            });
+        }
+    }
+    roots.sort_by_key(CrateRoot::rank);
+    roots
+}
+
+/// Picks the crate a file belongs to: a target whose root is the file itself,
+/// otherwise the target whose source directory most closely contains it.
+pub fn crate_root_for_file<'a>(roots: &'a [CrateRoot], file: &Path) -> Option<&'a CrateRoot> {
+    if let Some(root) = roots.iter().find(|root| root.src_path == file) {
+        return Some(root);
+    }
+    roots
+        .iter()
+        .filter(|root| {
+            root.src_path
+                .parent()
+                .is_some_and(|dir| file.starts_with(dir))
+        })
+        // Longest directory wins; on ties the library root is preferred.
+        .max_by_key(|root| (root.src_path.components().count(), root.is_lib()))
+}
+
+/// Derives the module path of `file` relative to its crate root following the
+/// `foo.rs` / `foo/mod.rs` layout, e.g. `src/net/http.rs` -> `["net", "http"]`.
+pub fn module_path_for_file(crate_root: &Path, file: &Path) -> Option<Vec<String>> {
+    if crate_root == file {
+        return Some(Vec::new());
+    }
+    let relative = file.strip_prefix(crate_root.parent()?).ok()?;
+    let mut segments: Vec<String> = relative
+        .iter()
+        .map(|component| component.to_string_lossy().into_owned())
+        .collect();
+    let file_name = segments.pop()?;
+    if file_name != "mod.rs" {
+        segments.push(file_name.strip_suffix(".rs")?.to_string());
+    }
+    Some(segments)
+}
+
+/// Flattens a symbol tree into a list of symbols with `::`-joined paths built
+/// from `prefix` and the enclosing modules, types and impls. An empty prefix
+/// yields paths relative to the file.
+pub fn qualified_symbols(nodes: &[SymbolNode], prefix: &str) -> Vec<Value> {
+    let mut symbols = Vec::new();
+    collect_qualified(nodes, prefix, &mut symbols);
+    symbols
+}
+
+fn collect_qualified(nodes: &[SymbolNode], prefix: &str, symbols: &mut Vec<Value>) {
+    for node in nodes {
+        let path = join_path(prefix, path_segment(node));
+        if !node.kind_is(SymbolKind::Impl) {
+            symbols.push(json!({
+                "path": path,
+                "name": node.name,
+                "kind": item_kind_name(node.kind, ""),
+                "range": node.range_json()
+            }));
+        }
+        collect_qualified(&node.children, &path, symbols);
+    }
+}
+
+/// Returns the path of the innermost symbol enclosing `position`, or `None`
+/// when no symbol covers it.
+pub fn qualified_path_at(
+    nodes: &[SymbolNode],
+    prefix: &str,
+    position: (u32, u32),
+) -> Option<String> {
+    let node = nodes.iter().find(|node| node.contains(position))?;
+    let path = join_path(prefix, path_segment(node));
+    Some(qualified_path_at(&node.children, &path, position).unwrap_or(path))
+}
+
+/// Joins the container path rust-analyzer shows in a hover with a path
+/// relative to the item's file, dropping the segments both spell out:
+/// `core::option::Option` and `Option::map` give `core::option::Option::map`.
+pub fn join_container_path(container: &str, relative: &str) -> String {
+    let container: Vec<&str> = container.split("::").collect();
+    let relative: Vec<&str> = relative.split("::").collect();
+    let overlap = (1..=container.len().min(relative.len()))
+        .rev()
+        .find(|&len| container[container.len() - len..] == relative[..len])
+        .unwrap_or(0);
+    container
+        .iter()
+        .chain(&relative[overlap..])
+        .copied()
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+fn join_path(prefix: &str, segment: Option<String>) -> String {
+    match segment {
+        Some(segment) if prefix.is_empty() => segment,
+        Some(segment) => format!("{}::{}", prefix, segment),
+        None => prefix.to_string(),
+    }
+}
+
+/// The path segment a symbol contributes: its name, or the self type for impl
+/// blocks (so methods of `impl Display for Foo` resolve as `Foo::fmt`).
+fn path_segment(node: &SymbolNode) -> Option<String> {
+    if node.kind_is(SymbolKind::Impl) {
+        return impl_self_type(&node.name);
+    }
+    Some(node.name.clone())
+}
+
+/// The path segments a workspace symbol's location suggests: the directories
+/// and module of its file, with crate directory versions dropped (so
+/// `tokio-1.38.0/src/sync/mutex.rs` gives `tokio`, `src`, `sync`, `mutex`),
+/// then the self type of its container and its own name.
+pub fn symbol_path_components(file: &Path, container: Option<&str>, name: &str) -> Vec<String> {
+    let mut components: Vec<String> = file
+        .iter()
+        .map(|component| {
+            let component = component.to_string_lossy();
+            let component = component.strip_suffix(".rs").unwrap_or(&component);
+            let component = match component.rsplit_once('-') {
+                Some((krate, version)) if version.starts_with(|c: char| c.is_ascii_digit()) => {
+                    krate
+                }
+                _ => component,
+            };
+            component.replace('-', "_")
+        })
+        .collect();
+    if let Some(container) = container {
+        let self_type = impl_self_type(container)
+            .unwrap_or_else(|| container.split('<').next().unwrap_or(container).to_string());
+        components.push(self_type.trim().to_string());
+    }
+    components.push(name.to_string());
+    components
+}
+
+/// How many trailing segments of `path` appear, in order, among `components`.
+/// Re-exported items match only the segments after the re-exporting crate, e.g.
+/// `std::vec::Vec` defined in `alloc/src/vec/mod.rs` matches two segments.
+pub fn matched_path_suffix(path: &[&str], components: &[String]) -> usize {
+    (1..=path.len())
+        .rev()
+        .find(|&len| {
+            let mut components = components.iter();
+            path[path.len() - len..]
+                .iter()
+                .all(|segment| components.any(|component| component == segment))
+        })
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use std::path::Path;
+
+    use super::{
+        crate_root_for_file, crate_roots, join_container_path, matched_path_suffix,
+        module_path_for_file, qualified_symbols, symbol_path_components,
+    };
+    use crate::symbols::build_symbol_tree;
+
+    #[test]
+    fn test_module_path_for_file() {
+        let root = Path::new("/ws/src/lib.rs");
+
+        assert_eq!(module_path_for_file(root, root), Some(vec![]));
+        assert_eq!(
+            module_path_for_file(root, Path::new("/ws/src/net/http.rs")),
+            Some(vec!["net".to_string(), "http".to_string()])
+        );
+        assert_eq!(
+            module_path_for_file(root, Path::new("/ws/src/net/mod.rs")),
+            Some(vec!["net".to_string()])
+        );
+        assert_eq!(module_path_for_file(root, Path::new("/other/x.rs")), None);
+    }
+
+    #[test]
+    fn test_crate_root_for_file_prefers_exact_and_library_roots() {
+        let metadata = json!({
+            "packages": [{
+                "name": "my-app",
+                "targets": [
+                    { "name": "my-app", "kind": ["bin"], "src_path": "/ws/src/main.rs" },
+                    { "name": "my-app", "kind": ["lib"], "src_path": "/ws/src/lib.rs" }
+                ]
+            }]
+        });
+        let roots = crate_roots(&metadata);
+
+        let main = crate_root_for_file(&roots, Path::new("/ws/src/main.rs")).unwrap();
+        let module = crate_root_for_file(&roots, Path::new("/ws/src/util.rs")).unwrap();
+
+        assert_eq!(main.kind, "bin");
+        assert!(module.is_lib());
+        assert_eq!(module.name, "my_app");
+    }
+
+    #[test]
+    fn test_qualified_symbols() {
+        let flat = |name: &str, kind: u64, start: u32, end: u32| {
+            json!({
+                "name": name,
+                "kind": kind,
+                "location": { "uri": "file:///ws/src/lib.rs", "range": {
+                    "start": { "line": start, "character": 0 },
+                    "end": { "line": end, "character": 1 }
+                }}
+            })
+        };
+        let tree = build_symbol_tree(&json!([
+            flat("shapes", 2, 0, 20),
+            flat("Circle", 23, 1, 3),
+            flat("impl Display for Circle", 19, 4, 8),
+            flat("fmt", 6, 5, 7)
+        ]));
+
+        let symbols = qualified_symbols(&tree, "crate");
+
+        let paths: Vec<&str> = symbols
+            .iter()
+            .map(|symbol| symbol["path"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "crate::shapes",
+                "crate::shapes::Circle",
+                "crate::shapes::Circle::fmt"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_join_container_path_drops_shared_segments() {
+        assert_eq!(
+            join_container_path("core::option::Option", "Option::map"),
+            "core::option::Option::map"
+        );
+        assert_eq!(
+            join_container_path("std::collections::hash::map", "HashMap"),
+            "std::collections::hash::map::HashMap"
+        );
+        assert_eq!(join_container_path("demo::io", "io"), "demo::io");
+    }
+
+    #[test]
+    fn test_matched_path_suffix() {
+        let lock = symbol_path_components(
+            Path::new("/cargo/registry/src/index-6f17/tokio-1.38.0/src/sync/mutex.rs"),
+            Some("impl<T: ?Sized> Mutex<T>"),
+            "lock",
+        );
+        assert!(lock.ends_with(&[
+            "tokio".to_string(),
+            "src".to_string(),
+            "sync".to_string(),
+            "mutex".to_string(),
+            "Mutex".to_string(),
+            "lock".to_string()
+        ]));
+        assert_eq!(
+            matched_path_suffix(&["tokio", "sync", "Mutex", "lock"], &lock),
+            4
+        );
+        assert_eq!(
+            matched_path_suffix(&["std", "sync", "Mutex", "lock"], &lock),
+            3
+        );
+        assert_eq!(matched_path_suffix(&["RwLock", "lock"], &lock), 1);
+
+        let fmt = symbol_path_components(
+            Path::new("/ws/src/shapes.rs"),
+            Some("Display for Circle"),
+            "fmt",
+        );
+        assert_eq!(matched_path_suffix(&["shapes", "Circle", "fmt"], &fmt), 3);
+    }
+}
@@ -0,0 +1,283 @@
+use serde_json::{json, Value};
+
+use super::{
+    impls::inherent_impl_type,
+    item_kind_name,
+    source::{item_header, ItemHeader},
+    tree::SymbolNode,
+    SymbolKind,
+};
+
+/// Public items found in a single source file of a crate.
+#[derive(Debug, Default)]
+pub struct FilePublicApi {
+    pub items: Vec<Value>,
+    pub reexports: Vec<Value>,
+    /// `pub mod name;` declarations whose bodies live in other files, as
+    /// `(module path, module name)` pairs.
+    pub out_of_line_modules: Vec<(String, String)>,
+}
+
+/// Collects the public items of one file whose top-level items belong to
+/// `module_path` (e.g. `my_crate::utils`).
+pub fn collect_public_items(
+    nodes: &[SymbolNode],
+    lines: &[&str],
+    module_path: &str,
+    file: &str,
+) -> FilePublicApi {
+    let mut api = FilePublicApi::default();
+    collect_module(
+        nodes,
+        lines,
+        module_path,
+        file,
+        (0, lines.len() as u32),
+        &mut api,
+    );
+    api
+}
+
+fn collect_module(
+    nodes: &[SymbolNode],
+    lines: &[&str],
+    module_path: &str,
+    file: &str,
+    line_span: (u32, u32),
+    api: &mut FilePublicApi,
+) {
+    for node in nodes {
+        let Some(header) = item_header(lines, node) else {
+            continue;
+        };
+
+        match SymbolKind::from_lsp(node.kind) {
+            SymbolKind::Module => {
+                if !header.is_public() {
+                    continue;
+                }
+                let child_path = format!("{}::{}", module_path, node.name);
+                api.items.push(item_json(
+                    &child_path,
+                    node,
+                    &header.signature,
+                    header.doc,
+                    file,
+                    header.line,
+                ));
+                if header.has_body {
+                    collect_module(
+                        &node.children,
+                        lines,
+                        &child_path,
+                        file,
+                        (node.start.0, node.end.0),
+                        api,
+                    );
+                } else {
+                    api.out_of_line_modules
+                        .push((child_path, node.name.clone()));
+                }
+            }
+            SymbolKind::Impl => {
+                // Trait impls expose the trait's items; only inherent methods add
+                // new public surface.
+                let Some(self_type) = inherent_impl_type(&node.name) else {
+                    continue;
+                };
+                for method in &node.children {
+                    let Some(method_header) = item_header(lines, method) else {
+                        continue;
+                    };
+                    if method_header.is_public() {
+                        let path = format!("{}::{}::{}", module_path, self_type, method.name);
+                        api.items.push(item_json(
+                            &path,
+                            method,
+                            &method_header.signature,
+                            method_header.doc,
+                            file,
+                            method_header.line,
+                        ));
+                    }
+                }
+            }
+            SymbolKind::Function
+            | SymbolKind::Struct
+            | SymbolKind::Enum
+            | SymbolKind::Trait
+            | SymbolKind::Constant
+            | SymbolKind::TypeAlias
+                if header.is_public() || is_exported_macro(lines, &header) =>
+            {
+                let path = format!("{}::{}", module_path, node.name);
+                api.items.push(item_json(
+                    &path,
+                    node,
+                    &header.signature,
+                    header.doc,
+                    file,
+                    header.line,
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    collect_reexports(nodes, lines, module_path, line_span, api);
+}
+
+/// Records `pub use` statements that sit directly in this module, outside any
+/// nested item.
+fn collect_reexports(
+    nodes: &[SymbolNode],
+    lines: &[&str],
+    module_path: &str,
+    (first, last): (u32, u32),
+    api: &mut FilePublicApi,
+) {
+    let mut line = first;
+    while line < last.min(lines.len() as u32) {
+        if let Some(node) = nodes
+            .iter()
+            .find(|node| node.start.0 <= line && line <= node.end.0)
+        {
+            line = node.end.0 + 1;
+            continue;
+        }
+
+        let trimmed = lines[line as usize].trim();
+        if trimmed.starts_with("pub use ") {
+            let mut statement = trimmed.to_string();
+            let start = line;
+            while !statement.ends_with(';') && line + 1 < lines.len() as u32 {
+                line += 1;
+                statement.push(' ');
+                statement.push_str(lines[line as usize].trim());
+            }
+            api.reexports.push(json!({
+                "module": module_path,
+                "statement": statement.split_whitespace().collect::<Vec<_>>().join(" "),
+                "line": start
+            }));
+        }
+        line += 1;
+    }
+}
+
+/// `macro_rules!` items are reported as functions and are public when marked
+/// `#[macro_export]`.
+fn is_exported_macro(lines: &[&str], header: &ItemHeader) -> bool {
+    header.signature.starts_with("macro_rules!")
+        && lines[..header.line as usize]
+            .iter()
+            .rev()
+            .take(3)
+            .any(|line| line.contains("#[macro_export]"))
+}
+
+fn item_json(
+    path: &str,
+    node: &SymbolNode,
+    signature: &str,
+    doc: Option<String>,
+    file: &str,
+    line: u32,
+) -> Value {
+    json!({
+        "path": path,
+        "kind": item_kind_name(node.kind, signature),
+        "signature": signature,
+        "doc": doc,
+        "file": file,
+        "line": line
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::collect_public_items;
+    use crate::symbols::build_symbol_tree;
+
+    fn flat(name: &str, kind: u64, start: u32, end: u32) -> serde_json::Value {
+        json!({
+            "name": name,
+            "kind": kind,
+            "location": {
+                "uri": "file:///src/lib.rs",
+                "range": {
+                    "start": { "line": start, "character": 0 },
+                    "end": { "line": end, "character": 1 }
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_collect_public_items() {
+        let source = [
+            "pub mod types;",
+            "mod private;",
+            "pub use types::Config;",
+            "",
+            "/// Entry point.",
+            "pub fn run() {}",
+            "",
+            "pub struct Counter {",
+            "    count: u32,",
+            "}",
+            "",
+            "impl Counter {",
+            "    pub fn new() -> Self {",
+            "        Self { count: 0 }",
+            "    }",
+            "    fn bump(&mut self) {}",
+            "}",
+            "",
+            "impl Default for Counter {",
+            "    fn default() -> Self { Self::new() }",
+            "}",
+            "",
+            "fn helper() {}",
+        ];
+        let tree = build_symbol_tree(&json!([
+            flat("types", 2, 0, 0),
+            flat("private", 2, 1, 1),
+            flat("run", 12, 4, 5),
+            flat("Counter", 23, 7, 9),
+            flat("count", 8, 8, 8),
+            flat("impl Counter", 19, 11, 16),
+            flat("new", 12, 12, 14),
+            flat("bump", 6, 15, 15),
+            flat("impl Default for Counter", 19, 18, 20),
+            flat("default", 6, 19, 19),
+            flat("helper", 12, 22, 22)
+        ]));
+
+        let api = collect_public_items(&tree, &source, "demo", "/src/lib.rs");
+
+        let paths: Vec<&str> = api
+            .items
+            .iter()
+            .map(|item| item["path"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "demo::types",
+                "demo::run",
+                "demo::Counter",
+                "demo::Counter::new"
+            ]
+        );
+        assert_eq!(api.items[1]["doc"], "Entry point.");
+        assert_eq!(api.items[3]["signature"], "pub fn new() -> Self");
+        assert_eq!(api.reexports[0]["statement"], "pub use types::Config;");
+        assert_eq!(
+            api.out_of_line_modules,
+            [("demo::types".to_string(), "types".to_string())]
+        );
+    }
+}
@@ -0,0 +1,297 @@
+use super::tree::SymbolNode;
+
+/// The declaration header of an item: its visibility, signature and leading doc
+/// comment, read from the source text covered by the symbol's range.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ItemHeader {
+    /// Zero-based line of the declaration, after attributes and doc comments.
+    pub line: u32,
+    pub visibility: String,
+    pub signature: String,
+    pub doc: Option<String>,
+    /// Whether the declaration is followed by a body or initializer (`{` or
+    /// `=`) rather than ending in `;`, as for `mod foo;` or a required trait
+    /// method.
+    pub has_body: bool,
+}
+
+impl ItemHeader {
+    pub fn is_public(&self) -> bool {
+        self.visibility == "pub"
+    }
+}
+
+/// Extracts the declaration header of `node` from the file's lines.
+pub fn item_header(lines: &[&str], node: &SymbolNode) -> Option<ItemHeader> {
+    let start = node.start.0 as usize;
+    let end = (node.end.0 as usize).min(lines.len().saturating_sub(1));
+
+    let mut doc = None;
+    let mut attribute_depth = 0i32;
+    let mut line_index = start;
+    while line_index <= end {
+        let trimmed = lines.get(line_index)?.trim();
+        if attribute_depth > 0 || trimmed.starts_with("#[") {
+            attribute_depth += bracket_balance(trimmed);
+        } else if let Some(text) = trimmed.strip_prefix("///") {
+            if doc.is_none() {
+                doc = Some(text.trim().to_string()).filter(|text| !text.is_empty());
+            }
+        } else if !trimmed.is_empty() && !trimmed.starts_with("//") {
+            break;
+        }
+        line_index += 1;
+    }
+    if line_index > end {
+        return None;
+    }
+
+    let mut signature = String::new();
+    let mut has_body = false;
+    for line in &lines[line_index..=end] {
+        let line = strip_line_comment(line);
+        if let Some(cut) = declaration_end(line, node.kind) {
+            signature.push_str(&line[..cut]);
+            has_body = !line[cut..].starts_with(';');
+            break;
+        }
+        signature.push_str(line);
+        signature.push(' ');
+    }
+    let signature = collapse_whitespace(&signature);
+
+    Some(ItemHeader {
+        line: line_index as u32,
+        visibility: visibility(&signature),
+        signature,
+        doc,
+        has_body,
+    })
+}
+
+/// The `cfg` and `cfg_attr` attributes leading the item that starts at line
+/// `start`, each on one line, in source order. Inner attributes (`#![cfg(..)]`)
+/// are included, and the outer attributes of the next item end them, so
+/// reading from the top of a file yields the file's own cfgs.
+pub fn cfg_attributes(lines: &[&str], start: usize) -> Vec<String> {
+    let mut attributes: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut attribute_depth = 0i32;
+    for line in lines.iter().skip(start) {
+        let trimmed = line.trim();
+        let inner_before = attributes
+            .last()
+            .is_some_and(|last| last.starts_with("#!["));
+        if attribute_depth == 0 && trimmed.starts_with("#[") && inner_before {
+            break;
+        }
+        if attribute_depth > 0 || trimmed.starts_with("#[") || trimmed.starts_with("#![") {
+            current.push_str(trimmed);
+            current.push(' ');
+            attribute_depth += bracket_balance(trimmed);
+            if attribute_depth <= 0 {
+                attributes.push(collapse_whitespace(&current));
+                current.clear();
+                attribute_depth = 0;
+            }
+        } else if !trimmed.is_empty() && !trimmed.starts_with("//") {
+            break;
+        }
+    }
+    attributes.retain(|attribute| {
+        let body = attribute.trim_start_matches(['#', '!', '[']);
+        body.starts_with("cfg(") || body.starts_with("cfg_attr(")
+    });
+    attributes
+}
+
+/// Returns `pub`, a restricted form such as `pub(crate)`, or `private`.
+pub fn visibility(signature: &str) -> String {
+    if let Some(rest) = signature.strip_prefix("pub(") {
+        let inner = rest.split(')').next().unwrap_or("");
+        return format!("pub({})", inner.trim());
+    }
+    if signature.starts_with("pub ") {
+        return "pub".to_string();
+    }
+    "private".to_string()
+}
+
+/// Returns the identifier covering the UTF-16 column `character` of `line`.
+pub fn word_at(line: &str, character: u32) -> Option<String> {
+    let is_ident = |ch: char| ch.is_alphanumeric() || ch == '_';
+    let offset = utf16_to_byte(line, character)?;
+    let start = line[..offset]
+        .rfind(|ch| !is_ident(ch))
+        .map(|index| index + 1)
+        .unwrap_or(0);
+    let end = line[offset..]
+        .find(|ch| !is_ident(ch))
+        .map(|index| offset + index)
+        .unwrap_or(line.len());
+    (start < end).then(|| line[start..end].to_string())
+}
+
+/// Returns the UTF-16 column of the first whole-word occurrence of `name`.
+pub fn identifier_column(line: &str, name: &str) -> Option<u32> {
+    let is_ident = |ch: char| ch.is_alphanumeric() || ch == '_';
+    line.match_indices(name)
+        .find(|(index, _)| {
+            let before = line[..*index].chars().next_back();
+            let after = line[index + name.len()..].chars().next();
+            !before.is_some_and(is_ident) && !after.is_some_and(is_ident)
+        })
+        .map(|(index, _)| line[..index].encode_utf16().count() as u32)
+}
+
+fn utf16_to_byte(line: &str, character: u32) -> Option<usize> {
+    let mut units = 0;
+    for (index, ch) in line.char_indices() {
+        if units >= character as usize {
+            return Some(index);
+        }
+        units += ch.len_utf16();
+    }
+    (units >= character as usize).then_some(line.len())
+}
+
+/// Finds where the declaration header ends: the opening brace of the body, a
+/// terminating semicolon, or the initializer of a constant or static.
+fn declaration_end(line: &str, kind: u64) -> Option<usize> {
+    let mut depth = 0i32;
+    for (index, ch) in line.char_indices() {
+        match ch {
+            '(' | '[' | '<' => depth += 1,
+            ')' | ']' => depth -= 1,
+            '>' if !line[..index].ends_with('-') => depth -= 1,
+            '{' | ';' if depth <= 0 => return Some(index),
+            '=' if depth <= 0 && matches!(kind, 13 | 14) => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn strip_line_comment(line: &str) -> &str {
+    match line.find("//") {
+        Some(index) => &line[..index],
+        None => line,
+    }
+}
+
+fn bracket_balance(line: &str) -> i32 {
+    line.chars().fold(0, |balance, ch| match ch {
+        '[' => balance + 1,
+        ']' => balance - 1,
+        _ => balance,
+    })
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace("( ", "(")
+        .replace(" )", ")")
+        .replace(", )", ")")
+        .replace(",)", ")")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cfg_attributes, identifier_column, item_header, visibility, word_at};
+    use crate::symbols::tree::SymbolNode;
+
+    fn node(kind: u64, start: u32, end: u32) -> SymbolNode {
+        SymbolNode {
+            name: String::new(),
+            kind,
+            start: (start, 0),
+            end: (end, 1),
+            detail: None,
+            children: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_item_header_skips_docs_and_attributes() {
+        let source = [
+            "/// Adds two numbers.",
+            "#[inline]",
+            "#[allow(",
+            "    dead_code",
+            ")]",
+            "pub fn add(",
+            "    a: i32,",
+            "    b: i32,",
+            ") -> Result<i32, String> {",
+            "    Ok(a + b)",
+            "}",
+        ];
+
+        let header = item_header(&source, &node(12, 0, 10)).unwrap();
+
+        assert_eq!(header.line, 5);
+        assert_eq!(
+            header.signature,
+            "pub fn add(a: i32, b: i32) -> Result<i32, String>"
+        );
+        assert_eq!(header.doc.as_deref(), Some("Adds two numbers."));
+        assert!(header.is_public());
+        assert!(header.has_body);
+    }
+
+    #[test]
+    fn test_item_header_for_constants_and_modules() {
+        let source = ["pub(crate) const LIMIT: usize = 10;", "pub mod utils;"];
+
+        let constant = item_header(&source, &node(14, 0, 0)).unwrap();
+        let module = item_header(&source, &node(2, 1, 1)).unwrap();
+
+        assert_eq!(constant.signature, "pub(crate) const LIMIT: usize");
+        assert_eq!(constant.visibility, "pub(crate)");
+        assert_eq!(module.signature, "pub mod utils");
+        assert!(!module.has_body);
+        assert_eq!(visibility("fn private()"), "private");
+    }
+
+    #[test]
+    fn test_cfg_attributes_reads_inner_and_outer_cfgs() {
+        let source = [
+            "//! Networking.",
+            "#![cfg(unix)]",
+            "",
+            "/// Connects over TLS.",
+            "#[cfg(all(",
+            "    feature = \"tls\",",
+            "    not(test)",
+            "))]",
+            "#[inline]",
+            "#[cfg_attr(docsrs, doc(cfg(feature = \"tls\")))]",
+            "pub fn connect() {}",
+        ];
+
+        assert_eq!(cfg_attributes(&source, 0), ["#![cfg(unix)]"]);
+        assert_eq!(
+            cfg_attributes(&source, 3),
+            [
+                "#[cfg(all(feature = \"tls\", not(test)))]",
+                "#[cfg_attr(docsrs, doc(cfg(feature = \"tls\")))]"
+            ]
+        );
+        assert!(cfg_attributes(&source, 10).is_empty());
+    }
+
+    #[test]
+    fn test_word_at_and_identifier_column() {
+        let line = "#[derive(Debug, Clone)] // héllo Clone";
+
+        assert_eq!(word_at(line, 17).as_deref(), Some("Clone"));
+        assert_eq!(word_at(line, 15), None);
+        assert_eq!(
+            identifier_column("pub struct ConfigBuilder; struct Config", "Config"),
+            Some(33)
+        );
+        assert_eq!(identifier_column(line, "héllo"), Some(27));
+    }
+}
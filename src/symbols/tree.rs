@@ -0,0 +1,217 @@
+use serde_json::{json, Value};
+
+use super::SymbolKind;
+
+/// A zero-based `(line, character)` position.
+pub type Position = (u32, u32);
+
+/// A document symbol with its nested children.
+///
+/// rust-analyzer answers `textDocument/documentSymbol` with a flat
+/// `SymbolInformation[]` list unless hierarchical support is negotiated, so the
+/// nesting is reconstructed from range containment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolNode {
+    pub name: String,
+    pub kind: u64,
+    pub start: Position,
+    pub end: Position,
+    pub detail: Option<String>,
+    pub children: Vec<SymbolNode>,
+}
+
+impl SymbolNode {
+    pub fn kind_is(&self, kind: SymbolKind) -> bool {
+        SymbolKind::from_lsp(self.kind) == kind
+    }
+
+    pub fn contains(&self, position: Position) -> bool {
+        self.start <= position && position <= self.end
+    }
+
+    fn encloses(&self, other: &SymbolNode) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    pub fn range_json(&self) -> Value {
+        json!({
+            "start": { "line": self.start.0, "character": self.start.1 },
+            "end": { "line": self.end.0, "character": self.end.1 }
+        })
+    }
+}
+
+/// Builds a symbol tree from either a flat `SymbolInformation[]` response or a
+/// hierarchical `DocumentSymbol[]` response.
+pub fn build_symbol_tree(result: &Value) -> Vec<SymbolNode> {
+    let Some(symbols) = result.as_array() else {
+        return Vec::new();
+    };
+
+    if symbols
+        .iter()
+        .any(|symbol| symbol.get("selectionRange").is_some())
+    {
+        return symbols.iter().filter_map(document_symbol_to_node).collect();
+    }
+
+    let mut nodes: Vec<SymbolNode> = symbols
+        .iter()
+        .filter_map(|symbol| {
+            let range = symbol.pointer("/location/range")?;
+            Some(SymbolNode {
+                name: symbol["name"].as_str()?.to_string(),
+                kind: symbol["kind"].as_u64().unwrap_or(0),
+                start: position(&range["start"]),
+                end: position(&range["end"]),
+                detail: None,
+                children: Vec::new(),
+            })
+        })
+        .collect();
+
+    // Parents sort before the symbols they enclose.
+    nodes.sort_by(|a, b| a.start.cmp(&b.start).then(b.end.cmp(&a.end)));
+
+    let mut roots = Vec::new();
+    let mut stack: Vec<SymbolNode> = Vec::new();
+    for node in nodes {
+        while let Some(top) = stack.last() {
+            if top.encloses(&node) {
+                break;
+            }
+            let finished = stack.pop().expect("stack is not empty");
+            attach(&mut stack, &mut roots, finished);
+        }
+        stack.push(node);
+    }
+    while let Some(finished) = stack.pop() {
+        attach(&mut stack, &mut roots, finished);
+    }
+
+    roots
+}
+
+/// Returns the chain of symbols enclosing `position`, outermost first.
+pub fn enclosing_symbols(nodes: &[SymbolNode], position: Position) -> Vec<&SymbolNode> {
+    let mut chain = Vec::new();
+    let mut level = nodes;
+    while let Some(node) = level.iter().find(|node| node.contains(position)) {
+        chain.push(node);
+        level = &node.children;
+    }
+    chain
+}
+
+fn attach(stack: &mut [SymbolNode], roots: &mut Vec<SymbolNode>, node: SymbolNode) {
+    match stack.last_mut() {
+        Some(parent) => parent.children.push(node),
+        None => roots.push(node),
+    }
+}
+
+fn document_symbol_to_node(symbol: &Value) -> Option<SymbolNode> {
+    let range = symbol.get("range")?;
+    Some(SymbolNode {
+        name: symbol["name"].as_str()?.to_string(),
+        kind: symbol["kind"].as_u64().unwrap_or(0),
+        start: position(&range["start"]),
+        end: position(&range["end"]),
+        detail: symbol["detail"].as_str().map(str::to_string),
+        children: symbol["children"]
+            .as_array()
+            .map(|children| {
+                children
+                    .iter()
+                    .filter_map(document_symbol_to_node)
+                    .collect()
+            })
+            .unwrap_or_default(),
+    })
+}
+
+fn position(value: &Value) -> Position {
+    (
+        value["line"].as_u64().unwrap_or(0) as u32,
+        value["character"].as_u64().unwrap_or(0) as u32,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::{build_symbol_tree, enclosing_symbols};
+
+    fn flat(name: &str, kind: u64, start: u32, end: u32) -> Value {
+        json!({
+            "name": name,
+            "kind": kind,
+            "location": {
+                "uri": "file:///src/main.rs",
+                "range": {
+                    "start": { "line": start, "character": 0 },
+                    "end": { "line": end, "character": 1 }
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_build_tree_from_flat_symbols() {
+        let result = json!([
+            flat("main", 12, 0, 11),
+            flat("Calculator", 23, 17, 19),
+            flat("value", 8, 18, 18),
+            flat("impl Calculator", 19, 21, 37),
+            flat("add", 6, 26, 28),
+            flat("new", 12, 22, 24),
+            flat("tests", 2, 39, 54)
+        ]);
+
+        let tree = build_symbol_tree(&result);
+
+        let names: Vec<&str> = tree.iter().map(|node| node.name.as_str()).collect();
+        assert_eq!(names, ["main", "Calculator", "impl Calculator", "tests"]);
+        assert_eq!(tree[1].children[0].name, "value");
+        let methods: Vec<&str> = tree[2]
+            .children
+            .iter()
+            .map(|node| node.name.as_str())
+            .collect();
+        assert_eq!(methods, ["new", "add"]);
+
+        let chain: Vec<&str> = enclosing_symbols(&tree, (27, 4))
+            .iter()
+            .map(|node| node.name.as_str())
+            .collect();
+        assert_eq!(chain, ["impl Calculator", "add"]);
+        assert!(enclosing_symbols(&tree, (20, 0)).is_empty());
+    }
+
+    #[test]
+    fn test_build_tree_from_document_symbols() {
+        let range = json!({
+            "start": { "line": 0, "character": 0 },
+            "end": { "line": 3, "character": 1 }
+        });
+        let result = json!([{
+            "name": "outer",
+            "kind": 2,
+            "range": range,
+            "selectionRange": range,
+            "children": [{
+                "name": "inner",
+                "kind": 12,
+                "detail": "fn()",
+                "range": range,
+                "selectionRange": range
+            }]
+        }]);
+
+        let tree = build_symbol_tree(&result);
+
+        assert_eq!(tree.len(), 1);
+        assert_eq!(tree[0].children[0].detail.as_deref(), Some("fn()"));
+    }
+}
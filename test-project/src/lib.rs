@@ -55,3 +55,9 @@ pub fn handle_option(opt: Option<String>) {
         None => {}
     }
 }
+
+impl types::Describe for Person {
+    fn describe(&self) -> String {
+        format!("{} ({})", self.name, self.age)
+    }
+}
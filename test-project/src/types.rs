@@ -26,3 +26,19 @@ impl Default for Config {
         Self::new("default".to_string())
     }
 }
+
+/// Types that can describe themselves in one line.
+pub trait Describe {
+    fn describe(&self) -> String;
+
+    /// Describes the value in upper case.
+    fn shout(&self) -> String {
+        self.describe().to_uppercase()
+    }
+}
+
+impl Describe for Config {
+    fn describe(&self) -> String {
+        format!("{} v{}", self.name, self.version)
+    }
+}
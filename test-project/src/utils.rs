@@ -13,3 +13,7 @@ fn validate(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
     }
     Ok(())
 }
+
+fn legacy_banner(config: &Config) -> String {
+    format!("== {} ==", config.name)
+}
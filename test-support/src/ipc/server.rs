@@ -77,8 +77,7 @@ pub fn start_server(workspace_path: &Path, project_type: &str) -> Result<()> {
     stdin.write_all(b"\n")?;
     stdin.flush()?;
 
-    let mut line = String::new();
-    stdout.read_line(&mut line)?;
+    let line = read_response_line(&mut stdout)?;
 
     let response: Value = serde_json::from_str(&line)?;
     if response.get("error").is_some() {
@@ -159,8 +158,7 @@ pub fn start_server(workspace_path: &Path, project_type: &str) -> Result<()> {
                     stdin.flush()?;
 
                     // Read response from rust-analyzer
-                    let mut response_line = String::new();
-                    stdout.read_line(&mut response_line)?;
+                    let response_line = read_response_line(&mut stdout)?;
 
                     // Forward response to client
                     stream.write_all(response_line.as_bytes())?;
@@ -189,6 +187,23 @@ pub fn start_server(workspace_path: &Path, project_type: &str) -> Result<()> {
     Ok(())
 }
 
+/// Reads the next response line, skipping notifications such as forwarded
+/// rust-analyzer log messages.
+fn read_response_line(stdout: &mut BufReader<std::process::ChildStdout>) -> Result<String> {
+    loop {
+        let mut line = String::new();
+        if stdout.read_line(&mut line)? == 0 {
+            return Err(anyhow::anyhow!("MCP server closed stdout"));
+        }
+        let is_notification = serde_json::from_str::<Value>(&line)
+            .map(|message| message.get("id").is_none())
+            .unwrap_or(false);
+        if !is_notification {
+            return Ok(line);
+        }
+    }
+}
+
 fn wait_for_ready(
     stdin: &mut std::process::ChildStdin,
     stdout: &mut BufReader<std::process::ChildStdout>,
@@ -227,8 +242,7 @@ fn wait_for_ready(
         stdin.write_all(b"\n")?;
         stdin.flush()?;
 
-        let mut line = String::new();
-        stdout.read_line(&mut line)?;
+        let line = read_response_line(stdout)?;
 
         let response: Value = serde_json::from_str(&line)?;
 
@@ -273,10 +273,18 @@ impl MCPTestClient {
 
         // Read response with timeout
         let response_line = timeout(timeout_duration, async {
-            let mut line = String::new();
             let mut stdout = self.stdout.lock().await;
-            stdout.read_line(&mut line).await?;
-            Ok::<String, anyhow::Error>(line)
+            loop {
+                let mut line = String::new();
+                stdout.read_line(&mut line).await?;
+                // Skip notifications such as forwarded rust-analyzer log messages.
+                let is_notification = serde_json::from_str::<Value>(&line)
+                    .map(|message| message.get("id").is_none())
+                    .unwrap_or(false);
+                if !is_notification {
+                    return Ok::<String, anyhow::Error>(line);
+                }
+            }
         })
         .await
         .map_err(|_| anyhow::anyhow!("Request timeout after {:?}", timeout_duration))??;
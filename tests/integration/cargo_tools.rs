@@ -0,0 +1,180 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+use test_support::IpcClient;
+
+fn tool_text_json(response: &Value) -> Value {
+    let text = response["content"][0]["text"]
+        .as_str()
+        .expect("Tool response should contain text content");
+    serde_json::from_str(text).expect("Tool response text should be JSON")
+}
+
+#[tokio::test]
+async fn test_cargo_build_links_binary() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project").await?;
+
+    let response = client
+        .call_tool(
+            "rust_analyzer_cargo_build",
+            json!({ "bin": "test-project" }),
+        )
+        .await?;
+    let parsed = tool_text_json(&response);
+
+    assert_eq!(parsed["success"], true, "Build failed: {parsed:#}");
+    assert_eq!(parsed["summary"]["errors"], 0);
+    assert!(parsed["command"]
+        .as_str()
+        .unwrap()
+        .contains("--bin test-project"));
+
+    let artifacts = parsed["artifacts"].as_array().unwrap();
+    assert!(
+        artifacts
+            .iter()
+            .any(|artifact| artifact["executable"].is_string()),
+        "Expected an executable artifact, got: {artifacts:#?}"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_toolchain_info() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project").await?;
+
+    let response = client.call_tool("rust_toolchain_info", json!({})).await?;
+    let parsed = tool_text_json(&response);
+
+    assert!(parsed["rustc"]["release"].is_string(), "{parsed:#}");
+    assert!(parsed["cargo"]["version"]
+        .as_str()
+        .unwrap()
+        .starts_with("cargo "));
+    assert_eq!(parsed["workspace"]["editions"], json!(["2021"]));
+    assert_eq!(parsed["workspace"]["members"][0]["name"], "test-project");
+    assert!(parsed["rust_analyzer"]["name"].is_string());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_status_reports_rust_src() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project").await?;
+
+    let response = client.call_tool("rust_analyzer_status", json!({})).await?;
+    let parsed = tool_text_json(&response);
+
+    assert_eq!(parsed["rust_analyzer"]["running"], true);
+    assert!(parsed["rust_src"]["installed"].is_boolean(), "{parsed:#}");
+    if parsed["rust_src"]["installed"] == false {
+        assert!(parsed["rust_src"]["hint"].is_string());
+    }
+
+    let readiness = &parsed["rust_analyzer"]["readiness"];
+    assert!(readiness["ready"].is_boolean(), "{parsed:#}");
+    assert!(readiness["tasks"].is_array());
+    if readiness["ready"] == true {
+        assert_eq!(readiness["percentage"], 100);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_server_stats_record_tool_and_lsp_timings() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project").await?;
+
+    client.call_tool("rust_analyzer_status", json!({})).await?;
+    let response = client
+        .call_tool("rust_analyzer_server_stats", json!({}))
+        .await?;
+    let parsed = tool_text_json(&response);
+
+    let status = &parsed["tools"]["rust_analyzer_status"];
+    assert!(status["calls"].as_u64().unwrap() >= 1, "{parsed:#}");
+    assert!(status["latency_ms"]["p50"].is_u64());
+    assert_eq!(parsed["lsp_requests"]["initialize"]["errors"], 0);
+    assert!(parsed["rust_analyzer"]["starts"].as_u64().unwrap() >= 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_logs_returns_recent_stderr_lines() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project").await?;
+
+    let response = client
+        .call_tool("rust_analyzer_logs", json!({ "lines": 5 }))
+        .await?;
+    let parsed = tool_text_json(&response);
+
+    let lines = parsed["lines"].as_array().unwrap();
+    assert!(lines.len() <= 5, "{parsed:#}");
+    assert_eq!(parsed["count"], lines.len());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_dead_code_report() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project").await?;
+
+    let response = client
+        .call_tool("rust_analyzer_dead_code", json!({}))
+        .await?;
+    let parsed = tool_text_json(&response);
+
+    assert_eq!(parsed["success"], true, "Check failed: {parsed:#}");
+    assert!(parsed["lints"]["dead_code"].as_u64().unwrap_or(0) >= 1);
+
+    let krate = parsed["crates"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|krate| krate["crate"] == "test_project")
+        .expect("Expected the test_project crate in the report");
+    let utils = krate["modules"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|module| module["module"] == "crate::utils")
+        .expect("Expected crate::utils in the report");
+    assert!(
+        utils["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|item| item["lint"] == "dead_code" && item["item"] == "legacy_banner"),
+        "{utils:#}"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_timeout_ms_bounds_a_single_call() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project").await?;
+
+    let error = client
+        .call_tool("rust_analyzer_cargo_check", json!({ "timeout_ms": 1 }))
+        .await
+        .expect_err("A 1 ms cargo check should time out");
+    assert!(
+        error.to_string().contains("timed out after 1 ms"),
+        "{error}"
+    );
+
+    let response = client
+        .call_tool("rust_analyzer_status", json!({ "timeout_ms": 60000 }))
+        .await?;
+    assert!(tool_text_json(&response).is_object());
+
+    let error = client
+        .call_tool("rust_analyzer_status", json!({ "timeout_ms": "soon" }))
+        .await
+        .expect_err("A non-numeric timeout should be rejected");
+    assert!(error.to_string().contains("timeout_ms"), "{error}");
+
+    Ok(())
+}
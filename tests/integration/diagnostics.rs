@@ -391,3 +391,74 @@ async fn test_diagnostics_severity_levels() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_diagnostics_snapshot_diff_without_changes() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project-diagnostics").await?;
+
+    let response = client
+        .call_tool(
+            "rust_analyzer_diagnostics_snapshot",
+            json!({ "name": "unchanged" }),
+        )
+        .await?;
+    assert_tool_response(&response);
+    let content = response["content"][0]["text"].as_str().unwrap();
+    let snapshot: serde_json::Value = serde_json::from_str(content).unwrap();
+    assert_eq!(snapshot["snapshot"], "unchanged");
+    assert!(snapshot["summary"]["total_diagnostics"].is_u64());
+
+    let response = client
+        .call_tool(
+            "rust_analyzer_diagnostics_diff",
+            json!({ "name": "unchanged" }),
+        )
+        .await?;
+    assert_tool_response(&response);
+    let content = response["content"][0]["text"].as_str().unwrap();
+    let diff: serde_json::Value = serde_json::from_str(content).unwrap();
+
+    // Nothing was edited between the two calls, so no errors may have been introduced.
+    assert!(diff["appeared"].is_array());
+    assert!(diff["disappeared"].is_array());
+    assert_eq!(diff["summary"]["errors_appeared"], 0);
+
+    // Diffing against an unknown snapshot is an error.
+    let result = client
+        .call_tool(
+            "rust_analyzer_diagnostics_diff",
+            json!({ "name": "does-not-exist" }),
+        )
+        .await;
+    assert!(
+        result.is_err(),
+        "Diff against a missing snapshot should fail"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cargo_check_reports_compiler_errors() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project-diagnostics").await?;
+
+    let response = client
+        .call_tool("rust_analyzer_cargo_check", json!({}))
+        .await?;
+    assert_tool_response(&response);
+    let content = response["content"][0]["text"].as_str().unwrap();
+    let parsed: serde_json::Value = serde_json::from_str(content).unwrap();
+
+    assert_eq!(parsed["success"], false);
+    assert!(parsed["summary"]["errors"].as_u64().unwrap() > 0);
+
+    let diagnostics = parsed["diagnostics"].as_array().unwrap();
+    let first_error = diagnostics
+        .iter()
+        .find(|diag| diag["level"] == "error" && diag["file"].is_string())
+        .expect("Expected at least one error with a file location");
+    assert!(first_error["file"].as_str().unwrap().ends_with(".rs"));
+    assert!(first_error["range"]["start"]["line"].is_u64());
+
+    Ok(())
+}
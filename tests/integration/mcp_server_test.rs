@@ -139,18 +139,13 @@ async fn test_all_lsp_tools() -> Result<()> {
 
 #[tokio::test]
 async fn test_workspace_change() -> Result<()> {
-    let mut client = IpcClient::get_or_create("test-project").await?;
+    // Use a dedicated server: switching the shared one would leave it pointing at
+    // a temporary project that is deleted when this test ends.
+    let client = test_support::MCPTestClient::start_isolated().await?;
 
     // Create a second isolated project to switch to
     let second_project = test_support::IsolatedProject::new()?;
-    let response = client
-        .call_tool(
-            "rust_analyzer_set_workspace",
-            json!({
-                "workspace_path": second_project.path().to_str().unwrap()
-            }),
-        )
-        .await?;
+    let response = client.set_workspace(second_project.path()).await?;
 
     // Verify workspace change succeeded
     if let Some(content) = response.get("content") {
@@ -564,3 +559,401 @@ async fn test_code_actions(client: &mut IpcClient, workspace_path: &Path) -> Res
     // Some files genuinely might not have code actions available
     Ok(true)
 }
+
+#[tokio::test]
+async fn test_navigation_into_std_sources() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project").await?;
+    let main_path = client.workspace_path().join("src/main.rs");
+
+    // `String` in `fn greet(name: &str) -> String` resolves into the standard library.
+    let mut target = None;
+    for _ in 0..10 {
+        let response = client
+            .call_tool(
+                "rust_analyzer_definition",
+                json!({
+                    "file_path": main_path.to_str().unwrap(),
+                    "line": 13,
+                    "character": 27
+                }),
+            )
+            .await?;
+        let text = response["content"][0]["text"].as_str().unwrap_or("null");
+        let definition: Value = serde_json::from_str(text)?;
+        let location = definition
+            .as_array()
+            .and_then(|items| items.first())
+            .cloned();
+        if let Some(location) = location {
+            target = Some(location);
+            break;
+        }
+        tokio::time::sleep(timeouts::tool_retry_delay()).await;
+    }
+
+    let Some(location) = target else {
+        eprintln!("⚠ Definition not ready; skipping std navigation check");
+        return Ok(());
+    };
+
+    let uri = location
+        .get("targetUri")
+        .or_else(|| location.get("uri"))
+        .and_then(|uri| uri.as_str())
+        .unwrap();
+    let range = location
+        .get("targetSelectionRange")
+        .or_else(|| location.get("range"))
+        .unwrap();
+    assert!(
+        !uri.contains("test-project"),
+        "Expected definition outside the workspace, got {uri}"
+    );
+
+    // Follow-up requests must work on the out-of-workspace file addressed by its URI.
+    let response = client
+        .call_tool(
+            "rust_analyzer_hover",
+            json!({
+                "file_path": uri,
+                "line": range["start"]["line"],
+                "character": range["start"]["character"]
+            }),
+        )
+        .await?;
+    assert!(response["content"][0]["text"].is_string());
+
+    let response = client
+        .call_tool("rust_analyzer_symbols", json!({ "file_path": uri }))
+        .await?;
+    assert!(response["content"][0]["text"].is_string());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_hover_formats() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project").await?;
+    let main_path = client.workspace_path().join("src/main.rs");
+    let hover = |format: &str| {
+        json!({
+            "file_path": main_path.to_str().unwrap(),
+            "line": 13,
+            "character": 4,
+            "format": format
+        })
+    };
+
+    let mut signature = String::new();
+    for _ in 0..10 {
+        let response = client
+            .call_tool("rust_analyzer_hover", hover("signature_only"))
+            .await?;
+        signature = response["content"][0]["text"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        if signature.starts_with("fn ") {
+            break;
+        }
+        tokio::time::sleep(timeouts::tool_retry_delay()).await;
+    }
+    assert_eq!(signature, "fn greet(name: &str) -> String");
+
+    let response = client
+        .call_tool("rust_analyzer_hover", hover("plaintext"))
+        .await?;
+    let text = response["content"][0]["text"].as_str().unwrap();
+    assert!(text.contains("fn greet(name: &str) -> String"), "{text}");
+    assert!(!text.contains("```"), "{text}");
+
+    let result = client.call_tool("rust_analyzer_hover", hover("yaml")).await;
+    assert!(result.is_err(), "Unknown hover formats should be rejected");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_preview_edit_renders_diffs_without_applying() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project").await?;
+    let lib_path = client.workspace_path().join("src/lib.rs");
+    let lib_uri = format!("file://{}", lib_path.display());
+    let before = std::fs::read_to_string(&lib_path)?;
+
+    let edit = json!({
+        "changes": {
+            lib_uri: [{
+                "range": { "start": { "line": 7, "character": 7 }, "end": { "line": 7, "character": 10 } },
+                "newText": "start"
+            }]
+        }
+    });
+    let response = client
+        .call_tool("rust_analyzer_preview_edit", json!({ "edit": edit }))
+        .await?;
+    let text = response["content"][0]["text"].as_str().unwrap();
+    let parsed: Value = serde_json::from_str(text)?;
+
+    assert_eq!(parsed["summary"]["files"], 1);
+    let file = &parsed["files"][0];
+    assert_eq!(file["path"], "src/lib.rs");
+    assert_eq!(file["operation"], "modify");
+    let diff = file["diff"].as_str().unwrap();
+    assert!(
+        diff.starts_with("--- a/src/lib.rs\n+++ b/src/lib.rs\n"),
+        "{diff}"
+    );
+    assert!(diff.contains("\n-pub fn run(config: Config)"), "{diff}");
+    assert!(diff.contains("\n+pub fn start(config: Config)"), "{diff}");
+    assert_eq!(std::fs::read_to_string(&lib_path)?, before);
+
+    let result = client
+        .call_tool("rust_analyzer_preview_edit", json!({ "edit": 42 }))
+        .await;
+    assert!(result.is_err(), "A missing edit should be rejected");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_rename_file_rejects_non_rust_files() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project").await?;
+    let manifest = client.workspace_path().join("Cargo.toml");
+
+    let result = client
+        .call_tool(
+            "rust_analyzer_rename_file",
+            json!({ "old_path": "Cargo.toml", "new_path": "Renamed.toml" }),
+        )
+        .await;
+    assert!(result.is_err(), "Only .rs files should be renamed");
+    assert!(manifest.exists());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_rename_file_rejects_paths_outside_the_workspace() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project").await?;
+    let utils_path = client.workspace_path().join("src/utils.rs");
+    let escaped = client.workspace_path().join("../escape.rs");
+
+    let result = client
+        .call_tool(
+            "rust_analyzer_rename_file",
+            json!({ "old_path": "src/utils.rs", "new_path": "../escape.rs" }),
+        )
+        .await;
+    assert!(
+        result.is_err(),
+        "Files should not move out of the workspace"
+    );
+    assert!(utils_path.exists());
+    assert!(!escaped.exists());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_rename_module_rejects_invalid_names() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project").await?;
+    let utils_path = client.workspace_path().join("src/utils.rs");
+
+    let result = client
+        .call_tool(
+            "rust_analyzer_rename_module",
+            json!({ "file_path": utils_path.to_str().unwrap(), "new_name": "not-a-module" }),
+        )
+        .await;
+    assert!(result.is_err(), "Module names must be identifiers");
+    assert!(utils_path.exists());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_highlights_from_return_show_exit_points() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project").await?;
+    let utils_path = client.workspace_path().join("src/utils.rs");
+
+    // `return` in `validate`: the early return and the tail `Ok(())`.
+    let mut texts = Vec::new();
+    for _ in 0..10 {
+        let response = client
+            .call_tool(
+                "rust_analyzer_highlights",
+                json!({ "file_path": utils_path.to_str().unwrap(), "line": 11, "character": 8 }),
+            )
+            .await?;
+        let parsed: Value = serde_json::from_str(response["content"][0]["text"].as_str().unwrap())?;
+        texts = parsed
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|highlight| highlight["text"].as_str().map(str::to_string))
+            .collect();
+        if !texts.is_empty() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    }
+
+    assert!(texts.iter().any(|text| text == "return"), "{texts:?}");
+    assert!(
+        texts.iter().any(|text| text.contains("Ok(())")),
+        "{texts:?}"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_expand_macro_formats_the_expansion() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project").await?;
+    let utils_path = client.workspace_path().join("src/utils.rs");
+
+    // `format!` in `legacy_banner`.
+    let response = client
+        .call_tool(
+            "rust_analyzer_expand_macro",
+            json!({
+                "file_path": utils_path.to_str().unwrap(),
+                "line": 17,
+                "character": 5,
+                "pretty": true
+            }),
+        )
+        .await?;
+    let parsed: Value = serde_json::from_str(response["content"][0]["text"].as_str().unwrap())?;
+
+    assert_eq!(parsed["name"], "format");
+    assert_eq!(parsed["formatted"], true, "{parsed}");
+    assert!(!parsed["expansion"].as_str().unwrap().is_empty());
+
+    let result = client
+        .call_tool(
+            "rust_analyzer_expand_macro",
+            json!({ "file_path": utils_path.to_str().unwrap(), "line": 1, "character": 0 }),
+        )
+        .await;
+    assert!(
+        result.is_err(),
+        "A position without a macro should be rejected"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_completion_filtering() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project").await?;
+    let main_path = client.workspace_path().join("src/main.rs");
+
+    // Methods of `calc` after the dot in `calc.add(2, 3)`.
+    let mut parsed = Value::Null;
+    for _ in 0..10 {
+        let response = client
+            .call_tool(
+                "rust_analyzer_completion",
+                json!({
+                    "file_path": main_path.to_str().unwrap(),
+                    "line": 5,
+                    "character": 22,
+                    "kinds": ["method"],
+                    "prefix": "mu",
+                    "limit": 5,
+                    "plain_text": true
+                }),
+            )
+            .await?;
+        parsed = serde_json::from_str(response["content"][0]["text"].as_str().unwrap())?;
+        if parsed["total"].as_u64().unwrap_or(0) > 0 {
+            break;
+        }
+        tokio::time::sleep(timeouts::tool_retry_delay()).await;
+    }
+
+    let items = parsed["items"].as_array().unwrap();
+    assert!(items.len() <= 5);
+    assert!(
+        items
+            .iter()
+            .any(|item| item["label"].as_str().unwrap().starts_with("multiply")),
+        "{parsed:#}"
+    );
+    assert!(items.iter().all(|item| item["kind"] == "method"));
+    assert!(
+        items.iter().all(|item| {
+            let insert_text = item["insert_text"].as_str().unwrap();
+            insert_text.starts_with("multiply") && !insert_text.contains('$')
+        }),
+        "{parsed:#}"
+    );
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_sigterm_stops_server_and_rust_analyzer() -> Result<()> {
+    use std::process::Stdio;
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+        process::Command,
+    };
+
+    let project = test_support::IsolatedProject::new()?;
+    let mut server = Command::new(env!("CARGO_BIN_EXE_rust-analyzer-mcp"))
+        .arg(project.path())
+        .env("CARGO_TARGET_DIR", project.path().join("target"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    let pid = server.id().unwrap();
+    let mut stdin = server.stdin.take().unwrap();
+    let mut stdout = BufReader::new(server.stdout.take().unwrap());
+    let mut stderr = server.stderr.take().unwrap();
+    let logs = tokio::spawn(async move {
+        let mut logs = String::new();
+        stderr.read_to_string(&mut logs).await.map(|_| logs)
+    });
+
+    // Any tool call starts rust-analyzer.
+    let status = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "tools/call",
+        "params": { "name": "rust_analyzer_status", "arguments": {} }
+    });
+    stdin.write_all(format!("{status}\n").as_bytes()).await?;
+    let mut line = String::new();
+    tokio::time::timeout(timeouts::tool_call(), stdout.read_line(&mut line)).await??;
+    assert!(line.contains("\"id\":1"), "{line}");
+
+    let children_file = format!("/proc/{pid}/task/{pid}/children");
+    let children: Vec<String> = std::fs::read_to_string(&children_file)?
+        .split_whitespace()
+        .map(str::to_string)
+        .collect();
+    assert!(!children.is_empty(), "rust-analyzer was not started");
+
+    let killed = std::process::Command::new("kill")
+        .args(["-TERM", &pid.to_string()])
+        .status()?;
+    assert!(killed.success());
+
+    let status = tokio::time::timeout(timeouts::request(), server.wait()).await??;
+    assert!(status.success(), "server exited with {status}");
+    let logs = logs.await??;
+    assert!(logs.contains("Received SIGTERM"), "{logs}");
+    for child in children {
+        assert!(
+            !Path::new(&format!("/proc/{child}")).exists(),
+            "rust-analyzer ({child}) outlived the server"
+        );
+    }
+
+    Ok(())
+}
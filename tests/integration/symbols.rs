@@ -0,0 +1,322 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+use test_support::{timeouts, IpcClient};
+
+fn tool_text_json(response: &Value) -> Value {
+    let text = response["content"][0]["text"]
+        .as_str()
+        .expect("Tool response should contain text content");
+    serde_json::from_str(text).expect("Tool response text should be JSON")
+}
+
+/// Calls a tool that looks items up by name, retrying while rust-analyzer is
+/// still indexing the workspace.
+async fn call_tool_when_indexed(
+    client: &mut IpcClient,
+    name: &str,
+    arguments: Value,
+) -> Result<Value> {
+    let mut result = client.call_tool(name, arguments.clone()).await;
+    for _ in 0..10 {
+        if result.is_ok() {
+            break;
+        }
+        tokio::time::sleep(timeouts::tool_retry_delay()).await;
+        result = client.call_tool(name, arguments.clone()).await;
+    }
+    result
+}
+
+fn item<'a>(items: &'a [Value], path: &str) -> Option<&'a Value> {
+    items.iter().find(|item| item["path"] == path)
+}
+
+#[tokio::test]
+async fn test_public_api_walks_module_tree() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project").await?;
+
+    let response = client
+        .call_tool(
+            "rust_analyzer_public_api",
+            json!({ "crate_name": "test-project" }),
+        )
+        .await?;
+    let parsed = tool_text_json(&response);
+
+    assert_eq!(parsed["crate"], "test_project");
+    assert_eq!(parsed["files_scanned"], 3);
+    let items = parsed["items"].as_array().unwrap();
+
+    let run = item(items, "test_project::run").expect("run should be listed");
+    assert_eq!(run["kind"], "function");
+    assert_eq!(
+        run["signature"],
+        "pub fn run(config: Config) -> Result<(), Box<dyn std::error::Error>>"
+    );
+    assert_eq!(run["doc"], "Main library entry point.");
+
+    let new =
+        item(items, "test_project::types::Config::new").expect("Config::new should be listed");
+    assert_eq!(new["signature"], "pub fn new(name: String) -> Self");
+    assert!(item(items, "test_project::utils::process").is_some());
+    assert!(
+        item(items, "test_project::utils::validate").is_none(),
+        "Private functions should not be listed"
+    );
+    assert!(
+        item(items, "test_project::types::Config::default").is_none(),
+        "Trait impl methods should not be listed"
+    );
+
+    let reexports = parsed["reexports"].as_array().unwrap();
+    assert!(reexports
+        .iter()
+        .any(|reexport| reexport["statement"] == "pub use types::Config;"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_public_api_unknown_crate() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project").await?;
+
+    let result = client
+        .call_tool(
+            "rust_analyzer_public_api",
+            json!({ "crate_name": "no-such-crate" }),
+        )
+        .await;
+
+    assert!(result.is_err(), "Unknown crates should be rejected");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_outline_nests_impl_methods() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project").await?;
+
+    let response = client
+        .call_tool(
+            "rust_analyzer_outline",
+            json!({ "file_path": "src/main.rs" }),
+        )
+        .await?;
+    let parsed = tool_text_json(&response);
+
+    let outline = parsed["outline"].as_array().unwrap();
+    let calculator_impl = outline
+        .iter()
+        .find(|node| node["kind"] == "impl")
+        .expect("impl Calculator should be in the outline");
+    let methods: Vec<&str> = calculator_impl["children"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|method| method["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(methods, ["new", "add", "multiply", "value"]);
+
+    let tests_module = outline
+        .iter()
+        .find(|node| node["name"] == "tests")
+        .expect("tests module should be in the outline");
+    assert_eq!(tests_module["kind"], "module");
+    assert!(
+        tests_module["range"]["end"]["line"].as_u64()
+            > tests_module["range"]["start"]["line"].as_u64()
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_qualified_document_symbols() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project").await?;
+
+    let response = client
+        .call_tool(
+            "rust_analyzer_symbols",
+            json!({ "file_path": "src/types.rs", "qualified": true }),
+        )
+        .await?;
+    let parsed = tool_text_json(&response);
+
+    assert_eq!(parsed["crate"], "test_project");
+    let symbols = parsed["symbols"].as_array().unwrap();
+    let paths: Vec<&str> = symbols
+        .iter()
+        .map(|symbol| symbol["path"].as_str().unwrap())
+        .collect();
+    assert!(paths.contains(&"crate::types::Config"), "{paths:?}");
+    assert!(
+        paths.contains(&"crate::types::Config::with_version"),
+        "{paths:?}"
+    );
+    assert!(
+        paths.contains(&"crate::types::Config::default"),
+        "{paths:?}"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_qualified_workspace_symbols() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project").await?;
+
+    let mut symbols = Vec::new();
+    for _ in 0..10 {
+        let response = client
+            .call_tool(
+                "rust_analyzer_workspace_symbols",
+                json!({ "query": "with_version", "qualified": true }),
+            )
+            .await?;
+        symbols = tool_text_json(&response)
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        if !symbols.is_empty() {
+            break;
+        }
+        tokio::time::sleep(timeouts::tool_retry_delay()).await;
+    }
+
+    assert!(
+        symbols.iter().any(
+            |symbol| symbol["path"] == "crate::types::Config::with_version"
+                && symbol["crate"] == "test_project"
+        ),
+        "Expected a qualified match, got: {symbols:#?}"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_enclosing_items_for_method_body() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project").await?;
+
+    // Line 26 is the body of `Calculator::add` in src/main.rs.
+    let response = client
+        .call_tool(
+            "rust_analyzer_enclosing_items",
+            json!({ "file_path": "src/main.rs", "line": 26, "character": 8 }),
+        )
+        .await?;
+    let parsed = tool_text_json(&response);
+
+    let kinds: Vec<&str> = parsed["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["kind"].as_str().unwrap())
+        .collect();
+    assert_eq!(kinds, ["impl", "method"]);
+    assert_eq!(parsed["innermost"]["name"], "add");
+    assert_eq!(
+        parsed["innermost"]["signature"],
+        "fn add(&self, a: i32, b: i32) -> i32"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_impls_for_type_by_name() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project").await?;
+
+    let response = call_tool_when_indexed(
+        &mut client,
+        "rust_analyzer_impls",
+        json!({ "type_name": "Config" }),
+    )
+    .await?;
+    let parsed = tool_text_json(&response);
+
+    let inherent = parsed["inherent_impls"].as_array().unwrap();
+    assert_eq!(inherent.len(), 1, "{parsed:#}");
+    assert_eq!(inherent[0]["header"], "impl Config");
+    assert!(inherent[0]["file"].as_str().unwrap().ends_with("types.rs"));
+
+    let traits: Vec<&str> = parsed["trait_impls"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|block| block["trait"].as_str().unwrap())
+        .collect();
+    assert!(traits.contains(&"Default"), "{parsed:#}");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_implementors_of_trait_by_name() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project").await?;
+
+    let response = call_tool_when_indexed(
+        &mut client,
+        "rust_analyzer_implementors",
+        json!({ "trait_name": "Describe" }),
+    )
+    .await?;
+    let parsed = tool_text_json(&response);
+
+    let mut types: Vec<&str> = parsed["implementors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|block| block["self_type"].as_str().unwrap())
+        .collect();
+    types.sort_unstable();
+    assert_eq!(types, ["Config", "Person"], "{parsed:#}");
+    assert_eq!(parsed["count"], 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_trait_surface_by_name() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project").await?;
+
+    let response = call_tool_when_indexed(
+        &mut client,
+        "rust_analyzer_trait_surface",
+        json!({ "trait_name": "Describe" }),
+    )
+    .await?;
+    let parsed = tool_text_json(&response);
+
+    assert_eq!(parsed["signature"], "pub trait Describe");
+    assert_eq!(
+        parsed["required"][0]["signature"],
+        "fn describe(&self) -> String"
+    );
+    assert_eq!(parsed["provided"][0]["name"], "shout");
+    assert_eq!(
+        parsed["provided"][0]["doc"],
+        "Describes the value in upper case."
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_trait_surface_follows_impl_header_to_std() -> Result<()> {
+    let mut client = IpcClient::get_or_create("test-project").await?;
+
+    // Line 23 is `impl Default for Config` in src/types.rs.
+    let response = call_tool_when_indexed(
+        &mut client,
+        "rust_analyzer_trait_surface",
+        json!({ "file_path": "src/types.rs", "line": 23, "character": 6 }),
+    )
+    .await?;
+    let parsed = tool_text_json(&response);
+
+    assert_eq!(parsed["name"], "Default");
+    assert_eq!(parsed["required"][0]["name"], "default");
+
+    Ok(())
+}
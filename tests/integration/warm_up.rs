@@ -0,0 +1,101 @@
+use anyhow::{anyhow, Result};
+use rust_analyzer_mcp::{config::ServerConfig, RustAnalyzerMCPServer};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::{
+    io::{duplex, split, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    time::timeout,
+};
+
+async fn write_message<W: AsyncWrite + Unpin>(writer: &mut W, message: &Value) -> Result<()> {
+    let body = message.to_string();
+    let frame = format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+    writer.write_all(frame.as_bytes()).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Reads the next response, skipping notifications.
+async fn read_response<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Value> {
+    loop {
+        let mut header = Vec::new();
+        while !header.ends_with(b"\r\n\r\n") {
+            header.push(reader.read_u8().await?);
+        }
+        let header = String::from_utf8(header)?;
+        let length: usize = header
+            .lines()
+            .find_map(|line| line.strip_prefix("Content-Length: "))
+            .ok_or_else(|| anyhow!("Missing Content-Length in {header:?}"))?
+            .trim()
+            .parse()?;
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).await?;
+        let message: Value = serde_json::from_slice(&body)?;
+        if message.get("id").is_some() {
+            return Ok(message);
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_warm_up_holds_tool_calls_until_ready() -> Result<()> {
+    let workspace = tempfile::tempdir()?;
+    std::fs::write(
+        workspace.path().join("Cargo.toml"),
+        "[package]\nname = \"warm-up\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+    )?;
+    std::fs::create_dir(workspace.path().join("src"))?;
+    std::fs::write(
+        workspace.path().join("src/lib.rs"),
+        "pub fn answer() -> u32 { 42 }\n",
+    )?;
+
+    let (client_io, server_io) = duplex(16 * 1024);
+    let (server_reader, server_writer) = split(server_io);
+    let config = ServerConfig {
+        warm_up: true,
+        ..ServerConfig::default()
+    };
+    let mut server = RustAnalyzerMCPServer::with_config(workspace.path().to_path_buf(), config);
+    let server_task =
+        tokio::spawn(async move { server.run_with_streams(server_reader, server_writer).await });
+
+    let (mut client_reader, mut client_writer) = split(client_io);
+
+    for request in [
+        json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }),
+        json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "tools/call",
+            "params": { "name": "rust_analyzer_server_stats", "arguments": {} }
+        }),
+        json!({ "jsonrpc": "2.0", "id": 3, "method": "ping" }),
+    ] {
+        write_message(&mut client_writer, &request).await?;
+    }
+
+    let initialize = timeout(Duration::from_secs(30), read_response(&mut client_reader)).await??;
+    assert_eq!(initialize["id"], 1);
+    assert_eq!(initialize["result"]["readiness"]["warmingUp"], true);
+    assert_eq!(initialize["result"]["readiness"]["ready"], false);
+
+    let ping = timeout(Duration::from_secs(5), read_response(&mut client_reader)).await??;
+    assert_eq!(ping["id"], 3, "ping is answered while the tool call waits");
+
+    let stats = timeout(Duration::from_secs(120), read_response(&mut client_reader)).await??;
+    assert_eq!(stats["id"], 2);
+
+    let initialize = json!({ "jsonrpc": "2.0", "id": 4, "method": "initialize", "params": {} });
+    write_message(&mut client_writer, &initialize).await?;
+    let initialize = timeout(Duration::from_secs(5), read_response(&mut client_reader)).await??;
+    assert_eq!(initialize["result"]["readiness"]["warmingUp"], false);
+
+    client_writer.shutdown().await?;
+    drop(client_writer);
+    drop(client_reader);
+    server_task.await??;
+
+    Ok(())
+}
@@ -1,6 +1,9 @@
 mod integration {
+    mod cargo_tools;
     mod document_sync;
     mod diagnostics;
     mod mcp_server_test;
+    mod symbols;
+    mod warm_up;
     // mod shared_test;  // This test file doesn't exist yet
 }
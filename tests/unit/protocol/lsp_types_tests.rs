@@ -0,0 +1,91 @@
+use rust_analyzer_mcp::protocol::{CodeAction, Diagnostic, Hover, SymbolInformation};
+use serde_json::{from_value, json, to_value, Value};
+
+fn range() -> Value {
+    json!({
+        "start": { "line": 3, "character": 8 },
+        "end": { "line": 3, "character": 14 }
+    })
+}
+
+#[test]
+fn test_diagnostic_round_trips_rust_analyzer_payload() {
+    let payload = json!({
+        "range": range(),
+        "severity": 2,
+        "code": "unused_variables",
+        "codeDescription": { "href": "https://doc.rust-lang.org/rustc/lints" },
+        "source": "rustc",
+        "message": "unused variable: `total`",
+        "tags": [1],
+        "relatedInformation": [{
+            "location": { "uri": "file:///work/src/lib.rs", "range": range() },
+            "message": "if this is intentional, prefix it with an underscore"
+        }],
+        "data": { "rendered": "warning: unused variable" }
+    });
+
+    let diagnostic: Diagnostic = from_value(payload.clone()).unwrap();
+    assert_eq!(diagnostic.severity, Some(2));
+    assert_eq!(diagnostic.range.start.character, 8);
+    assert_eq!(to_value(&diagnostic).unwrap(), payload);
+
+    let numeric: Diagnostic = from_value(json!({
+        "range": range(),
+        "code": 308,
+        "message": "mismatched types"
+    }))
+    .unwrap();
+    assert_eq!(numeric.code.unwrap().to_string(), "308");
+}
+
+#[test]
+fn test_hover_and_symbol_shapes() {
+    let hover = json!({
+        "contents": { "kind": "markdown", "value": "```rust\nfn main()\n```" },
+        "range": range()
+    });
+    assert_eq!(
+        to_value(from_value::<Hover>(hover.clone()).unwrap()).unwrap(),
+        hover
+    );
+
+    let symbol = json!({
+        "name": "Calculator",
+        "kind": 23,
+        "location": { "uri": "file:///work/src/lib.rs", "range": range() },
+        "containerName": "demo"
+    });
+    let parsed: SymbolInformation = from_value(symbol.clone()).unwrap();
+    assert_eq!(parsed.container_name.as_deref(), Some("demo"));
+    assert_eq!(to_value(parsed).unwrap(), symbol);
+}
+
+#[test]
+fn test_code_action_with_document_changes() {
+    let action = json!({
+        "title": "Rename to `total_sum`",
+        "kind": "refactor.rewrite",
+        "edit": {
+            "documentChanges": [
+                {
+                    "textDocument": { "uri": "file:///work/src/lib.rs", "version": 4 },
+                    "edits": [{ "range": range(), "newText": "total_sum" }]
+                },
+                { "kind": "rename", "oldUri": "file:///work/a.rs", "newUri": "file:///work/b.rs" }
+            ]
+        },
+        "data": { "id": "rename:0" }
+    });
+
+    let parsed: CodeAction = from_value(action.clone()).unwrap();
+    let changes = parsed
+        .edit
+        .as_ref()
+        .unwrap()
+        .document_changes
+        .as_ref()
+        .unwrap();
+    assert_eq!(changes.len(), 2);
+    assert_eq!(to_value(&parsed).unwrap(), action);
+}
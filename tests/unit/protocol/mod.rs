@@ -1,2 +1,3 @@
+mod lsp_types_tests;
 mod request_tests;
 mod tool_tests;
\ No newline at end of file
@@ -1,3 +1,4 @@
+use rust_analyzer_mcp::protocol::ContentItem;
 use serde_json::{json, Value};
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -130,6 +131,30 @@ fn test_tool_response_formats() {
     assert_eq!(completion_response["items"][0]["label"], "println!");
 }
 
+#[test]
+fn test_content_items_are_tagged_by_type() {
+    // Text items keep their text even when it is empty.
+    assert_eq!(
+        serde_json::to_value(ContentItem::text("")).unwrap(),
+        json!({ "type": "text", "text": "" })
+    );
+    let link = ContentItem::resource_link("file:///ws/src/lib.rs", "src/lib.rs");
+    assert_eq!(
+        serde_json::to_value(&link).unwrap(),
+        json!({
+            "type": "resource_link",
+            "uri": "file:///ws/src/lib.rs",
+            "name": "src/lib.rs",
+            "mimeType": "text/x-rust"
+        })
+    );
+    assert_eq!(link.as_text(), None);
+
+    let parsed: ContentItem =
+        serde_json::from_value(json!({ "type": "text", "text": "ok" })).unwrap();
+    assert_eq!(parsed, ContentItem::text("ok"));
+}
+
 #[test]
 fn test_error_response_codes() {
     let error_codes = vec![
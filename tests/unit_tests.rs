@@ -1,5 +1,6 @@
 mod unit {
     mod protocol {
+        mod lsp_types_tests;
         mod request_tests;
         mod tool_tests;
     }